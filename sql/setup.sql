@@ -2,8 +2,26 @@
 CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
 
 -- Drop types and tables in reverse order of dependency if they exist
+DROP TABLE IF EXISTS ai_usage CASCADE;
+DROP TABLE IF EXISTS ai_messages CASCADE;
+DROP TABLE IF EXISTS ai_threads CASCADE;
+DROP TABLE IF EXISTS personal_access_tokens CASCADE;
+DROP TABLE IF EXISTS oidc_logins CASCADE;
+DROP TABLE IF EXISTS user_identities CASCADE;
+DROP TABLE IF EXISTS auth_failures CASCADE;
+DROP TABLE IF EXISTS webauthn_ceremonies CASCADE;
+DROP TABLE IF EXISTS passkeys CASCADE;
+DROP TABLE IF EXISTS tfa_recovery_codes CASCADE;
+DROP TABLE IF EXISTS tfa_challenges CASCADE;
+DROP TABLE IF EXISTS sessions CASCADE;
+DROP TABLE IF EXISTS reminder_deliveries CASCADE;
+DROP TABLE IF EXISTS reminders CASCADE;
+DROP TABLE IF EXISTS sync_change_log CASCADE;
+DROP TABLE IF EXISTS sync_sequences CASCADE;
 DROP TABLE IF EXISTS event_exceptions CASCADE;
+DROP TABLE IF EXISTS pending_event_invitations CASCADE;
 DROP TABLE IF EXISTS event_invitations CASCADE;
+DROP TABLE IF EXISTS pending_calendar_shares CASCADE;
 DROP TABLE IF EXISTS calendar_share_categories CASCADE;
 DROP TABLE IF EXISTS calendar_shares CASCADE;
 DROP TABLE IF EXISTS open_calendar_share_categories CASCADE;
@@ -16,6 +34,7 @@ DROP TYPE IF EXISTS event_invitation_status;
 DROP TYPE IF EXISTS share_privacy_level;
 DROP TYPE IF EXISTS deadline_priority_level;
 DROP TYPE IF EXISTS workload_unit_type;
+DROP TYPE IF EXISTS reminder_channel;
 
 -- Function to automatically update updated_at timestamp
 DO $$ BEGIN
@@ -46,11 +65,171 @@ CREATE TABLE users (
     updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     deleted_at TIMESTAMP WITH TIME ZONE NULL,
     tfa_enabled BOOLEAN NOT NULL DEFAULT FALSE,
-    tfa_secret TEXT NULL
+    tfa_secret TEXT NULL,
+    tfa_last_used_step BIGINT NULL, -- TOTP time step of the last accepted code; older or equal steps are rejected
+    webauthn_user_id UUID NULL UNIQUE -- WebAuthn user handle, set when the first passkey is registered
 );
 DROP TRIGGER IF EXISTS set_timestamp_users ON users;
 CREATE TRIGGER set_timestamp_users BEFORE UPDATE ON users FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
 
+-- Sessions Table
+-- One row per signed-in device. Access tokens carry the session_id and stop working once it is revoked.
+-- Refresh tokens rotate on every use; only SHA-256 hashes are stored.
+CREATE TABLE sessions (
+    session_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    refresh_token_hash VARCHAR(64) NOT NULL UNIQUE,
+    previous_token_hash VARCHAR(64) NULL, -- Presenting it again means the token leaked: the session is revoked
+    user_agent TEXT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), -- Last refresh
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL, -- Pushed back on every refresh
+    revoked_at TIMESTAMP WITH TIME ZONE NULL,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- TFA Challenges Table
+-- Created by the password step of a login with 2FA enabled. POST /api/auth/verify-tfa must present
+-- the signed token for it; each challenge allows a few code attempts and completes only once.
+CREATE TABLE tfa_challenges (
+    challenge_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0, -- Code attempts made so far
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    completed_at TIMESTAMP WITH TIME ZONE NULL, -- Set once a code was accepted
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- TFA Recovery Codes Table
+-- One-time fallback codes, handed out when 2FA setup completes (or on regeneration). Stored hashed.
+CREATE TABLE tfa_recovery_codes (
+    recovery_code_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    code_hash TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    used_at TIMESTAMP WITH TIME ZONE NULL,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- Passkeys Table
+-- WebAuthn credentials. A passkey logs a user in without a password, or stands in for the TOTP code.
+CREATE TABLE passkeys (
+    passkey_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    credential_id BYTEA NOT NULL UNIQUE,
+    name VARCHAR(100) NOT NULL,
+    passkey JSONB NOT NULL, -- Serialized webauthn-rs Passkey (public key, signature counter)
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP WITH TIME ZONE NULL,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- WebAuthn Ceremonies Table
+-- Server-side state between the start and finish requests of a passkey registration or login.
+-- Single use (deleted on finish) and short-lived.
+CREATE TABLE webauthn_ceremonies (
+    ceremony_id UUID PRIMARY KEY,
+    user_id INTEGER NULL, -- NULL for passwordless login: the user is only known from the assertion
+    kind VARCHAR(20) NOT NULL, -- 'registration', 'login' or 'second_factor'
+    state JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- Auth Failures Table
+-- Failed login, 2FA, email verification and password reset attempts per account.
+-- Past a few failures the account is locked for that action, with the lockout doubling each time.
+-- Cleared by a successful attempt.
+CREATE TABLE auth_failures (
+    user_id INTEGER NOT NULL,
+    action VARCHAR(20) NOT NULL, -- 'login', 'verify_tfa', 'verify_email' or 'reset_password'
+    failures INTEGER NOT NULL DEFAULT 0,
+    locked_until TIMESTAMP WITH TIME ZONE NULL,
+    last_failure_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    alerted_at TIMESTAMP WITH TIME ZONE NULL, -- When the owner was last emailed about it
+    PRIMARY KEY (user_id, action),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- Personal Access Tokens Table
+-- Long-lived API tokens for scripts and integrations, limited to their scopes. Stored as SHA-256 hashes.
+CREATE TABLE personal_access_tokens (
+    token_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    token_prefix VARCHAR(16) NOT NULL, -- Start of the token, to recognize it in the list
+    scopes TEXT[] NOT NULL, -- e.g. {'calendar:read','events:write'}
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NULL, -- NULL = never expires
+    last_used_at TIMESTAMP WITH TIME ZONE NULL,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- AI Threads Table
+-- Conversations with the AI assistant. Each prompt and final answer is kept in ai_messages;
+-- intermediate tool calls are not stored.
+CREATE TABLE ai_threads (
+    thread_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    title VARCHAR(100) NOT NULL, -- Start of the first prompt
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(), -- Last message
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE ai_messages (
+    message_id SERIAL PRIMARY KEY,
+    thread_id INTEGER NOT NULL,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('user', 'assistant')),
+    content TEXT NOT NULL,
+    image_count INTEGER NOT NULL DEFAULT 0, -- Images sent with a user message (not stored)
+    proposal JSONB NULL, -- Items an assistant message proposed
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (thread_id) REFERENCES ai_threads(thread_id) ON DELETE CASCADE
+);
+
+-- AI Usage Table
+-- Tokens the AI provider reported per user and UTC day, for quotas and GET /api/me/ai-assistant/usage.
+CREATE TABLE ai_usage (
+    user_id INTEGER NOT NULL,
+    usage_date DATE NOT NULL, -- UTC
+    prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    completion_tokens BIGINT NOT NULL DEFAULT 0,
+    total_tokens BIGINT NOT NULL DEFAULT 0,
+    request_count INTEGER NOT NULL DEFAULT 0, -- Prompts sent to the assistant; each may take several model turns
+    PRIMARY KEY (user_id, usage_date),
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+
+-- User Identities Table
+-- Accounts at external OpenID Connect providers (Google, Microsoft, ...) that log in as a user.
+CREATE TABLE user_identities (
+    identity_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    provider VARCHAR(50) NOT NULL, -- Provider ID from OIDC_PROVIDERS
+    subject VARCHAR(255) NOT NULL, -- The provider's stable user ID ('sub' claim)
+    email VARCHAR(255) NULL, -- Email the provider reported when linking, for display
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP WITH TIME ZONE NULL,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+    UNIQUE (provider, subject)
+);
+
+-- OIDC Logins Table
+-- Pending authorization-code flows, between the redirect to the provider and the callback.
+-- Single use (deleted on finish) and short-lived.
+CREATE TABLE oidc_logins (
+    state VARCHAR(64) PRIMARY KEY, -- Sent through the provider and back
+    provider VARCHAR(50) NOT NULL,
+    code_verifier VARCHAR(128) NOT NULL, -- PKCE
+    nonce VARCHAR(64) NOT NULL, -- Must come back in the ID token
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
 -- Categories Table
 CREATE TABLE categories (
     category_id SERIAL PRIMARY KEY,
@@ -75,6 +254,7 @@ CREATE TYPE deadline_priority_level AS ENUM ('normal', 'important', 'urgent');
 CREATE TYPE workload_unit_type AS ENUM ('minutes', 'hours', 'days');
 CREATE TYPE event_invitation_status AS ENUM ('pending', 'accepted', 'rejected', 'maybe');
 CREATE TYPE share_privacy_level AS ENUM ('full', 'limited');
+CREATE TYPE reminder_channel AS ENUM ('email', 'in_app');
 
 -- Deadlines Table
 CREATE TABLE deadlines (
@@ -88,6 +268,7 @@ CREATE TABLE deadlines (
     priority deadline_priority_level DEFAULT 'normal',
     workload_magnitude INTEGER,
     workload_unit workload_unit_type,
+    ical_uid TEXT, -- UID of the VTODO this was imported from (re-imports update it)
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     deleted_at TIMESTAMP WITH TIME ZONE NULL,
@@ -128,6 +309,7 @@ CREATE TABLE events (
     end_time TIMESTAMP WITH TIME ZONE NOT NULL,   -- For recurring, this defines the duration relative to the start_time
     location VARCHAR(255),
     rrule TEXT,                                   -- Stores the iCalendar RRULE string
+    ical_uid TEXT,                                -- UID of the VEVENT this was imported from (re-imports update it)
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     deleted_at TIMESTAMP WITH TIME ZONE NULL,
@@ -137,32 +319,32 @@ CREATE TABLE events (
 DROP TRIGGER IF EXISTS set_timestamp_events ON events;
 CREATE TRIGGER set_timestamp_events BEFORE UPDATE ON events FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
 
--- -- Stores modifications or deletions of specific occurrences within a recurring event series
--- CREATE TABLE event_exceptions (
---     exception_id SERIAL PRIMARY KEY,
---     event_id INTEGER NOT NULL,                          -- Foreign key to the parent recurring event in the 'events' table
---     original_occurrence_time TIMESTAMP WITH TIME ZONE NOT NULL, -- The start time of the occurrence this exception replaces/deletes, as generated by the RRULE
+-- Stores modifications or deletions of specific occurrences within a recurring event series
+CREATE TABLE event_exceptions (
+    exception_id SERIAL PRIMARY KEY,
+    event_id INTEGER NOT NULL,                          -- Foreign key to the parent recurring event in the 'events' table
+    original_occurrence_time TIMESTAMP WITH TIME ZONE NOT NULL, -- The start time of the occurrence this exception replaces/deletes, as generated by the RRULE
 
---     is_deleted BOOLEAN NOT NULL DEFAULT FALSE,          -- If true, this specific occurrence is simply cancelled/deleted
+    is_deleted BOOLEAN NOT NULL DEFAULT FALSE,          -- If true, this specific occurrence is simply cancelled/deleted
 
---     -- Override fields (NULL means inherit from parent event unless deleted)
---     title VARCHAR(255),
---     description TEXT,
---     start_time TIMESTAMP WITH TIME ZONE,                -- The *new* start time for this modified occurrence
---     end_time TIMESTAMP WITH TIME ZONE,                  -- The *new* end time for this modified occurrence
---     location VARCHAR(255),
+    -- Override fields (NULL means inherit from parent event unless deleted)
+    title VARCHAR(255),
+    description TEXT,
+    start_time TIMESTAMP WITH TIME ZONE,                -- The *new* start time for this modified occurrence
+    end_time TIMESTAMP WITH TIME ZONE,                  -- The *new* end time for this modified occurrence
+    location VARCHAR(255),
 
---     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
---     updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
 
---     FOREIGN KEY (event_id) REFERENCES events(event_id) ON DELETE CASCADE,
---     UNIQUE (event_id, original_occurrence_time),       -- Ensure only one exception per original occurrence time for a given event
+    FOREIGN KEY (event_id) REFERENCES events(event_id) ON DELETE CASCADE,
+    UNIQUE (event_id, original_occurrence_time),       -- Ensure only one exception per original occurrence time for a given event
 
---     -- Check constraint: A modification must provide new timing info
---     CONSTRAINT chk_exception_modification CHECK (is_deleted OR (start_time IS NOT NULL AND end_time IS NOT NULL))
--- );
--- DROP TRIGGER IF EXISTS set_timestamp_event_exceptions ON event_exceptions;
--- CREATE TRIGGER set_timestamp_event_exceptions BEFORE UPDATE ON event_exceptions FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
+    -- Check constraint: A modification must provide new timing info
+    CONSTRAINT chk_exception_modification CHECK (is_deleted OR (start_time IS NOT NULL AND end_time IS NOT NULL))
+);
+DROP TRIGGER IF EXISTS set_timestamp_event_exceptions ON event_exceptions;
+CREATE TRIGGER set_timestamp_event_exceptions BEFORE UPDATE ON event_exceptions FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
 
 
 -- Event Invitations Table
@@ -201,6 +383,44 @@ CREATE TABLE calendar_shares (
 DROP TRIGGER IF EXISTS set_timestamp_calendar_shares ON calendar_shares;
 CREATE TRIGGER set_timestamp_calendar_shares BEFORE UPDATE ON calendar_shares FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
 
+-- Pending Event Invitations Table
+-- Invitations for email addresses without an account. They become regular event_invitations
+-- once someone registers with the address and verifies it; until then the invitee responds by email.
+CREATE TABLE pending_event_invitations (
+    pending_invitation_id SERIAL PRIMARY KEY,
+    event_id INTEGER NOT NULL,
+    owner_user_id INTEGER NOT NULL,
+    invited_email VARCHAR(255) NOT NULL, -- Stored lowercased
+    status event_invitation_status NOT NULL DEFAULT 'pending', -- Set through RSVP links, carried over when claimed
+    invitation_id INTEGER NULL, -- The regular invitation this became, once claimed
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    deleted_at TIMESTAMP WITH TIME ZONE NULL, -- Set when revoked or claimed
+    FOREIGN KEY (event_id) REFERENCES events(event_id) ON DELETE CASCADE,
+    FOREIGN KEY (owner_user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+    FOREIGN KEY (invitation_id) REFERENCES event_invitations(invitation_id) ON DELETE SET NULL
+);
+DROP TRIGGER IF EXISTS set_timestamp_pending_event_invitations ON pending_event_invitations;
+CREATE TRIGGER set_timestamp_pending_event_invitations BEFORE UPDATE ON pending_event_invitations FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
+
+-- Pending Calendar Shares Table
+-- Shares with email addresses without an account, turned into calendar_shares on verification.
+CREATE TABLE pending_calendar_shares (
+    pending_share_id SERIAL PRIMARY KEY,
+    owner_user_id INTEGER NOT NULL,
+    shared_with_email VARCHAR(255) NOT NULL, -- Stored lowercased
+    category_ids INTEGER[] NOT NULL, -- Checked again when claimed; categories deleted meanwhile are skipped
+    message TEXT,
+    privacy_level share_privacy_level NOT NULL DEFAULT 'full',
+    expires_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    deleted_at TIMESTAMP WITH TIME ZONE NULL, -- Set when revoked or claimed
+    FOREIGN KEY (owner_user_id) REFERENCES users(user_id) ON DELETE CASCADE
+);
+DROP TRIGGER IF EXISTS set_timestamp_pending_calendar_shares ON pending_calendar_shares;
+CREATE TRIGGER set_timestamp_pending_calendar_shares BEFORE UPDATE ON pending_calendar_shares FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
+
 -- Calendar Share Categories Table
 CREATE TABLE calendar_share_categories (
     share_id INTEGER NOT NULL,
@@ -244,24 +464,70 @@ CREATE TABLE open_calendar_share_categories (
 );
 
 
+-- Reminders Table
+-- Per-user rules that fire some time before each occurrence of an event or before a deadline's due date.
+-- Event reminders may belong to the owner or to an invitee who accepted.
+CREATE TABLE reminders (
+    reminder_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL, -- Who gets reminded
+    event_id INTEGER,
+    deadline_id INTEGER,
+    minutes_before INTEGER NOT NULL,
+    channel reminder_channel NOT NULL DEFAULT 'email',
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    deleted_at TIMESTAMP WITH TIME ZONE NULL,
+    FOREIGN KEY (user_id) REFERENCES users(user_id) ON DELETE CASCADE,
+    FOREIGN KEY (event_id) REFERENCES events(event_id) ON DELETE CASCADE,
+    FOREIGN KEY (deadline_id) REFERENCES deadlines(deadline_id) ON DELETE CASCADE,
+    CONSTRAINT chk_reminder_target CHECK ((event_id IS NULL) <> (deadline_id IS NULL)), -- Exactly one target
+    CONSTRAINT chk_reminder_minutes_before CHECK (minutes_before >= 0)
+);
+DROP TRIGGER IF EXISTS set_timestamp_reminders ON reminders;
+CREATE TRIGGER set_timestamp_reminders BEFORE UPDATE ON reminders FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
+
+-- Reminders that already fired. The scheduler claims a row before delivering, so a reminder fires
+-- at most once per occurrence, across restarts and with several server instances.
+CREATE TABLE reminder_deliveries (
+    reminder_id INTEGER NOT NULL,
+    occurrence_time TIMESTAMP WITH TIME ZONE NOT NULL, -- Original start of the event occurrence, or the deadline's due date
+    fired_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (reminder_id, occurrence_time),
+    FOREIGN KEY (reminder_id) REFERENCES reminders(reminder_id) ON DELETE CASCADE
+);
+
+
 -- Indexes
 -- CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
 CREATE UNIQUE INDEX idx_users_email_active ON users(email) WHERE deleted_at IS NULL;
 CREATE INDEX IF NOT EXISTS idx_categories_user_id ON categories(user_id);
 
+CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id) WHERE revoked_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_sessions_previous_token ON sessions(previous_token_hash) WHERE previous_token_hash IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_tfa_challenges_user_id ON tfa_challenges(user_id);
+CREATE INDEX IF NOT EXISTS idx_tfa_recovery_codes_user_id ON tfa_recovery_codes(user_id) WHERE used_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_passkeys_user_id ON passkeys(user_id);
+CREATE INDEX IF NOT EXISTS idx_webauthn_ceremonies_expires_at ON webauthn_ceremonies(expires_at);
+CREATE INDEX IF NOT EXISTS idx_personal_access_tokens_user_id ON personal_access_tokens(user_id);
+CREATE INDEX IF NOT EXISTS idx_ai_threads_user_id ON ai_threads(user_id, updated_at);
+CREATE INDEX IF NOT EXISTS idx_ai_messages_thread_id ON ai_messages(thread_id, message_id);
+CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);
+CREATE INDEX IF NOT EXISTS idx_oidc_logins_expires_at ON oidc_logins(expires_at);
+
 CREATE INDEX IF NOT EXISTS idx_deadlines_user_id ON deadlines(user_id);
 CREATE INDEX IF NOT EXISTS idx_deadlines_user_updated ON deadlines(user_id, updated_at);
 CREATE INDEX IF NOT EXISTS idx_deadlines_due_date ON deadlines(user_id, due_date);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_deadlines_ical_uid ON deadlines(user_id, ical_uid) WHERE ical_uid IS NOT NULL;
 
 CREATE INDEX IF NOT EXISTS idx_events_user_id ON events(user_id);
 CREATE INDEX IF NOT EXISTS idx_events_user_updated ON events(user_id, updated_at);
 CREATE INDEX IF NOT EXISTS idx_events_time_range ON events(user_id, start_time, end_time);
 CREATE INDEX IF NOT EXISTS idx_events_rrule ON events(rrule) WHERE rrule IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_events_ical_uid ON events(user_id, ical_uid) WHERE ical_uid IS NOT NULL;
 
 -- Indexes for Event Exceptions
--- CREATE INDEX IF NOT EXISTS idx_event_exceptions_event_id ON event_exceptions(event_id);
--- CREATE INDEX IF NOT EXISTS idx_event_exceptions_original_time ON event_exceptions(event_id, original_occurrence_time); -- For lookup during expansion
--- CREATE INDEX IF NOT EXISTS idx_event_exceptions_updated ON event_exceptions(updated_at); -- For sync
+-- (event_id, original_occurrence_time) lookups are covered by the UNIQUE constraint
+CREATE INDEX IF NOT EXISTS idx_event_exceptions_updated ON event_exceptions(updated_at); -- For sync
 
 -- Indexes for Event Invitations
 CREATE INDEX IF NOT EXISTS idx_event_invitations_event_id ON event_invitations(event_id);
@@ -269,6 +535,12 @@ CREATE INDEX IF NOT EXISTS idx_event_invitations_invited_user ON event_invitatio
 CREATE INDEX IF NOT EXISTS idx_event_invitations_status ON event_invitations(invited_user_id, status);
 CREATE INDEX IF NOT EXISTS idx_event_invitations_updated ON event_invitations(invited_user_id, updated_at);
 
+-- Indexes for Pending Invitations and Shares (one live entry per address)
+CREATE UNIQUE INDEX IF NOT EXISTS idx_pending_event_invitations_active ON pending_event_invitations(event_id, invited_email) WHERE deleted_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_pending_event_invitations_email ON pending_event_invitations(invited_email) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_pending_calendar_shares_active ON pending_calendar_shares(owner_user_id, shared_with_email) WHERE deleted_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_pending_calendar_shares_email ON pending_calendar_shares(shared_with_email) WHERE deleted_at IS NULL;
+
 -- Indexes for Calendar Shares
 CREATE INDEX IF NOT EXISTS idx_calendar_shares_owner ON calendar_shares(owner_user_id);
 CREATE INDEX IF NOT EXISTS idx_calendar_shares_shared_with ON calendar_shares(shared_with_user_id);
@@ -281,6 +553,12 @@ CREATE INDEX IF NOT EXISTS idx_open_calendar_shares_deleted_at ON open_calendar_
 CREATE INDEX IF NOT EXISTS idx_open_calendar_share_categories_open_share_id ON open_calendar_share_categories(open_share_id);
 CREATE INDEX IF NOT EXISTS idx_open_calendar_share_categories_category_id ON open_calendar_share_categories(category_id);
 
+-- Indexes for Reminders
+CREATE INDEX IF NOT EXISTS idx_reminders_user_id ON reminders(user_id);
+CREATE INDEX IF NOT EXISTS idx_reminders_event_id ON reminders(event_id) WHERE event_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_reminders_deadline_id ON reminders(deadline_id) WHERE deadline_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_reminder_deliveries_fired_at ON reminder_deliveries(fired_at);
+
 -- Indexes for 2FA
 CREATE INDEX IF NOT EXISTS idx_users_tfa_enabled ON users(tfa_enabled);
 
@@ -306,3 +584,151 @@ CREATE TRIGGER trigger_create_default_categories
 AFTER INSERT ON users -- Fire after a row is inserted
 FOR EACH ROW          -- For each inserted row
 EXECUTE FUNCTION create_default_categories(); -- Execute our new function
+
+-- Per-user change log backing sync tokens (GET /api/sync/me, GET /api/sync/stream) ---
+-- Every change to a user's sync data bumps that user's sequence and records the entity with the new value.
+-- The sequence row stays locked until the writing transaction commits, so change IDs become visible in
+-- order and a reader never sees a later ID before an earlier one (no gaps to skip over).
+-- No foreign keys: rows may be written while a user's data is being cascade-deleted.
+CREATE TABLE sync_sequences (
+    user_id INTEGER PRIMARY KEY,
+    last_change_id BIGINT NOT NULL DEFAULT 0
+);
+
+-- Compacted: one row per (user, entity), holding the latest change and whether it removed the entity
+-- from the user's sync data (deleted, revoked, declined, ...).
+CREATE TABLE sync_change_log (
+    user_id INTEGER NOT NULL,
+    entity TEXT NOT NULL, -- 'category', 'deadline', 'event', 'eventException', 'invitation', 'share', 'shareCategory', 'openShare'
+    entity_id TEXT NOT NULL, -- Numeric ID, UUID for open shares, '<share_id>:<category_id>' for share categories
+    change_id BIGINT NOT NULL,
+    deleted BOOLEAN NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, entity, entity_id)
+);
+CREATE INDEX IF NOT EXISTS idx_sync_change_log_user_change ON sync_change_log(user_id, change_id);
+
+-- Record one change for one user and publish it to the sync stream.
+-- Payload: {"userId", "changeId", "entity", "id", "deleted", "updatedAt"}.
+-- NOTIFY is transactional, so listeners only hear about committed changes.
+CREATE OR REPLACE FUNCTION log_sync_change(p_user_id INTEGER, p_entity TEXT, p_entity_id TEXT, p_deleted BOOLEAN)
+RETURNS VOID AS $$
+DECLARE
+    next_change_id BIGINT;
+BEGIN
+    INSERT INTO sync_sequences (user_id, last_change_id) VALUES (p_user_id, 1)
+    ON CONFLICT (user_id) DO UPDATE SET last_change_id = sync_sequences.last_change_id + 1
+    RETURNING last_change_id INTO next_change_id;
+
+    INSERT INTO sync_change_log (user_id, entity, entity_id, change_id, deleted)
+    VALUES (p_user_id, p_entity, p_entity_id, next_change_id, p_deleted)
+    ON CONFLICT (user_id, entity, entity_id) DO UPDATE
+    SET change_id = EXCLUDED.change_id, deleted = EXCLUDED.deleted, updated_at = NOW();
+
+    PERFORM pg_notify('sync_changes', json_build_object(
+        'userId', p_user_id,
+        'changeId', next_change_id,
+        'entity', p_entity,
+        'id', p_entity_id,
+        'deleted', p_deleted,
+        'updatedAt', NOW()
+    )::TEXT);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Function to log row changes for every user whose sync data the row belongs to ---
+-- Recipients are handled in ascending user_id order to keep sequence lock order consistent.
+CREATE OR REPLACE FUNCTION notify_sync_change()
+RETURNS TRIGGER AS $$
+DECLARE
+    row_data RECORD;
+    is_deleted BOOLEAN;
+    recipient INTEGER;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        row_data := OLD;
+    ELSE
+        row_data := NEW;
+    END IF;
+
+    -- Soft-deleted rows count as deleted (event_exceptions has no deleted_at)
+    IF TG_TABLE_NAME = 'event_exceptions' THEN
+        is_deleted := TG_OP = 'DELETE';
+    ELSE
+        is_deleted := TG_OP = 'DELETE' OR row_data.deleted_at IS NOT NULL;
+    END IF;
+
+    CASE TG_TABLE_NAME
+        WHEN 'categories' THEN
+            PERFORM log_sync_change(row_data.user_id, 'category', row_data.category_id::TEXT, is_deleted);
+        WHEN 'deadlines' THEN
+            PERFORM log_sync_change(row_data.user_id, 'deadline', row_data.deadline_id::TEXT, is_deleted);
+        WHEN 'events' THEN
+            -- Owner and invitees who accepted
+            FOR recipient IN
+                SELECT row_data.user_id
+                UNION
+                SELECT invited_user_id FROM event_invitations
+                WHERE event_id = row_data.event_id AND status = 'accepted' AND deleted_at IS NULL
+                ORDER BY 1
+            LOOP
+                PERFORM log_sync_change(recipient, 'event', row_data.event_id::TEXT, is_deleted);
+            END LOOP;
+        WHEN 'event_exceptions' THEN
+            -- Cancelled occurrences are regular rows (is_deleted), not tombstones
+            FOR recipient IN
+                SELECT user_id FROM events WHERE event_id = row_data.event_id
+                UNION
+                SELECT invited_user_id FROM event_invitations
+                WHERE event_id = row_data.event_id AND status = 'accepted' AND deleted_at IS NULL
+                ORDER BY 1
+            LOOP
+                PERFORM log_sync_change(recipient, 'eventException', row_data.exception_id::TEXT, is_deleted);
+            END LOOP;
+        WHEN 'event_invitations' THEN
+            PERFORM log_sync_change(row_data.invited_user_id, 'invitation', row_data.invitation_id::TEXT, is_deleted);
+            -- The invitee syncs the event itself only while the invitation is accepted
+            IF (TG_OP = 'INSERT' AND NEW.status = 'accepted') OR TG_OP = 'DELETE'
+               OR (TG_OP = 'UPDATE' AND (OLD.status IS DISTINCT FROM NEW.status OR OLD.deleted_at IS DISTINCT FROM NEW.deleted_at)) THEN
+                PERFORM log_sync_change(row_data.invited_user_id, 'event', row_data.event_id::TEXT,
+                    is_deleted OR row_data.status <> 'accepted'
+                    OR NOT EXISTS (SELECT 1 FROM events WHERE event_id = row_data.event_id AND deleted_at IS NULL));
+            END IF;
+        WHEN 'calendar_shares' THEN
+            PERFORM log_sync_change(LEAST(row_data.owner_user_id, row_data.shared_with_user_id), 'share', row_data.share_id::TEXT, is_deleted);
+            PERFORM log_sync_change(GREATEST(row_data.owner_user_id, row_data.shared_with_user_id), 'share', row_data.share_id::TEXT, is_deleted);
+        WHEN 'calendar_share_categories' THEN
+            -- Lets the sharee drop a category's items from the shared calendar view
+            FOR recipient IN
+                SELECT owner_user_id FROM calendar_shares WHERE share_id = row_data.share_id
+                UNION
+                SELECT shared_with_user_id FROM calendar_shares WHERE share_id = row_data.share_id
+                ORDER BY 1
+            LOOP
+                PERFORM log_sync_change(recipient, 'shareCategory', row_data.share_id || ':' || row_data.category_id, is_deleted);
+            END LOOP;
+        WHEN 'open_calendar_shares' THEN
+            PERFORM log_sync_change(row_data.owner_user_id, 'openShare', row_data.open_share_id::TEXT, is_deleted);
+    END CASE;
+
+    RETURN NULL; -- Result is ignored for AFTER triggers
+END;
+$$ LANGUAGE plpgsql;
+
+-- Triggers to log changes of all synced tables ---
+DROP TRIGGER IF EXISTS notify_sync_categories ON categories;
+CREATE TRIGGER notify_sync_categories AFTER INSERT OR UPDATE OR DELETE ON categories FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
+DROP TRIGGER IF EXISTS notify_sync_deadlines ON deadlines;
+CREATE TRIGGER notify_sync_deadlines AFTER INSERT OR UPDATE OR DELETE ON deadlines FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
+DROP TRIGGER IF EXISTS notify_sync_events ON events;
+CREATE TRIGGER notify_sync_events AFTER INSERT OR UPDATE OR DELETE ON events FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
+DROP TRIGGER IF EXISTS notify_sync_event_exceptions ON event_exceptions;
+CREATE TRIGGER notify_sync_event_exceptions AFTER INSERT OR UPDATE OR DELETE ON event_exceptions FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
+DROP TRIGGER IF EXISTS notify_sync_event_invitations ON event_invitations;
+CREATE TRIGGER notify_sync_event_invitations AFTER INSERT OR UPDATE OR DELETE ON event_invitations FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
+DROP TRIGGER IF EXISTS notify_sync_calendar_shares ON calendar_shares;
+CREATE TRIGGER notify_sync_calendar_shares AFTER INSERT OR UPDATE OR DELETE ON calendar_shares FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
+DROP TRIGGER IF EXISTS notify_sync_calendar_share_categories ON calendar_share_categories;
+CREATE TRIGGER notify_sync_calendar_share_categories AFTER INSERT OR UPDATE OR DELETE ON calendar_share_categories FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
+DROP TRIGGER IF EXISTS notify_sync_open_calendar_shares ON open_calendar_shares;
+CREATE TRIGGER notify_sync_open_calendar_shares AFTER INSERT OR UPDATE OR DELETE ON open_calendar_shares FOR EACH ROW EXECUTE FUNCTION notify_sync_change();
@@ -1,124 +1,182 @@
-use crate::config::Config;
+use crate::config::{AiConfig, AiProviderKind, AiQuotas};
 use crate::errors::AppError;
-use async_openai::{
-    config::OpenAIConfig, types::{
-        ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPartImage, ChatCompletionRequestMessageContentPartText,
-        ChatCompletionRequestSystemMessage, ChatCompletionRequestSystemMessageContent, ChatCompletionRequestUserMessage,
-        ChatCompletionRequestUserMessageContent, ChatCompletionRequestUserMessageContentPart, CreateChatCompletionRequest,
-        CreateChatCompletionResponse, ImageUrl
-    }, Client
-};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
 use std::sync::Arc;
 
+pub mod assistant; // Tool calling loop of the calendar assistant
+pub mod context; // Calendar summary added to every request
+pub mod mock; // Deterministic provider for tests and local development
+pub mod openai; // OpenAI and OpenAI-compatible servers
+pub mod thread; // Stored conversations
+pub mod usage; // Token accounting and quotas
+
+
+// --- Provider-neutral conversation types ---
+
+// One message of a conversation with the model
+#[derive(Debug, Clone)]
+pub enum AiMessage {
+    System(String),
+    User { text: String, images: Vec<(String, String)> }, // images: Vec<(base64_string, mime_type)>
+    Assistant { content: Option<String>, tool_calls: Vec<AiToolCall> },
+    Tool { call_id: String, content: String }, // Result of one tool call
+}
+
+impl AiMessage {
+    // Characters of text sent to the model, without images
+    pub fn text_len(&self) -> usize {
+        match self {
+            AiMessage::System(text) | AiMessage::User { text, .. } | AiMessage::Tool { content: text, .. } => text.len(),
+            AiMessage::Assistant { content, tool_calls } => {
+                content.as_ref().map_or(0, String::len) + tool_calls.iter().map(|call| call.arguments.len()).sum::<usize>()
+            }
+        }
+    }
+}
+
+// A function the model may call
+#[derive(Debug, Clone)]
+pub struct AiTool {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value, // JSON Schema of the arguments object
+}
+
+#[derive(Debug, Clone)]
+pub struct AiToolCall {
+    pub id: String, // Chosen by the model, echoed back in the tool result
+    pub name: String,
+    pub arguments: String, // JSON generated by the model, not validated yet
+}
+
+// Tokens billed for one or more completions
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl AiUsage {
+    pub fn add(&mut self, other: AiUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+// The model's turn: a final answer, or tool calls to run before asking again
+#[derive(Debug, Default)]
+pub struct AiReply {
+    pub content: Option<String>,
+    pub tool_calls: Vec<AiToolCall>,
+    pub finish_reason: Option<String>, // As reported by the provider, e.g. "stop", "length" or "tool_calls"
+    pub usage: Option<AiUsage>,        // None if the provider doesn't report it
+}
+
+// Receives the text of a streamed reply piece by piece
+pub type OnDelta<'a> = dyn Fn(&str) + Send + Sync + 'a;
+
+// A backend that runs chat completions with tool calling
+#[async_trait]
+pub trait AiProvider: Send + Sync {
+    async fn complete(&self, messages: &[AiMessage], tools: &[AiTool]) -> Result<AiReply, AppError>;
+
+    // Like complete, but passes the text of the reply to on_delta piece by piece while it is generated.
+    // Providers that can't stream hand over the whole text at once.
+    async fn complete_stream(
+        &self,
+        messages: &[AiMessage],
+        tools: &[AiTool],
+        on_delta: &OnDelta<'_>,
+    ) -> Result<AiReply, AppError> {
+        let reply = self.complete(messages, tools).await?;
+        if let Some(content) = &reply.content {
+            on_delta(content);
+        }
+        Ok(reply)
+    }
+}
+
 
 #[derive(Clone)] // Client can be cloned
-pub struct OpenAIClient {
-    client: Client<OpenAIConfig>,
-    system_prompt: String, // System prompt for OpenAI API
-    // We might need config here for model choice, etc.
-    // config: Arc<Config>, // If needed later
+pub struct AiClient {
+    provider: Arc<dyn AiProvider>,
+    system_prompt: String, // System prompt for every conversation
+    context_tokens: usize, // Budget of the calendar summary, 0 to leave it out
+    max_tokens: Option<u32>, // Per model reply; provider default when unset
+    quotas: AiQuotas, // Tokens each user may use
 }
 
-impl OpenAIClient {
-    pub fn new(config: &Arc<Config>) -> Self {
-        // New implicitly create the OpenAI client with default and env
-        let openai_config = OpenAIConfig::new()
-            .with_api_key(config.openai_api_key.clone()); // Use the API key from config
-        let client = Client::with_config(openai_config); // Create the OpenAI client
-        // If you need enterprise features or different host:
-        // let client = Client::builder()
-        //    .api_key(config.openai_api_key.clone())
-        //    .base_url("...")
-        //    .build();
-        let system_prompt = config.openai_system_prompt.clone(); // Load system prompt from config
-        tracing::info!("OpenAI client initialized with system prompt: {}", system_prompt); // Log the system prompt
-        Self { client /*, config: config.clone() */, system_prompt } // Store the client and system prompt
+impl AiClient {
+    // Pick the provider named in the config (see config::load_ai_config)
+    pub fn new(config: &AiConfig) -> Self {
+        let provider: Arc<dyn AiProvider> = match config.provider {
+            AiProviderKind::OpenAi | AiProviderKind::OpenAiCompatible => Arc::new(openai::OpenAiProvider::new(config)),
+            AiProviderKind::Mock => Arc::new(mock::MockProvider),
+        };
+        tracing::info!("AI provider {:?} with model {} initialized", config.provider, config.model);
+        Self { provider, system_prompt: config.system_prompt.clone(), context_tokens: config.context_tokens, max_tokens: config.max_tokens, quotas: config.quotas }
     }
 
-    // Send a chat completion request
+    pub fn context_tokens(&self) -> usize {
+        self.context_tokens
+    }
+
+    pub fn max_tokens(&self) -> Option<u32> {
+        self.max_tokens
+    }
+
+    pub fn quotas(&self) -> AiQuotas {
+        self.quotas
+    }
+
+    // Build the opening messages of a conversation
     // prompt_text: the main text instruction from the user
     // image_data_base64: vector of Base64 encoded image strings + their mime types
-    pub async fn create_chat_completion(
+    // context: extra system instructions for this request (e.g. the current time), may be empty
+    // history: earlier prompts and answers of the thread, oldest first
+    pub fn initial_messages(
         &self,
         prompt_text: &str,
         image_data_base64: Vec<(String, String)>, // Vec<(base64_string, mime_type)>
-    ) -> Result<CreateChatCompletionResponse, AppError> {
-
-        // let model = if image_data_base64.is_empty() {
-        //     // Use a text-only model if no images are provided
-        //     "gpt-4-turbo-preview" // Or "gpt-4", or "gpt-3.5-turbo"
-        // } else {
-        //     // Use a vision model if images are provided
-        //     "gpt-4-vision-preview"
-        // };
-
-        let model = "gpt-4.1";
-
-        // Build the user message content
-        let mut content_parts: Vec<ChatCompletionRequestUserMessageContentPart> = vec![];
-
-        // Add the text part
-        if !prompt_text.trim().is_empty() {
-             content_parts.push(ChatCompletionRequestUserMessageContentPart::Text(
-                ChatCompletionRequestMessageContentPartText {
-                    text: prompt_text.to_string(),
-                }
-            ));
-        }
-
-        // Add image parts if any
-        for (base64_string, mime_type) in image_data_base64 {
-            // Ensure base64 string includes the data URL prefix
-            let data_url = format!("data:{};base64,{}", mime_type, base64_string);
-             content_parts.push(ChatCompletionRequestUserMessageContentPart::ImageUrl(
-                ChatCompletionRequestMessageContentPartImage { image_url: ImageUrl {
-                    url: data_url,
-                    detail: None, // Use default detail level
-                } }
-            ));
+        context: &str,
+        history: Vec<AiMessage>,
+    ) -> Vec<AiMessage> {
+        let mut system_prompt = self.system_prompt.to_string();
+        if !context.trim().is_empty() {
+            system_prompt = format!("{}\n\n{}", system_prompt, context);
         }
 
         // Handle case where only images are provided without text (add a default prompt)
-        if content_parts.is_empty() {
-             // This shouldn't happen if prompt_text is handled, but good defense
-             content_parts.push(ChatCompletionRequestUserMessageContentPart::Text(
-                ChatCompletionRequestMessageContentPartText {
-                    text: "Analyze the provided image(s).".to_string(),
-                }
-            ));
-        }
-
-
-        let messages = vec![
-            // System prompt
-            ChatCompletionRequestMessage::System(
-                ChatCompletionRequestSystemMessage {
-                    content: ChatCompletionRequestSystemMessageContent::Text(self.system_prompt.to_string()),
-                    name: Some("system".to_string())
-                }
-            ),
-            // User message (can contain text and images)
-            ChatCompletionRequestMessage::User(
-                ChatCompletionRequestUserMessage {
-                    content: ChatCompletionRequestUserMessageContent::Array(content_parts),
-                    name: None
-                }
-            ),
-            // Add assistant messages for conversation history if needed (not in this basic request)
-        ];
-
-
-        let request = CreateChatCompletionRequest {
-            model: model.to_string(),
-            messages,
-            // max_tokens: Some(1000), // Limit response length (optional)
-            // Other parameters like temperature, top_p, etc. can be added
-            ..Default::default() // Use default for other fields
+        let text = if prompt_text.trim().is_empty() {
+            "Analyze the provided image(s).".to_string()
+        } else {
+            prompt_text.to_string()
         };
 
-        // Call the OpenAI API
-        let response = self.client.chat().create(request).await?; // Propagates async_openai::Error
+        let mut messages = vec![AiMessage::System(system_prompt)];
+        messages.extend(history);
+        messages.push(AiMessage::User { text, images: image_data_base64 }); // Can contain text and images
+        messages
+    }
 
-        Ok(response)
+    // Send the conversation so far and the tools the model may call.
+    // Returns the model's reply: either the final answer or the tool calls it wants to make.
+    pub async fn complete_with_tools(&self, messages: &[AiMessage], tools: &[AiTool]) -> Result<AiReply, AppError> {
+        self.provider.complete(messages, tools).await
+    }
+
+    // Same as complete_with_tools, streaming the reply's text to on_delta
+    pub async fn complete_with_tools_stream(
+        &self,
+        messages: &[AiMessage],
+        tools: &[AiTool],
+        on_delta: &OnDelta<'_>,
+    ) -> Result<AiReply, AppError> {
+        self.provider.complete_stream(messages, tools, on_delta).await
     }
 }
\ No newline at end of file
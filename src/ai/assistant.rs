@@ -0,0 +1,272 @@
+use chrono::Utc;
+use chrono_tz::Tz;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use crate::{
+    ai::{
+        context::calendar_summary,
+        usage::{estimate_turn_tokens, hold_tokens, record_usage, reserve_request},
+        AiMessage, AiTool, AiUsage,
+    },
+    errors::AppError,
+    handlers::{
+        category_handler::load_categories,
+        deadline_handler::validate_new_deadline,
+        event_handler::validate_new_event,
+        scheduling_handler::suggest_times,
+    },
+    models::{
+        ai::AiProposal,
+        deadline::CreateDeadlinePayload,
+        event::CreateEventPayload,
+        scheduling::SuggestTimesPayload,
+    },
+    state::AppState,
+};
+
+const MAX_TOOL_ROUNDS: usize = 8; // Model turns per request before giving up
+
+// How the assistant should use the tools; appended to the configured system prompt
+const TOOL_INSTRUCTIONS: &str = "You can read the user's calendar and propose new items with the tools. \
+Call create_event or create_deadline once for every item the user asks for or that appears in the attached images \
+(e.g. each exam, lecture series or assignment of a syllabus). These calls only propose items, the user confirms \
+them afterwards. Use list_categories to pick a fitting category ID and find_free_time to schedule work in free slots. \
+Write all timestamps in RFC 3339, use RRULEs for recurring events, and summarize the proposed items in your answer.";
+
+// Progress of a streamed answer
+pub enum AssistantEvent {
+    Delta(String),    // Text of the answer as the model writes it
+    ToolCall(String), // Name of a tool the assistant is about to run
+}
+
+pub struct AssistantOutcome {
+    pub response: String,
+    pub proposal: AiProposal,
+    pub usage: AiUsage,                // Summed over all model turns
+    pub finish_reason: Option<String>, // Of the final turn
+}
+
+// --- The tools the model may call, with JSON Schemas matching the create payloads ---
+fn tool(name: &str, description: &str, parameters: Value) -> AiTool {
+    AiTool { name: name.to_string(), description: description.to_string(), parameters }
+}
+
+fn tool_definitions() -> Vec<AiTool> {
+    vec![
+        tool(
+            "list_categories",
+            "List the user's categories (ID, name, color). Every event and deadline needs one.",
+            json!({ "type": "object", "properties": {} }),
+        ),
+        tool(
+            "find_free_time",
+            "Find free slots of the given length in the user's calendar, best first. Considers events and deadline workload.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "durationMinutes": { "type": "integer", "minimum": 5, "maximum": 1440 },
+                    "from": { "type": "string", "description": "RFC 3339 start of the search window" },
+                    "to": { "type": "string", "description": "RFC 3339 end of the search window" },
+                    "workingHours": {
+                        "type": "object",
+                        "properties": {
+                            "timeZone": { "type": "string", "description": "IANA name, default UTC" },
+                            "start": { "type": "string", "description": "Local HH:MM, default 09:00" },
+                            "end": { "type": "string", "description": "Local HH:MM, default 17:00" },
+                            "days": { "type": "array", "items": { "type": "string", "enum": ["mon", "tue", "wed", "thu", "fri", "sat", "sun"] } }
+                        }
+                    }
+                },
+                "required": ["durationMinutes", "from", "to"]
+            }),
+        ),
+        tool(
+            "create_event",
+            "Propose a new event. Returns an error to fix if it is invalid.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string", "maxLength": 255 },
+                    "categoryId": { "type": "integer" },
+                    "description": { "type": "string", "maxLength": 1000 },
+                    "startTime": { "type": "string", "description": "RFC 3339" },
+                    "endTime": { "type": "string", "description": "RFC 3339; for recurring events the end of the first occurrence" },
+                    "location": { "type": "string", "maxLength": 255 },
+                    "rrule": { "type": "string", "description": "iCalendar RRULE without the RRULE: prefix, e.g. FREQ=WEEKLY;BYDAY=MO;COUNT=12" }
+                },
+                "required": ["title", "categoryId", "startTime", "endTime"]
+            }),
+        ),
+        tool(
+            "create_deadline",
+            "Propose a new deadline. Returns an error to fix if it is invalid.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string", "maxLength": 255 },
+                    "categoryId": { "type": "integer" },
+                    "description": { "type": "string", "maxLength": 1000 },
+                    "dueDate": { "type": "string", "description": "RFC 3339" },
+                    "virtualDueDate": { "type": "string", "description": "RFC 3339, an earlier personal target date" },
+                    "priority": { "type": "string", "enum": ["normal", "important", "urgent"] },
+                    "workloadMagnitude": { "type": "integer", "description": "Estimated effort, together with workloadUnit" },
+                    "workloadUnit": { "type": "string", "enum": ["minutes", "hours", "days"] }
+                },
+                "required": ["title", "categoryId", "dueDate", "priority"]
+            }),
+        ),
+    ]
+}
+
+// --- Run the assistant on a prompt until it answers without calling tools ---
+// Tools run against the user's data. Items are only collected into the proposal, nothing is created here.
+// history holds the earlier prompts and answers of the thread, oldest first.
+// With on_event, the answer is streamed from the provider and reported as it arrives.
+pub async fn run_assistant(
+    state: &AppState,
+    user_id: i32,
+    prompt_text: &str,
+    image_data: Vec<(String, String)>, // Vec<(base64_string, mime_type)>
+    time_zone: Tz,
+    history: Vec<AiMessage>,
+    on_event: Option<&(dyn Fn(AssistantEvent) + Send + Sync)>,
+) -> Result<AssistantOutcome, AppError> {
+    let ai_client = state.ai_client.as_ref()
+        .ok_or_else(|| AppError::InternalServerError("AI assistant is not configured".to_string()))?; // Route is not mounted then
+
+    let mut context = format!(
+        "{}\n\nThe current time is {} (UTC). The user's time zone is {}.",
+        TOOL_INSTRUCTIONS,
+        Utc::now().to_rfc3339(),
+        time_zone.name()
+    );
+    if ai_client.context_tokens() > 0 {
+        let summary = calendar_summary(&state.pool, user_id, time_zone, ai_client.context_tokens()).await?;
+        context = format!("{}\n\n{}", context, summary);
+    }
+    let mut messages = ai_client.initial_messages(prompt_text, image_data, &context, history);
+    let tools = tool_definitions();
+    let mut proposal = AiProposal::default();
+    let mut usage = AiUsage::default();
+
+    for round in 0..MAX_TOOL_ROUNDS {
+        // Charge an estimate until the provider reports the turn's usage; the first turn checks the quotas
+        let estimate = estimate_turn_tokens(&messages, &tools, ai_client.max_tokens());
+        let hold = if round == 0 {
+            reserve_request(&state.pool, user_id, ai_client.quotas(), estimate).await?
+        } else {
+            hold_tokens(&state.pool, user_id, estimate).await?
+        };
+
+        let reply = match on_event {
+            Some(on_event) => {
+                let on_delta = |text: &str| on_event(AssistantEvent::Delta(text.to_string()));
+                ai_client.complete_with_tools_stream(&messages, &tools, &on_delta).await
+            }
+            None => ai_client.complete_with_tools(&messages, &tools).await,
+        };
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                record_usage(&state.pool, user_id, hold, Some(AiUsage::default())).await?; // Failed turns are not charged
+                return Err(e);
+            }
+        };
+        // Every turn is billed, even if a later one fails
+        record_usage(&state.pool, user_id, hold, reply.usage).await?;
+        usage.add(reply.usage.unwrap_or_default()); // Not every compatible server reports it
+
+        if reply.tool_calls.is_empty() {
+            let response = reply.content.unwrap_or_else(|| "AI returned no text response.".to_string());
+            return Ok(AssistantOutcome { response, proposal, usage, finish_reason: reply.finish_reason });
+        }
+
+        // Echo the calls back, followed by one result per call
+        let tool_calls = reply.tool_calls.clone();
+        messages.push(AiMessage::Assistant { content: reply.content, tool_calls: reply.tool_calls });
+        for call in tool_calls {
+            if let Some(on_event) = on_event {
+                on_event(AssistantEvent::ToolCall(call.name.clone()));
+            }
+            let result = run_tool(state, user_id, &call.name, &call.arguments, &mut proposal).await?;
+            messages.push(AiMessage::Tool { call_id: call.id, content: result.to_string() });
+        }
+    }
+
+    tracing::warn!("AI assistant for user {} still calling tools after {} rounds", user_id, MAX_TOOL_ROUNDS);
+    Err(AppError::AiProviderError(format!("Assistant did not finish within {} tool rounds", MAX_TOOL_ROUNDS)))
+}
+
+// Execute one tool call. Mistakes the model can fix (bad arguments, validation, unknown category)
+// are returned to it as {"error": ...}; anything else fails the request.
+async fn run_tool(
+    state: &AppState,
+    user_id: i32,
+    name: &str,
+    arguments: &str,
+    proposal: &mut AiProposal,
+) -> Result<Value, AppError> {
+    tracing::info!("AI assistant for user {} calls {}", user_id, name);
+    match call_tool(state, user_id, name, arguments, proposal).await {
+        Ok(value) => Ok(value),
+        Err(AppError::ValidationFailed(errors)) if errors.is_empty() => {
+            // parse_timestamp reports bad timestamps without details
+            Ok(json!({ "error": "Invalid timestamp, use RFC 3339 (e.g. 2025-05-01T09:00:00Z)" }))
+        }
+        Err(AppError::ValidationFailed(errors)) => Ok(json!({ "error": format!("Validation failed: {}", errors) })),
+        Err(AppError::CategoryNotFound) => Ok(json!({ "error": "Category not found, use a categoryId from list_categories" })),
+        Err(e) => Err(e),
+    }
+}
+
+async fn call_tool(
+    state: &AppState,
+    user_id: i32,
+    name: &str,
+    arguments: &str,
+    proposal: &mut AiProposal,
+) -> Result<Value, AppError> {
+    match name {
+        "list_categories" => {
+            let categories: Vec<Value> = load_categories(&state.pool, user_id)
+                .await?
+                .into_iter()
+                .filter(|category| category.deleted_at.is_none())
+                .map(|category| json!({ "categoryId": category.category_id, "name": category.name, "color": category.color }))
+                .collect();
+            Ok(json!({ "categories": categories }))
+        }
+        "find_free_time" => {
+            let mut payload: SuggestTimesPayload = parse_arguments(arguments)?;
+            payload.attendee_emails = None; // Only the caller's own calendar
+            let response = suggest_times(&state.pool, user_id, payload).await?;
+            Ok(json!({ "durationMinutes": response.duration_minutes, "slots": response.suggestions }))
+        }
+        "create_event" => {
+            let payload: CreateEventPayload = parse_arguments(arguments)?;
+            let mut conn = state.pool.acquire().await?;
+            validate_new_event(&mut conn, user_id, &payload).await?;
+            proposal.events.push(payload);
+            Ok(json!({ "proposed": true }))
+        }
+        "create_deadline" => {
+            let payload: CreateDeadlinePayload = parse_arguments(arguments)?;
+            let mut conn = state.pool.acquire().await?;
+            validate_new_deadline(&mut conn, user_id, &payload).await?;
+            proposal.deadlines.push(payload);
+            Ok(json!({ "proposed": true }))
+        }
+        _ => Ok(json!({ "error": format!("Unknown tool: {}", name) })),
+    }
+}
+
+// Tool arguments are JSON generated by the model; malformed ones count as a validation error
+fn parse_arguments<T: DeserializeOwned>(arguments: &str) -> Result<T, AppError> {
+    serde_json::from_str(arguments).map_err(|e| {
+        let mut err = validator::ValidationError::new("invalid_arguments");
+        err.message = Some(e.to_string().into());
+        let mut errors = validator::ValidationErrors::new();
+        errors.add("arguments", err);
+        AppError::ValidationFailed(errors)
+    })
+}
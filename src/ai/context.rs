@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use crate::{
+    errors::AppError,
+    handlers::category_handler::load_categories,
+    models::enums::{DeadlinePriorityLevel, WorkloadUnitType},
+    utils::freebusy::load_busy_occurrences,
+};
+
+// --- Compact summary of the user's calendar, added to the assistant's context on every request ---
+// Saves the model a round of tool calls for questions like "what's due this week?".
+const EVENT_DAYS: i64 = 14; // Upcoming events from now
+const DEADLINE_DAYS: i64 = 30; // Upcoming deadlines from now
+const OVERDUE_DAYS: i64 = 7; // Recently missed deadlines are still worth mentioning
+const CHARS_PER_TOKEN: usize = 4; // Rough estimate, good enough for budgeting
+const TIME_FORMAT: &str = "%a %Y-%m-%d %H:%M";
+
+// Categories, upcoming events and deadlines as plain text lines, in the user's time zone.
+// Each section gets a share of the budget; what doesn't fit is counted instead of listed.
+pub async fn calendar_summary(pool: &PgPool, user_id: i32, tz: Tz, token_budget: usize) -> Result<String, AppError> {
+    let now = Utc::now();
+    let budget = token_budget * CHARS_PER_TOKEN;
+    let local = |time: DateTime<Utc>| time.with_timezone(&tz).format(TIME_FORMAT).to_string();
+
+    // Categories
+    let categories: Vec<_> = load_categories(pool, user_id)
+        .await?
+        .into_iter()
+        .filter(|category| category.deleted_at.is_none())
+        .collect();
+    let category_names: HashMap<i32, &str> = categories.iter().map(|c| (c.category_id, c.name.as_str())).collect();
+    let category_lines: Vec<String> = categories
+        .iter()
+        .map(|category| format!("#{} {}", category.category_id, category.name))
+        .collect();
+
+    // Events, including accepted invitations and expanded recurring series
+    let (events, occurrences) = load_busy_occurrences(pool, user_id, (now, now + Duration::days(EVENT_DAYS))).await?;
+    let events_by_id: HashMap<i32, _> = events.iter().map(|e| (e.event_id, e)).collect();
+    let event_lines: Vec<String> = occurrences
+        .iter()
+        .filter_map(|occurrence| {
+            let event = events_by_id.get(&occurrence.event_id)?;
+            let title = occurrence.title.as_deref().unwrap_or(&event.title);
+            let category = if event.user_id == user_id {
+                category_names.get(&event.category_id).copied().unwrap_or("?").to_string()
+            } else {
+                "invited".to_string()
+            };
+            let mut line = format!(
+                "{} - {} {} ({})",
+                local(occurrence.occurrence_start),
+                local(occurrence.occurrence_end),
+                title,
+                category
+            );
+            if let Some(location) = occurrence.location.as_deref().or(event.location.as_deref()).filter(|l| !l.is_empty()) {
+                line.push_str(&format!(" at {}", location));
+            }
+            Some(line)
+        })
+        .collect();
+
+    // Deadlines
+    let deadlines = sqlx::query!(
+        r#"
+        SELECT category_id, title, due_date, priority as "priority!: DeadlinePriorityLevel",
+               workload_magnitude, workload_unit as "workload_unit: WorkloadUnitType"
+        FROM deadlines
+        WHERE user_id = $1 AND deleted_at IS NULL AND due_date >= $2 AND due_date < $3
+        ORDER BY due_date
+        "#,
+        user_id,
+        now - Duration::days(OVERDUE_DAYS),
+        now + Duration::days(DEADLINE_DAYS)
+    )
+    .fetch_all(pool)
+    .await?;
+    let deadline_lines: Vec<String> = deadlines
+        .iter()
+        .map(|deadline| {
+            let mut line = format!(
+                "{}{} {} ({}, {:?})",
+                if deadline.due_date < now { "OVERDUE " } else { "" },
+                local(deadline.due_date),
+                deadline.title,
+                category_names.get(&deadline.category_id).copied().unwrap_or("?"),
+                deadline.priority
+            );
+            if let (Some(magnitude), Some(unit)) = (deadline.workload_magnitude, deadline.workload_unit) {
+                line.push_str(&format!(", about {} {:?}", magnitude, unit).to_lowercase());
+            }
+            line
+        })
+        .collect();
+
+    let category_section = section("Categories", &category_lines, budget * 15 / 100);
+    let deadline_section = section(
+        &format!("Deadlines (overdue up to {} days, due in the next {} days)", OVERDUE_DAYS, DEADLINE_DAYS),
+        &deadline_lines,
+        budget * 40 / 100,
+    );
+    let event_budget = budget.saturating_sub(category_section.len() + deadline_section.len());
+    let event_section = section(&format!("Events in the next {} days", EVENT_DAYS), &event_lines, event_budget);
+
+    Ok(format!(
+        "The user's calendar, times in {}:\n{}\n{}\n{}",
+        tz.name(),
+        category_section,
+        event_section,
+        deadline_section
+    ))
+}
+
+// A heading followed by as many lines as fit into max_chars
+fn section(heading: &str, lines: &[String], max_chars: usize) -> String {
+    let mut text = format!("{}:\n", heading);
+    if lines.is_empty() {
+        text.push_str("- none\n");
+        return text;
+    }
+    for (index, line) in lines.iter().enumerate() {
+        if text.len() + line.len() + 3 > max_chars {
+            text.push_str(&format!("- ... {} more not shown\n", lines.len() - index));
+            break;
+        }
+        text.push_str(&format!("- {}\n", line));
+    }
+    text
+}
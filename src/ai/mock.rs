@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use crate::ai::{AiMessage, AiProvider, AiReply, AiTool, AiToolCall, AiUsage, OnDelta};
+use crate::errors::AppError;
+
+// Deterministic stand-in for a model (AI_PROVIDER=mock), so the assistant can be exercised without a key.
+// Lines of the latest user prompt of the form `/call <tool> <json arguments>` are issued as tool calls;
+// once their results are in, or if there are none, it answers with a fixed summary.
+// Usage is estimated at four characters per token; streamed replies arrive word by word.
+pub struct MockProvider;
+
+fn reply(messages: &[AiMessage], content: Option<String>, tool_calls: Vec<AiToolCall>) -> AiReply {
+    let prompt_chars: usize = messages.iter().map(AiMessage::text_len).sum();
+    let completion_chars = content.as_ref().map_or(0, String::len) + tool_calls.iter().map(|call| call.arguments.len()).sum::<usize>();
+    let (prompt_tokens, completion_tokens) = (prompt_chars.div_ceil(4) as u32, completion_chars.div_ceil(4) as u32);
+
+    AiReply {
+        finish_reason: Some(if tool_calls.is_empty() { "stop" } else { "tool_calls" }.to_string()),
+        usage: Some(AiUsage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }),
+        content,
+        tool_calls,
+    }
+}
+
+#[async_trait]
+impl AiProvider for MockProvider {
+    async fn complete(&self, messages: &[AiMessage], tools: &[AiTool]) -> Result<AiReply, AppError> {
+        // Everything after the latest user message belongs to the current turn
+        let turn_start = messages
+            .iter()
+            .rposition(|message| matches!(message, AiMessage::User { .. }))
+            .ok_or_else(|| AppError::AiProviderError("Mock provider got no user message".to_string()))?;
+        let (text, image_count) = match &messages[turn_start] {
+            AiMessage::User { text, images } => (text.as_str(), images.len()),
+            _ => unreachable!(), // Found by rposition above
+        };
+        let results: Vec<&str> = messages[turn_start..]
+            .iter()
+            .filter_map(|message| match message {
+                AiMessage::Tool { content, .. } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        if results.is_empty() {
+            let tool_calls: Vec<AiToolCall> = text
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("/call "))
+                .enumerate()
+                .map(|(index, call)| {
+                    let (name, arguments) = call.trim().split_once(' ').unwrap_or((call.trim(), "{}"));
+                    AiToolCall { id: format!("mock_call_{}", index + 1), name: name.to_string(), arguments: arguments.trim().to_string() }
+                })
+                .filter(|call| tools.iter().any(|tool| tool.name == call.name))
+                .collect();
+            if !tool_calls.is_empty() {
+                return Ok(reply(messages, None, tool_calls));
+            }
+            let content = format!("Mock reply to \"{}\" with {} image(s).", text.trim(), image_count);
+            return Ok(reply(messages, Some(content), Vec::new()));
+        }
+
+        let content = format!("Mock reply after {} tool call(s): {}", results.len(), results.join(" "));
+        Ok(reply(messages, Some(content), Vec::new()))
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[AiMessage],
+        tools: &[AiTool],
+        on_delta: &OnDelta<'_>,
+    ) -> Result<AiReply, AppError> {
+        let reply = self.complete(messages, tools).await?;
+        for word in reply.content.as_deref().unwrap_or_default().split_inclusive(' ') {
+            on_delta(word);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await; // Paced like a real model
+        }
+        Ok(reply)
+    }
+}
@@ -0,0 +1,208 @@
+use async_openai::{
+    config::OpenAIConfig, types::{
+        ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessage, ChatCompletionRequestAssistantMessageContent,
+        ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPartImage, ChatCompletionRequestMessageContentPartText,
+        ChatCompletionRequestSystemMessage, ChatCompletionRequestSystemMessageContent, ChatCompletionRequestToolMessage,
+        ChatCompletionRequestToolMessageContent, ChatCompletionRequestUserMessage, ChatCompletionRequestUserMessageContent,
+        ChatCompletionRequestUserMessageContentPart, ChatCompletionStreamOptions, ChatCompletionTool, ChatCompletionToolChoiceOption,
+        ChatCompletionToolType, CompletionUsage, CreateChatCompletionRequest, FinishReason, FunctionCall, FunctionObject, ImageUrl
+    }, Client
+};
+use async_trait::async_trait;
+use tokio_stream::StreamExt;
+use crate::ai::{AiMessage, AiProvider, AiReply, AiTool, AiToolCall, AiUsage, OnDelta};
+use crate::config::{AiConfig, AiProviderKind};
+use crate::errors::AppError;
+
+// Chat completions through async_openai, against api.openai.com or the AI_BASE_URL of an
+// OpenAI-compatible server (Ollama, vLLM, ...)
+pub struct OpenAiProvider {
+    client: Client<OpenAIConfig>,
+    model: String,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    legacy_max_tokens: bool, // Send max_tokens instead of max_completion_tokens, which not every compatible server knows
+}
+
+impl OpenAiProvider {
+    pub fn new(config: &AiConfig) -> Self {
+        let mut openai_config = OpenAIConfig::new()
+            .with_api_key(config.api_key.clone().unwrap_or_default()); // Local servers usually ignore the key
+        if let Some(base_url) = &config.base_url {
+            openai_config = openai_config.with_api_base(base_url);
+        }
+
+        Self {
+            client: Client::with_config(openai_config),
+            model: config.model.clone(),
+            temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            legacy_max_tokens: config.provider == AiProviderKind::OpenAiCompatible,
+        }
+    }
+
+    fn request(&self, messages: &[AiMessage], tools: &[AiTool]) -> CreateChatCompletionRequest {
+        let has_tools = !tools.is_empty();
+
+        #[allow(deprecated)] // max_tokens is still what most OpenAI-compatible servers read
+        CreateChatCompletionRequest {
+            model: self.model.clone(),
+            messages: messages.iter().map(to_request_message).collect(),
+            tools: has_tools.then(|| tools.iter().map(to_tool).collect()),
+            tool_choice: has_tools.then_some(ChatCompletionToolChoiceOption::Auto),
+            temperature: self.temperature,
+            max_tokens: self.max_tokens.filter(|_| self.legacy_max_tokens),
+            max_completion_tokens: self.max_tokens.filter(|_| !self.legacy_max_tokens),
+            ..Default::default() // Use default for other fields
+        }
+    }
+}
+
+#[async_trait]
+impl AiProvider for OpenAiProvider {
+    async fn complete(&self, messages: &[AiMessage], tools: &[AiTool]) -> Result<AiReply, AppError> {
+        let request = self.request(messages, tools);
+
+        // Call the API
+        let response = self.client.chat().create(request).await?; // Propagates async_openai::Error
+
+        // A single choice is requested
+        let choice = response.choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::AiProviderError("AI provider returned no choices".to_string()))?;
+        let message = choice.message;
+
+        Ok(AiReply {
+            content: message.content.filter(|text| !text.is_empty()),
+            tool_calls: message.tool_calls
+                .unwrap_or_default()
+                .into_iter()
+                .map(|call| AiToolCall { id: call.id, name: call.function.name, arguments: call.function.arguments })
+                .collect(),
+            finish_reason: choice.finish_reason.map(finish_reason_name),
+            usage: response.usage.map(to_usage),
+        })
+    }
+
+    async fn complete_stream(
+        &self,
+        messages: &[AiMessage],
+        tools: &[AiTool],
+        on_delta: &OnDelta<'_>,
+    ) -> Result<AiReply, AppError> {
+        let mut request = self.request(messages, tools);
+        request.stream_options = Some(ChatCompletionStreamOptions { include_usage: true }); // Usage arrives in a last, choice-less chunk
+
+        // Dropping the stream (e.g. when the client disconnects) closes the upstream connection
+        let mut stream = self.client.chat().create_stream(request).await?;
+
+        let mut reply = AiReply::default();
+        let mut content = String::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if let Some(usage) = chunk.usage {
+                reply.usage = Some(to_usage(usage));
+            }
+            let Some(choice) = chunk.choices.into_iter().next() else { continue };
+
+            if let Some(text) = choice.delta.content.filter(|text| !text.is_empty()) {
+                on_delta(&text);
+                content.push_str(&text);
+            }
+            // Tool calls arrive in fragments: id and name first, then the arguments in pieces
+            for fragment in choice.delta.tool_calls.unwrap_or_default() {
+                let index = fragment.index as usize;
+                while reply.tool_calls.len() <= index {
+                    reply.tool_calls.push(AiToolCall { id: String::new(), name: String::new(), arguments: String::new() });
+                }
+                let call = &mut reply.tool_calls[index];
+                if let Some(id) = fragment.id {
+                    call.id = id;
+                }
+                if let Some(function) = fragment.function {
+                    call.name.push_str(&function.name.unwrap_or_default());
+                    call.arguments.push_str(&function.arguments.unwrap_or_default());
+                }
+            }
+            if let Some(reason) = choice.finish_reason {
+                reply.finish_reason = Some(finish_reason_name(reason));
+            }
+        }
+
+        reply.content = (!content.is_empty()).then_some(content);
+        Ok(reply)
+    }
+}
+
+fn to_usage(usage: CompletionUsage) -> AiUsage {
+    AiUsage { prompt_tokens: usage.prompt_tokens, completion_tokens: usage.completion_tokens, total_tokens: usage.total_tokens }
+}
+
+fn finish_reason_name(reason: FinishReason) -> String {
+    match reason {
+        FinishReason::Stop => "stop",
+        FinishReason::Length => "length",
+        FinishReason::ToolCalls => "tool_calls",
+        FinishReason::ContentFilter => "content_filter",
+        FinishReason::FunctionCall => "function_call",
+    }
+    .to_string()
+}
+
+fn to_request_message(message: &AiMessage) -> ChatCompletionRequestMessage {
+    match message {
+        AiMessage::System(text) => ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+            content: ChatCompletionRequestSystemMessageContent::Text(text.clone()),
+            name: None,
+        }),
+        AiMessage::User { text, images } => {
+            let mut content_parts = vec![ChatCompletionRequestUserMessageContentPart::Text(
+                ChatCompletionRequestMessageContentPartText { text: text.clone() }
+            )];
+            for (base64_string, mime_type) in images {
+                // Images are sent inline as data URLs
+                content_parts.push(ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                    ChatCompletionRequestMessageContentPartImage { image_url: ImageUrl {
+                        url: format!("data:{};base64,{}", mime_type, base64_string),
+                        detail: None, // Use default detail level
+                    } }
+                ));
+            }
+            ChatCompletionRequestMessage::User(ChatCompletionRequestUserMessage {
+                content: ChatCompletionRequestUserMessageContent::Array(content_parts),
+                name: None,
+            })
+        }
+        AiMessage::Assistant { content, tool_calls } => ChatCompletionRequestMessage::Assistant(ChatCompletionRequestAssistantMessage {
+            content: content.clone().map(ChatCompletionRequestAssistantMessageContent::Text),
+            tool_calls: (!tool_calls.is_empty()).then(|| {
+                tool_calls
+                    .iter()
+                    .map(|call| ChatCompletionMessageToolCall {
+                        id: call.id.clone(),
+                        r#type: ChatCompletionToolType::Function,
+                        function: FunctionCall { name: call.name.clone(), arguments: call.arguments.clone() },
+                    })
+                    .collect()
+            }),
+            ..Default::default()
+        }),
+        AiMessage::Tool { call_id, content } => ChatCompletionRequestMessage::Tool(ChatCompletionRequestToolMessage {
+            content: ChatCompletionRequestToolMessageContent::Text(content.clone()),
+            tool_call_id: call_id.clone(),
+        }),
+    }
+}
+
+fn to_tool(tool: &AiTool) -> ChatCompletionTool {
+    ChatCompletionTool {
+        r#type: ChatCompletionToolType::Function,
+        function: FunctionObject {
+            name: tool.name.clone(),
+            description: Some(tool.description.clone()),
+            parameters: Some(tool.parameters.clone()),
+            strict: None,
+        },
+    }
+}
@@ -0,0 +1,206 @@
+use sqlx::PgPool;
+use crate::{
+    ai::AiMessage,
+    errors::AppError,
+    models::ai::{AiProposal, AiThreadInfo, AiThreadMessage, AiThreadResponse},
+};
+
+// --- Conversation threads of the AI assistant (ai_threads, ai_messages) ---
+// Only prompts and final answers are stored; images and intermediate tool calls are not.
+const TITLE_MAX_CHARS: usize = 100;
+const HISTORY_MAX_MESSAGES: i64 = 40;
+const HISTORY_TOKEN_BUDGET: usize = 4000; // Earlier messages resent with a new prompt, newest first
+const CHARS_PER_TOKEN: usize = 4; // Rough estimate, good enough for budgeting
+
+// Earlier messages of a thread, oldest first, as far as they fit into the history budget
+pub async fn load_history(pool: &PgPool, user_id: i32, thread_id: i32) -> Result<Vec<AiMessage>, AppError> {
+    let owned = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM ai_threads WHERE thread_id = $1 AND user_id = $2) as "exists!""#,
+        thread_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    if !owned {
+        return Err(AppError::ThreadNotFound);
+    }
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT role, content, image_count
+        FROM ai_messages
+        WHERE thread_id = $1
+        ORDER BY message_id DESC
+        LIMIT $2
+        "#,
+        thread_id,
+        HISTORY_MAX_MESSAGES
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut budget = HISTORY_TOKEN_BUDGET * CHARS_PER_TOKEN;
+    let mut history = Vec::new();
+    for row in rows {
+        if row.content.len() > budget {
+            break;
+        }
+        budget -= row.content.len();
+        history.push(match row.role.as_str() {
+            "user" if row.image_count > 0 => AiMessage::User {
+                text: format!("{}\n[{} image(s) were attached to this message]", row.content, row.image_count),
+                images: Vec::new(),
+            },
+            "user" => AiMessage::User { text: row.content, images: Vec::new() },
+            _ => AiMessage::Assistant { content: Some(row.content), tool_calls: Vec::new() },
+        });
+    }
+    history.reverse();
+
+    // Start with a prompt, not with an answer whose question was cut off
+    while matches!(history.first(), Some(AiMessage::Assistant { .. })) {
+        history.remove(0);
+    }
+    Ok(history)
+}
+
+// Store a prompt and the assistant's answer, in a new thread if thread_id is None.
+// Returns the thread ID.
+pub async fn save_exchange(
+    pool: &PgPool,
+    user_id: i32,
+    thread_id: Option<i32>,
+    prompt_text: &str,
+    image_count: usize,
+    response: &str,
+    proposal: &AiProposal,
+) -> Result<i32, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let thread_id = match thread_id {
+        Some(thread_id) => sqlx::query_scalar!(
+            "UPDATE ai_threads SET updated_at = NOW() WHERE thread_id = $1 AND user_id = $2 RETURNING thread_id",
+            thread_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::ThreadNotFound)?, // Deleted while the assistant was answering
+        None => {
+            let title = thread_title(prompt_text);
+            sqlx::query_scalar!(
+                "INSERT INTO ai_threads (user_id, title) VALUES ($1, $2) RETURNING thread_id",
+                user_id,
+                title
+            )
+            .fetch_one(&mut *tx)
+            .await?
+        }
+    };
+
+    let proposal_json = if proposal.events.is_empty() && proposal.deadlines.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_value(proposal).map_err(|e| AppError::InternalServerError(format!("Failed to store proposal: {}", e)))?)
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO ai_messages (thread_id, role, content, image_count, proposal)
+        VALUES ($1, 'user', $2, $3, NULL), ($1, 'assistant', $4, 0, $5)
+        "#,
+        thread_id,
+        prompt_text,
+        image_count as i32,
+        response,
+        proposal_json
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(thread_id)
+}
+
+// First line of the first prompt, shortened
+fn thread_title(prompt_text: &str) -> String {
+    let first_line = prompt_text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or("Image analysis");
+    if first_line.chars().count() <= TITLE_MAX_CHARS {
+        return first_line.to_string();
+    }
+    let shortened: String = first_line.chars().take(TITLE_MAX_CHARS - 3).collect();
+    format!("{}...", shortened.trim_end())
+}
+
+// The user's threads, most recently active first
+pub async fn list_threads(pool: &PgPool, user_id: i32) -> Result<Vec<AiThreadInfo>, AppError> {
+    let threads = sqlx::query_as!(
+        AiThreadInfo,
+        r#"
+        SELECT t.thread_id, t.title, COUNT(m.message_id) as "message_count!", t.created_at, t.updated_at
+        FROM ai_threads t
+        LEFT JOIN ai_messages m ON m.thread_id = t.thread_id
+        WHERE t.user_id = $1
+        GROUP BY t.thread_id
+        ORDER BY t.updated_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(threads)
+}
+
+pub async fn get_thread(pool: &PgPool, user_id: i32, thread_id: i32) -> Result<AiThreadResponse, AppError> {
+    let thread = sqlx::query_as!(
+        AiThreadInfo,
+        r#"
+        SELECT t.thread_id, t.title, COUNT(m.message_id) as "message_count!", t.created_at, t.updated_at
+        FROM ai_threads t
+        LEFT JOIN ai_messages m ON m.thread_id = t.thread_id
+        WHERE t.thread_id = $1 AND t.user_id = $2
+        GROUP BY t.thread_id
+        "#,
+        thread_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::ThreadNotFound)?;
+
+    let messages = sqlx::query_as!(
+        AiThreadMessage,
+        r#"
+        SELECT message_id, role, content, image_count, proposal, created_at
+        FROM ai_messages
+        WHERE thread_id = $1
+        ORDER BY message_id
+        "#,
+        thread_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(AiThreadResponse { thread, messages })
+}
+
+pub async fn delete_thread(pool: &PgPool, user_id: i32, thread_id: i32) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM ai_threads WHERE thread_id = $1 AND user_id = $2",
+        thread_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::ThreadNotFound);
+    }
+    Ok(())
+}
+
+pub async fn delete_all_threads(pool: &PgPool, user_id: i32) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM ai_threads WHERE user_id = $1", user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
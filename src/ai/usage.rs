@@ -0,0 +1,210 @@
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use crate::{
+    ai::{AiMessage, AiTool, AiUsage},
+    config::AiQuotas,
+    errors::AppError,
+    models::ai::{AiUsageDay, AiUsagePeriod, AiUsageResponse},
+};
+
+// --- Token accounting of the AI assistant (ai_usage), per user and UTC day ---
+// Every model turn is charged an estimate of its tokens before it is sent, which is replaced by the usage
+// the provider reports once the reply is in. Parallel prompts thereby see each other's turns when the first
+// turn of a prompt checks the quotas, and a turn that is cut off (e.g. the client closed the stream) stays
+// charged with its estimate. A prompt that starts below its quota runs to the end, so usage can still go
+// over by what that prompt takes beyond its first estimate.
+
+const CHARS_PER_TOKEN: usize = 4; // Rough estimate, good enough for budgeting
+const IMAGE_TOKENS: i64 = 1000; // Per attached image
+const REPLY_TOKENS: i64 = 1000; // Expected reply length when AI_MAX_TOKENS is unset
+
+// Tokens charged for a model turn until its actual usage is known
+#[derive(Debug, Clone, Copy)]
+pub struct TokenHold {
+    date: NaiveDate, // The actual usage is booked on the same day
+    tokens: i64,
+}
+
+// Rough token count of one model turn: the conversation, the tool definitions and the longest reply
+pub fn estimate_turn_tokens(messages: &[AiMessage], tools: &[AiTool], max_tokens: Option<u32>) -> i64 {
+    let message_chars: usize = messages.iter().map(AiMessage::text_len).sum();
+    let tool_chars: usize = tools.iter().map(|tool| tool.name.len() + tool.description.len() + tool.parameters.to_string().len()).sum();
+    let images = messages.iter().map(|message| match message {
+        AiMessage::User { images, .. } => images.len(),
+        _ => 0,
+    });
+    let reply_tokens = max_tokens.map_or(REPLY_TOKENS, i64::from);
+    ((message_chars + tool_chars).div_ceil(CHARS_PER_TOKEN) as i64) + images.sum::<usize>() as i64 * IMAGE_TOKENS + reply_tokens
+}
+
+// Start a prompt: count the request and hold the tokens of its first turn.
+// Fails with AiQuotaExceeded if the user used up the daily or monthly quota; the user's row of the day stays
+// locked from the check until the hold is added, so parallel prompts are checked one after another.
+pub async fn reserve_request(pool: &PgPool, user_id: i32, quotas: AiQuotas, tokens: i64) -> Result<TokenHold, AppError> {
+    let now = Utc::now();
+    let hold = TokenHold { date: now.date_naive(), tokens };
+    if quotas.daily_tokens.is_none() && quotas.monthly_tokens.is_none() {
+        add_usage(&mut *pool.acquire().await?, user_id, hold.date, 0, 0, tokens, 1).await?;
+        return Ok(hold);
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        "INSERT INTO ai_usage (user_id, usage_date) VALUES ($1, $2) ON CONFLICT (user_id, usage_date) DO NOTHING",
+        user_id,
+        hold.date
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "SELECT user_id FROM ai_usage WHERE user_id = $1 AND usage_date = $2 FOR UPDATE",
+        user_id,
+        hold.date
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    ensure_below_quota(&mut tx, user_id, quotas, now).await?; // Dropping tx rolls back
+    add_usage(&mut tx, user_id, hold.date, 0, 0, tokens, 1).await?;
+    tx.commit().await?;
+    Ok(hold)
+}
+
+// Hold the tokens of a later turn of a running prompt
+pub async fn hold_tokens(pool: &PgPool, user_id: i32, tokens: i64) -> Result<TokenHold, AppError> {
+    let hold = TokenHold { date: Utc::now().date_naive(), tokens };
+    add_usage(&mut *pool.acquire().await?, user_id, hold.date, 0, 0, tokens, 0).await?;
+    Ok(hold)
+}
+
+// Replace the hold of a turn by the usage the provider reported.
+// Without a report (not every compatible server sends one), the estimate stays charged.
+pub async fn record_usage(pool: &PgPool, user_id: i32, hold: TokenHold, usage: Option<AiUsage>) -> Result<(), AppError> {
+    let Some(usage) = usage else {
+        return Ok(());
+    };
+    add_usage(
+        &mut *pool.acquire().await?,
+        user_id,
+        hold.date,
+        i64::from(usage.prompt_tokens),
+        i64::from(usage.completion_tokens),
+        i64::from(usage.total_tokens) - hold.tokens,
+        0,
+    )
+    .await
+}
+
+async fn add_usage(
+    conn: &mut PgConnection,
+    user_id: i32,
+    date: NaiveDate,
+    prompt_tokens: i64,
+    completion_tokens: i64,
+    total_tokens: i64,
+    requests: i32,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO ai_usage (user_id, usage_date, prompt_tokens, completion_tokens, total_tokens, request_count)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (user_id, usage_date) DO UPDATE SET
+            prompt_tokens = ai_usage.prompt_tokens + EXCLUDED.prompt_tokens,
+            completion_tokens = ai_usage.completion_tokens + EXCLUDED.completion_tokens,
+            total_tokens = ai_usage.total_tokens + EXCLUDED.total_tokens,
+            request_count = ai_usage.request_count + EXCLUDED.request_count
+        "#,
+        user_id,
+        date,
+        prompt_tokens,
+        completion_tokens,
+        total_tokens,
+        requests
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+// Fails with AiQuotaExceeded if the user used up the daily or monthly quota.
+// Lets the streaming endpoint answer with a plain error; the binding check is in reserve_request.
+pub async fn check_quota(pool: &PgPool, user_id: i32, quotas: AiQuotas) -> Result<(), AppError> {
+    if quotas.daily_tokens.is_none() && quotas.monthly_tokens.is_none() {
+        return Ok(());
+    }
+    ensure_below_quota(&mut *pool.acquire().await?, user_id, quotas, Utc::now()).await
+}
+
+async fn ensure_below_quota(conn: &mut PgConnection, user_id: i32, quotas: AiQuotas, now: DateTime<Utc>) -> Result<(), AppError> {
+    let today = now.date_naive();
+    let month_start = today.with_day(1).unwrap_or(today);
+
+    let used = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(total_tokens) FILTER (WHERE usage_date = $2), 0)::BIGINT as "today!",
+               COALESCE(SUM(total_tokens), 0)::BIGINT as "month!"
+        FROM ai_usage
+        WHERE user_id = $1 AND usage_date >= $3
+        "#,
+        user_id,
+        today,
+        month_start
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if quotas.daily_tokens.is_some_and(|quota| used.today >= quota) {
+        tracing::info!("User {} reached the daily AI quota", user_id);
+        return Err(AppError::AiQuotaExceeded { period: "Daily", retry_after: seconds_until(now, next_day(today)) });
+    }
+    if quotas.monthly_tokens.is_some_and(|quota| used.month >= quota) {
+        tracing::info!("User {} reached the monthly AI quota", user_id);
+        return Err(AppError::AiQuotaExceeded { period: "Monthly", retry_after: seconds_until(now, next_month(month_start)) });
+    }
+    Ok(())
+}
+
+// Usage of today and the current month, with the quotas and a per-day breakdown of the month
+pub async fn usage_report(pool: &PgPool, user_id: i32, quotas: AiQuotas) -> Result<AiUsageResponse, AppError> {
+    let today = Utc::now().date_naive();
+    let month_start = today.with_day(1).unwrap_or(today);
+
+    let days = sqlx::query_as!(
+        AiUsageDay,
+        r#"
+        SELECT usage_date as date, prompt_tokens, completion_tokens, total_tokens, request_count
+        FROM ai_usage
+        WHERE user_id = $1 AND usage_date >= $2
+        ORDER BY usage_date
+        "#,
+        user_id,
+        month_start
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let used_today = days.iter().filter(|day| day.date == today).map(|day| day.total_tokens).sum();
+    let used_month = days.iter().map(|day| day.total_tokens).sum();
+    Ok(AiUsageResponse {
+        today: period(used_today, quotas.daily_tokens, next_day(today)),
+        month: period(used_month, quotas.monthly_tokens, next_month(month_start)),
+        days,
+    })
+}
+
+fn period(used_tokens: i64, quota: Option<i64>, resets_at: DateTime<Utc>) -> AiUsagePeriod {
+    AiUsagePeriod { used_tokens, quota, remaining: quota.map(|quota| (quota - used_tokens).max(0)), resets_at }
+}
+
+fn next_day(today: NaiveDate) -> DateTime<Utc> {
+    (today + Duration::days(1)).and_time(NaiveTime::MIN).and_utc()
+}
+
+fn next_month(month_start: NaiveDate) -> DateTime<Utc> {
+    let next = month_start.checked_add_months(Months::new(1)).unwrap_or(month_start);
+    next.and_time(NaiveTime::MIN).and_utc()
+}
+
+fn seconds_until(now: DateTime<Utc>, reset: DateTime<Utc>) -> u64 {
+    (reset - now).num_seconds().max(1) as u64
+}
@@ -1,2 +1,7 @@
 pub mod jwt; // JSON Web Token (JWT) module
-pub mod tfa; // Two-Factor Authentication (TFA) module
\ No newline at end of file
+pub mod tfa; // Two-Factor Authentication (TFA) module
+pub mod session; // Sessions with rotating refresh tokens
+pub mod passkey; // Passkeys (WebAuthn)
+pub mod lockout; // Per-account lockout after failed attempts
+pub mod oidc; // Social login with OpenID Connect providers
+pub mod token; // Personal access tokens with scopes
\ No newline at end of file
@@ -3,15 +3,17 @@ use crate::errors::AppError;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation };
 // use jsonwebtoken::{DecodingKey, Validation, Algorithm, decode, TokenData};
 use serde::{Deserialize, Serialize};
-use chrono::Utc;
+use chrono::{Duration, Utc};
+use crate::models::enums::EventInvitationStatus;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i32, // Subject (user_id)
+    pub sid: i32, // Session the token was issued for (see auth::session)
     pub exp: i64, // Expiration timestamp (seconds since epoch) - Use i64 to match Duration::seconds
 }
 
-pub fn create_token(user_id: i32, config: &Config) -> Result<String, AppError> {
+pub fn create_token(user_id: i32, session_id: i32, config: &Config) -> Result<String, AppError> {
 
     let now_ts = Utc::now().timestamp(); // Get current timestamp as i64
     let expiration = now_ts.checked_add(config.jwt_expiration_seconds) // Directly add seconds
@@ -19,6 +21,7 @@ pub fn create_token(user_id: i32, config: &Config) -> Result<String, AppError> {
 
     let claims = Claims {
         sub: user_id,
+        sid: session_id,
         exp: expiration, // Assign the directly calculated i64 timestamp
     };
 
@@ -40,6 +43,65 @@ pub fn validate_token(token: &str, config: &Config) -> Result<Claims, AppError>
         .map_err(AppError::from) // Convert jsonwebtoken errors to AppError
 }
 
+// --- RSVP tokens (one-click links in invitation emails, GET /api/invitations/respond?token=) ---
+// Signed with a key derived from JWT_SECRET, so they can never pass as login tokens (and vice versa).
+const RSVP_TOKEN_VALID_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RsvpClaims {
+    pub invitation_id: i32, // A pending_invitation_id when `pending` is set
+    #[serde(default)]
+    pub pending: bool, // Invitee had no account when invited
+    pub status: EventInvitationStatus, // The response this link records
+    pub exp: i64,
+}
+
+fn rsvp_secret(config: &Config) -> Vec<u8> {
+    format!("{}:rsvp", config.jwt_secret).into_bytes()
+}
+
+pub fn create_rsvp_token(invitation_id: i32, pending: bool, status: EventInvitationStatus, config: &Config) -> Result<String, AppError> {
+    let claims = RsvpClaims {
+        invitation_id,
+        pending,
+        status,
+        exp: (Utc::now() + Duration::days(RSVP_TOKEN_VALID_DAYS)).timestamp(),
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(&rsvp_secret(config))).map_err(AppError::from)
+}
+
+pub fn validate_rsvp_token(token: &str, config: &Config) -> Result<RsvpClaims, AppError> {
+    decode::<RsvpClaims>(token, &DecodingKey::from_secret(&rsvp_secret(config)), &Validation::default())
+        .map(|data| data.claims)
+        .map_err(AppError::from)
+}
+
+// --- TFA challenge tokens (password step of a login with 2FA -> POST /api/auth/verify-tfa) ---
+// Signed with their own derived key, like RSVP tokens. Single use and attempt limits are tracked
+// in the tfa_challenges table (see auth::tfa).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TfaChallengeClaims {
+    pub sub: i32, // user_id that passed the password step
+    pub cid: i32, // challenge_id
+    pub exp: i64,
+}
+
+fn tfa_challenge_secret(config: &Config) -> Vec<u8> {
+    format!("{}:tfa", config.jwt_secret).into_bytes()
+}
+
+pub fn create_tfa_challenge_token(user_id: i32, challenge_id: i32, expires_at: i64, config: &Config) -> Result<String, AppError> {
+    let claims = TfaChallengeClaims { sub: user_id, cid: challenge_id, exp: expires_at };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(&tfa_challenge_secret(config))).map_err(AppError::from)
+}
+
+pub fn validate_tfa_challenge_token(token: &str, config: &Config) -> Result<TfaChallengeClaims, AppError> {
+    decode::<TfaChallengeClaims>(token, &DecodingKey::from_secret(&tfa_challenge_secret(config)), &Validation::default())
+        .map(|data| data.claims)
+        .map_err(|_| AppError::TfaChallengeInvalid)
+}
+
 // pub fn validate_token(token: &str, config: &Config) -> Result<Claims, AppError> {
 //     let decoding_key = DecodingKey::from_secret(config.jwt_secret.as_ref());
 
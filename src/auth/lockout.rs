@@ -0,0 +1,150 @@
+use sqlx::PgPool;
+use crate::{errors::AppError, state::AppState};
+
+// --- Per-account lockout after failed attempts (auth_failures table) ---
+// Each action is counted separately. The first FREE_FAILURES failures are free, after that the
+// account is locked for that action, starting at BASE_LOCKOUT_SECONDS and doubling per failure.
+// A successful attempt clears the count; failures older than FORGET_AFTER_HOURS start over.
+const FREE_FAILURES: i32 = 5;
+const BASE_LOCKOUT_SECONDS: i64 = 30;
+const MAX_LOCKOUT_SECONDS: i64 = 60 * 60;
+const FORGET_AFTER_HOURS: i64 = 24;
+const ALERT_AFTER_FAILURES: i32 = 10; // Email the owner once per run of failures
+
+#[derive(Debug, Clone, Copy)]
+pub enum AuthAction {
+    Login,         // Password step
+    VerifyTfa,     // TOTP code, recovery code or passkey after the password step
+    VerifyEmail,   // Email verification code
+    ResetPassword, // Password reset code
+}
+
+impl AuthAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuthAction::Login => "login",
+            AuthAction::VerifyTfa => "verify_tfa",
+            AuthAction::VerifyEmail => "verify_email",
+            AuthAction::ResetPassword => "reset_password",
+        }
+    }
+
+    // For the alert email: "There were N failed attempts to ..."
+    fn describe(self) -> &'static str {
+        match self {
+            AuthAction::Login => "sign in",
+            AuthAction::VerifyTfa => "pass two-factor authentication",
+            AuthAction::VerifyEmail => "verify the email address",
+            AuthAction::ResetPassword => "reset the password",
+        }
+    }
+}
+
+fn lockout_seconds(failures: i32) -> Option<i64> {
+    if failures < FREE_FAILURES {
+        return None;
+    }
+    let doublings = (failures - FREE_FAILURES).min(16) as u32; // Keeps the shift from overflowing
+    Some((BASE_LOCKOUT_SECONDS << doublings).min(MAX_LOCKOUT_SECONDS))
+}
+
+// Fails with RateLimited while the account is locked for this action
+pub async fn ensure_not_locked(pool: &PgPool, user_id: i32, action: AuthAction) -> Result<(), AppError> {
+    let remaining = sqlx::query_scalar!(
+        r#"
+        SELECT CEIL(EXTRACT(EPOCH FROM (locked_until - NOW())))::BIGINT as "remaining!"
+        FROM auth_failures
+        WHERE user_id = $1 AND action = $2 AND locked_until > NOW()
+        "#,
+        user_id,
+        action.as_str()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match remaining {
+        Some(seconds) => {
+            tracing::warn!("Blocked {} attempt for locked user {} ({}s left)", action.as_str(), user_id, seconds);
+            Err(AppError::RateLimited(seconds.max(1) as u64))
+        }
+        None => Ok(()),
+    }
+}
+
+// Count a failed attempt, lock the account once past the free failures and alert the owner
+// when it keeps going
+pub async fn record_failure(state: &AppState, user_id: i32, action: AuthAction) -> Result<(), AppError> {
+    let failures = sqlx::query_scalar!(
+        r#"
+        INSERT INTO auth_failures (user_id, action, failures, last_failure_at)
+        VALUES ($1, $2, 1, NOW())
+        ON CONFLICT (user_id, action) DO UPDATE SET
+            failures = CASE
+                WHEN auth_failures.last_failure_at < NOW() - make_interval(hours => $3::INT) THEN 1
+                ELSE auth_failures.failures + 1
+            END,
+            alerted_at = CASE
+                WHEN auth_failures.last_failure_at < NOW() - make_interval(hours => $3::INT) THEN NULL
+                ELSE auth_failures.alerted_at
+            END,
+            last_failure_at = NOW()
+        RETURNING failures
+        "#,
+        user_id,
+        action.as_str(),
+        FORGET_AFTER_HOURS as i32
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    if let Some(seconds) = lockout_seconds(failures) {
+        sqlx::query!(
+            "UPDATE auth_failures SET locked_until = NOW() + make_interval(secs => $3) WHERE user_id = $1 AND action = $2",
+            user_id,
+            action.as_str(),
+            seconds as f64
+        )
+        .execute(&state.pool)
+        .await?;
+        tracing::warn!("User {} locked for {}s after {} failed {} attempts", user_id, seconds, failures, action.as_str());
+    }
+
+    if failures >= ALERT_AFTER_FAILURES {
+        // Claim the alert, so concurrent failures send it only once
+        let recipient = sqlx::query_scalar!(
+            r#"
+            UPDATE auth_failures af SET alerted_at = NOW()
+            FROM users u
+            WHERE af.user_id = $1 AND af.action = $2 AND af.alerted_at IS NULL AND u.user_id = af.user_id
+            RETURNING u.email
+            "#,
+            user_id,
+            action.as_str()
+        )
+        .fetch_optional(&state.pool)
+        .await?;
+
+        if let Some(email) = recipient {
+            let email_service = state.email_service.clone();
+            tokio::spawn(async move {
+                if let Err(e) = email_service.send_security_alert_email(&email, action.describe(), failures).await {
+                    tracing::error!("Failed to send security alert to user {}: {:?}", user_id, e);
+                }
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// A successful attempt wipes the slate for this action
+pub async fn clear_failures(pool: &PgPool, user_id: i32, action: AuthAction) -> Result<(), AppError> {
+    sqlx::query!(
+        "DELETE FROM auth_failures WHERE user_id = $1 AND action = $2",
+        user_id,
+        action.as_str()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,428 @@
+use crate::config::{Config, OidcProviderConfig};
+use crate::errors::AppError;
+use crate::models::oidc::IdentityInfo;
+use crate::utils::{pending_invites::claim_pending_invites, security::{generate_secure_code, hash_password}};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{collections::HashMap, sync::Arc, time::{Duration as StdDuration, Instant}};
+use tokio::sync::RwLock;
+
+// --- Social login (OpenID Connect, authorization code flow with PKCE) ---
+// *start* stores a state, nonce and PKCE verifier in oidc_logins and returns the provider's authorization
+// URL. The provider sends the browser back to OIDC_REDIRECT_URL with `code` and `state`, which the
+// frontend passes to *finish*. That redeems the code for an ID token, verifies it against the provider's
+// keys and maps the identity to a user (see resolve_user).
+
+const LOGIN_TTL_MINUTES: i64 = 10;
+const PROVIDER_CACHE_TTL: StdDuration = StdDuration::from_secs(60 * 60); // Discovery document and signing keys
+const HTTP_TIMEOUT: StdDuration = StdDuration::from_secs(10);
+const MAX_DISPLAY_NAME_LENGTH: usize = 100; // users.display_name
+
+// The parts of {issuer}/.well-known/openid-configuration we use
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String, // Microsoft's multi-tenant endpoint has a {tenantid} placeholder here
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct CachedProvider {
+    metadata: ProviderMetadata,
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+// HTTP client for the providers, with their discovery documents and keys cached
+#[derive(Clone)]
+pub struct OidcClient {
+    http: reqwest::Client,
+    providers: Arc<RwLock<HashMap<String, Arc<CachedProvider>>>>,
+}
+
+impl OidcClient {
+    pub fn new() -> Result<Self, AppError> {
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .map_err(|e| AppError::ConfigurationError(format!("Failed to create OIDC HTTP client: {}", e)))?;
+        Ok(Self { http, providers: Arc::new(RwLock::new(HashMap::new())) })
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::OidcProviderUnavailable(format!("GET {} failed: {}", url, e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::OidcProviderUnavailable(format!("Invalid response from {}: {}", url, e)))
+    }
+
+    // Metadata and keys of a provider; `refresh` refetches them, e.g. after the provider rotated its keys
+    async fn provider(&self, provider: &OidcProviderConfig, refresh: bool) -> Result<Arc<CachedProvider>, AppError> {
+        if !refresh
+            && let Some(cached) = self.providers.read().await.get(&provider.id)
+            && cached.fetched_at.elapsed() < PROVIDER_CACHE_TTL
+        {
+            return Ok(cached.clone());
+        }
+
+        let metadata: ProviderMetadata = self
+            .get_json(&format!("{}/.well-known/openid-configuration", provider.issuer))
+            .await?;
+        let keys: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+
+        let cached = Arc::new(CachedProvider { metadata, keys, fetched_at: Instant::now() });
+        self.providers.write().await.insert(provider.id.clone(), cached.clone());
+        Ok(cached)
+    }
+}
+
+// A provider from OIDC_PROVIDERS, by its ID
+pub fn find_provider<'a>(config: &'a Config, provider_id: &str) -> Result<&'a OidcProviderConfig, AppError> {
+    config
+        .oidc_providers
+        .iter()
+        .find(|provider| provider.id == provider_id)
+        .ok_or(AppError::OidcProviderNotFound)
+}
+
+// --- Start: build the authorization URL ---
+pub async fn start_login(
+    pool: &PgPool,
+    oidc: &OidcClient,
+    config: &Config,
+    provider: &OidcProviderConfig,
+) -> Result<String, AppError> {
+    let metadata = &oidc.provider(provider, false).await?.metadata;
+
+    // Drop abandoned logins while at it
+    sqlx::query!("DELETE FROM oidc_logins WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+
+    let state = generate_secure_code(43);
+    let nonce = generate_secure_code(43);
+    let code_verifier = generate_secure_code(64); // Alphanumeric is within the PKCE verifier charset
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+    sqlx::query!(
+        r#"
+        INSERT INTO oidc_logins (state, provider, code_verifier, nonce, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        state,
+        provider.id,
+        code_verifier,
+        nonce,
+        Utc::now() + Duration::minutes(LOGIN_TTL_MINUTES)
+    )
+    .execute(pool)
+    .await?;
+
+    let params = [
+        ("response_type", "code"),
+        ("client_id", provider.client_id.as_str()),
+        ("redirect_uri", config.oidc_redirect_url.as_str()),
+        ("scope", provider.scopes.as_str()),
+        ("state", state.as_str()),
+        ("nonce", nonce.as_str()),
+        ("code_challenge", code_challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ]
+    .iter()
+    .map(|(key, value)| format!("{}={}", key, urlencoding::encode(value)))
+    .collect::<Vec<_>>()
+    .join("&");
+
+    let separator = if metadata.authorization_endpoint.contains('?') { '&' } else { '?' };
+    Ok(format!("{}{}{}", metadata.authorization_endpoint, separator, params))
+}
+
+// --- Finish: redeem the code and verify the ID token ---
+
+// Who the provider says logged in
+#[derive(Debug)]
+pub struct ExternalIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool, // Only if the provider vouches for it
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<serde_json::Value>, // Some providers send "true" as a string
+    name: Option<String>,
+    tid: Option<String>, // Microsoft tenant, fills the {tenantid} placeholder of the issuer
+}
+
+pub async fn finish_login(
+    pool: &PgPool,
+    oidc: &OidcClient,
+    config: &Config,
+    provider: &OidcProviderConfig,
+    code: &str,
+    state: &str,
+) -> Result<ExternalIdentity, AppError> {
+    // Single use: the login is gone whether the rest succeeds or not
+    let login = sqlx::query!(
+        r#"
+        DELETE FROM oidc_logins
+        WHERE state = $1 AND provider = $2 AND expires_at > NOW()
+        RETURNING code_verifier, nonce
+        "#,
+        state,
+        provider.id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::OidcStateInvalid)?;
+
+    let cached = oidc.provider(provider, false).await?;
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", config.oidc_redirect_url.as_str()),
+        ("client_id", provider.client_id.as_str()),
+        ("code_verifier", login.code_verifier.as_str()),
+    ];
+    if let Some(client_secret) = &provider.client_secret {
+        form.push(("client_secret", client_secret.as_str()));
+    }
+
+    let response = oidc.http
+        .post(&cached.metadata.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| AppError::OidcProviderUnavailable(format!("Token request to {} failed: {}", provider.id, e)))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::OidcLoginFailed(format!("{} rejected the code ({}): {}", provider.id, status, body)));
+    }
+    let id_token = response
+        .json::<TokenResponse>()
+        .await
+        .map_err(|e| AppError::OidcProviderUnavailable(format!("Invalid token response from {}: {}", provider.id, e)))?
+        .id_token
+        .ok_or_else(|| AppError::OidcLoginFailed(format!("{} returned no ID token", provider.id)))?;
+
+    let claims = verify_id_token(oidc, provider, cached, &id_token).await?;
+
+    if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+        return Err(AppError::OidcLoginFailed("ID token nonce does not match".to_string()));
+    }
+
+    let email_verified = match &claims.email_verified {
+        Some(serde_json::Value::Bool(verified)) => *verified,
+        Some(serde_json::Value::String(verified)) => verified == "true",
+        _ => false,
+    };
+
+    Ok(ExternalIdentity {
+        subject: claims.sub,
+        email: claims.email,
+        email_verified,
+        name: claims.name,
+    })
+}
+
+async fn verify_id_token(
+    oidc: &OidcClient,
+    provider: &OidcProviderConfig,
+    mut cached: Arc<CachedProvider>,
+    id_token: &str,
+) -> Result<IdTokenClaims, AppError> {
+    let header = decode_header(id_token)
+        .map_err(|e| AppError::OidcLoginFailed(format!("Malformed ID token: {}", e)))?;
+
+    // An unknown key ID may mean the provider rotated its keys since we cached them
+    let kid = header.kid.as_deref();
+    if kid.is_some_and(|kid| cached.keys.find(kid).is_none()) {
+        cached = oidc.provider(provider, true).await?;
+    }
+    let jwk = match kid {
+        Some(kid) => cached.keys.find(kid),
+        None if cached.keys.keys.len() == 1 => cached.keys.keys.first(),
+        None => None,
+    }
+    .ok_or_else(|| AppError::OidcLoginFailed("ID token signed with an unknown key".to_string()))?;
+
+    let key = DecodingKey::from_jwk(jwk)
+        .map_err(|e| AppError::OidcLoginFailed(format!("Unusable provider key: {}", e)))?;
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[&provider.client_id]);
+    validation.set_required_spec_claims(&["exp", "aud", "iss", "sub"]);
+    validation.leeway = 60; // Clock skew between us and the provider
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|e| AppError::OidcLoginFailed(format!("Invalid ID token: {}", e)))?
+        .claims;
+
+    // Checked by hand for the {tenantid} placeholder
+    let expected_issuer = cached.metadata.issuer.replace("{tenantid}", claims.tid.as_deref().unwrap_or_default());
+    if claims.iss != expected_issuer {
+        return Err(AppError::OidcLoginFailed(format!("ID token issuer {} is not {}", claims.iss, expected_issuer)));
+    }
+
+    Ok(claims)
+}
+
+// --- Map the identity to a user ---
+// 1. An identity linked before logs in as its user.
+// 2. Otherwise it is linked to the account with the same email, if both the provider and we have
+//    verified that address. Anything else could hand someone else's account over.
+// 3. Without an account for the email, a new one is created. It gets a random password; the user
+//    can set one with the password reset.
+pub async fn resolve_user(pool: &PgPool, provider_id: &str, identity: &ExternalIdentity) -> Result<i32, AppError> {
+    let linked_user_id = sqlx::query_scalar!(
+        r#"
+        UPDATE user_identities ui SET last_used_at = NOW()
+        FROM users u
+        WHERE ui.provider = $1 AND ui.subject = $2 AND u.user_id = ui.user_id AND u.deleted_at IS NULL
+        RETURNING ui.user_id
+        "#,
+        provider_id,
+        identity.subject
+    )
+    .fetch_optional(pool)
+    .await?;
+    if let Some(user_id) = linked_user_id {
+        return Ok(user_id);
+    }
+
+    let email = identity
+        .email
+        .as_deref()
+        .ok_or_else(|| AppError::OidcLoginFailed(format!("{} did not share an email address", provider_id)))?;
+
+    let existing = sqlx::query!(
+        r#"
+        SELECT user_id, email_verified as "email_verified!", deleted_at
+        FROM users
+        WHERE lower(email) = lower($1)
+        ORDER BY deleted_at NULLS FIRST, user_id
+        LIMIT 1
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let created = existing.is_none();
+    let mut tx = pool.begin().await?;
+    let user_id = match existing {
+        Some(user) => {
+            if user.deleted_at.is_some() || !user.email_verified || !identity.email_verified {
+                tracing::warn!("Not linking {} identity to user {}: email not verified on both sides", provider_id, user.user_id);
+                return Err(AppError::EmailInUse);
+            }
+            tracing::info!("Linking {} identity to user {} by email", provider_id, user.user_id);
+            user.user_id
+        }
+        None => {
+            let display_name = identity
+                .name
+                .as_deref()
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| email.split('@').next().unwrap_or(email))
+                .chars()
+                .take(MAX_DISPLAY_NAME_LENGTH)
+                .collect::<String>();
+            let password_hash = hash_password(&generate_secure_code(32)).await?;
+
+            let user_id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO users (display_name, email, password_hash, email_verified)
+                VALUES ($1, $2, $3, $4)
+                RETURNING user_id
+                "#,
+                display_name,
+                email,
+                password_hash,
+                identity.email_verified
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            tracing::info!("Created user {} from {} login", user_id, provider_id);
+            user_id
+        }
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_identities (user_id, provider, subject, email, last_used_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        "#,
+        user_id,
+        provider_id,
+        identity.subject,
+        identity.email
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    // Hand over invitations and shares that were waiting for this address, like verify-email does
+    if created
+        && identity.email_verified
+        && let Err(e) = claim_pending_invites(pool, user_id, email).await
+    {
+        tracing::error!("Failed to claim pending invitations for user {}: {:?}", user_id, e);
+    }
+
+    Ok(user_id)
+}
+
+// --- Linked identities (/api/me/identities) ---
+
+pub async fn list_identities(pool: &PgPool, user_id: i32) -> Result<Vec<IdentityInfo>, AppError> {
+    let identities = sqlx::query_as!(
+        IdentityInfo,
+        r#"
+        SELECT identity_id, provider, email, created_at, last_used_at
+        FROM user_identities
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(identities)
+}
+
+// Unlinking doesn't lock anyone out: every account has a password, which the password reset can set
+pub async fn delete_identity(pool: &PgPool, user_id: i32, identity_id: i32) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM user_identities WHERE identity_id = $1 AND user_id = $2",
+        identity_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::IdentityNotFound);
+    }
+    Ok(())
+}
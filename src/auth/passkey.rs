@@ -0,0 +1,363 @@
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::models::passkey::PasskeyInfo;
+use chrono::{Duration, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::PgPool;
+use webauthn_rs::prelude::*;
+use webauthn_rs_proto::ResidentKeyRequirement;
+
+// --- Passkeys (WebAuthn) ---
+// Each ceremony takes two requests: *start* returns the options for navigator.credentials.create()/get()
+// together with a ceremony id, *finish* verifies the browser's response. The state in between lives in
+// the webauthn_ceremonies table; it is single use and expires after a few minutes.
+
+const CEREMONY_TTL_MINUTES: i64 = 5;
+const DEFAULT_PASSKEY_NAME: &str = "Passkey";
+
+// Builds the relying party from the config (WEBAUTHN_RP_ORIGIN / WEBAUTHN_RP_ID)
+pub fn build_webauthn(config: &Config) -> Result<Webauthn, AppError> {
+    let origin = Url::parse(&config.webauthn_rp_origin)
+        .map_err(|e| AppError::ConfigurationError(format!("Invalid WEBAUTHN_RP_ORIGIN: {}", e)))?;
+    let rp_id = match &config.webauthn_rp_id {
+        Some(rp_id) => rp_id.clone(),
+        None => origin
+            .host_str()
+            .ok_or_else(|| AppError::ConfigurationError("WEBAUTHN_RP_ORIGIN has no host".to_string()))?
+            .to_string(),
+    };
+
+    WebauthnBuilder::new(&rp_id, &origin)
+        .and_then(|builder| builder.rp_name("Qalendar").build())
+        .map_err(|e| AppError::ConfigurationError(format!("Invalid WebAuthn relying party: {}", e)))
+}
+
+// What a ceremony is for; stored in webauthn_ceremonies.kind
+#[derive(Clone, Copy)]
+enum CeremonyKind {
+    Registration,
+    Login,        // Passwordless, the user is unknown until the assertion
+    SecondFactor, // Instead of a TOTP code, after the password step
+}
+
+impl CeremonyKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            CeremonyKind::Registration => "registration",
+            CeremonyKind::Login => "login",
+            CeremonyKind::SecondFactor => "second_factor",
+        }
+    }
+}
+
+async fn save_ceremony<T: Serialize>(
+    pool: &PgPool,
+    kind: CeremonyKind,
+    user_id: Option<i32>,
+    state: &T,
+) -> Result<Uuid, AppError> {
+    // Drop abandoned ceremonies while at it
+    sqlx::query!("DELETE FROM webauthn_ceremonies WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+
+    let ceremony_id = Uuid::new_v4();
+    let state = serde_json::to_value(state)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to serialize WebAuthn state: {}", e)))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO webauthn_ceremonies (ceremony_id, user_id, kind, state, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        ceremony_id,
+        user_id,
+        kind.as_str(),
+        state,
+        Utc::now() + Duration::minutes(CEREMONY_TTL_MINUTES)
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(ceremony_id)
+}
+
+// Removes a ceremony and returns its state; it cannot be finished twice
+async fn take_ceremony<T: DeserializeOwned>(
+    pool: &PgPool,
+    kind: CeremonyKind,
+    user_id: Option<i32>,
+    ceremony_id: Uuid,
+) -> Result<T, AppError> {
+    let state = sqlx::query_scalar!(
+        r#"
+        DELETE FROM webauthn_ceremonies
+        WHERE ceremony_id = $1 AND kind = $2 AND user_id IS NOT DISTINCT FROM $3 AND expires_at > NOW()
+        RETURNING state
+        "#,
+        ceremony_id,
+        kind.as_str(),
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::PasskeyCeremonyInvalid)?;
+
+    serde_json::from_value(state)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to deserialize WebAuthn state: {}", e)))
+}
+
+// A stored passkey with its row id
+struct StoredPasskey {
+    passkey_id: i32,
+    passkey: Passkey,
+}
+
+async fn load_passkeys(pool: &PgPool, user_id: i32) -> Result<Vec<StoredPasskey>, AppError> {
+    let rows = sqlx::query!(
+        "SELECT passkey_id, passkey FROM passkeys WHERE user_id = $1 ORDER BY passkey_id",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let passkey = serde_json::from_value(row.passkey)
+                .map_err(|e| AppError::InternalServerError(format!("Failed to deserialize passkey {}: {}", row.passkey_id, e)))?;
+            Ok(StoredPasskey { passkey_id: row.passkey_id, passkey })
+        })
+        .collect()
+}
+
+// Stores the new signature counter after a successful assertion and bumps last_used_at
+async fn record_passkey_use(
+    pool: &PgPool,
+    mut stored: StoredPasskey,
+    result: &AuthenticationResult,
+) -> Result<(), AppError> {
+    stored.passkey.update_credential(result);
+    let passkey = serde_json::to_value(&stored.passkey)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to serialize passkey: {}", e)))?;
+    sqlx::query!(
+        "UPDATE passkeys SET passkey = $2, last_used_at = NOW() WHERE passkey_id = $1",
+        stored.passkey_id,
+        passkey
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Whether the user can use a passkey as second factor
+pub async fn has_passkeys(pool: &PgPool, user_id: i32) -> Result<bool, AppError> {
+    let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM passkeys WHERE user_id = $1)", user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(exists.unwrap_or(false))
+}
+
+// --- Registration (signed-in user adds a passkey) ---
+
+pub async fn start_registration(
+    pool: &PgPool,
+    webauthn: &Webauthn,
+    user_id: i32,
+) -> Result<(Uuid, CreationChallengeResponse), AppError> {
+    // The user handle is generated once and shared by all of the user's passkeys
+    let user = sqlx::query!(
+        r#"
+        UPDATE users SET webauthn_user_id = COALESCE(webauthn_user_id, $2)
+        WHERE user_id = $1 AND deleted_at IS NULL
+        RETURNING email, display_name, webauthn_user_id as "webauthn_user_id!"
+        "#,
+        user_id,
+        Uuid::new_v4()
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::UserNotFound)?;
+
+    // Keeps authenticators from registering a second passkey for the same account
+    let exclude_credentials: Vec<CredentialID> = load_passkeys(pool, user_id)
+        .await?
+        .iter()
+        .map(|stored| stored.passkey.cred_id().clone())
+        .collect();
+
+    let (mut options, state) = webauthn
+        .start_passkey_registration(user.webauthn_user_id, &user.email, &user.display_name, Some(exclude_credentials))
+        .map_err(|e| AppError::InternalServerError(format!("Failed to start passkey registration: {}", e)))?;
+
+    // Ask for a discoverable credential, so the passkey also works for passwordless login
+    if let Some(selection) = options.public_key.authenticator_selection.as_mut() {
+        selection.resident_key = Some(ResidentKeyRequirement::Required);
+        selection.require_resident_key = true;
+    }
+
+    let ceremony_id = save_ceremony(pool, CeremonyKind::Registration, Some(user_id), &state).await?;
+    Ok((ceremony_id, options))
+}
+
+pub async fn finish_registration(
+    pool: &PgPool,
+    webauthn: &Webauthn,
+    user_id: i32,
+    ceremony_id: Uuid,
+    name: Option<String>,
+    credential: &RegisterPublicKeyCredential,
+) -> Result<PasskeyInfo, AppError> {
+    let state: PasskeyRegistration = take_ceremony(pool, CeremonyKind::Registration, Some(user_id), ceremony_id).await?;
+
+    let passkey = webauthn.finish_passkey_registration(credential, &state).map_err(|e| {
+        tracing::warn!("Passkey registration failed for user {}: {}", user_id, e);
+        AppError::PasskeyVerificationFailed
+    })?;
+
+    let passkey_json = serde_json::to_value(&passkey)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to serialize passkey: {}", e)))?;
+    let info = sqlx::query_as!(
+        PasskeyInfo,
+        r#"
+        INSERT INTO passkeys (user_id, credential_id, name, passkey)
+        VALUES ($1, $2, $3, $4)
+        RETURNING passkey_id, name, created_at, last_used_at
+        "#,
+        user_id,
+        passkey.cred_id().as_ref(),
+        name.unwrap_or_else(|| DEFAULT_PASSKEY_NAME.to_string()),
+        passkey_json
+    )
+    .fetch_one(pool)
+    .await?;
+
+    tracing::info!("User {} registered passkey {}", user_id, info.passkey_id);
+    Ok(info)
+}
+
+// --- Passwordless login (discoverable credentials) ---
+
+pub async fn start_login(pool: &PgPool, webauthn: &Webauthn) -> Result<(Uuid, RequestChallengeResponse), AppError> {
+    let (options, state) = webauthn
+        .start_discoverable_authentication()
+        .map_err(|e| AppError::InternalServerError(format!("Failed to start passkey login: {}", e)))?;
+
+    let ceremony_id = save_ceremony(pool, CeremonyKind::Login, None, &state).await?;
+    Ok((ceremony_id, options))
+}
+
+// Returns the user the passkey belongs to
+pub async fn finish_login(
+    pool: &PgPool,
+    webauthn: &Webauthn,
+    ceremony_id: Uuid,
+    credential: &PublicKeyCredential,
+) -> Result<i32, AppError> {
+    let state: DiscoverableAuthentication = take_ceremony(pool, CeremonyKind::Login, None, ceremony_id).await?;
+
+    // The user handle and credential id come from the authenticator; the signature check below proves them
+    let (user_handle, credential_id) = webauthn.identify_discoverable_authentication(credential).map_err(|e| {
+        tracing::warn!("Passkey login without a usable user handle: {}", e);
+        AppError::PasskeyVerificationFailed
+    })?;
+
+    let user_id = sqlx::query_scalar!(
+        r#"
+        SELECT u.user_id FROM users u
+        JOIN passkeys p ON p.user_id = u.user_id
+        WHERE u.webauthn_user_id = $1 AND p.credential_id = $2 AND u.deleted_at IS NULL
+        "#,
+        user_handle,
+        credential_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::PasskeyVerificationFailed)?;
+
+    let passkeys = load_passkeys(pool, user_id).await?;
+    let keys: Vec<DiscoverableKey> = passkeys.iter().map(|stored| (&stored.passkey).into()).collect();
+    let result = webauthn.finish_discoverable_authentication(credential, state, &keys).map_err(|e| {
+        tracing::warn!("Passkey login failed for user {}: {}", user_id, e);
+        AppError::PasskeyVerificationFailed
+    })?;
+
+    if let Some(stored) = passkeys.into_iter().find(|stored| stored.passkey.cred_id() == result.cred_id()) {
+        record_passkey_use(pool, stored, &result).await?;
+    }
+    Ok(user_id)
+}
+
+// --- Second factor (instead of a TOTP code; see auth::tfa challenges) ---
+
+pub async fn start_second_factor(
+    pool: &PgPool,
+    webauthn: &Webauthn,
+    user_id: i32,
+) -> Result<(Uuid, RequestChallengeResponse), AppError> {
+    let passkeys: Vec<Passkey> = load_passkeys(pool, user_id).await?.into_iter().map(|stored| stored.passkey).collect();
+    if passkeys.is_empty() {
+        return Err(AppError::PasskeyNotFound);
+    }
+
+    let (options, state) = webauthn
+        .start_passkey_authentication(&passkeys)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to start passkey verification: {}", e)))?;
+
+    let ceremony_id = save_ceremony(pool, CeremonyKind::SecondFactor, Some(user_id), &state).await?;
+    Ok((ceremony_id, options))
+}
+
+// False if the response does not verify against one of the user's passkeys
+pub async fn finish_second_factor(
+    pool: &PgPool,
+    webauthn: &Webauthn,
+    user_id: i32,
+    ceremony_id: Uuid,
+    credential: &PublicKeyCredential,
+) -> Result<bool, AppError> {
+    let state: PasskeyAuthentication = take_ceremony(pool, CeremonyKind::SecondFactor, Some(user_id), ceremony_id).await?;
+
+    let result = match webauthn.finish_passkey_authentication(credential, &state) {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!("Passkey second factor failed for user {}: {}", user_id, e);
+            return Ok(false);
+        }
+    };
+
+    if let Some(stored) = load_passkeys(pool, user_id)
+        .await?
+        .into_iter()
+        .find(|stored| stored.passkey.cred_id() == result.cred_id())
+    {
+        record_passkey_use(pool, stored, &result).await?;
+    }
+    Ok(true)
+}
+
+// --- Management (GET/DELETE /api/me/passkeys) ---
+
+pub async fn list_passkeys(pool: &PgPool, user_id: i32) -> Result<Vec<PasskeyInfo>, AppError> {
+    let passkeys = sqlx::query_as!(
+        PasskeyInfo,
+        "SELECT passkey_id, name, created_at, last_used_at FROM passkeys WHERE user_id = $1 ORDER BY created_at",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(passkeys)
+}
+
+pub async fn delete_passkey(pool: &PgPool, user_id: i32, passkey_id: i32) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM passkeys WHERE passkey_id = $1 AND user_id = $2",
+        passkey_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::PasskeyNotFound);
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use crate::auth::jwt::create_token;
+use crate::config::Config;
+use crate::errors::AppError;
+use crate::utils::security::generate_secure_code;
+use axum::http::{header::USER_AGENT, HeaderMap};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+// --- Sessions (one per signed-in device) ---
+// Login issues a short-lived access token (JWT carrying the session id) and a refresh token.
+// Refresh tokens are random, rotate on every use and are stored as SHA-256 hashes only
+// (they carry enough entropy that a slow hash like bcrypt adds nothing).
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+// Tokens handed to the client after login or refresh
+pub struct IssuedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64, // Access token lifetime in seconds
+}
+
+fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// User-Agent of the request, to tell sessions apart in GET /api/me/sessions
+pub fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect())
+}
+
+// --- Start a session after a successful login ---
+pub async fn start_session(
+    pool: &PgPool,
+    config: &Config,
+    user_id: i32,
+    user_agent: Option<String>,
+) -> Result<IssuedTokens, AppError> {
+    let refresh_token = generate_secure_code(REFRESH_TOKEN_LENGTH);
+    let expires_at = Utc::now() + Duration::days(config.refresh_token_expiration_days);
+
+    let session_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO sessions (user_id, refresh_token_hash, user_agent, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING session_id
+        "#,
+        user_id,
+        hash_refresh_token(&refresh_token),
+        user_agent,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(IssuedTokens {
+        access_token: create_token(user_id, session_id, config)?,
+        refresh_token,
+        expires_in: config.jwt_expiration_seconds,
+    })
+}
+
+// --- Exchange a refresh token for a new access token and refresh token ---
+// Each refresh extends the session. A refresh token that was already rotated out being presented
+// again means someone else holds a copy, so that session is revoked.
+pub async fn refresh_session(pool: &PgPool, config: &Config, refresh_token: &str) -> Result<IssuedTokens, AppError> {
+    let token_hash = hash_refresh_token(refresh_token);
+    let new_refresh_token = generate_secure_code(REFRESH_TOKEN_LENGTH);
+    let expires_at = Utc::now() + Duration::days(config.refresh_token_expiration_days);
+
+    let session = sqlx::query!(
+        r#"
+        UPDATE sessions s
+        SET refresh_token_hash = $2, previous_token_hash = $1, last_used_at = NOW(), expires_at = $3
+        FROM users u
+        WHERE s.refresh_token_hash = $1 AND s.revoked_at IS NULL AND s.expires_at > NOW()
+          AND u.user_id = s.user_id AND u.deleted_at IS NULL
+        RETURNING s.session_id, s.user_id
+        "#,
+        token_hash,
+        hash_refresh_token(&new_refresh_token),
+        expires_at
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(session) = session else {
+        let reused = sqlx::query!(
+            "UPDATE sessions SET revoked_at = NOW() WHERE previous_token_hash = $1 AND revoked_at IS NULL",
+            token_hash
+        )
+        .execute(pool)
+        .await?;
+        if reused.rows_affected() > 0 {
+            tracing::warn!("Rotated-out refresh token was reused; session revoked");
+        }
+        return Err(AppError::SessionInvalid);
+    };
+
+    Ok(IssuedTokens {
+        access_token: create_token(session.user_id, session.session_id, config)?,
+        refresh_token: new_refresh_token,
+        expires_in: config.jwt_expiration_seconds,
+    })
+}
+
+// --- End the session a refresh token belongs to (logout) ---
+// Unknown or already revoked tokens are ignored.
+pub async fn end_session(pool: &PgPool, refresh_token: &str) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE sessions SET revoked_at = NOW() WHERE refresh_token_hash = $1 AND revoked_at IS NULL",
+        hash_refresh_token(refresh_token)
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// --- Revoke all of a user's sessions, optionally keeping one (e.g. the caller's) ---
+pub async fn revoke_user_sessions(pool: &PgPool, user_id: i32, keep_session_id: Option<i32>) -> Result<u64, AppError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE sessions SET revoked_at = NOW()
+        WHERE user_id = $1 AND revoked_at IS NULL AND session_id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        keep_session_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// --- Whether an access token's session is still usable ---
+// False once the session is revoked or expired, or the user soft-deleted.
+pub async fn is_session_active(pool: &PgPool, session_id: i32, user_id: i32) -> Result<bool, AppError> {
+    let active = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM sessions s
+            JOIN users u ON u.user_id = s.user_id
+            WHERE s.session_id = $1 AND s.user_id = $2
+              AND s.revoked_at IS NULL AND s.expires_at > NOW()
+              AND u.deleted_at IS NULL
+        )
+        "#,
+        session_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(active.unwrap_or(false))
+}
@@ -3,6 +3,10 @@ use crate::config::Config;
 use totp_rs::{Secret, TOTP, Algorithm}; // Import necessary items from totp-rs
 use base32::{encode, Alphabet}; // Use base32 for encoding/decoding secrets
 use std::time::SystemTime; // Needed for TOTP timestamp
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use crate::auth::jwt::{create_tfa_challenge_token, validate_tfa_challenge_token};
+use crate::utils::security::{generate_secure_code, hash_code, verify_code};
 
 
 // Constants for TOTP (default values from RFC 6238)
@@ -41,32 +45,238 @@ pub fn generate_otp_auth_uri(email: &str, secret_base32: &str, issuer: &str) ->
     Ok(totp.to_string()) // Convert the TOTPUrl struct to its string representation
 }
 
-// Helper to validate a TOTP code against a secret
-pub fn verify_tfa_code(secret_base32: &str, code: &str) -> Result<bool, AppError> {
-    // Recreate Secret from base32 string
+// --- Replay protection ---
+// A code stays valid for its whole window, so the time step of every accepted code is stored and
+// codes from that step or an earlier one are refused afterwards (RFC 6238, section 5.2).
+
+// Helper to validate a TOTP code against a secret. Returns the time step the code belongs to.
+fn matching_tfa_step(secret_base32: &str, code: &str) -> Result<Option<i64>, AppError> {
     let secret = Secret::Encoded(secret_base32.to_string())
         .to_bytes()
         .map_err(|e| AppError::InternalServerError(format!("Failed to decode base32 secret for verification: {}", e)))?;
 
-    // Build the TOTP validator
-     let totp = TOTP::new(
-        Algorithm::SHA1,
-        DIGITS,
-        1, // Window size - allow codes from one time step before and after
-        TIME_STEP,
-        secret,
-        None, // No issuer needed for verification
-        String::new(), // Empty label for verification
-     )
-     .map_err(|e| AppError::InternalServerError(format!("Failed to build TOTP validator: {}", e)))?;
+    // No skew here: each step is checked on its own
+    let totp = TOTP::new(Algorithm::SHA1, DIGITS, 0, TIME_STEP, secret, None, String::new())
+        .map_err(|e| AppError::InternalServerError(format!("Failed to build TOTP validator: {}", e)))?;
+
+    let current_step = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+        .map_err(|e| AppError::InternalServerError(format!("Failed to get system time: {}", e)))?
+        .as_secs() / TIME_STEP;
+
+    // Window - allow codes from one time step before and after
+    Ok((current_step - 1..=current_step + 1)
+        .find(|step| totp.check(code, step * TIME_STEP))
+        .map(|step| step as i64))
+}
+
+// Helper to verify a TOTP code and mark it used. False if the code is wrong or was already used.
+pub async fn consume_tfa_code(pool: &PgPool, user_id: i32, secret_base32: &str, code: &str) -> Result<bool, AppError> {
+    let Some(step) = matching_tfa_step(secret_base32, code)? else {
+        return Ok(false);
+    };
+
+    // Conditional update, so two requests racing with the same code cannot both succeed
+    let result = sqlx::query!(
+        r#"
+        UPDATE users SET tfa_last_used_step = $2
+        WHERE user_id = $1 AND (tfa_last_used_step IS NULL OR tfa_last_used_step < $2)
+        "#,
+        user_id,
+        step
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// --- Login challenges (tfa_challenges table) ---
+// The password step starts a challenge; POST /api/auth/verify-tfa has to present its signed token.
+
+const TFA_CHALLENGE_TTL_MINUTES: i64 = 5;
+pub const MAX_TFA_ATTEMPTS: i32 = 5; // Code attempts per challenge
+
+pub struct TfaChallenge {
+    pub challenge_id: i32,
+    pub user_id: i32,
+    pub attempts: i32, // Including the current one
+}
+
+// Starts a challenge for a user who passed the password step.
+// Returns the challenge token and its lifetime in seconds.
+pub async fn start_tfa_challenge(pool: &PgPool, config: &Config, user_id: i32) -> Result<(String, i64), AppError> {
+    // Drop the user's stale challenges while at it
+    sqlx::query!("DELETE FROM tfa_challenges WHERE user_id = $1 AND expires_at < NOW()", user_id)
+        .execute(pool)
+        .await?;
+
+    let expires_at = Utc::now() + Duration::minutes(TFA_CHALLENGE_TTL_MINUTES);
+    let challenge_id = sqlx::query_scalar!(
+        "INSERT INTO tfa_challenges (user_id, expires_at) VALUES ($1, $2) RETURNING challenge_id",
+        user_id,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let token = create_tfa_challenge_token(user_id, challenge_id, expires_at.timestamp(), config)?;
+    Ok((token, TFA_CHALLENGE_TTL_MINUTES * 60))
+}
+
+// Counts a code attempt against the challenge of `token`, before the code itself is checked
+pub async fn begin_tfa_attempt(pool: &PgPool, config: &Config, token: &str) -> Result<TfaChallenge, AppError> {
+    let claims = validate_tfa_challenge_token(token, config)?;
+
+    let attempts = sqlx::query_scalar!(
+        r#"
+        UPDATE tfa_challenges SET attempts = attempts + 1
+        WHERE challenge_id = $1 AND user_id = $2
+          AND completed_at IS NULL AND expires_at > NOW() AND attempts < $3
+        RETURNING attempts
+        "#,
+        claims.cid,
+        claims.sub,
+        MAX_TFA_ATTEMPTS
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match attempts {
+        Some(attempts) => Ok(TfaChallenge { challenge_id: claims.cid, user_id: claims.sub, attempts }),
+        None => {
+            // Tell a locked challenge apart from a used or expired one
+            let locked = sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM tfa_challenges
+                    WHERE challenge_id = $1 AND completed_at IS NULL AND expires_at > NOW() AND attempts >= $2
+                )
+                "#,
+                claims.cid,
+                MAX_TFA_ATTEMPTS
+            )
+            .fetch_one(pool)
+            .await?;
+            Err(if locked.unwrap_or(false) { AppError::TfaTooManyAttempts } else { AppError::TfaChallengeInvalid })
+        }
+    }
+}
+
+// Marks a challenge used once its code was accepted
+pub async fn complete_tfa_challenge(pool: &PgPool, challenge_id: i32) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        "UPDATE tfa_challenges SET completed_at = NOW() WHERE challenge_id = $1 AND completed_at IS NULL",
+        challenge_id
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::TfaChallengeInvalid);
+    }
+    Ok(())
+}
 
-    // // Get the current timestamp for logging or debugging purposes
-    // let current_timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
-    //     .map_err(|e| AppError::InternalServerError(format!("Failed to get system time: {}", e)))?
-    //     .as_secs();
+// --- Recovery codes (tfa_recovery_codes table) ---
+// One-time codes accepted in place of a TOTP code, for when the authenticator is lost.
+// Shown to the user once; only bcrypt hashes are stored.
 
-    let code_is_valid = totp.check_current(code)
-        .map_err(|e| AppError::InternalServerError(format!("Failed to check TOTP code: {}", e)))?;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: usize = 10; // Shown as two groups of five, e.g. "k3x9a-p2m7q"
+
+// Lowercase without separators, so codes can be typed in any format they were shown in
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
 
-    Ok(code_is_valid)
+// Replaces all of a user's recovery codes with a fresh set and returns the new codes
+pub async fn generate_recovery_codes(pool: &PgPool, user_id: i32) -> Result<Vec<String>, AppError> {
+    let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    let mut hashes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+    for _ in 0..RECOVERY_CODE_COUNT {
+        let code = generate_secure_code(RECOVERY_CODE_LENGTH).to_ascii_lowercase();
+        hashes.push(hash_code(&code).await?);
+        codes.push(format!("{}-{}", &code[..RECOVERY_CODE_LENGTH / 2], &code[RECOVERY_CODE_LENGTH / 2..]));
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM tfa_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO tfa_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::TEXT[])",
+        user_id,
+        &hashes
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(codes)
+}
+
+// Removes all recovery codes of a user (when 2FA is disabled)
+pub async fn delete_recovery_codes(pool: &PgPool, user_id: i32) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM tfa_recovery_codes WHERE user_id = $1", user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Number of recovery codes the user has left
+pub async fn remaining_recovery_codes(pool: &PgPool, user_id: i32) -> Result<i64, AppError> {
+    let remaining = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM tfa_recovery_codes WHERE user_id = $1 AND used_at IS NULL"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(remaining)
+}
+
+// Helper to check a recovery code and use it up. False if it matches none of the unused codes.
+async fn consume_recovery_code(pool: &PgPool, user_id: i32, code: &str) -> Result<bool, AppError> {
+    let code = normalize_recovery_code(code);
+    if code.len() != RECOVERY_CODE_LENGTH {
+        return Ok(false);
+    }
+
+    let candidates = sqlx::query!(
+        "SELECT recovery_code_id, code_hash FROM tfa_recovery_codes WHERE user_id = $1 AND used_at IS NULL",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for candidate in candidates {
+        if verify_code(&code, &candidate.code_hash).await? {
+            // Conditional update, so a code cannot be used twice by racing requests
+            let result = sqlx::query!(
+                "UPDATE tfa_recovery_codes SET used_at = NOW() WHERE recovery_code_id = $1 AND used_at IS NULL",
+                candidate.recovery_code_id
+            )
+            .execute(pool)
+            .await?;
+            return Ok(result.rows_affected() > 0);
+        }
+    }
+    Ok(false)
+}
+
+// Helper to check the second factor of a user with 2FA enabled: a TOTP code or, failing that, a
+// recovery code. Either one is used up on success.
+pub async fn consume_second_factor(pool: &PgPool, user_id: i32, secret_base32: &str, code: &str) -> Result<bool, AppError> {
+    let code = code.trim();
+    if code.len() == DIGITS && code.chars().all(|c| c.is_ascii_digit()) {
+        return consume_tfa_code(pool, user_id, secret_base32, code).await;
+    }
+
+    let used = consume_recovery_code(pool, user_id, code).await?;
+    if used {
+        tracing::info!("User {} used a 2FA recovery code", user_id);
+    }
+    Ok(used)
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+use crate::errors::AppError;
+use crate::models::{enums::TokenScope, token::TokenInfo};
+use crate::utils::security::generate_secure_code;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::str::FromStr;
+
+// --- Personal access tokens (/api/me/tokens) ---
+// Sent as `Authorization: Bearer qal_pat_...` instead of an access token. They don't expire unless
+// asked to and only pass handlers that require one of their scopes (see middleware::auth::RequireScope).
+// Stored as SHA-256 hashes like refresh tokens.
+
+const TOKEN_PREFIX: &str = "qal_pat_"; // Tells them apart from JWTs, and makes leaked tokens easy to scan for
+const TOKEN_SECRET_LENGTH: usize = 40;
+const DISPLAY_PREFIX_LENGTH: usize = TOKEN_PREFIX.len() + 4;
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn is_personal_access_token(token: &str) -> bool {
+    token.starts_with(TOKEN_PREFIX)
+}
+
+// Returns the token (to show once) and its list entry
+pub async fn create_token(
+    pool: &PgPool,
+    user_id: i32,
+    name: &str,
+    scopes: &[TokenScope],
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<(String, TokenInfo), AppError> {
+    let token = format!("{}{}", TOKEN_PREFIX, generate_secure_code(TOKEN_SECRET_LENGTH));
+
+    let mut scopes: Vec<String> = scopes.iter().map(|scope| scope.as_str().to_string()).collect();
+    scopes.sort();
+    scopes.dedup();
+
+    let info = sqlx::query_as!(
+        TokenInfo,
+        r#"
+        INSERT INTO personal_access_tokens (user_id, name, token_hash, token_prefix, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING token_id, name, token_prefix, scopes, created_at, expires_at, last_used_at
+        "#,
+        user_id,
+        name,
+        hash_token(&token),
+        &token[..DISPLAY_PREFIX_LENGTH],
+        &scopes,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((token, info))
+}
+
+pub async fn list_tokens(pool: &PgPool, user_id: i32) -> Result<Vec<TokenInfo>, AppError> {
+    let tokens = sqlx::query_as!(
+        TokenInfo,
+        r#"
+        SELECT token_id, name, token_prefix, scopes, created_at, expires_at, last_used_at
+        FROM personal_access_tokens
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(tokens)
+}
+
+pub async fn delete_token(pool: &PgPool, user_id: i32, token_id: i32) -> Result<(), AppError> {
+    let result = sqlx::query!(
+        "DELETE FROM personal_access_tokens WHERE token_id = $1 AND user_id = $2",
+        token_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::TokenNotFound);
+    }
+    Ok(())
+}
+
+// After a password reset, like revoke_user_sessions
+pub async fn delete_user_tokens(pool: &PgPool, user_id: i32) -> Result<(), AppError> {
+    sqlx::query!("DELETE FROM personal_access_tokens WHERE user_id = $1", user_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Resolves a token sent as Bearer to its user and scopes
+pub async fn authenticate_token(pool: &PgPool, token: &str) -> Result<(i32, Vec<TokenScope>), AppError> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE personal_access_tokens t SET last_used_at = NOW()
+        FROM users u
+        WHERE t.token_hash = $1 AND u.user_id = t.user_id
+          AND (t.expires_at IS NULL OR t.expires_at > NOW())
+          AND u.deleted_at IS NULL
+        RETURNING t.user_id, t.scopes
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::TokenInvalid)?;
+
+    // Scopes this version doesn't know (anymore) are ignored
+    let scopes = row.scopes.iter().filter_map(|scope| TokenScope::from_str(scope).ok()).collect();
+    Ok((row.user_id, scopes))
+}
@@ -1,13 +1,53 @@
 use crate::errors::AppError;
 use std::env;
 
+// An OpenID Connect provider for social login, from OIDC_PROVIDERS and OIDC_<ID>_* variables
+#[derive(Clone)]
+pub struct OidcProviderConfig {
+    pub id: String, // Lowercase, used in URLs (/api/auth/oidc/{id}/...) and user_identities.provider
+    pub name: String, // Shown on the login button
+    pub issuer: String, // Discovery document is at {issuer}/.well-known/openid-configuration
+    pub client_id: String,
+    pub client_secret: Option<String>, // None for public clients, which rely on PKCE alone
+    pub scopes: String,
+}
+
+// Backend of the AI assistant, from AI_PROVIDER
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AiProviderKind {
+    OpenAi,           // api.openai.com
+    OpenAiCompatible, // Any server speaking the OpenAI chat completions API (Ollama, vLLM, ...) at AI_BASE_URL
+    Mock,             // Deterministic canned replies, for tests and local development
+}
+
+#[derive(Clone)]
+pub struct AiConfig {
+    pub provider: AiProviderKind,
+    pub api_key: Option<String>,  // Required for OpenAi
+    pub base_url: Option<String>, // Required for OpenAiCompatible, e.g. http://localhost:11434/v1
+    pub model: String,
+    pub temperature: Option<f32>, // Provider default when unset
+    pub max_tokens: Option<u32>,  // Per model reply; provider default when unset
+    pub system_prompt: String,
+    pub context_tokens: usize,    // Budget of the calendar summary sent with every request; 0 leaves it out
+    pub quotas: AiQuotas,
+}
+
+// Tokens each user may use; None = unlimited
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AiQuotas {
+    pub daily_tokens: Option<i64>,   // Per UTC day
+    pub monthly_tokens: Option<i64>, // Per calendar month (UTC)
+}
+
 #[derive(Clone)]
 pub struct Config {
     pub version: String, // Version 
     pub database_url: String,
     pub jwt_secret: String,
     pub server_address: String,
-    pub jwt_expiration_seconds: i64,
+    pub jwt_expiration_seconds: i64, // Access token lifetime
+    pub refresh_token_expiration_days: i64, // Session lifetime without a refresh
 
     // Email Service Configuration
     pub smtp_server: String,
@@ -24,10 +64,23 @@ pub struct Config {
     // Frontend Configuration
     pub frontend_url: String,
 
-    // AI Configuration
-    pub openai_api_key: String,
+    // Public base URL of this API, for links in emails that call it directly (e.g. RSVP links)
+    pub api_url: String,
+
+    // Passkeys (WebAuthn)
+    pub webauthn_rp_id: Option<String>, // Relying party ID; defaults to the host of webauthn_rp_origin
+    pub webauthn_rp_origin: String, // Origin the browser ceremonies run on; defaults to FRONTEND_URL
 
-    pub openai_system_prompt: String, // System prompt for OpenAI API
+    // Social login (OpenID Connect)
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    pub oidc_redirect_url: String, // Where providers send the browser back to; defaults to {FRONTEND_URL}/auth/callback
+
+    // Rate limiting
+    pub auth_rate_limit_per_minute: u32, // Requests per client IP to the login, code and password reset endpoints
+    pub trust_proxy_headers: bool, // Take the client IP from X-Forwarded-For (only behind a reverse proxy)
+
+    // AI Configuration
+    pub ai: Option<AiConfig>, // None disables the AI assistant
 }
 
 impl Config {
@@ -45,11 +98,16 @@ impl Config {
             .unwrap_or_else(|_| "0.0.0.0:8000".to_string()); // Default is fine here
 
         let jwt_expiration_str = env::var("JWT_EXPIRATION_SECONDS")
-            .unwrap_or_else(|_| "86400".to_string()); // Default is fine
+            .unwrap_or_else(|_| "900".to_string()); // Short-lived; clients refresh
 
         let jwt_expiration_seconds = jwt_expiration_str.parse::<i64>()
             .map_err(|e| AppError::ConfigurationError(format!("Invalid JWT_EXPIRATION_SECONDS format: {}", e)))?;
 
+        let refresh_expiration_str = env::var("REFRESH_TOKEN_EXPIRATION_DAYS")
+            .unwrap_or_else(|_| "30".to_string()); // Default 30 days
+        let refresh_token_expiration_days = refresh_expiration_str.parse::<i64>()
+            .map_err(|e| AppError::ConfigurationError(format!("Invalid REFRESH_TOKEN_EXPIRATION_DAYS format: {}", e)))?;
+
         // Load Email Config
         let smtp_server = env::var("SMTP_SERVER")
             .map_err(|e| AppError::ConfigurationError(format!("Missing SMTP_SERVER: {}", e)))?;
@@ -81,13 +139,39 @@ impl Config {
         let frontend_url = env::var("FRONTEND_URL")
             .map_err(|e| AppError::ConfigurationError(format!("Missing FRONTEND_URL: {}", e)))?;
 
-        // --- Load OpenAI API Key ---
-        let openai_api_key = env::var("OPENAI_API_KEY")
-            .map_err(|e| AppError::ConfigurationError(format!("Missing OPENAI_API_KEY: {}", e)))?;
+        // --- Load public API URL ---
+        let api_url = env::var("API_URL")
+            .unwrap_or_else(|_| "http://localhost:8000".to_string()) // Default for local development
+            .trim_end_matches('/')
+            .to_string();
 
-        // --- Load OpenAI System Prompt ---
-        let openai_system_prompt = env::var("OPENAI_SYSTEM_PROMPT")
-            .unwrap_or_else(|_| "You are a helpful assistant.".to_string()); // Default system prompt
+        // --- Load WebAuthn relying party ---
+        let webauthn_rp_id = env::var("WEBAUTHN_RP_ID").ok();
+        let webauthn_rp_origin = env::var("WEBAUTHN_RP_ORIGIN")
+            .unwrap_or_else(|_| frontend_url.clone());
+
+        // --- Load OpenID Connect providers ---
+        let oidc_providers = env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|id| id.trim().to_lowercase())
+            .filter(|id| !id.is_empty())
+            .map(|id| load_oidc_provider(&id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let oidc_redirect_url = env::var("OIDC_REDIRECT_URL")
+            .unwrap_or_else(|_| format!("{}/auth/callback", frontend_url.trim_end_matches('/')));
+
+        // --- Load rate limiting ---
+        let auth_rate_limit_per_minute = env::var("AUTH_RATE_LIMIT_PER_MINUTE")
+            .unwrap_or_else(|_| "20".to_string()) // Default 20 requests per minute
+            .parse::<u32>()
+            .map_err(|e| AppError::ConfigurationError(format!("Invalid AUTH_RATE_LIMIT_PER_MINUTE format: {}", e)))?;
+        let trust_proxy_headers = env::var("TRUST_PROXY_HEADERS")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+
+        // --- Load AI provider ---
+        let ai = load_ai_config()?;
 
         Ok(Self {
             version,
@@ -95,6 +179,7 @@ impl Config {
             jwt_secret,
             server_address,
             jwt_expiration_seconds,
+            refresh_token_expiration_days,
             smtp_server,
             smtp_port,
             smtp_user,
@@ -104,8 +189,112 @@ impl Config {
             verification_code_expires_minutes,
             reset_code_expires_minutes,
             frontend_url,
-            openai_api_key,
-            openai_system_prompt,
+            api_url,
+            webauthn_rp_id,
+            webauthn_rp_origin,
+            oidc_providers,
+            oidc_redirect_url,
+            auth_rate_limit_per_minute,
+            trust_proxy_headers,
+            ai,
         })
     }
+}
+
+// Reads OIDC_<ID>_ISSUER, _CLIENT_ID, _CLIENT_SECRET, _NAME and _SCOPES.
+// Google and Microsoft get their issuer and name by default.
+fn load_oidc_provider(id: &str) -> Result<OidcProviderConfig, AppError> {
+    let var = |suffix: &str| env::var(format!("OIDC_{}_{}", id.to_uppercase(), suffix)).ok();
+
+    let (default_issuer, default_name) = match id {
+        "google" => (Some("https://accounts.google.com"), "Google"),
+        "microsoft" => (Some("https://login.microsoftonline.com/common/v2.0"), "Microsoft"),
+        _ => (None, id),
+    };
+
+    let issuer = var("ISSUER")
+        .or(default_issuer.map(str::to_string))
+        .ok_or_else(|| AppError::ConfigurationError(format!("Missing OIDC_{}_ISSUER", id.to_uppercase())))?;
+    let client_id = var("CLIENT_ID")
+        .ok_or_else(|| AppError::ConfigurationError(format!("Missing OIDC_{}_CLIENT_ID", id.to_uppercase())))?;
+
+    Ok(OidcProviderConfig {
+        id: id.to_string(),
+        name: var("NAME").unwrap_or_else(|| default_name.to_string()),
+        issuer: issuer.trim_end_matches('/').to_string(),
+        client_id,
+        client_secret: var("CLIENT_SECRET"),
+        scopes: var("SCOPES").unwrap_or_else(|| "openid email profile".to_string()),
+    })
+}
+
+// Reads AI_PROVIDER (openai, openai-compatible, mock or none), AI_API_KEY (or OPENAI_API_KEY), AI_BASE_URL,
+// AI_MODEL, AI_TEMPERATURE, AI_MAX_TOKENS, AI_SYSTEM_PROMPT (or OPENAI_SYSTEM_PROMPT), AI_CONTEXT_TOKENS,
+// AI_DAILY_TOKEN_QUOTA and AI_MONTHLY_TOKEN_QUOTA.
+// Without AI_PROVIDER, OpenAI is used if an API key is set and the assistant is disabled otherwise.
+fn load_ai_config() -> Result<Option<AiConfig>, AppError> {
+    let api_key = env::var("AI_API_KEY").or_else(|_| env::var("OPENAI_API_KEY")).ok().filter(|key| !key.is_empty());
+
+    let provider = match env::var("AI_PROVIDER").ok().map(|value| value.trim().to_lowercase()).as_deref() {
+        Some("openai") => AiProviderKind::OpenAi,
+        Some("openai-compatible") => AiProviderKind::OpenAiCompatible,
+        Some("mock") => AiProviderKind::Mock,
+        Some("none") | Some("") => return Ok(None),
+        Some(other) => return Err(AppError::ConfigurationError(format!("Unknown AI_PROVIDER: {}", other))),
+        None if api_key.is_some() => AiProviderKind::OpenAi,
+        None => return Ok(None),
+    };
+
+    let base_url = env::var("AI_BASE_URL").ok().map(|url| url.trim_end_matches('/').to_string());
+    let model = env::var("AI_MODEL").ok();
+    let (model, base_url) = match provider {
+        AiProviderKind::OpenAi => {
+            if api_key.is_none() {
+                return Err(AppError::ConfigurationError("AI_PROVIDER=openai needs AI_API_KEY or OPENAI_API_KEY".to_string()));
+            }
+            (model.unwrap_or_else(|| "gpt-4.1".to_string()), base_url)
+        }
+        AiProviderKind::OpenAiCompatible => {
+            // There is no sensible default model or address for a self-hosted server
+            let base_url = base_url
+                .ok_or_else(|| AppError::ConfigurationError("AI_PROVIDER=openai-compatible needs AI_BASE_URL".to_string()))?;
+            let model = model
+                .ok_or_else(|| AppError::ConfigurationError("AI_PROVIDER=openai-compatible needs AI_MODEL".to_string()))?;
+            (model, Some(base_url))
+        }
+        AiProviderKind::Mock => (model.unwrap_or_else(|| "mock".to_string()), None),
+    };
+
+    let temperature = env::var("AI_TEMPERATURE")
+        .ok()
+        .map(|value| value.parse::<f32>())
+        .transpose()
+        .map_err(|e| AppError::ConfigurationError(format!("Invalid AI_TEMPERATURE format: {}", e)))?;
+    if temperature.is_some_and(|t| !(0.0..=2.0).contains(&t)) {
+        return Err(AppError::ConfigurationError("AI_TEMPERATURE must be between 0 and 2".to_string()));
+    }
+    let max_tokens = env::var("AI_MAX_TOKENS")
+        .ok()
+        .map(|value| value.parse::<u32>())
+        .transpose()
+        .map_err(|e| AppError::ConfigurationError(format!("Invalid AI_MAX_TOKENS format: {}", e)))?;
+    let context_tokens = env::var("AI_CONTEXT_TOKENS")
+        .unwrap_or_else(|_| "1500".to_string()) // Default: about two weeks of a busy calendar
+        .parse::<usize>()
+        .map_err(|e| AppError::ConfigurationError(format!("Invalid AI_CONTEXT_TOKENS format: {}", e)))?;
+    let quota = |name: &str| {
+        env::var(name)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.trim().parse::<u32>().map(i64::from)) // Unset = unlimited
+            .transpose()
+            .map_err(|e| AppError::ConfigurationError(format!("Invalid {} format: {}", name, e)))
+    };
+    let quotas = AiQuotas { daily_tokens: quota("AI_DAILY_TOKEN_QUOTA")?, monthly_tokens: quota("AI_MONTHLY_TOKEN_QUOTA")? };
+
+    let system_prompt = env::var("AI_SYSTEM_PROMPT")
+        .or_else(|_| env::var("OPENAI_SYSTEM_PROMPT"))
+        .unwrap_or_else(|_| "You are a helpful assistant.".to_string()); // Default system prompt
+
+    Ok(Some(AiConfig { provider, api_key, base_url, model, temperature, max_tokens, system_prompt, context_tokens, quotas }))
 }
\ No newline at end of file
@@ -1,15 +1,24 @@
 use lettre::{
-    message::{header::ContentType, Mailbox, Message},
+    message::{header::ContentType, Attachment, Mailbox, Message, MultiPart, SinglePart},
     transport::smtp::{authentication::Credentials, client::{Tls, TlsParameters}},
     SmtpTransport, Transport, Address,
 };
 use crate::config::Config;
 use crate::errors::AppError; // Use AppError for email sending errors
+use crate::models::{enums::EventInvitationStatus, event::Event};
 use crate::utils::security::split_code;
 use std::sync::Arc; // For Arc<Config>
+use chrono::{DateTime, Utc};
 use rustls::ClientConfig as RustlsClientConfig;
 use urlencoding; // For URL encoding
 
+// One-click RSVP links of an invitation email (GET /api/invitations/respond?token=)
+pub struct RsvpLinks {
+    pub accept: String,
+    pub maybe: String,
+    pub reject: String,
+}
+
 #[derive(Clone)] // EmailService needs to be cloneable to be in AppState
 pub struct EmailService {
     sender: Mailbox,
@@ -135,6 +144,221 @@ impl EmailService {
         Ok(())
     }
 
-    // Add other email sending methods here (e.g., for event invitations) LATER
-    // pub async fn send_invitation_email(...) -> Result<(), AppError> { ... }
+    // Send a reminder for an upcoming event occurrence or deadline
+    pub async fn send_reminder_email(&self, recipient_email: &str, title: &str, at: DateTime<Utc>, is_deadline: bool) -> Result<(), AppError> {
+        let recipient_address: Address = recipient_email.parse()
+            .map_err(|e| AppError::EmailSendingError(format!("Invalid recipient email address: {}", e)))?;
+
+        let when = at.format("%A, %B %-d %Y at %H:%M UTC");
+        let (subject, email_body) = if is_deadline {
+            (
+                format!("Reminder: {} is due soon", title),
+                format!("Hi,\n\nThis is a reminder that \"{}\" is due on {}.\n\nOpen Qalendar to see the details: {}", title, when, self.config.frontend_url),
+            )
+        } else {
+            (
+                format!("Reminder: {}", title),
+                format!("Hi,\n\nThis is a reminder that \"{}\" starts on {}.\n\nOpen Qalendar to see the details: {}", title, when, self.config.frontend_url),
+            )
+        };
+
+        let email = Message::builder()
+            .from(self.sender.clone())
+            .to(Mailbox::new(None, recipient_address))
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email_body)
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to build email message: {}", e)))?;
+
+        let mailer = self.mailer.clone(); // Clone mailer for the blocking task
+        tokio::task::spawn_blocking(move || mailer.send(&email))
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Email sending task failed: {}", e)))?
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to send email: {:?}", e)))?;
+
+        tracing::info!("Reminder email sent to {}", recipient_email);
+        Ok(())
+    }
+
+    // Send an event invitation with the details, an .ics REQUEST attachment and one-click RSVP links
+    pub async fn send_invitation_email(
+        &self,
+        recipient_email: &str,
+        organizer_name: &str,
+        event: &Event,
+        ics: String,
+        links: &RsvpLinks,
+        has_account: bool, // Without one, point the recipient to sign-up instead of the app
+    ) -> Result<(), AppError> {
+        let recipient_address: Address = recipient_email.parse()
+            .map_err(|e| AppError::EmailSendingError(format!("Invalid recipient email address: {}", e)))?;
+
+        let mut details = format!(
+            "When: {} - {} (UTC)",
+            event.start_time.format("%A, %B %-d %Y %H:%M"),
+            event.end_time.format("%H:%M"),
+        );
+        if event.rrule.as_deref().is_some_and(|r| !r.trim().is_empty()) {
+            details.push_str("\nRepeats: see the attached calendar file");
+        }
+        if let Some(location) = event.location.as_deref().filter(|l| !l.is_empty()) {
+            details.push_str(&format!("\nWhere: {}", location));
+        }
+        if let Some(description) = event.description.as_deref().filter(|d| !d.is_empty()) {
+            details.push_str(&format!("\n\n{}", description));
+        }
+
+        let closing = if has_account {
+            format!("You can also open the attached invite in your calendar app, or respond in Qalendar: {}", self.config.frontend_url)
+        } else {
+            format!(
+                "You can also open the attached invite in your calendar app. Sign up for Qalendar with this email address to find the event in your calendar: {}",
+                self.config.frontend_url
+            )
+        };
+        let email_body = format!(
+            "Hi,\n\n{} invited you to \"{}\" on Qalendar.\n\n{}\n\nRespond with one click:\nAccept: {}\nMaybe: {}\nDecline: {}\n\n{}",
+            organizer_name,
+            event.title,
+            details,
+            links.accept,
+            links.maybe,
+            links.reject,
+            closing
+        );
+
+        let ics_content_type = ContentType::parse("text/calendar; charset=utf-8; method=REQUEST")
+            .map_err(|e| AppError::EmailSendingError(format!("Invalid attachment content type: {}", e)))?;
+        let email = Message::builder()
+            .from(self.sender.clone())
+            .to(Mailbox::new(None, recipient_address))
+            .subject(format!("Invitation: {}", event.title))
+            .multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(email_body))
+                    .singlepart(Attachment::new("invite.ics".to_string()).body(ics, ics_content_type)),
+            )
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to build email message: {}", e)))?;
+
+        let mailer = self.mailer.clone(); // Clone mailer for the blocking task
+        tokio::task::spawn_blocking(move || mailer.send(&email))
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Email sending task failed: {}", e)))?
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to send email: {:?}", e)))?;
+
+        tracing::info!("Invitation email sent to {}", recipient_email);
+        Ok(())
+    }
+
+    // Tell the event owner how an invitee responded
+    pub async fn send_invitation_response_email(
+        &self,
+        recipient_email: &str,
+        invitee_name: &str,
+        event_title: &str,
+        status: EventInvitationStatus,
+    ) -> Result<(), AppError> {
+        let recipient_address: Address = recipient_email.parse()
+            .map_err(|e| AppError::EmailSendingError(format!("Invalid recipient email address: {}", e)))?;
+
+        let response = match status {
+            EventInvitationStatus::Accepted => "accepted",
+            EventInvitationStatus::Maybe => "replied maybe to",
+            EventInvitationStatus::Rejected => "declined",
+            EventInvitationStatus::Pending => "has not yet responded to",
+        };
+        let email_body = format!(
+            "Hi,\n\n{} {} your invitation to \"{}\".\n\nSee all responses in Qalendar: {}",
+            invitee_name,
+            response,
+            event_title,
+            self.config.frontend_url
+        );
+
+        let email = Message::builder()
+            .from(self.sender.clone())
+            .to(Mailbox::new(None, recipient_address))
+            .subject(format!("{} {} \"{}\"", invitee_name, response, event_title))
+            .header(ContentType::TEXT_PLAIN)
+            .body(email_body)
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to build email message: {}", e)))?;
+
+        let mailer = self.mailer.clone(); // Clone mailer for the blocking task
+        tokio::task::spawn_blocking(move || mailer.send(&email))
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Email sending task failed: {}", e)))?
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to send email: {:?}", e)))?;
+
+        tracing::info!("Invitation response email sent to {}", recipient_email);
+        Ok(())
+    }
+
+    // Tell someone without an account that a calendar was shared with them
+    pub async fn send_pending_share_email(
+        &self,
+        recipient_email: &str,
+        owner_name: &str,
+        message: Option<&str>,
+    ) -> Result<(), AppError> {
+        let recipient_address: Address = recipient_email.parse()
+            .map_err(|e| AppError::EmailSendingError(format!("Invalid recipient email address: {}", e)))?;
+
+        let note = message
+            .filter(|m| !m.is_empty())
+            .map(|m| format!("\n\nTheir message:\n{}", m))
+            .unwrap_or_default();
+        let email_body = format!(
+            "Hi,\n\n{} shared their calendar with you on Qalendar.{}\n\nSign up with this email address to see it: {}",
+            owner_name,
+            note,
+            self.config.frontend_url
+        );
+
+        let email = Message::builder()
+            .from(self.sender.clone())
+            .to(Mailbox::new(None, recipient_address))
+            .subject(format!("{} shared their calendar with you", owner_name))
+            .header(ContentType::TEXT_PLAIN)
+            .body(email_body)
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to build email message: {}", e)))?;
+
+        let mailer = self.mailer.clone(); // Clone mailer for the blocking task
+        tokio::task::spawn_blocking(move || mailer.send(&email))
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Email sending task failed: {}", e)))?
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to send email: {:?}", e)))?;
+
+        tracing::info!("Share email sent to {}", recipient_email);
+        Ok(())
+    }
+
+    // Warn the owner of an account that was locked after repeated failed sign-in attempts
+    pub async fn send_security_alert_email(&self, recipient_email: &str, attempt: &str, failures: i32) -> Result<(), AppError> {
+        let recipient_address: Address = recipient_email.parse()
+            .map_err(|e| AppError::EmailSendingError(format!("Invalid recipient email address: {}", e)))?;
+
+        let email_body = format!(
+            "Hi,\n\nThere were {} failed attempts to {} on your Qalendar account. Further attempts are blocked for a while.\n\nIf this was you, just wait and try again. If not, someone may be trying to get into your account; consider changing your password and enabling two-factor authentication: {}",
+            failures,
+            attempt,
+            self.config.frontend_url
+        );
+
+        let email = Message::builder()
+            .from(self.sender.clone())
+            .to(Mailbox::new(None, recipient_address))
+            .subject("Suspicious activity on your Qalendar account")
+            .header(ContentType::TEXT_PLAIN)
+            .body(email_body)
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to build email message: {}", e)))?;
+
+        let mailer = self.mailer.clone(); // Clone mailer for the blocking task
+        tokio::task::spawn_blocking(move || mailer.send(&email))
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Email sending task failed: {}", e)))?
+            .map_err(|e| AppError::EmailSendingError(format!("Failed to send email: {:?}", e)))?;
+
+        tracing::info!("Security alert email sent to {}", recipient_email);
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -21,10 +21,16 @@ pub enum AppError {
     InternalServerError(String), // Catch-all for unexpected errors
     DeadlineNotFound,
     EventNotFound,
+    OccurrenceNotFound, // original_start doesn't match an occurrence of the series
     CategoryNotFound,
     CategoryNameAlreadyExists, // For unique constraint violation
     ShareNotFound,         // For calendar_shares
+    ShareAlreadyExists, // The owner already shares with this user or email address
     InvitationNotFound,    // For event_invitations
+    InvitationAlreadyExists, // The event already has an invitation for this user or email address
+    ReminderNotFound,      // For reminders
+    SessionNotFound,       // For sessions
+    SessionInvalid, // Refresh token unknown or expired, or the access token's session was revoked
     CannotModifySharedItem, // Trying to edit/delete an item you don't own via a share
     CannotInviteToNonOwnedEvent, // Trying to invite to an event you don't own
     CannotRespondToNonInvitedEvent, // Trying to respond to an invitation you didn't receive
@@ -38,15 +44,36 @@ pub enum AppError {
     TfaCodeInvalid, // Invalid TFA code
     TfaAlreadyEnabled, // TFA is already enabled for the user
     TfaNotEnabled, // For cases where TFA is not enabled but required
+    TfaChallengeInvalid, // Challenge token from the password step is bad, expired or already used
+    TfaTooManyAttempts, // Too many wrong codes for one challenge; the login has to start over
+    PasskeyNotFound,      // For passkeys
+    PasskeyAlreadyRegistered, // The credential is already registered (to this or another account)
+    PasskeyCeremonyInvalid, // Unknown, expired or already finished passkey ceremony
+    PasskeyVerificationFailed, // The browser's passkey response did not verify
+    RateLimited(u64), // Too many requests or failed attempts; seconds until the client may retry
+    OidcProviderNotFound, // Provider ID not in OIDC_PROVIDERS
+    OidcStateInvalid, // Unknown, expired or already finished social login
+    OidcProviderUnavailable(String), // Provider's discovery, keys or token endpoint could not be reached
+    OidcLoginFailed(String), // Provider rejected the code, or its ID token did not verify
+    IdentityNotFound,      // For user_identities
+    IdentityAlreadyLinked, // The provider account is already linked to a user
+    TokenNotFound,         // For personal_access_tokens
+    TokenInvalid, // Personal access token unknown, expired or revoked
+    InsufficientScope(&'static str), // Personal access token lacks the scope the endpoint requires
+    AccessTokenNotAllowed, // Endpoint needs a login session, personal access tokens can't use it
+    ThreadNotFound,        // For ai_threads
+    AiQuotaExceeded { period: &'static str, retry_after: u64 }, // Daily or monthly token quota used up; seconds until it resets
     // Consider UserNotFound for when an email address isn't found for password reset/resend\
-    OpenAIError(String), // <-- Add this
+    AiProviderError(String), // The AI provider failed or returned something unusable
     FileUploadError(String), // For issues reading/processing uploaded files
     InvalidMultipartData(String), // For malformed multipart requests
+    Conflict(&'static str), // Other unique constraint violations; the message says what already exists
 }
 
 // How AppError should be converted into an HTTP response
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let mut retry_after = None; // Retry-After header, for RateLimited
         let (status, error_message) = match self {
             AppError::DatabaseConnectionError(e) => {
                 tracing::error!("Database connection error: {:?}", e);
@@ -91,9 +118,15 @@ impl IntoResponse for AppError {
             },
             AppError::DeadlineNotFound => (StatusCode::NOT_FOUND, "Deadline not found".to_string()),
             AppError::EventNotFound => (StatusCode::NOT_FOUND, "Event not found".to_string()),
+            AppError::OccurrenceNotFound => (StatusCode::NOT_FOUND, "Occurrence not found".to_string()),
             AppError::CategoryNotFound => (StatusCode::NOT_FOUND, "Category not found".to_string()),
             AppError::ShareNotFound => (StatusCode::NOT_FOUND, "Share not found".to_string()),
+            AppError::ShareAlreadyExists => (StatusCode::CONFLICT, "Calendar is already shared with this user".to_string()),
             AppError::InvitationNotFound => (StatusCode::NOT_FOUND, "Invitation not found".to_string()),
+            AppError::InvitationAlreadyExists => (StatusCode::CONFLICT, "This user is already invited to the event".to_string()),
+            AppError::ReminderNotFound => (StatusCode::NOT_FOUND, "Reminder not found".to_string()),
+            AppError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found".to_string()),
+            AppError::SessionInvalid => (StatusCode::UNAUTHORIZED, "Session expired or revoked".to_string()),
             AppError::CategoryNameAlreadyExists => (StatusCode::CONFLICT, "A category with this name already exists".to_string()),
             AppError::CannotModifySharedItem => (StatusCode::FORBIDDEN, "Cannot modify item shared with you".to_string()),
             AppError::CannotInviteToNonOwnedEvent => (StatusCode::FORBIDDEN, "Cannot invite to an event you do not own".to_string()),
@@ -111,10 +144,41 @@ impl IntoResponse for AppError {
             AppError::TfaCodeInvalid => (StatusCode::BAD_REQUEST, "Invalid TFA code".to_string()), // Keep vague for security
             AppError::TfaAlreadyEnabled => (StatusCode::BAD_REQUEST, "TFA is already enabled".to_string()), // Keep vague for security
             AppError::TfaNotEnabled => (StatusCode::BAD_REQUEST, "TFA is not enabled".to_string()), // Keep vague for security
+            AppError::TfaChallengeInvalid => (StatusCode::UNAUTHORIZED, "TFA challenge expired or invalid, please log in again".to_string()),
+            AppError::TfaTooManyAttempts => (StatusCode::TOO_MANY_REQUESTS, "Too many invalid TFA codes, please log in again".to_string()),
+            AppError::PasskeyNotFound => (StatusCode::NOT_FOUND, "Passkey not found".to_string()),
+            AppError::PasskeyAlreadyRegistered => (StatusCode::CONFLICT, "This passkey is already registered".to_string()),
+            AppError::PasskeyCeremonyInvalid => (StatusCode::BAD_REQUEST, "Passkey request expired or invalid, please start again".to_string()),
+            AppError::PasskeyVerificationFailed => (StatusCode::UNAUTHORIZED, "Passkey verification failed".to_string()),
+            AppError::OidcProviderNotFound => (StatusCode::NOT_FOUND, "Sign-in provider not found".to_string()),
+            AppError::OidcStateInvalid => (StatusCode::BAD_REQUEST, "Sign-in request expired or invalid, please start again".to_string()),
+            AppError::OidcProviderUnavailable(msg) => {
+                tracing::error!("OIDC provider unavailable: {}", msg);
+                (StatusCode::BAD_GATEWAY, "Sign-in provider is unavailable".to_string())
+            }
+            AppError::OidcLoginFailed(msg) => {
+                tracing::warn!("OIDC login failed: {}", msg);
+                (StatusCode::UNAUTHORIZED, "Sign-in with the provider failed".to_string())
+            }
+            AppError::IdentityNotFound => (StatusCode::NOT_FOUND, "Linked account not found".to_string()),
+            AppError::IdentityAlreadyLinked => (StatusCode::CONFLICT, "This provider account is already linked to a user".to_string()),
+            AppError::TokenNotFound => (StatusCode::NOT_FOUND, "Access token not found".to_string()),
+            AppError::ThreadNotFound => (StatusCode::NOT_FOUND, "Thread not found".to_string()),
+            AppError::TokenInvalid => (StatusCode::UNAUTHORIZED, "Access token invalid, expired or revoked".to_string()),
+            AppError::InsufficientScope(scope) => (StatusCode::FORBIDDEN, format!("Access token lacks the required scope: {}", scope)),
+            AppError::AccessTokenNotAllowed => (StatusCode::FORBIDDEN, "Personal access tokens cannot be used for this endpoint".to_string()),
+            AppError::RateLimited(seconds) => {
+                retry_after = Some(seconds);
+                (StatusCode::TOO_MANY_REQUESTS, "Too many attempts, please try again later".to_string())
+            }
+            AppError::AiQuotaExceeded { period, retry_after: seconds } => {
+                retry_after = Some(seconds);
+                (StatusCode::TOO_MANY_REQUESTS, format!("{} AI usage quota exceeded", period))
+            }
             // Use existing errors for cases like UserNotFound, InvalidCredentials, ValidationFailed
             // e.g., trying to resend verification email to non-existent email -> UserNotFound (404)
-            AppError::OpenAIError(msg) => {
-                tracing::error!("OpenAI API error: {}", msg);
+            AppError::AiProviderError(msg) => {
+                tracing::error!("AI provider error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Failed to communicate with AI service".to_string()) // Don't expose internal details
            }
             AppError::FileUploadError(msg) => {
@@ -125,27 +189,38 @@ impl IntoResponse for AppError {
                 tracing::warn!("Invalid multipart data: {}", msg);
                 (StatusCode::BAD_REQUEST, format!("Invalid request data: {}", msg))
            }
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.to_string()),
         };
 
         let body = Json(json!({ "error": error_message }));
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
 // Convenience conversions using `?` operator
 impl From<sqlx::Error> for AppError {
     fn from(e: sqlx::Error) -> Self {
-        // Check if it's a unique constraint violation for categories
+        // Unique constraint violations (PostgreSQL code 23505) become 409s, by the violated constraint
         if let sqlx::Error::Database(db_error) = &e {
-            // PostgreSQL unique violation error code is often "23505"
-            // Check your database driver docs for exact code if needed
-            if let Some(code) = db_error.code() {
-                if code.as_ref() == "23505" {
-                    // You might need more specific checks if other unique constraints exist
-                    // For now, assume 23505 on category INSERT implies name conflict
-                     tracing::warn!("Database Unique Constraint Error: {:?}", e);
-                    return AppError::CategoryNameAlreadyExists;
-                }
+            if db_error.code().as_deref() == Some("23505") {
+                tracing::warn!("Database Unique Constraint Error: {:?}", e);
+                return match db_error.constraint().unwrap_or_default() {
+                    "users_email_key" | "idx_users_email_active" => AppError::EmailInUse,
+                    "categories_user_id_name_key" => AppError::CategoryNameAlreadyExists,
+                    "calendar_shares_owner_user_id_shared_with_user_id_key" | "idx_pending_calendar_shares_active" => AppError::ShareAlreadyExists,
+                    "event_invitations_event_id_invited_user_id_key" | "idx_pending_event_invitations_active" => AppError::InvitationAlreadyExists,
+                    "passkeys_credential_id_key" => AppError::PasskeyAlreadyRegistered,
+                    "user_identities_provider_subject_key" => AppError::IdentityAlreadyLinked,
+                    "idx_events_ical_uid" => AppError::Conflict("An event with this iCalendar UID already exists"),
+                    "idx_deadlines_ical_uid" => AppError::Conflict("A deadline with this iCalendar UID already exists"),
+                    "event_exceptions_event_id_original_occurrence_time_key" => AppError::Conflict("This occurrence was already changed"),
+                    // Random tokens and IDs colliding, or two requests racing to create the same row
+                    _ => AppError::Conflict("The request conflicts with a concurrent change, please try again"),
+                };
             }
         }
         tracing::error!("Unmapped Database Error: {:?}", e);
@@ -173,6 +248,6 @@ impl From<jsonwebtoken::errors::Error> for AppError {
 
 impl From<AsyncOpenAIError> for AppError {
     fn from(e: AsyncOpenAIError) -> Self {
-        AppError::OpenAIError(e.to_string()) // Convert to string for storing in our error variant
+        AppError::AiProviderError(e.to_string()) // Convert to string for storing in our error variant
     }
 }
\ No newline at end of file
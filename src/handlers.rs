@@ -3,9 +3,19 @@ pub mod me_handler;
 pub mod category_handler;
 pub mod deadline_handler;
 pub mod event_handler;
+pub mod event_exception_handler;
 pub mod invitation_handler;
 pub mod share_handler;
 pub mod calendar_handler;
+pub mod ics_handler;
+pub mod import_handler;
 pub mod sync_handler;
+pub mod freebusy_handler;
+pub mod scheduling_handler;
+pub mod reminder_handler;
+pub mod session_handler;
+pub mod passkey_handler;
 pub mod ai_handler;
-pub mod open_share_handler;
\ No newline at end of file
+pub mod open_share_handler;
+pub mod oidc_handler;
+pub mod token_handler;
\ No newline at end of file
@@ -1,11 +1,7 @@
-use async_openai::{
-    config,
-    types::{
-        ChatCompletionResponseMessage,
-    },
-};
 use axum::{
-    extract::{State, Multipart},
+    extract::{Path, State, Multipart, Query},
+    http::StatusCode,
+    response::{sse::{Event as SseEvent, KeepAlive, Sse}, IntoResponse},
     Json,
 };
 use bytes::Bytes; // For handling file bytes
@@ -13,22 +9,130 @@ use mime::Mime; // For MIME type handling
 use base64::Engine as Base64Engine; // For Base64 encoding
 use base64::engine::general_purpose::STANDARD as Base64Standard; // Standard Base64 alphabet
 use crate::{
-    ai::OpenAIClient, errors::AppError, middleware::auth::AuthenticatedUser, config::Config, AppState // Import our OpenAI client wrapper
+    errors::AppError, middleware::auth::AuthenticatedUser, AppState
+};
+use crate::ai::{assistant::{run_assistant, AssistantEvent, AssistantOutcome}, thread, usage, AiClient, AiMessage};
+use crate::handlers::{deadline_handler::insert_deadline, event_handler::insert_event};
+use crate::config::AiQuotas;
+use crate::models::ai::{
+    AiAssistantParams, AiAssistantResponse, AiProposal, AiStreamDone, AiThreadInfo, AiThreadResponse, AiUsageResponse, AppliedProposal
 };
-use serde_json::json; // For simple JSON response
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio::{sync::mpsc, task::AbortHandle};
+use tokio_stream::{wrappers::UnboundedReceiverStream, Stream, StreamExt};
+use validator::Validate;
 
 
 // Define the system prompt here or load from config (default behavior)
 // const SYSTEM_PROMPT: &str = "Act as a helpful calendar assistant. Provide concise answers.";
 
 // --- POST /api/me/ai-assistant handler ---
+// Multipart fields: 'prompt', 'files' (images) and optionally 'timeZone' (IANA name, for reading local dates)
+// and 'threadId' (continue an earlier conversation; a new thread is started without it).
+// The assistant proposes events and deadlines through tool calls; they are created with ?apply=true,
+// otherwise the client shows them for confirmation and sends them to /api/me/ai-assistant/apply.
 pub async fn handle_ai_prompt(
     State(state): State<AppState>,
     AuthenticatedUser { user_id }: AuthenticatedUser, // Ensure user is authenticated
-    mut multipart: Multipart, // Extract multipart data
-) -> Result<Json<serde_json::Value>, AppError> { // Return JSON response (AI text)
+    Query(params): Query<AiAssistantParams>,
+    multipart: Multipart, // Extract multipart data
+) -> Result<Json<AiAssistantResponse>, AppError> { // Return the AI text and its proposal
+    let prompt = read_prompt(multipart).await?;
+    let history = load_thread_history(&state.pool, user_id, prompt.thread_id).await?;
+    let image_count = prompt.image_data.len();
+
+    // Let the assistant answer, calling tools against the user's calendar as needed
+    let outcome = run_assistant(&state, user_id, &prompt.text, prompt.image_data, prompt.time_zone, history, None).await?;
+    let response = finish_prompt(&state.pool, user_id, prompt.thread_id, &prompt.text, image_count, outcome, &params).await?;
+    Ok(Json(response))
+}
+
+// --- POST /api/me/ai-assistant/stream handler ---
+// Same input and query parameters as handle_ai_prompt, answered as Server-Sent Events while the model writes:
+// 'delta' events with pieces of the answer, 'tool' events when the assistant runs a tool, and finally one
+// 'done' event (the AiAssistantResponse plus token usage and finish reason) or one 'error' event.
+// The work runs in a task that is aborted when the client disconnects, which closes the request to the provider.
+pub async fn stream_ai_prompt(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Query(params): Query<AiAssistantParams>,
+    multipart: Multipart,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, axum::Error>>>, AppError> {
+    // Bad input, used up quotas and unknown threads still fail with a plain error response.
+    // Prompts racing for the rest of a quota are only told apart by the first turn, with an 'error' event.
+    let prompt = read_prompt(multipart).await?;
+    usage::check_quota(&state.pool, user_id, ai_quotas(&state)).await?;
+    let history = load_thread_history(&state.pool, user_id, prompt.thread_id).await?;
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let task = tokio::spawn(async move {
+        let image_count = prompt.image_data.len();
+        let events = sender.clone();
+        let on_event = move |event: AssistantEvent| {
+            let _ = events.send(match event {
+                AssistantEvent::Delta(text) => SseEvent::default().event("delta").json_data(json!({ "text": text })),
+                AssistantEvent::ToolCall(name) => SseEvent::default().event("tool").json_data(json!({ "name": name })),
+            });
+        };
+
+        let result = async {
+            let outcome = run_assistant(&state, user_id, &prompt.text, prompt.image_data, prompt.time_zone, history, Some(&on_event)).await?;
+            let (usage, finish_reason) = (outcome.usage, outcome.finish_reason.clone());
+            let response = finish_prompt(&state.pool, user_id, prompt.thread_id, &prompt.text, image_count, outcome, &params).await?;
+            Ok::<_, AppError>(AiStreamDone { response, usage, finish_reason })
+        }
+        .await;
+
+        let last_event = match result {
+            Ok(done) => SseEvent::default().event("done").json_data(done),
+            Err(e) => error_event(e).await,
+        };
+        let _ = sender.send(last_event); // Fails only if the client is gone
+    });
 
+    // The stream owns the guard, so dropping it on disconnect aborts the task
+    let guard = AbortOnDrop(task.abort_handle());
+    let stream = UnboundedReceiverStream::new(receiver).map(move |event| {
+        let _guard = &guard;
+        event
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// Aborts a spawned task when dropped
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// An error during streaming, with the body and status it would have as a plain response
+async fn error_event(error: AppError) -> Result<SseEvent, axum::Error> {
+    let response = error.into_response(); // Logs the error like any other
+    let status = response.status().as_u16();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+    let mut data: Value = serde_json::from_slice(&body).unwrap_or_else(|_| json!({}));
+    data["status"] = json!(status);
+    SseEvent::default().event("error").json_data(data)
+}
+
+// Input of a prompt request
+struct PromptInput {
+    text: String, // Empty if only images were sent
+    image_data: Vec<(String, String)>, // Vec<(base64_string, mime_type)>
+    time_zone: Tz,
+    thread_id: Option<i32>,
+}
+
+// --- Helper: Read the multipart fields of a prompt request ---
+async fn read_prompt(mut multipart: Multipart) -> Result<PromptInput, AppError> {
     let mut prompt_text: Option<String> = None;
+    let mut time_zone: Option<Tz> = None;
+    let mut thread_id: Option<i32> = None;
     let mut image_data: Vec<(String, String)> = Vec::new(); // Vec<(base64_string, mime_type)>
 
     // Process multipart fields
@@ -42,6 +146,18 @@ pub async fn handle_ai_prompt(
                 let text = field.text().await.map_err(|e| AppError::FileUploadError(format!("Failed to read prompt text: {}", e)))?;
                 prompt_text = Some(text);
             }
+            "timeZone" => {
+                let text = field.text().await.map_err(|e| AppError::InvalidMultipartData(format!("Failed to read timeZone: {}", e)))?;
+                let tz = text.trim().parse::<Tz>()
+                    .map_err(|_| AppError::InvalidMultipartData(format!("Unknown time zone: {}", text.trim())))?;
+                time_zone = Some(tz);
+            }
+            "threadId" => {
+                let text = field.text().await.map_err(|e| AppError::InvalidMultipartData(format!("Failed to read threadId: {}", e)))?;
+                let id = text.trim().parse::<i32>()
+                    .map_err(|_| AppError::InvalidMultipartData(format!("Invalid threadId: {}", text.trim())))?;
+                thread_id = Some(id);
+            }
             "files" => {
                 // Expecting file field for images
                 let file_name_str = field.file_name()
@@ -88,28 +204,122 @@ pub async fn handle_ai_prompt(
     }
     let prompt_text = prompt_text.unwrap_or_default(); // Use empty string if none provided, but files are present
 
+    Ok(PromptInput { text: prompt_text, image_data, time_zone: time_zone.unwrap_or(Tz::UTC), thread_id })
+}
 
-    // Get the OpenAI client from state
-    let openai_client = &state.openai_client; // Assuming openai_client is in AppState
+// --- Helper: The configured quotas (the routes are only mounted with an AI client) ---
+fn ai_quotas(state: &AppState) -> AiQuotas {
+    state.ai_client.as_ref().map(AiClient::quotas).unwrap_or_default()
+}
 
-    // Define system prompt (or load from config if implemented)
-    // let system_prompt = "Act as a helpful calendar assistant. Provide concise answers.";
-    // If loading from config: let system_prompt = &state.config.openai_system_prompt;
+// --- Helper: Earlier messages of the thread, checked before spending a model call ---
+async fn load_thread_history(pool: &PgPool, user_id: i32, thread_id: Option<i32>) -> Result<Vec<AiMessage>, AppError> {
+    match thread_id {
+        Some(thread_id) => thread::load_history(pool, user_id, thread_id).await,
+        None => Ok(Vec::new()),
+    }
+}
 
-    // Call the OpenAI API client
-    let response = openai_client.create_chat_completion(
-        &prompt_text,
-        image_data, // Pass the image data vector
-    ).await?; // Propagates AppError::OpenAIError
+// --- Helper: Store the exchange in its thread and, with ?apply=true, create the proposed items ---
+async fn finish_prompt(
+    pool: &PgPool,
+    user_id: i32,
+    thread_id: Option<i32>,
+    prompt_text: &str,
+    image_count: usize,
+    outcome: AssistantOutcome,
+    params: &AiAssistantParams,
+) -> Result<AiAssistantResponse, AppError> {
+    let AssistantOutcome { response, proposal, .. } = outcome;
+    let thread_id = thread::save_exchange(pool, user_id, thread_id, prompt_text, image_count, &response, &proposal).await?;
 
-    // Extract the text content from the response
-    // Assuming a single choice and a single text message from the assistant
-    let ai_text = response.choices
-        .into_iter()
-        .next() // Get the first choice
-        .and_then(|choice| choice.message.content) // Get the message content
-        .unwrap_or_else(|| "AI returned no text response.".to_string()); // Default message if response is empty
+    let applied = if params.apply.unwrap_or(false) {
+        Some(apply_proposal(pool, user_id, &proposal).await?)
+    } else {
+        None
+    };
+
+    Ok(AiAssistantResponse { thread_id, response, proposal, applied })
+}
+
+// --- POST /api/me/ai-assistant/apply handler ---
+// Creates the items of a proposal the user confirmed (possibly edited or trimmed down)
+pub async fn apply_ai_proposal(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Json(proposal): Json<AiProposal>,
+) -> Result<(StatusCode, Json<AppliedProposal>), AppError> {
+    proposal.validate()?;
+    let applied = apply_proposal(&state.pool, user_id, &proposal).await?;
+    Ok((StatusCode::CREATED, Json(applied)))
+}
+
+// --- GET /api/me/ai-assistant/usage handler ---
+// Tokens used today and this month, against the configured quotas
+pub async fn get_ai_usage(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<Json<AiUsageResponse>, AppError> {
+    let report = usage::usage_report(&state.pool, user_id, ai_quotas(&state)).await?;
+    Ok(Json(report))
+}
+
+// --- GET /api/me/ai-assistant/threads handler ---
+// The user's conversations, most recently active first
+pub async fn list_ai_threads(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<Json<Vec<AiThreadInfo>>, AppError> {
+    let threads = thread::list_threads(&state.pool, user_id).await?;
+    Ok(Json(threads))
+}
+
+// --- GET /api/me/ai-assistant/threads/{thread_id} handler ---
+pub async fn get_ai_thread(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(thread_id): Path<i32>,
+) -> Result<Json<AiThreadResponse>, AppError> {
+    let thread = thread::get_thread(&state.pool, user_id, thread_id).await?;
+    Ok(Json(thread))
+}
+
+// --- DELETE /api/me/ai-assistant/threads/{thread_id} handler ---
+pub async fn delete_ai_thread(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(thread_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    thread::delete_thread(&state.pool, user_id, thread_id).await?;
+    tracing::info!("Deleted AI thread {} of user {}", thread_id, user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- DELETE /api/me/ai-assistant/threads handler ---
+// Clears the whole conversation history
+pub async fn delete_ai_threads(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<StatusCode, AppError> {
+    thread::delete_all_threads(&state.pool, user_id).await?;
+    tracing::info!("Deleted all AI threads of user {}", user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Helper: Create all items of a proposal, or none if one of them is invalid ---
+async fn apply_proposal(pool: &PgPool, user_id: i32, proposal: &AiProposal) -> Result<AppliedProposal, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let mut events = Vec::with_capacity(proposal.events.len());
+    for payload in &proposal.events {
+        events.push(insert_event(&mut tx, user_id, payload.clone()).await?);
+    }
+    let mut deadlines = Vec::with_capacity(proposal.deadlines.len());
+    for payload in &proposal.deadlines {
+        deadlines.push(insert_deadline(&mut tx, user_id, payload.clone()).await?);
+    }
 
-    // Return the AI's text response as JSON
-    Ok(Json(json!({ "response": ai_text })))
+    tx.commit().await?;
+    tracing::info!("Applied AI proposal for user {}: {} events, {} deadlines", user_id, events.len(), deadlines.len());
+    Ok(AppliedProposal { events, deadlines })
 }
\ No newline at end of file
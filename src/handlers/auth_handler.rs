@@ -1,13 +1,14 @@
-use axum::{extract::{State, Json}, http::StatusCode}; // Added StatusCode
+use axum::{extract::{State, Json}, http::{HeaderMap, StatusCode}}; // Added StatusCode
 use validator::Validate;
 use crate::{
-    auth::{jwt::create_token, tfa::{generate_otp_auth_uri, generate_tfa_secret_base32, verify_tfa_code}}, email::EmailService,
+    auth::{lockout::{clear_failures, ensure_not_locked, record_failure, AuthAction}, passkey::has_passkeys, session::{self, end_session, refresh_session, revoke_user_sessions, start_session}, token::delete_user_tokens, tfa::{begin_tfa_attempt, complete_tfa_challenge, consume_second_factor, consume_tfa_code, delete_recovery_codes, generate_otp_auth_uri, generate_recovery_codes, generate_tfa_secret_base32, start_tfa_challenge, MAX_TFA_ATTEMPTS}},
     errors::AppError, middleware::auth::AuthenticatedUser, models::user::{
-        AuthResponse, CodeResponse, CompleteTfaSetupPayload, DisableTfaPayload, ForgotPasswordPayload, InitiateTfaResponse, InitiateTfaSetupPayload, LoginResponse, LoginUserPayload, RegisterUserPayload, ResendVerificationEmailPayload, ResetPasswordPayload, TfaRequiredResponse, TfaUserInfo, User, UserData, VerifyEmailPayload, VerifyTfaLoginPayload
-    }, state::AppState, utils::security::{generate_secure_code, hash_code, hash_password, verify_code, verify_password} // Import EmailService
+        AuthResponse, CodeResponse, CompleteTfaSetupPayload, DisableTfaPayload, ForgotPasswordPayload, InitiateTfaResponse, InitiateTfaSetupPayload, LoginResponse, RecoveryCodesResponse, RegenerateRecoveryCodesPayload, LoginUserPayload, RegisterUserPayload, ResendVerificationEmailPayload, ResetPasswordPayload, TfaRequiredResponse, TfaUserInfo, User, UserData, VerifyEmailPayload, VerifyTfaLoginPayload
+    }, models::session::{RefreshTokenPayload, TokenResponse}, state::AppState, utils::security::{generate_secure_code, hash_code, hash_password, verify_code, verify_password} // Import EmailService
 };
 use chrono::{NaiveDate, Utc, Duration, DateTime}; // Added DateTime
 use sqlx::PgPool; // For type hints
+use crate::utils::pending_invites::claim_pending_invites;
 
 // Re-use parse_timestamp helper or ensure it's imported from utils
 // fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, AppError> { ... }
@@ -40,6 +41,7 @@ async fn find_user_by_email(pool: &PgPool, email: &str) -> Result<Option<User>,
 // --- Registration Handler (Modified) ---
 pub async fn register_user_handler(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(payload): Json<RegisterUserPayload>,
 ) -> Result<Json<AuthResponse>, AppError> {
     payload.validate()?;
@@ -126,18 +128,60 @@ pub async fn register_user_handler(
         email_verified: insert_result.email_verified.unwrap_or(false), // Should be false
         created_at: insert_result.created_at.unwrap(), // Should not be null
         date_of_birth: insert_result.date_of_birth,
-        tfa_enabled: Some(false)
+        tfa_enabled: Some(false),
+        tfa_recovery_codes_remaining: None,
     };
 
-    let token = create_token(user_data.user_id, &state.config)?;
+    let tokens = start_session(&state.pool, &state.config, user_data.user_id, session::user_agent(&headers)).await?;
 
-    let response = AuthResponse { token, user: user_data, code_prefix: Some(verification_code_prefix) }; // Include prefix for verification code
+    let response = AuthResponse {
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
+        user: user_data,
+        code_prefix: Some(verification_code_prefix), // Include prefix for verification code
+    };
     Ok(Json(response))
 }
 
+// --- Helper: Start a session for a user who logged in without a password (passkey, OIDC) ---
+pub async fn session_auth_response(state: &AppState, user_id: i32, headers: &HeaderMap) -> Result<AuthResponse, AppError> {
+    let user = sqlx::query!(
+        r#"
+        SELECT user_id, display_name, email, email_verified as "email_verified!", created_at as "created_at!",
+               date_of_birth, tfa_enabled
+        FROM users WHERE user_id = $1 AND deleted_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::InvalidCredentials)?;
+
+    let tokens = start_session(&state.pool, &state.config, user.user_id, session::user_agent(headers)).await?;
+
+    Ok(AuthResponse {
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
+        user: UserData {
+            user_id: user.user_id,
+            display_name: user.display_name,
+            email: user.email,
+            email_verified: user.email_verified,
+            created_at: user.created_at,
+            date_of_birth: user.date_of_birth,
+            tfa_enabled: Some(user.tfa_enabled),
+            tfa_recovery_codes_remaining: None,
+        },
+        code_prefix: None,
+    })
+}
+
 // --- Login Handler (Modified) ---
 pub async fn login_user_handler(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(payload): Json<LoginUserPayload>,
 ) -> Result<Json<LoginResponse>, AppError> {
     payload.validate()?;
@@ -155,22 +199,30 @@ pub async fn login_user_handler(
          return Err(AppError::InvalidCredentials); // Treat soft-deleted as non-existent for login
     }
 
+    // Locked after too many wrong passwords; checked first so a locked account reveals nothing
+    ensure_not_locked(&state.pool, user.user_id, AuthAction::Login).await?;
+
     let is_valid_password = verify_password(&password, &user.password_hash).await?;
     if !is_valid_password {
+        record_failure(&state, user.user_id, AuthAction::Login).await?;
         return Err(AppError::InvalidCredentials);
     }
+    clear_failures(&state.pool, user.user_id, AuthAction::Login).await?;
 
     // --- 2FA Check ---
     if user.tfa_enabled {
         tracing::info!("2FA required for user {}. Prompting for code.", user.user_id);
-        // Password is correct, but 2FA is enabled. Return response indicating 2FA is needed.
+        // Password is correct, but 2FA is enabled. Hand out a challenge the code has to be sent with.
+        let (challenge_token, expires_in) = start_tfa_challenge(&state.pool, &state.config, user.user_id).await?;
         Ok(Json(LoginResponse::TfaRequired(TfaRequiredResponse {
             user_id: user.user_id,
-            // Add other necessary info, but keep minimal
+            challenge_token,
+            expires_in,
+            passkey_available: has_passkeys(&state.pool, user.user_id).await?,
         })))
     } else {
-        // Authentication successful, 2FA not enabled. Issue JWT.
-        let token = create_token(user.user_id, &state.config)?;
+        // Authentication successful, 2FA not enabled. Start a session.
+        let tokens = start_session(&state.pool, &state.config, user.user_id, session::user_agent(&headers)).await?;
 
         let user_data = UserData {
             user_id: user.user_id,
@@ -180,8 +232,15 @@ pub async fn login_user_handler(
             created_at: user.created_at,
             date_of_birth: user.date_of_birth,
             tfa_enabled: Some(user.tfa_enabled), // Include this for clarity
+            tfa_recovery_codes_remaining: None,
         };
-        Ok(Json(LoginResponse::Auth(AuthResponse { token, user: user_data, code_prefix: None }))) // No prefix needed for login response
+        Ok(Json(LoginResponse::Auth(AuthResponse {
+            token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in: tokens.expires_in,
+            user: user_data,
+            code_prefix: None, // No prefix needed for login response
+        })))
     }
 }
 
@@ -189,14 +248,17 @@ pub async fn login_user_handler(
 // Called after successful password login if 2FA is required
 pub async fn verify_tfa_login_handler(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(payload): Json<VerifyTfaLoginPayload>,
 ) -> Result<Json<AuthResponse>, AppError> { // Return regular AuthResponse on success
     payload.validate()?;
-    let user_id = payload.user_id.unwrap();
+    let challenge_token = payload.challenge_token.unwrap();
     let tfa_code = payload.tfa_code.unwrap();
 
-    // 1. Find user by user_id (need tfa_enabled and tfa_secret)
-    // Fetch using user_id instead of email here, as we have the ID from the TfaRequiredResponse
+    // 1. Count this attempt against the challenge from the password step (fails once it is used up)
+    let challenge = begin_tfa_attempt(&state.pool, &state.config, &challenge_token).await?;
+
+    // 2. Find the user the challenge was issued to (need tfa_enabled and tfa_secret)
     let user = sqlx::query_as!(
         User,
         r#"
@@ -209,22 +271,25 @@ pub async fn verify_tfa_login_handler(
                deleted_at as "deleted_at!: _",
                tfa_enabled as "tfa_enabled!",
                tfa_secret
-        FROM users WHERE user_id = $1
+        FROM users WHERE user_id = $1 AND deleted_at IS NULL
         "#,
-        user_id
+        challenge.user_id
     )
     .fetch_optional(&state.pool)
     .await?
     .ok_or(AppError::InvalidCredentials)?; // Use generic invalid credentials
 
 
-    // 2. Check if 2FA is actually enabled for this user (redundant check but safe)
+    // Every challenge has its own attempt limit; this one spans all challenges of the account
+    ensure_not_locked(&state.pool, user.user_id, AuthAction::VerifyTfa).await?;
+
+    // 3. Check if 2FA is actually enabled for this user (redundant check but safe)
     if !user.tfa_enabled {
         tracing::warn!("2FA verification attempted for user {} where 2FA is not enabled.", user.user_id);
         return Err(AppError::InvalidCredentials); // Or a more specific error if desired
     }
 
-    // 3. Get the stored secret
+    // 4. Get the stored secret
     let tfa_secret_base32: String = match user.tfa_secret {
         Some(secret) => secret,
         None => {
@@ -233,16 +298,22 @@ pub async fn verify_tfa_login_handler(
         }
     };
 
-    // 4. Verify the provided 2FA code
-    let code_is_valid = verify_tfa_code(&tfa_secret_base32, &tfa_code)?;
+    // 5. Verify the provided 2FA code or recovery code (one that was already used counts as invalid)
+    let code_is_valid = consume_second_factor(&state.pool, user.user_id, &tfa_secret_base32, &tfa_code).await?;
 
     if !code_is_valid {
-        tracing::warn!("Invalid 2FA code attempt for user {}", user.user_id);
+        tracing::warn!("Invalid 2FA code attempt {} of {} for user {}", challenge.attempts, MAX_TFA_ATTEMPTS, user.user_id);
+        record_failure(&state, user.user_id, AuthAction::VerifyTfa).await?;
+        if challenge.attempts >= MAX_TFA_ATTEMPTS {
+            return Err(AppError::TfaTooManyAttempts); // Challenge is locked now
+        }
         return Err(AppError::TfaCodeInvalid); // Specific error for invalid code
     }
+    complete_tfa_challenge(&state.pool, challenge.challenge_id).await?;
+    clear_failures(&state.pool, user.user_id, AuthAction::VerifyTfa).await?;
 
-    // 5. Authentication successful (both factors verified). Issue JWT.
-    let token = create_token(user.user_id, &state.config)?;
+    // 6. Authentication successful (both factors verified). Start a session.
+    let tokens = start_session(&state.pool, &state.config, user.user_id, session::user_agent(&headers)).await?;
 
      let user_data = UserData {
         user_id: user.user_id,
@@ -251,12 +322,51 @@ pub async fn verify_tfa_login_handler(
         email_verified: user.email_verified,
         created_at: user.created_at,
         date_of_birth: user.date_of_birth,
-        tfa_enabled: Some(user.tfa_enabled)
+        tfa_enabled: Some(user.tfa_enabled),
+        tfa_recovery_codes_remaining: None,
     };
 
     tracing::info!("User {} successfully logged in with 2FA.", user.user_id);
 
-    Ok(Json(AuthResponse { token, user: user_data, code_prefix: None })) // No prefix needed for login response
+    Ok(Json(AuthResponse {
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
+        user: user_data,
+        code_prefix: None, // No prefix needed for login response
+    }))
+}
+
+// --- Refresh Tokens Handler (POST /api/auth/refresh) ---
+// Rotates the refresh token: the one sent stops working and a new pair is returned
+pub async fn refresh_token_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshTokenPayload>,
+) -> Result<Json<TokenResponse>, AppError> {
+    payload.validate()?;
+    let refresh_token = payload.refresh_token.unwrap(); // Required by validation
+
+    let tokens = refresh_session(&state.pool, &state.config, &refresh_token).await?;
+
+    Ok(Json(TokenResponse {
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        expires_in: tokens.expires_in,
+    }))
+}
+
+// --- Logout Handler (POST /api/auth/logout) ---
+// Ends the session of the refresh token; its access tokens stop working right away
+pub async fn logout_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshTokenPayload>,
+) -> Result<StatusCode, AppError> {
+    payload.validate()?;
+    let refresh_token = payload.refresh_token.unwrap(); // Required by validation
+
+    end_session(&state.pool, &refresh_token).await?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
 //     // Optional: Enforce email verification on login
@@ -304,6 +414,8 @@ pub async fn verify_email_handler(
         return Err(AppError::UserAlreadyVerified);
     }
 
+    ensure_not_locked(&state.pool, user.user_id, AuthAction::VerifyEmail).await?;
+
     // 3. Check if verification code and expiry exist
     let stored_code_hash = match user.verification_code {
         Some(hash) => hash.to_string(),  // Convert &str to owned String
@@ -335,7 +447,7 @@ pub async fn verify_email_handler(
 
     if !is_valid_code {
         tracing::warn!("Invalid verification code attempt for user: {}", user.user_id);
-        // Optional: Increment failed attempts or clear code after N failures
+        record_failure(&state, user.user_id, AuthAction::VerifyEmail).await?;
         return Err(AppError::VerificationCodeInvalid);
     }
 
@@ -351,8 +463,14 @@ pub async fn verify_email_handler(
     .execute(&state.pool)
     .await?;
 
+    clear_failures(&state.pool, user.user_id, AuthAction::VerifyEmail).await?;
     tracing::info!("Email verified for user: {}", user.user_id);
 
+    // 7. Hand over invitations and shares that were waiting for this address
+    if let Err(e) = claim_pending_invites(&state.pool, user.user_id, &user.email).await {
+        tracing::error!("Failed to claim pending invitations for user {}: {:?}", user.user_id, e);
+    }
+
     Ok(StatusCode::NO_CONTENT) // 204 No Content indicates success with no body
 }
 
@@ -504,6 +622,8 @@ pub async fn reset_password_handler(
         }
     };
 
+    ensure_not_locked(&state.pool, user.user_id, AuthAction::ResetPassword).await?;
+
     // 2. Check if reset code and expiry exist
     let stored_code_hash = match user.reset_code {
         Some(hash) => hash.to_string(),  // Convert &str to owned String
@@ -535,7 +655,7 @@ pub async fn reset_password_handler(
 
     if !is_valid_code {
          tracing::warn!("Invalid reset code attempt for user: {}", user.user_id);
-         // Optional: Increment failed attempts or clear code after N failures
+         record_failure(&state, user.user_id, AuthAction::ResetPassword).await?;
         return Err(AppError::ResetCodeInvalid);
     }
 
@@ -555,6 +675,12 @@ pub async fn reset_password_handler(
     .execute(&state.pool)
     .await?;
 
+    // 7. Sign out everywhere; whoever knew the old password may hold a session or access token
+    revoke_user_sessions(&state.pool, user.user_id, None).await?;
+    delete_user_tokens(&state.pool, user.user_id).await?;
+    clear_failures(&state.pool, user.user_id, AuthAction::ResetPassword).await?;
+    clear_failures(&state.pool, user.user_id, AuthAction::Login).await?; // The new password is known to be right
+
     tracing::info!("Password reset successful for user: {}", user.user_id);
 
     Ok(StatusCode::NO_CONTENT) // 204 No Content indicates success
@@ -626,7 +752,7 @@ pub async fn complete_tfa_setup_handler(
     State(state): State<AppState>,
     AuthenticatedUser { user_id }: AuthenticatedUser, // Must be authenticated
     Json(payload): Json<CompleteTfaSetupPayload>,
-) -> Result<StatusCode, AppError> { // Return 204 No Content on success
+) -> Result<Json<RecoveryCodesResponse>, AppError> { // Return the recovery codes on success
     payload.validate()?;
     let tfa_code = payload.tfa_code.unwrap();
 
@@ -655,8 +781,8 @@ pub async fn complete_tfa_setup_handler(
         }
     };
 
-    // 2. Verify the provided 2FA code against the temporary secret
-    let code_is_valid = verify_tfa_code(&tfa_secret_base32, &tfa_code)?;
+    // 2. Verify the provided 2FA code against the temporary secret (and mark it used)
+    let code_is_valid = consume_tfa_code(&state.pool, user_id, &tfa_secret_base32, &tfa_code).await?;
 
     if !code_is_valid {
         tracing::warn!("Invalid 2FA code during setup completion for user {}", user.user_id);
@@ -682,9 +808,10 @@ pub async fn complete_tfa_setup_handler(
 
     tracing::info!("2FA successfully enabled for user {}.", user.user_id);
 
-    // Optional: Generate and return recovery codes here if implementing them
+    // 4. Generate recovery codes (fallback for a lost authenticator); shown only this once
+    let recovery_codes = generate_recovery_codes(&state.pool, user_id).await?;
 
-    Ok(StatusCode::NO_CONTENT)
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
 }
 
 // --- NEW: Disable 2FA Handler (POST /api/me/tfa/disable) ---
@@ -695,6 +822,7 @@ pub async fn disable_tfa_handler(
 ) -> Result<StatusCode, AppError> { // Return 204 No Content on success
     payload.validate()?;
     let password = payload.password.unwrap();
+    let tfa_code = payload.tfa_code.unwrap();
 
     // 1. Fetch user to check password, 2FA status, and get secret
     let user = sqlx::query_as!(
@@ -722,9 +850,17 @@ pub async fn disable_tfa_handler(
         return Err(AppError::InvalidCredentials);
     }
 
-    // Optional: If you required current 2FA code, verify it here too
+    // 4. Verify the current 2FA code or a recovery code
+    let tfa_secret_base32 = user.tfa_secret.ok_or_else(|| {
+        tracing::error!("User {} has TFA enabled but no secret stored!", user.user_id);
+        AppError::InternalServerError("2FA configuration missing".to_string())
+    })?;
+    if !consume_second_factor(&state.pool, user_id, &tfa_secret_base32, &tfa_code).await? {
+        tracing::warn!("Invalid 2FA code while disabling 2FA for user {}", user.user_id);
+        return Err(AppError::TfaCodeInvalid);
+    }
 
-    // 4. Disable 2FA and clear the secret (it's no longer needed or valid)
+    // 5. Disable 2FA and clear the secret (it's no longer needed or valid)
     sqlx::query!(
         r#"
         UPDATE users
@@ -737,9 +873,51 @@ pub async fn disable_tfa_handler(
     .execute(&state.pool)
     .await?;
 
-    tracing::info!("2FA successfully disabled for user {}.", user.user_id);
+    // 6. Recovery codes belong to the old secret
+    delete_recovery_codes(&state.pool, user_id).await?;
 
-    // Optional: Invalidate any recovery codes here
+    tracing::info!("2FA successfully disabled for user {}.", user.user_id);
 
     Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Regenerate 2FA Recovery Codes Handler (POST /api/me/tfa/recovery-codes/regenerate) ---
+// Replaces all recovery codes, used or not, with a fresh set
+pub async fn regenerate_recovery_codes_handler(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser, // Must be authenticated
+    Json(payload): Json<RegenerateRecoveryCodesPayload>,
+) -> Result<Json<RecoveryCodesResponse>, AppError> {
+    payload.validate()?;
+    let password = payload.password.unwrap();
+
+    // 1. Fetch user to check password and 2FA status
+    let user = sqlx::query_as!(
+        TfaUserInfo,
+        r#"SELECT
+            user_id, password_hash,
+            tfa_enabled as "tfa_enabled!", tfa_secret, deleted_at as "deleted_at!: _"
+        FROM users WHERE user_id = $1 AND deleted_at IS NULL
+            "#,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::UserNotFound)?;
+
+    if !user.tfa_enabled {
+        return Err(AppError::TfaNotEnabled);
+    }
+
+    // 2. Verify user's password for confirmation
+    if !verify_password(&password, &user.password_hash).await? {
+        return Err(AppError::InvalidCredentials);
+    }
+
+    // 3. Replace the codes
+    let recovery_codes = generate_recovery_codes(&state.pool, user_id).await?;
+
+    tracing::info!("2FA recovery codes regenerated for user {}.", user.user_id);
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
 }
\ No newline at end of file
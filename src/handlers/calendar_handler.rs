@@ -1,13 +1,13 @@
 use axum::{
     extract::{State, Query, Path, Json},
-    http::StatusCode,
+    http::HeaderMap,
 };
 use sqlx::{PgPool, types::{
     chrono::Utc, Uuid
  } };
 use validator::Validate;
 use crate::{
-    errors::AppError, middleware::auth::AuthenticatedUser, models::{
+    errors::AppError, middleware::auth::{scope, AuthenticatedUser, RequireScope}, models::{
         calendar::{SharedCalendarDeadline, SharedCalendarEvent, SharedCalendarResponse, UserCalendarResponse
         }, calendar_share::{
             CalendarShare, ListSharesResponseItem, ReceivedShareResponseItem, ShareOwnerDetail // Import new models
@@ -17,9 +17,11 @@ use crate::{
     }, AppState
 };
 use chrono::DateTime;
-use crate::models::calendar::OpenSharedCalendarResponse;
+use crate::models::calendar::{CalendarViewParams, OpenSharedCalendarResponse};
 // For parsing date strings
-use crate::utils::calendar::parse_timestamp;
+use crate::utils::calendar::{fetch_event_exceptions, mask_exception_details, parse_optional_timestamp, resolve_expansion_window};
+use crate::utils::pagination::{next_cursor_headers, resolve_page, take_calendar_page, CursorKind};
+use crate::utils::recurrence::{event_overlaps, expand_events};
 
 // Re-use or create a shared helper for timestamp parsing
 // Ideally in src/utils/datetime.rs
@@ -38,6 +40,7 @@ use crate::utils::calendar::parse_timestamp;
 // This doesn't return the calendar items, just the list of shares they have received.
 pub async fn list_received_shares(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id: shared_with_user_id }: AuthenticatedUser, // The user receiving shares
 ) -> Result<Json<Vec<crate::models::calendar_share::ListSharesResponseItem>>, AppError> { // Returns the same item structure as listing owner's shares, but filtered differently
 
@@ -63,7 +66,7 @@ pub async fn list_received_shares(
             ARRAY_AGG(csc.category_id) FILTER (WHERE csc.category_id IS NOT NULL) AS "shared_category_ids!: Vec<i32>"
         FROM calendar_shares cs
         JOIN users u ON cs.owner_user_id = u.user_id -- JOIN with the owner user
-        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id
+        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
         WHERE cs.shared_with_user_id = $1 -- Filter by the shared_with user (authenticated user)
         GROUP BY cs.share_id, u.user_id -- Group required for array_agg
         ORDER BY cs.created_at DESC -- Optional: order by creation date
@@ -77,15 +80,28 @@ pub async fn list_received_shares(
 }
 
 // --- Get User Calendar Items (GET /api/calendar) ---
-// Returns all owned events, owned deadlines, and accepted invited events
+// Returns all owned events, owned deadlines, and accepted invited events.
+// Optional ?from=&to= window, ?categoryId= filter and ?limit=&after= pagination over the merged timeline.
 pub async fn get_user_calendar(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id: authenticated_user_id }: AuthenticatedUser,
-    // No Query parameters needed for ranges in this simplified version
-) -> Result<Json<UserCalendarResponse>, AppError> {
-
-    // Query 1: Fetch all owned events AND events where the user is an accepted invitee
-    let events = sqlx::query_as!(
+    Query(params): Query<CalendarViewParams>,
+) -> Result<(HeaderMap, Json<UserCalendarResponse>), AppError> {
+    let expansion_window = resolve_expansion_window(&params)?;
+    let (from, to) = match expansion_window {
+        Some((from, to)) => (Some(from), Some(to)),
+        None => (parse_optional_timestamp(params.from)?, parse_optional_timestamp(params.to)?),
+    };
+    let (limit, cursor) = resolve_page(params.limit, params.after.as_deref())?;
+    let (events_after_time, events_after_id) = cursor.map(|c| c.bound_for(CursorKind::Event)).unzip();
+    let (deadlines_after_time, deadlines_after_id) = cursor.map(|c| c.bound_for(CursorKind::Deadline)).unzip();
+    // Series outside the window are only dropped after the query, so the events of a window are paged in memory
+    let windowed = from.is_some() || to.is_some();
+
+    // Query 1: Fetch owned events AND events where the user is an accepted invitee
+    // Recurring series pass the 'from' check and are narrowed down after expansion below
+    let mut events = sqlx::query_as!(
         Event,
         r#"
         SELECT
@@ -93,23 +109,34 @@ pub async fn get_user_calendar(
            start_time, end_time, location as "location!: _", rrule as "rrule!: _",
            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM events
-        WHERE user_id = $1 -- Owned events
+        WHERE ( user_id = $1 -- Owned events
            OR event_id IN (
                SELECT event_id
                FROM event_invitations
                WHERE invited_user_id = $1 AND status = $2
-           ) -- Accepted invited events
-        ORDER BY start_time
+           ) ) -- Accepted invited events
+          AND ( ($3::TIMESTAMPTZ IS NULL) OR end_time > $3 OR start_time >= $3 OR COALESCE(rrule, '') <> '' )
+          AND ( ($4::TIMESTAMPTZ IS NULL) OR start_time < $4 )
+          AND ( ($5::INT IS NULL) OR category_id = $5 )
+          AND ( ($6::TIMESTAMPTZ IS NULL) OR (start_time, event_id) > ($6, $7::INT) ) -- Keyset cursor
+        ORDER BY start_time, event_id
+        LIMIT $8
         "#,
         authenticated_user_id,
-        EventInvitationStatus::Accepted as EventInvitationStatus // Bind the ENUM value for filtering accepted invites
+        EventInvitationStatus::Accepted as EventInvitationStatus, // Bind the ENUM value for filtering accepted invites
+        from,
+        to,
+        params.category_id,
+        events_after_time,
+        events_after_id,
+        if windowed { None } else { limit.map(|l| l + 1) } // One extra row tells us whether there is a next page
     )
     .fetch_all(&state.pool)
     .await?; // Propagates sqlx::Error -> AppError::DatabaseError
 
 
-    // Query 2: Fetch all owned deadlines
-    let deadlines = sqlx::query_as!(
+    // Query 2: Fetch owned deadlines
+    let mut deadlines = sqlx::query_as!(
         Deadline,
         r#"
         SELECT
@@ -119,33 +146,67 @@ pub async fn get_user_calendar(
            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM deadlines
         WHERE user_id = $1 -- Owned deadlines
-        ORDER BY due_date -- Order by due date
+          AND ( ($2::TIMESTAMPTZ IS NULL) OR due_date >= $2 )
+          AND ( ($3::TIMESTAMPTZ IS NULL) OR due_date < $3 )
+          AND ( ($4::INT IS NULL) OR category_id = $4 )
+          AND ( ($5::TIMESTAMPTZ IS NULL) OR (due_date, deadline_id) > ($5, $6::INT) ) -- Keyset cursor
+        ORDER BY due_date, deadline_id
+        LIMIT $7
         "#,
         authenticated_user_id,
-        // No second parameter needed for deadlines query
+        from,
+        to,
+        params.category_id,
+        deadlines_after_time,
+        deadlines_after_id,
+        limit.map(|l| l + 1)
     )
     .fetch_all(&state.pool)
     .await?; // Propagates sqlx::Error -> AppError::DatabaseError
 
+    // Drop series whose instances all fall outside the window, before they can take up room on the page
+    if windowed {
+        events.retain(|e| event_overlaps(e.start_time, e.end_time, e.rrule.as_deref(), from, to));
+    }
+
+    // Both lists share one page of `limit` items, ordered by time
+    let next_cursor = take_calendar_page(&mut events, &mut deadlines, limit);
+
+
+    // Query 3: Cancelled/modified occurrences of those events
+    let event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let mut exceptions = fetch_event_exceptions(&state.pool, &event_ids).await?;
+
+    // Expand recurring events when requested; only items touching the window are kept
+    let occurrences = match expansion_window {
+        Some((from, to)) => {
+            let occurrences = expand_events(&events, &exceptions, from, to);
+            events.retain(|e| occurrences.iter().any(|o| o.event_id == e.event_id));
+            exceptions.retain(|ex| events.iter().any(|e| e.event_id == ex.event_id));
+            deadlines.retain(|d| d.deleted_at.is_none() && d.due_date >= from && d.due_date < to);
+            Some(occurrences)
+        }
+        None => None,
+    };
 
     // Combine results into the response struct
     let response = UserCalendarResponse {
         events,
         deadlines,
+        exceptions,
+        occurrences,
     };
 
-    Ok(Json(response))
+    Ok((next_cursor_headers(next_cursor), Json(response)))
 }
 
-// --- Get Shared Calendar Items (GET /api/calendar/shares/:share_id) ---
-// Fetches items from a shared calendar for the invitee
-pub async fn get_shared_calendar(
-    State(state): State<AppState>,
-    AuthenticatedUser { user_id: authenticated_user_id }: AuthenticatedUser, // The sharee
-    Path(share_id): Path<i32>, // The ID of the specific share instance
-    // No Query parameters for ranges in this simplified version
-) -> Result<Json<SharedCalendarResponse>, AppError> {
-
+// --- Helper: Load a private share for its sharee, with the events and deadlines it exposes ---
+// Shared by the JSON view and the .ics export. Items are returned unmasked; callers apply the privacy level.
+pub async fn load_shared_calendar(
+    pool: &PgPool,
+    share_id: i32,
+    authenticated_user_id: i32, // The sharee
+) -> Result<(CalendarShare, Vec<Event>, Vec<Deadline>), AppError> {
     // 1. Verify the share exists and is intended for the authenticated user (sharee)
     let share = sqlx::query_as!(
         CalendarShare,
@@ -160,7 +221,7 @@ pub async fn get_shared_calendar(
         share_id,
         authenticated_user_id // Check if the authenticated user is the shared_with_user
     )
-    .fetch_optional(&state.pool)
+    .fetch_optional(pool)
     .await?;
 
     let share = match share {
@@ -176,14 +237,13 @@ pub async fn get_shared_calendar(
     }
 
     let owner_user_id = share.owner_user_id; // The sharer's ID
-    let privacy_level = share.privacy_level;
 
     // 2. Get the list of categories included in this share
     let shared_category_ids: Vec<i32> = sqlx::query_scalar!(
-        "SELECT category_id FROM calendar_share_categories WHERE share_id = $1",
+        "SELECT category_id FROM calendar_share_categories WHERE share_id = $1 AND deleted_at IS NULL",
         share_id
     )
-    .fetch_all(&state.pool)
+    .fetch_all(pool)
     .await?;
 
 
@@ -217,14 +277,14 @@ pub async fn get_shared_calendar(
         EventInvitationStatus::Accepted as EventInvitationStatus // $3 - Bind the ENUM
     );
 
-    let mut events = events_query.fetch_all(&state.pool).await?;
+    let events = events_query.fetch_all(pool).await?;
 
 
     // 4. Fetch Deadlines (owned by the sharer AND in shared categories - assuming deadlines follow category sharing?)
     // UPDATE: Deadlines are only owned by the sharer according to plan, and not invitable.
     // It seems the intent is to share deadlines based on shared *categories*, same as events.
     // Let's update the query to filter deadlines by categories too.
-     let mut deadlines = sqlx::query_as!(
+    let deadlines = sqlx::query_as!(
         Deadline,
         r#"
         SELECT
@@ -240,11 +300,47 @@ pub async fn get_shared_calendar(
         owner_user_id, // $1
         &shared_category_ids // $2
     )
-    .fetch_all(&state.pool)
+    .fetch_all(pool)
     .await?;
 
+    Ok((share, events, deadlines))
+}
+
+// --- Get Shared Calendar Items (GET /api/calendar/shares/:share_id) ---
+// Fetches items from a shared calendar for the invitee
+pub async fn get_shared_calendar(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id: authenticated_user_id }: AuthenticatedUser, // The sharee
+    Path(share_id): Path<i32>, // The ID of the specific share instance
+    Query(params): Query<CalendarViewParams>, // Optional ?expand=true&from=&to=
+) -> Result<Json<SharedCalendarResponse>, AppError> {
+    let expansion_window = resolve_expansion_window(&params)?;
+
+    // 1-4. Verify the share and load the items it exposes
+    let (share, mut events, mut deadlines) = load_shared_calendar(&state.pool, share_id, authenticated_user_id).await?;
+    let privacy_level = share.privacy_level;
+
+
+    // Cancelled/modified occurrences of the shared events
+    let event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let mut exceptions = fetch_event_exceptions(&state.pool, &event_ids).await?;
+
+    // Expand before applying privacy, since Limited mode strips the rrule
+    let mut occurrences = match expansion_window {
+        Some((from, to)) => {
+            let occurrences = expand_events(&events, &exceptions, from, to);
+            events.retain(|e| occurrences.iter().any(|o| o.event_id == e.event_id));
+            exceptions.retain(|ex| events.iter().any(|e| e.event_id == ex.event_id));
+            deadlines.retain(|d| d.deleted_at.is_none() && d.due_date >= from && d.due_date < to);
+            Some(occurrences)
+        }
+        None => None,
+    };
 
     // 5. Apply Privacy Level and convert to shared calendar formats
+    if privacy_level == SharePrivacyLevel::Limited {
+        mask_exception_details(occurrences.as_mut(), &mut exceptions);
+    }
     let events = if privacy_level == SharePrivacyLevel::Limited {
         // Apply Limited transformation to Events
         events.into_iter().map(|event| {
@@ -318,20 +414,19 @@ pub async fn get_shared_calendar(
         privacy_level: share.privacy_level,
         events,
         deadlines,
+        exceptions,
+        occurrences,
     };
 
     Ok(Json(response))
 }
 
-// --- NEW: Get Open Shared Calendar Items (GET /api/calendar/open-shares/:uuid) ---
-// Fetches items from a public shared calendar
-pub async fn get_open_shared_calendar(
-    State(state): State<AppState>,
-    Path(open_share_id): Path<Uuid>, // Extract UUID from path
-    // No authentication required for this public endpoint
-    // No Query parameters for ranges in this simplified version
-) -> Result<Json<OpenSharedCalendarResponse>, AppError> { // Reuse SharedCalendarResponse struct
-
+// --- Helper: Load an accessible open share with the events and deadlines it exposes ---
+// Shared by the JSON view and the .ics feed. Items are returned unmasked; callers apply the privacy level.
+pub async fn load_open_shared_calendar(
+    pool: &PgPool,
+    open_share_id: Uuid,
+) -> Result<(OpenCalendarShare, Vec<Event>, Vec<Deadline>), AppError> {
     // 1. Verify the open share exists and is accessible (not deleted, not expired)
     let share = sqlx::query_as!(
         OpenCalendarShare, // Use the OpenCalendarShare model
@@ -348,7 +443,7 @@ pub async fn get_open_shared_calendar(
         open_share_id,
         Utc::now() // Check expiry against current time
     )
-        .fetch_optional(&state.pool)
+        .fetch_optional(pool)
         .await?;
 
     let share = match share {
@@ -357,14 +452,13 @@ pub async fn get_open_shared_calendar(
     };
 
     let owner_user_id = share.owner_user_id; // The sharer's ID
-    let privacy_level = share.privacy_level;
 
     // 2. Get the list of categories included in this open share
     let shared_category_ids: Vec<i32> = sqlx::query_scalar!(
         "SELECT category_id FROM open_calendar_share_categories WHERE open_share_id = $1",
         open_share_id
     )
-        .fetch_all(&state.pool)
+        .fetch_all(pool)
         .await?;
 
     // 3. Fetch Events (owned by sharer AND in shared categories) - NO accepted invites here for open shares
@@ -386,11 +480,11 @@ pub async fn get_open_shared_calendar(
         &shared_category_ids, // $2
     );
 
-    let mut events = events_query.fetch_all(&state.pool).await?;
+    let events = events_query.fetch_all(pool).await?;
 
 
     // 4. Fetch Deadlines (owned by sharer, in shared categories) - Only non-deleted deadlines
-    let mut deadlines = sqlx::query_as!(
+    let deadlines = sqlx::query_as!(
         Deadline,
         r#"
         SELECT
@@ -407,11 +501,47 @@ pub async fn get_open_shared_calendar(
         owner_user_id, // $1
         &shared_category_ids // $2
     )
-        .fetch_all(&state.pool)
+        .fetch_all(pool)
         .await?;
 
+    Ok((share, events, deadlines))
+}
+
+// --- NEW: Get Open Shared Calendar Items (GET /api/calendar/open-shares/:uuid) ---
+// Fetches items from a public shared calendar
+pub async fn get_open_shared_calendar(
+    State(state): State<AppState>,
+    Path(open_share_id): Path<Uuid>, // Extract UUID from path
+    // No authentication required for this public endpoint
+    Query(params): Query<CalendarViewParams>, // Optional ?expand=true&from=&to=
+) -> Result<Json<OpenSharedCalendarResponse>, AppError> { // Reuse SharedCalendarResponse struct
+    let expansion_window = resolve_expansion_window(&params)?;
+
+    // 1-4. Verify the open share and load the items it exposes
+    let (share, mut events, mut deadlines) = load_open_shared_calendar(&state.pool, open_share_id).await?;
+    let privacy_level = share.privacy_level;
+
+
+    // Cancelled/modified occurrences of the shared events
+    let event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let mut exceptions = fetch_event_exceptions(&state.pool, &event_ids).await?;
+
+    // Expand before applying privacy, since Limited mode strips the rrule
+    let mut occurrences = match expansion_window {
+        Some((from, to)) => {
+            let occurrences = expand_events(&events, &exceptions, from, to);
+            events.retain(|e| occurrences.iter().any(|o| o.event_id == e.event_id));
+            exceptions.retain(|ex| events.iter().any(|e| e.event_id == ex.event_id));
+            deadlines.retain(|d| d.due_date >= from && d.due_date < to);
+            Some(occurrences)
+        }
+        None => None,
+    };
 
     // 5. Apply Privacy Level and convert to shared calendar formats (Reusing the same logic as private shares)
+    if privacy_level == SharePrivacyLevel::Limited {
+        mask_exception_details(occurrences.as_mut(), &mut exceptions);
+    }
     let events_formatted = if privacy_level == SharePrivacyLevel::Limited {
         events.into_iter().map(|event| {
             SharedCalendarEvent {
@@ -513,6 +643,8 @@ pub async fn get_open_shared_calendar(
         // deleted_at: share.deleted_at, // Include share deleted_at in the response metadata
         events: events_formatted, // Use formatted events
         deadlines: deadlines_formatted, // Use formatted deadlines
+        exceptions,
+        occurrences,
     };
 
     Ok(Json(response))
@@ -2,21 +2,31 @@ use axum::{
     extract::{State, Path, Json},
     http::StatusCode,
 };
-use sqlx;
+use sqlx::{PgConnection, PgPool};
 use validator::Validate;
 use crate::{
     AppState,
     errors::AppError,
     models::category::{Category, CreateCategoryPayload, UpdateCategoryPayload},
-    middleware::auth::AuthenticatedUser, // Import the AuthenticatedUser extractor
+    middleware::auth::{scope, AuthenticatedUser, RequireScope}, // Import the AuthenticatedUser extractor
 };
 
 // --- Create Category ---
 pub async fn create_category(
     State(state): State<AppState>,
+    _: RequireScope<scope::CategoriesWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser, // Extract authenticated user ID
     Json(payload): Json<CreateCategoryPayload>,
 ) -> Result<(StatusCode, Json<Category>), AppError> {
+    let mut conn = state.pool.acquire().await?;
+    let created_category = insert_category(&mut conn, user_id, payload).await?;
+
+    // Return 201 Created status code with the created category
+    Ok((StatusCode::CREATED, Json(created_category)))
+}
+
+// --- Helper: Create a category owned by the user ---
+pub async fn insert_category(conn: &mut PgConnection, user_id: i32, payload: CreateCategoryPayload) -> Result<Category, AppError> {
     payload.validate()?; // Validate the input payload
 
     let name = payload.name.unwrap(); // Safe unwrap after validation
@@ -36,18 +46,24 @@ pub async fn create_category(
         name,
         color,
     )
-    .fetch_one(&state.pool) // Use the pool from state
+    .fetch_one(&mut *conn)
     .await?; // sqlx::Error is automatically mapped to AppError
 
-    // Return 201 Created status code with the created category
-    Ok((StatusCode::CREATED, Json(created_category)))
+    Ok(created_category)
 }
 
 // --- Get All Categories for User ---
 pub async fn get_categories(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id }: AuthenticatedUser, // Extract authenticated user ID
 ) -> Result<Json<Vec<Category>>, AppError> {
+    let categories = load_categories(&state.pool, user_id).await?;
+    Ok(Json(categories))
+}
+
+// --- Helper: All categories of the user, also used by the AI assistant ---
+pub async fn load_categories(pool: &PgPool, user_id: i32) -> Result<Vec<Category>, AppError> {
     let categories = sqlx::query_as!(
         Category,
         r#"
@@ -59,15 +75,16 @@ pub async fn get_categories(
         "#,
         user_id // Fetch categories for the authenticated user
     )
-    .fetch_all(&state.pool)
+    .fetch_all(pool)
     .await?;
 
-    Ok(Json(categories))
+    Ok(categories)
 }
 
 // --- Get Single Category by ID for User ---
 pub async fn get_category_by_id(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id }: AuthenticatedUser, // Extract authenticated user ID
     Path(category_id): Path<i32>, // Extract category_id from the path
 ) -> Result<Json<Category>, AppError> {
@@ -94,6 +111,7 @@ pub async fn get_category_by_id(
 // --- Update Category ---
 pub async fn update_category(
     State(state): State<AppState>,
+    _: RequireScope<scope::CategoriesWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser, // Extract authenticated user ID
     Path(category_id): Path<i32>, // Extract category_id from the path
     Json(payload): Json<UpdateCategoryPayload>,
@@ -101,6 +119,7 @@ pub async fn update_category(
     payload.validate()?; // Validate the input payload
 
     // We need to check if the category exists AND belongs to the user first
+    let mut conn = state.pool.acquire().await?;
     let existing_category = sqlx::query_as!(
         Category,
         r#"
@@ -112,14 +131,26 @@ pub async fn update_category(
         category_id,
         user_id
     )
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *conn)
     .await?;
 
-    let mut category_to_update = match existing_category {
+    let category_to_update = match existing_category {
         Some(cat) => cat,
         None => return Err(AppError::CategoryNotFound),
     };
 
+    let updated_category = apply_category_update(&mut conn, user_id, category_to_update, payload).await?;
+    Ok(Json(updated_category))
+}
+
+// --- Helper: Apply a validated update payload to a category owned by the user ---
+// Also used by the client sync batch, which locks the category and checks for conflicts first
+pub async fn apply_category_update(
+    conn: &mut PgConnection,
+    user_id: i32,
+    mut category_to_update: Category,
+    payload: UpdateCategoryPayload,
+) -> Result<Category, AppError> {
     // Apply updates only if the field is provided in the payload
     if let Some(name) = payload.name {
         category_to_update.name = name;
@@ -144,40 +175,45 @@ pub async fn update_category(
         category_to_update.name,
         category_to_update.color,
         category_to_update.is_visible,
-        category_id,
+        category_to_update.category_id,
         user_id // Crucial check
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await?; // Propagates sqlx errors (including unique constraint for name)
 
-    Ok(Json(updated_category))
+    Ok(updated_category)
 }
 
 // --- Delete Category ---
 pub async fn delete_category(
     State(state): State<AppState>,
+    _: RequireScope<scope::CategoriesWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser, // Extract authenticated user ID
     Path(category_id): Path<i32>, // Extract category_id from the path
 ) -> Result<StatusCode, AppError> {
+    let mut conn = state.pool.acquire().await?;
+    soft_delete_category(&mut conn, user_id, category_id).await?;
+
+    // Return 204 No Content on successful deletion
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Helper: Soft delete a category owned by the user, returning the deleted row ---
+pub async fn soft_delete_category(conn: &mut PgConnection, user_id: i32, category_id: i32) -> Result<Category, AppError> {
     // Perform the delete query. Check for user_id!
-    let delete_result = sqlx::query!(
+    sqlx::query_as!(
+        Category,
         r#"
         UPDATE categories
         SET deleted_at = NOW() -- Soft delete
         WHERE category_id = $1 AND user_id = $2
+        RETURNING category_id, user_id, name, color, is_visible as "is_visible!",
+        created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         "#,
         category_id,
         user_id // Ensure the category belongs to the authenticated user
     )
-    .execute(&state.pool) // Use execute for DELETE
-    .await?;
-
-    // Check how many rows were affected
-    if delete_result.rows_affected() == 0 {
-        // No rows deleted means the category didn't exist or didn't belong to the user
-        Err(AppError::CategoryNotFound)
-    } else {
-        // Return 204 No Content on successful deletion
-        Ok(StatusCode::NO_CONTENT)
-    }
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::CategoryNotFound) // The category didn't exist or didn't belong to the user
 }
\ No newline at end of file
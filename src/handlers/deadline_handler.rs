@@ -1,28 +1,57 @@
 use axum::{
-    extract::{State, Path, Json},
-    http::StatusCode,
+    extract::{State, Path, Json, Query},
+    http::{HeaderMap, StatusCode},
 };
-use sqlx::{PgPool, types::chrono::Utc};
+use sqlx::PgConnection;
 use validator::Validate;
 use crate::{
     AppState,
     errors::AppError,
-    models::deadline::{Deadline, CreateDeadlinePayload, UpdateDeadlinePayload}, // Import deadline models
-    middleware::auth::AuthenticatedUser,
+    models::deadline::{Deadline, CreateDeadlinePayload, UpdateDeadlinePayload, ListDeadlinesParams}, // Import deadline models
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
 };
 use chrono::DateTime; // For parsing date strings
-use crate::utils::calendar::parse_timestamp; // Import the helper function for parsing timestamps
+use crate::handlers::event_handler::ensure_category_owned;
+use crate::utils::calendar::{parse_optional_timestamp, parse_timestamp}; // Import the helper functions for parsing timestamps
+use crate::utils::pagination::{next_cursor_headers, resolve_page, take_page, CursorKind, PageCursor};
 
 use crate::models::enums::{DeadlinePriorityLevel, WorkloadUnitType}; // Import enums
 
 // --- Create Deadline ---
 pub async fn create_deadline(
     State(state): State<AppState>,
+    _: RequireScope<scope::DeadlinesWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
-    Json(mut payload): Json<CreateDeadlinePayload>, // Use 'mut' because we modify it slightly
+    Json(payload): Json<CreateDeadlinePayload>,
 ) -> Result<(StatusCode, Json<Deadline>), AppError> {
+    let mut conn = state.pool.acquire().await?;
+    let created_deadline = insert_deadline(&mut conn, user_id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(created_deadline)))
+}
+
+// --- Helper: Check a new deadline against the user's data without creating it ---
+// Also used by the AI assistant to vet the deadlines it proposes
+pub async fn validate_new_deadline(conn: &mut PgConnection, user_id: i32, payload: &CreateDeadlinePayload) -> Result<(), AppError> {
     payload.validate()?;
 
+    parse_timestamp(payload.due_date.as_deref().unwrap())?; // Required by validation
+    if let Some(date_str) = &payload.virtual_due_date {
+        parse_timestamp(date_str)?;
+    }
+
+    // Validate category_id exists and belongs to the user
+    if let Some(cat_id) = payload.category_id {
+        ensure_category_owned(conn, user_id, cat_id).await?;
+    }
+
+    Ok(())
+}
+
+// --- Helper: Create a deadline owned by the user ---
+pub async fn insert_deadline(conn: &mut PgConnection, user_id: i32, payload: CreateDeadlinePayload) -> Result<Deadline, AppError> {
+    validate_new_deadline(conn, user_id, &payload).await?;
+
     let title = payload.title.unwrap();
     let category_id = payload.category_id; // Option<i32> is fine
     let description = payload.description; // Option<String> is fine
@@ -37,24 +66,6 @@ pub async fn create_deadline(
     let workload_magnitude = payload.workload_magnitude; // Option<i32>
     let workload_unit = payload.workload_unit; // Option<WorkloadUnitType>
 
-    // Optional: Validate category_id exists and belongs to the user if provided
-    if let Some(cat_id) = category_id {
-       let category_exists: Option<bool> = sqlx::query_scalar!(
-            "SELECT EXISTS(SELECT 1 FROM categories WHERE category_id = $1 AND user_id = $2)",
-            cat_id,
-            user_id
-        )
-        .fetch_one(&state.pool)
-        .await?;
-
-        if category_exists != Some(true) {
-             // Category ID is invalid or doesn't belong to the user
-             // Consider a more specific error like AppError::InvalidCategoryId
-             return Err(AppError::CategoryNotFound); // Re-using CategoryNotFound for now
-        }
-    }
-
-
     let virtual_due_date = match &payload.virtual_due_date {
         Some(date_str) => Some(parse_timestamp(date_str)?),
         None => None,
@@ -81,18 +92,26 @@ pub async fn create_deadline(
         workload_magnitude,
         workload_unit as Option<WorkloadUnitType>,
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await?;
 
-    Ok((StatusCode::CREATED, Json(created_deadline)))
+    Ok(created_deadline)
 }
 
 // --- Get All Deadlines for User ---
+// Optional ?from=&to= window on due_date, ?categoryId= filter and ?limit=&after= cursor pagination
 pub async fn get_deadlines(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
-) -> Result<Json<Vec<Deadline>>, AppError> {
-    let deadlines = sqlx::query_as!(
+    Query(params): Query<ListDeadlinesParams>,
+) -> Result<(HeaderMap, Json<Vec<Deadline>>), AppError> {
+    let from = parse_optional_timestamp(params.from)?;
+    let to = parse_optional_timestamp(params.to)?;
+    let (limit, cursor) = resolve_page(params.limit, params.after.as_deref())?;
+    let (after_time, after_id) = cursor.map(|c| c.bound_for(CursorKind::Deadline)).unzip();
+
+    let mut deadlines = sqlx::query_as!(
         Deadline,
         r#"
         SELECT
@@ -101,19 +120,33 @@ pub async fn get_deadlines(
            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM deadlines
         WHERE user_id = $1 AND deleted_at IS NULL
-        ORDER BY due_date -- Optional: order by due date
+          AND ( ($2::TIMESTAMPTZ IS NULL) OR due_date >= $2 )
+          AND ( ($3::TIMESTAMPTZ IS NULL) OR due_date < $3 )
+          AND ( ($4::INT IS NULL) OR category_id = $4 )
+          AND ( ($5::TIMESTAMPTZ IS NULL) OR (due_date, deadline_id) > ($5, $6::INT) ) -- Keyset cursor
+        ORDER BY due_date, deadline_id
+        LIMIT $7
         "#,
-        user_id
+        user_id,
+        from,
+        to,
+        params.category_id,
+        after_time,
+        after_id,
+        limit.map(|l| l + 1) // One extra row tells us whether there is a next page
     )
     .fetch_all(&state.pool)
     .await?;
 
-    Ok(Json(deadlines))
+    let next_cursor = take_page(&mut deadlines, limit, PageCursor::from_deadline);
+
+    Ok((next_cursor_headers(next_cursor), Json(deadlines)))
 }
 
 // --- Get Single Deadline by ID for User ---
 pub async fn get_deadline_by_id(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
     Path(deadline_id): Path<i32>,
 ) -> Result<Json<Deadline>, AppError> {
@@ -142,6 +175,7 @@ pub async fn get_deadline_by_id(
 // --- Update Deadline ---
 pub async fn update_deadline(
     State(state): State<AppState>,
+    _: RequireScope<scope::DeadlinesWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
     Path(deadline_id): Path<i32>,
     Json(payload): Json<UpdateDeadlinePayload>,
@@ -149,6 +183,7 @@ pub async fn update_deadline(
     payload.validate()?;
 
     // Fetch existing deadline to check ownership and get current values
+    let mut conn = state.pool.acquire().await?;
     let existing_deadline = sqlx::query_as!(
         Deadline,
         r#"
@@ -163,34 +198,34 @@ pub async fn update_deadline(
         deadline_id,
         user_id
     )
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *conn)
     .await?;
 
-    let mut deadline_to_update = match existing_deadline {
+    let deadline_to_update = match existing_deadline {
         Some(d) => d,
         None => return Err(AppError::DeadlineNotFound),
     };
 
+    let updated_deadline = apply_deadline_update(&mut conn, user_id, deadline_to_update, payload).await?;
+    Ok(Json(updated_deadline))
+}
+
+// --- Helper: Apply a validated update payload to a deadline owned by the user ---
+// Also used by the client sync batch, which locks the deadline and checks for conflicts first
+pub async fn apply_deadline_update(
+    conn: &mut PgConnection,
+    user_id: i32,
+    mut deadline_to_update: Deadline,
+    payload: UpdateDeadlinePayload,
+) -> Result<Deadline, AppError> {
     // Apply updates only if the field is provided in the payload
     if let Some(title) = payload.title {
         deadline_to_update.title = title;
     }
     // First validate if the new category_id exists and belongs to the user
     if let Some(new_cat_id) = payload.category_id {
-       let category_exists: Option<bool> = sqlx::query_scalar!(
-            "SELECT EXISTS(SELECT 1 FROM categories WHERE category_id = $1 AND user_id = $2)",
-            new_cat_id,
-            user_id
-        )
-        .fetch_one(&state.pool)
-        .await?;
-
-        if category_exists != Some(true) {
-             return Err(AppError::CategoryNotFound);
-        }
-    } else {
-        // If category_id is not provided, keep the existing one
-        deadline_to_update.category_id = deadline_to_update.category_id;
+        ensure_category_owned(conn, user_id, new_cat_id).await?;
+        deadline_to_update.category_id = new_cat_id;
     }
     // If description is explicitly set to null in JSON, it should become None
     if payload.description.is_some() || (payload.description.is_none() && payload.description.as_ref().is_some()) {
@@ -200,6 +235,9 @@ pub async fn update_deadline(
     if let Some(due_date_str) = payload.due_date {
         deadline_to_update.due_date = parse_timestamp(&due_date_str)?;
     }
+    if let Some(virtual_due_date_str) = payload.virtual_due_date {
+        deadline_to_update.virtual_due_date = Some(parse_timestamp(&virtual_due_date_str)?);
+    }
     if let Some(priority) = payload.priority {
         deadline_to_update.priority = priority;
     }
@@ -247,36 +285,46 @@ pub async fn update_deadline(
         deadline_to_update.priority as DeadlinePriorityLevel,
         deadline_to_update.workload_magnitude,
         deadline_to_update.workload_unit as Option<WorkloadUnitType>,
-        deadline_id,
+        deadline_to_update.deadline_id,
         user_id // Crucial check
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await?;
 
-    Ok(Json(updated_deadline))
+    Ok(updated_deadline)
 }
 
 // --- Delete Deadline ---
 pub async fn delete_deadline(
     State(state): State<AppState>,
+    _: RequireScope<scope::DeadlinesWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
     Path(deadline_id): Path<i32>,
 ) -> Result<StatusCode, AppError> {
-    let delete_result = sqlx::query!(
+    let mut conn = state.pool.acquire().await?;
+    soft_delete_deadline(&mut conn, user_id, deadline_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Helper: Soft delete a deadline owned by the user, returning the deleted row ---
+pub async fn soft_delete_deadline(conn: &mut PgConnection, user_id: i32, deadline_id: i32) -> Result<Deadline, AppError> {
+    sqlx::query_as!(
+        Deadline,
         r#"
         UPDATE deadlines
         SET deleted_at = NOW() -- Soft delete
         WHERE deadline_id = $1 AND user_id = $2
+        RETURNING
+           deadline_id, user_id, category_id, title, description, due_date, virtual_due_date as "virtual_due_date!: _",
+           priority as "priority!: _",
+           workload_magnitude as "workload_magnitude!: _", workload_unit as "workload_unit!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         "#,
         deadline_id,
         user_id
     )
-    .execute(&state.pool)
-    .await?;
-
-    if delete_result.rows_affected() == 0 {
-        Err(AppError::DeadlineNotFound)
-    } else {
-        Ok(StatusCode::NO_CONTENT)
-    }
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::DeadlineNotFound)
 }
\ No newline at end of file
@@ -0,0 +1,132 @@
+use axum::{
+    extract::{State, Path, Json},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use validator::Validate;
+use crate::{
+    AppState,
+    errors::AppError,
+    models::{event::Event, event_exception::{EventException, OverrideOccurrencePayload}},
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
+};
+
+use crate::utils::calendar::parse_timestamp;
+use crate::utils::recurrence::is_occurrence;
+
+// --- Helper: Fetch an owned, non-deleted event and check that original_start is one of its occurrences ---
+async fn fetch_owned_occurrence(
+    pool: &PgPool,
+    event_id: i32,
+    user_id: i32,
+    original_start: DateTime<Utc>,
+) -> Result<Event, AppError> {
+    let event = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+           event_id, user_id, category_id, title, description as "description!: _", start_time, end_time,
+           location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE event_id = $1 AND user_id = $2 AND deleted_at IS NULL
+        "#,
+        event_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::EventNotFound)?;
+
+    // Only generated occurrences of a recurring event can have exceptions
+    if !is_occurrence(&event, original_start) {
+        return Err(AppError::OccurrenceNotFound);
+    }
+
+    Ok(event)
+}
+
+// --- Cancel Occurrence (DELETE /api/me/events/{event_id}/occurrences/{original_start}) ---
+pub async fn delete_occurrence(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path((event_id, original_start)): Path<(i32, String)>,
+) -> Result<StatusCode, AppError> {
+    let original_start = parse_timestamp(&original_start)?;
+    fetch_owned_occurrence(&state.pool, event_id, user_id, original_start).await?;
+
+    // Upsert: cancelling a previously modified occurrence drops its overrides
+    sqlx::query!(
+        r#"
+        INSERT INTO event_exceptions (event_id, original_occurrence_time, is_deleted)
+        VALUES ($1, $2, TRUE)
+        ON CONFLICT (event_id, original_occurrence_time) DO UPDATE
+        SET is_deleted = TRUE, title = NULL, description = NULL, start_time = NULL, end_time = NULL, location = NULL
+        "#,
+        event_id,
+        original_start
+    )
+    .execute(&state.pool)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Override Occurrence (PUT /api/me/events/{event_id}/occurrences/{original_start}) ---
+// Replaces any existing exception for this occurrence (also restores a cancelled one)
+pub async fn override_occurrence(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path((event_id, original_start)): Path<(i32, String)>,
+    Json(payload): Json<OverrideOccurrencePayload>,
+) -> Result<Json<EventException>, AppError> {
+    payload.validate()?;
+
+    let original_start = parse_timestamp(&original_start)?;
+    let event = fetch_owned_occurrence(&state.pool, event_id, user_id, original_start).await?;
+
+    // Timing defaults to the original occurrence
+    let start_time = match payload.start_time {
+        Some(s) => parse_timestamp(&s)?,
+        None => original_start,
+    };
+    let end_time = match payload.end_time {
+        Some(s) => parse_timestamp(&s)?,
+        None => start_time + (event.end_time - event.start_time),
+    };
+    if end_time < start_time {
+        let mut err = validator::ValidationErrors::new();
+        err.add("endTime", validator::ValidationError::new("end_before_start"));
+        return Err(AppError::ValidationFailed(err));
+    }
+
+    let exception = sqlx::query_as!(
+        EventException,
+        r#"
+        INSERT INTO event_exceptions (event_id, original_occurrence_time, is_deleted, title, description, start_time, end_time, location)
+        VALUES ($1, $2, FALSE, $3, $4, $5, $6, $7)
+        ON CONFLICT (event_id, original_occurrence_time) DO UPDATE
+        SET is_deleted = FALSE, title = EXCLUDED.title, description = EXCLUDED.description,
+            start_time = EXCLUDED.start_time, end_time = EXCLUDED.end_time, location = EXCLUDED.location
+        RETURNING
+            exception_id, event_id, original_occurrence_time, is_deleted,
+            title as "title!: _", description as "description!: _",
+            start_time as "start_time!: _", end_time as "end_time!: _", location as "location!: _",
+            created_at as "created_at!", updated_at as "updated_at!"
+        "#,
+        event_id,
+        original_start,
+        payload.title,
+        payload.description,
+        start_time,
+        end_time,
+        payload.location
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Json(exception))
+}
@@ -1,27 +1,55 @@
 use axum::{
-    extract::{State, Path, Json},
-    http::StatusCode,
+    extract::{State, Path, Json, Query},
+    http::{HeaderMap, StatusCode},
 };
-use sqlx::{PgPool, types::chrono::Utc};
+use sqlx::PgConnection;
 use validator::Validate;
 use crate::{
     AppState,
     errors::AppError,
-    models::event::{Event, CreateEventPayload, UpdateEventPayload}, // Import event models
-    middleware::auth::AuthenticatedUser,
+    models::event::{Event, CreateEventPayload, UpdateEventPayload, ListEventsParams}, // Import event models
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
 };
 use chrono::DateTime; // For parsing date strings
 
-use crate::utils::calendar::parse_timestamp; // Utility function for parsing timestamps
+use crate::utils::calendar::{parse_optional_timestamp, parse_timestamp}; // Utility functions for parsing timestamps
+use crate::utils::pagination::{next_cursor_headers, resolve_page, take_page, CursorKind, PageCursor};
+use crate::utils::recurrence::{event_overlaps, validate_rrule};
 
 
 // --- Create Event ---
 pub async fn create_event(
     State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
     Json(payload): Json<CreateEventPayload>,
 ) -> Result<(StatusCode, Json<Event>), AppError> {
+    let mut conn = state.pool.acquire().await?;
+    let created_event = insert_event(&mut conn, user_id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(created_event)))
+}
+
+// --- Helper: Check a new event against the user's data without creating it ---
+// Also used by the AI assistant to vet the events it proposes
+pub async fn validate_new_event(conn: &mut PgConnection, user_id: i32, payload: &CreateEventPayload) -> Result<(), AppError> {
     payload.validate()?;
+    validate_rrule(payload.rrule.as_deref())?; // Reject rules the expansion engine can't handle
+
+    // Required by validation
+    parse_timestamp(payload.start_time.as_deref().unwrap())?;
+    parse_timestamp(payload.end_time.as_deref().unwrap())?;
+
+    if let Some(cat_id) = payload.category_id {
+        ensure_category_owned(conn, user_id, cat_id).await?;
+    }
+
+    Ok(())
+}
+
+// --- Helper: Create an event owned by the user ---
+pub async fn insert_event(conn: &mut PgConnection, user_id: i32, payload: CreateEventPayload) -> Result<Event, AppError> {
+    validate_new_event(conn, user_id, &payload).await?;
 
     let title = payload.title.unwrap(); // Required by validation
     let category_id = payload.category_id; // Required by validation
@@ -35,21 +63,6 @@ pub async fn create_event(
     let end_time_str = payload.end_time.unwrap();
     let end_time = parse_timestamp(&end_time_str)?;
 
-    // Optional: Validate category_id existence and ownership if provided
-    if let Some(cat_id) = category_id {
-       let category_exists: Option<bool> = sqlx::query_scalar!(
-            "SELECT EXISTS(SELECT 1 FROM categories WHERE category_id = $1 AND user_id = $2)",
-            cat_id,
-            user_id
-        )
-        .fetch_one(&state.pool)
-        .await?;
-
-        if category_exists != Some(true) {
-             return Err(AppError::CategoryNotFound); // Re-using error for now
-        }
-    }
-
     let created_event = sqlx::query_as!(
         Event,
         r#"
@@ -69,18 +82,28 @@ pub async fn create_event(
         location,
         rrule,
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await?; // sqlx::Error -> AppError::DatabaseError
 
-    Ok((StatusCode::CREATED, Json(created_event)))
+    Ok(created_event)
 }
 
 // --- Get All Events for User ---
+// Optional ?from=&to= window, ?categoryId= filter and ?limit=&after= cursor pagination
 pub async fn get_events(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
-) -> Result<Json<Vec<Event>>, AppError> {
-    let events = sqlx::query_as!(
+    Query(params): Query<ListEventsParams>,
+) -> Result<(HeaderMap, Json<Vec<Event>>), AppError> {
+    let from = parse_optional_timestamp(params.from)?;
+    let to = parse_optional_timestamp(params.to)?;
+    let (limit, cursor) = resolve_page(params.limit, params.after.as_deref())?;
+    let (after_time, after_id) = cursor.map(|c| c.bound_for(CursorKind::Event)).unzip();
+
+    // Recurring series can't be bounded by end_time, so they pass the 'from' check here and are
+    // narrowed down after expansion below
+    let mut events = sqlx::query_as!(
         Event,
         r#"
         SELECT
@@ -89,19 +112,38 @@ pub async fn get_events(
            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM events
         WHERE user_id = $1 AND deleted_at IS NULL
-        ORDER BY start_time -- Optional: order by start time
+          AND ( ($2::TIMESTAMPTZ IS NULL) OR end_time > $2 OR start_time >= $2 OR COALESCE(rrule, '') <> '' )
+          AND ( ($3::TIMESTAMPTZ IS NULL) OR start_time < $3 )
+          AND ( ($4::INT IS NULL) OR category_id = $4 )
+          AND ( ($5::TIMESTAMPTZ IS NULL) OR (start_time, event_id) > ($5, $6::INT) ) -- Keyset cursor
+        ORDER BY start_time, event_id
+        LIMIT $7
         "#,
-        user_id
+        user_id,
+        from,
+        to,
+        params.category_id,
+        after_time,
+        after_id,
+        limit.map(|l| l + 1) // One extra row tells us whether there is a next page
     )
     .fetch_all(&state.pool)
     .await?;
 
-    Ok(Json(events))
+    let next_cursor = take_page(&mut events, limit, PageCursor::from_event);
+
+    // Drop series whose instances all fall outside the window
+    if from.is_some() || to.is_some() {
+        events.retain(|e| event_overlaps(e.start_time, e.end_time, e.rrule.as_deref(), from, to));
+    }
+
+    Ok((next_cursor_headers(next_cursor), Json(events)))
 }
 
 // --- Get Single Event by ID for User ---
 pub async fn get_event_by_id(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
     Path(event_id): Path<i32>,
 ) -> Result<Json<Event>, AppError> {
@@ -130,13 +172,15 @@ pub async fn get_event_by_id(
 // --- Update Event ---
 pub async fn update_event(
     State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
     Path(event_id): Path<i32>,
     Json(payload): Json<UpdateEventPayload>,
 ) -> Result<Json<Event>, AppError> {
-    payload.validate()?;
+    validate_event_update(&payload)?;
 
     // Fetch existing event to check ownership and get current values
+    let mut conn = state.pool.acquire().await?;
     let existing_event = sqlx::query_as!(
         Event,
         r#"
@@ -150,33 +194,41 @@ pub async fn update_event(
         event_id,
         user_id
     )
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *conn)
     .await?;
 
-    let mut event_to_update = match existing_event {
+    let event_to_update = match existing_event {
         Some(e) => e,
         None => return Err(AppError::EventNotFound),
     };
 
+    let updated_event = apply_event_update(&mut conn, user_id, event_to_update, payload).await?;
+    Ok(Json(updated_event))
+}
 
+// --- Helper: Check an update payload before the event is looked up ---
+pub fn validate_event_update(payload: &UpdateEventPayload) -> Result<(), AppError> {
+    payload.validate()?;
+    validate_rrule(payload.rrule.as_deref())?;
+    Ok(())
+}
+
+// --- Helper: Apply an update payload (checked by validate_event_update) to an event owned by the user ---
+// Also used by the client sync batch, which locks the event and checks for conflicts first
+pub async fn apply_event_update(
+    conn: &mut PgConnection,
+    user_id: i32,
+    mut event_to_update: Event,
+    payload: UpdateEventPayload,
+) -> Result<Event, AppError> {
     // Apply updates only if the field is provided in the payload (Option::is_some())
     if let Some(title) = payload.title {
         event_to_update.title = title;
     }
     // First validate if the new category_id exists and belongs to the user
     if let Some(new_cat_id) = payload.category_id {
-        let category_exists: Option<bool> = sqlx::query_scalar!(
-            "SELECT EXISTS(SELECT 1 FROM categories WHERE category_id = $1 AND user_id = $2)",
-            new_cat_id,
-            user_id
-        )
-        .fetch_one(&state.pool)
-        .await?;
-
-        if category_exists != Some(true) {
-            return Err(AppError::CategoryNotFound);
-        }
-        
+        ensure_category_owned(conn, user_id, new_cat_id).await?;
+
         // Only update the category_id after validation
         event_to_update.category_id = new_cat_id;
     }
@@ -234,18 +286,35 @@ pub async fn update_event(
         event_to_update.end_time,
         event_to_update.location,
         event_to_update.rrule,
-        event_id,
+        event_to_update.event_id,
         user_id // Crucial check
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *conn)
     .await?;
 
-    Ok(Json(updated_event))
+    Ok(updated_event)
+}
+
+// Validate category_id existence and ownership (also used for deadlines)
+pub async fn ensure_category_owned(conn: &mut PgConnection, user_id: i32, category_id: i32) -> Result<(), AppError> {
+    let category_exists: Option<bool> = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM categories WHERE category_id = $1 AND user_id = $2)",
+        category_id,
+        user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if category_exists != Some(true) {
+        return Err(AppError::CategoryNotFound);
+    }
+    Ok(())
 }
 
 // --- Delete Event ---
 pub async fn delete_event(
     State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
     AuthenticatedUser { user_id }: AuthenticatedUser,
     Path(event_id): Path<i32>,
 ) -> Result<StatusCode, AppError> {
@@ -0,0 +1,85 @@
+use axum::extract::{State, Json};
+use validator::Validate;
+use crate::{
+    AppState,
+    errors::AppError,
+    models::{
+        enums::SharePrivacyLevel,
+        freebusy::{FreeBusyAccess, FreeBusyCalendar, FreeBusyEvent, FreeBusyPayload, FreeBusyResponse},
+    },
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
+};
+use crate::utils::calendar::{parse_timestamp, validate_time_window};
+use crate::utils::freebusy::{load_busy_occurrences, merge_busy_intervals, shared_category_levels};
+
+// --- Free/Busy Query (POST /api/freebusy) ---
+// Busy blocks for each requested user within [from, to). Users who shared categories with the caller
+// (calendar share, or an open share link the caller passes) also expose those events, per the share's privacy level.
+pub async fn query_free_busy(
+    State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    AuthenticatedUser { user_id: caller_user_id }: AuthenticatedUser,
+    Json(payload): Json<FreeBusyPayload>,
+) -> Result<Json<FreeBusyResponse>, AppError> {
+    payload.validate()?;
+
+    let from = parse_timestamp(&payload.from.unwrap())?; // Required by validation
+    let to = parse_timestamp(&payload.to.unwrap())?;
+    let window = validate_time_window(from, to)?;
+    let open_share_ids = payload.open_share_ids.unwrap_or_default();
+
+    let mut calendars = Vec::new();
+    for email in payload.emails.unwrap() {
+        let user_id = sqlx::query_scalar!(
+            "SELECT user_id FROM users WHERE lower(email) = lower($1) AND deleted_at IS NULL",
+            email
+        )
+        .fetch_optional(&state.pool)
+        .await?;
+
+        // Unknown emails look like users without events, so the endpoint can't be used to probe for accounts
+        let Some(user_id) = user_id else {
+            calendars.push(FreeBusyCalendar { email, access: FreeBusyAccess::BusyOnly, busy: vec![], events: None });
+            continue;
+        };
+
+        let (events, occurrences) = load_busy_occurrences(&state.pool, user_id, window).await?;
+        let busy = merge_busy_intervals(&occurrences, window);
+
+        // The caller sees all of their own events; others only what they shared
+        let levels = if user_id == caller_user_id {
+            None
+        } else {
+            Some(shared_category_levels(&state.pool, user_id, caller_user_id, &open_share_ids).await?)
+        };
+        if levels.as_ref().is_some_and(|l| l.is_empty()) {
+            calendars.push(FreeBusyCalendar { email, access: FreeBusyAccess::BusyOnly, busy, events: None });
+            continue;
+        }
+
+        // Only the user's own events belong to their categories; accepted invitations stay opaque
+        let shared_events = occurrences
+            .into_iter()
+            .filter_map(|occurrence| {
+                let event = events.iter().find(|e| e.event_id == occurrence.event_id && e.user_id == user_id)?;
+                let level = match &levels {
+                    Some(levels) => *levels.get(&event.category_id)?,
+                    None => SharePrivacyLevel::Full,
+                };
+                let full = level == SharePrivacyLevel::Full;
+                Some(FreeBusyEvent {
+                    event_id: event.event_id,
+                    category_id: event.category_id,
+                    start: occurrence.occurrence_start,
+                    end: occurrence.occurrence_end,
+                    title: full.then(|| occurrence.title.clone().unwrap_or_else(|| event.title.clone())),
+                    location: if full { occurrence.location.clone().or_else(|| event.location.clone()) } else { None },
+                })
+            })
+            .collect();
+
+        calendars.push(FreeBusyCalendar { email, access: FreeBusyAccess::Shared, busy, events: Some(shared_events) });
+    }
+
+    Ok(Json(FreeBusyResponse { from, to, calendars }))
+}
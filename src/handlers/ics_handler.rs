@@ -0,0 +1,173 @@
+use axum::{
+    extract::{State, Query, Path},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use uuid::Uuid;
+use crate::{
+    AppState,
+    errors::AppError,
+    models::{
+        calendar::CalendarViewParams,
+        deadline::Deadline,
+        enums::{EventInvitationStatus, SharePrivacyLevel},
+        event::Event,
+        event_exception::EventException,
+    },
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
+};
+
+use crate::handlers::calendar_handler::{get_open_shared_calendar, get_shared_calendar, load_open_shared_calendar, load_shared_calendar};
+use crate::utils::calendar::fetch_event_exceptions;
+use crate::utils::ics::IcsCalendar;
+
+// --- Helper: Serialize non-deleted items and wrap them in a text/calendar response ---
+fn ics_response(
+    name: &str,
+    filename: &str,
+    privacy_level: SharePrivacyLevel,
+    events: &[Event],
+    deadlines: &[Deadline],
+    exceptions: &[EventException],
+) -> Response {
+    let mut calendar = IcsCalendar::new(name, privacy_level == SharePrivacyLevel::Limited);
+    for event in events.iter().filter(|e| e.deleted_at.is_none()) {
+        calendar.add_event(event, exceptions);
+    }
+    for deadline in deadlines.iter().filter(|d| d.deleted_at.is_none()) {
+        calendar.add_deadline(deadline);
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        calendar.finish(),
+    )
+        .into_response()
+}
+
+// --- Export User Calendar (GET /api/calendar.ics) ---
+// Owned events, accepted invited events and owned deadlines
+pub async fn export_user_calendar(
+    State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<Response, AppError> {
+    let events = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+           event_id, user_id, category_id, title, description as "description!: _",
+           start_time, end_time, location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE ( user_id = $1 -- Owned events
+           OR event_id IN (
+               SELECT event_id
+               FROM event_invitations
+               WHERE invited_user_id = $1 AND status = $2
+           ) ) -- Accepted invited events
+          AND deleted_at IS NULL
+        ORDER BY start_time, event_id
+        "#,
+        user_id,
+        EventInvitationStatus::Accepted as EventInvitationStatus
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let deadlines = sqlx::query_as!(
+        Deadline,
+        r#"
+        SELECT
+           deadline_id, user_id, category_id, title, description as "description!: _",
+           due_date, virtual_due_date as "virtual_due_date!: _", priority as "priority!: _",
+           workload_magnitude as "workload_magnitude!: _", workload_unit as "workload_unit!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM deadlines
+        WHERE user_id = $1 AND deleted_at IS NULL
+        ORDER BY due_date, deadline_id
+        "#,
+        user_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let exceptions = fetch_event_exceptions(&state.pool, &event_ids).await?;
+
+    Ok(ics_response("Qalendar", "calendar.ics", SharePrivacyLevel::Full, &events, &deadlines, &exceptions))
+}
+
+// --- Export Shared Calendar (GET /api/calendar/shares/{share_id}.ics) ---
+pub async fn export_shared_calendar(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id: authenticated_user_id }: AuthenticatedUser, // The sharee
+    share_id: i32,
+) -> Result<Response, AppError> {
+    let (share, events, deadlines) = load_shared_calendar(&state.pool, share_id, authenticated_user_id).await?;
+
+    let event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let exceptions = fetch_event_exceptions(&state.pool, &event_ids).await?;
+
+    let filename = format!("share-{}.ics", share.share_id);
+    Ok(ics_response("Shared calendar", &filename, share.privacy_level, &events, &deadlines, &exceptions))
+}
+
+// --- Export Open Shared Calendar (GET /api/calendar/open-shares/{uuid}.ics) ---
+// Public, so the URL can be used as a subscription feed
+pub async fn export_open_shared_calendar(
+    State(state): State<AppState>,
+    open_share_id: Uuid,
+) -> Result<Response, AppError> {
+    let (share, events, deadlines) = load_open_shared_calendar(&state.pool, open_share_id).await?;
+
+    let event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let exceptions = fetch_event_exceptions(&state.pool, &event_ids).await?;
+
+    let filename = format!("{}.ics", share.open_share_id);
+    Ok(ics_response("Shared calendar", &filename, share.privacy_level, &events, &deadlines, &exceptions))
+}
+
+// --- Route: GET /api/calendar/shares/{share_id}[.ics] ---
+// The router cannot match a "{share_id}.ics" segment next to "{share_id}", so one route serves both
+pub async fn shared_calendar_route(
+    state: State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    user: AuthenticatedUser,
+    Path(segment): Path<String>,
+    params: Query<CalendarViewParams>,
+) -> Result<Response, AppError> {
+    let (id, is_ics) = match segment.strip_suffix(".ics") {
+        Some(id) => (id, true),
+        None => (segment.as_str(), false),
+    };
+    let share_id: i32 = id.parse().map_err(|_| AppError::ShareNotFound)?;
+
+    if is_ics {
+        export_shared_calendar(state, user, share_id).await
+    } else {
+        Ok(get_shared_calendar(state, user, Path(share_id), params).await?.into_response())
+    }
+}
+
+// --- Route: GET /api/calendar/open-shares/{uuid}[.ics] ---
+pub async fn open_shared_calendar_route(
+    state: State<AppState>,
+    Path(segment): Path<String>,
+    params: Query<CalendarViewParams>,
+) -> Result<Response, AppError> {
+    let (id, is_ics) = match segment.strip_suffix(".ics") {
+        Some(id) => (id, true),
+        None => (segment.as_str(), false),
+    };
+    let open_share_id: Uuid = id.parse().map_err(|_| AppError::ShareNotFound)?;
+
+    if is_ics {
+        export_open_shared_calendar(state, open_share_id).await
+    } else {
+        Ok(get_open_shared_calendar(state, Path(open_share_id), params).await?.into_response())
+    }
+}
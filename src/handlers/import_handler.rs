@@ -0,0 +1,433 @@
+use axum::{
+    extract::{State, Multipart},
+    Json,
+};
+use sqlx::PgConnection;
+use crate::{
+    AppState,
+    errors::AppError,
+    models::{
+        enums::DeadlinePriorityLevel,
+        event::Event,
+        ics_import::{IcsImportReport, ImportItemKind, ImportItemResult, ImportItemStatus},
+    },
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
+};
+
+use crate::utils::ics::{parse_components, ImportedDeadline, ImportedEvent};
+use crate::utils::recurrence::is_occurrence;
+
+const MAX_FILE_SIZE: usize = 2 * 1024 * 1024; // 2MB, the default request body limit
+const MAX_IMPORT_ITEMS: usize = 5000;
+
+// --- Import iCalendar File (POST /api/me/import/ics) ---
+// Multipart fields: 'file' (the .ics file) and 'categoryId' (target category for everything imported).
+// Items are matched by UID, so re-importing the same file updates earlier imports instead of duplicating them.
+pub async fn import_ics(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    _: RequireScope<scope::DeadlinesWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    mut multipart: Multipart,
+) -> Result<Json<IcsImportReport>, AppError> {
+    let mut file: Option<String> = None;
+    let mut category_id: Option<i32> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::InvalidMultipartData(format!("Failed to read multipart field: {}", e)))? {
+        match field.name() {
+            Some("file") => {
+                let bytes = field.bytes().await.map_err(|e| AppError::FileUploadError(format!("Failed to read file bytes: {}", e)))?;
+                if bytes.len() > MAX_FILE_SIZE {
+                    return Err(AppError::FileUploadError(format!("File size exceeds limit (max {} bytes)", MAX_FILE_SIZE)));
+                }
+                let text = String::from_utf8(bytes.to_vec()).map_err(|_| AppError::FileUploadError("File is not valid UTF-8".to_string()))?;
+                file = Some(text);
+            }
+            Some("categoryId") => {
+                let text = field.text().await.map_err(|e| AppError::InvalidMultipartData(format!("Failed to read categoryId: {}", e)))?;
+                let id = text.trim().parse::<i32>().map_err(|_| AppError::InvalidMultipartData("categoryId must be an integer".to_string()))?;
+                category_id = Some(id);
+            }
+            other => tracing::warn!("Ignoring unexpected multipart field: {:?}", other),
+        }
+    }
+
+    let file = file.ok_or(AppError::InvalidMultipartData("Request must include 'file'".to_string()))?;
+    let category_id = category_id.ok_or(AppError::InvalidMultipartData("Request must include 'categoryId'".to_string()))?;
+
+    // Validate category ownership
+    let category_exists: Option<bool> = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM categories WHERE category_id = $1 AND user_id = $2)",
+        category_id,
+        user_id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+    if category_exists != Some(true) {
+        return Err(AppError::CategoryNotFound);
+    }
+
+    let components = parse_components(&file).map_err(AppError::FileUploadError)?;
+    if components.len() > MAX_IMPORT_ITEMS {
+        return Err(AppError::FileUploadError(format!("Too many items (max {})", MAX_IMPORT_ITEMS)));
+    }
+
+    // Sort into series/single events, occurrence overrides and deadlines.
+    // Overrides are applied last so they can find series created by this same import.
+    let mut report = IcsImportReport::default();
+    let mut events = Vec::new();
+    let mut overrides = Vec::new();
+    let mut deadlines = Vec::new();
+    for component in &components {
+        if component.kind == "VTODO" {
+            match ImportedDeadline::from_component(component) {
+                Ok(deadline) => deadlines.push(deadline),
+                Err(reason) => report.push(skipped(ImportItemKind::Deadline, component.text("UID"), component.text("SUMMARY"), reason)),
+            }
+            continue;
+        }
+        let kind = if component.property("RECURRENCE-ID").is_some() { ImportItemKind::Occurrence } else { ImportItemKind::Event };
+        match ImportedEvent::from_component(component) {
+            Ok(event) if event.recurrence_id.is_some() => overrides.push(event),
+            Ok(event) => events.push(event),
+            Err(reason) => report.push(skipped(kind, component.text("UID"), component.text("SUMMARY"), reason)),
+        }
+    }
+
+    // All or nothing on database errors; per-item problems are reported as skipped
+    let mut tx = state.pool.begin().await?;
+    for event in &events {
+        report.push(import_event(&mut tx, user_id, category_id, event).await?);
+    }
+    for event in &overrides {
+        report.push(import_occurrence(&mut tx, user_id, event).await?);
+    }
+    for deadline in &deadlines {
+        report.push(import_deadline(&mut tx, user_id, category_id, deadline).await?);
+    }
+    tx.commit().await?;
+
+    tracing::info!("User {} imported ics: {} created, {} updated, {} skipped", user_id, report.created, report.updated, report.skipped);
+    Ok(Json(report))
+}
+
+fn skipped(kind: ImportItemKind, uid: Option<String>, title: Option<String>, reason: String) -> ImportItemResult {
+    ImportItemResult { kind, status: ImportItemStatus::Skipped, uid, title, event_id: None, deadline_id: None, reason: Some(reason) }
+}
+
+// --- Helper: Create or update one event (and its cancelled occurrences) by UID ---
+async fn import_event(
+    conn: &mut PgConnection,
+    user_id: i32,
+    category_id: i32,
+    imported: &ImportedEvent,
+) -> Result<ImportItemResult, AppError> {
+    let existing = match &imported.uid {
+        Some(uid) => sqlx::query_as!(
+            Event,
+            r#"
+            SELECT
+               event_id, user_id, category_id, title, description as "description!: _", start_time, end_time,
+               location as "location!: _", rrule as "rrule!: _",
+               created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+            FROM events
+            WHERE user_id = $1 AND ical_uid = $2
+            "#,
+            user_id,
+            uid
+        )
+        .fetch_optional(&mut *conn)
+        .await?,
+        None => None,
+    };
+
+    let (event, status) = match existing {
+        // Respect deletions made after an earlier import
+        Some(existing) if existing.deleted_at.is_some() => {
+            let reason = "Previously imported event was deleted".to_string();
+            return Ok(skipped(ImportItemKind::Event, imported.uid.clone(), Some(imported.title.clone()), reason));
+        }
+        Some(existing) => {
+            let unchanged = existing.category_id == category_id
+                && existing.title == imported.title
+                && existing.description == imported.description
+                && existing.location == imported.location
+                && existing.start_time == imported.start_time
+                && existing.end_time == imported.end_time
+                && existing.rrule == imported.rrule;
+            if unchanged {
+                (existing, ImportItemStatus::Skipped)
+            } else {
+                let updated = sqlx::query_as!(
+                    Event,
+                    r#"
+                    UPDATE events
+                    SET category_id = $2, title = $3, description = $4, start_time = $5, end_time = $6, location = $7, rrule = $8
+                    WHERE event_id = $1
+                    RETURNING
+                       event_id, user_id, category_id, title, description as "description!: _", start_time, end_time,
+                       location as "location!: _", rrule as "rrule!: _",
+                       created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+                    "#,
+                    existing.event_id,
+                    category_id,
+                    imported.title,
+                    imported.description,
+                    imported.start_time,
+                    imported.end_time,
+                    imported.location,
+                    imported.rrule
+                )
+                .fetch_one(&mut *conn)
+                .await?;
+                (updated, ImportItemStatus::Updated)
+            }
+        }
+        None => {
+            let created = sqlx::query_as!(
+                Event,
+                r#"
+                INSERT INTO events (user_id, category_id, title, description, start_time, end_time, location, rrule, ical_uid)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                RETURNING
+                   event_id, user_id, category_id, title, description as "description!: _", start_time, end_time,
+                   location as "location!: _", rrule as "rrule!: _",
+                   created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+                "#,
+                user_id,
+                category_id,
+                imported.title,
+                imported.description,
+                imported.start_time,
+                imported.end_time,
+                imported.location,
+                imported.rrule,
+                imported.uid
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            (created, ImportItemStatus::Created)
+        }
+    };
+
+    // EXDATEs become cancelled occurrences; dates that aren't occurrences of the rule are ignored
+    let mut exceptions_changed = false;
+    for exdate in imported.exdates.iter().filter(|exdate| is_occurrence(&event, **exdate)) {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO event_exceptions (event_id, original_occurrence_time, is_deleted)
+            VALUES ($1, $2, TRUE)
+            ON CONFLICT (event_id, original_occurrence_time) DO UPDATE
+            SET is_deleted = TRUE, title = NULL, description = NULL, start_time = NULL, end_time = NULL, location = NULL
+            WHERE NOT event_exceptions.is_deleted
+            "#,
+            event.event_id,
+            exdate
+        )
+        .execute(&mut *conn)
+        .await?;
+        exceptions_changed |= result.rows_affected() > 0;
+    }
+
+    let (status, reason) = match status {
+        ImportItemStatus::Skipped if exceptions_changed => (ImportItemStatus::Updated, None),
+        ImportItemStatus::Skipped => (ImportItemStatus::Skipped, Some("Unchanged".to_string())),
+        other => (other, None),
+    };
+
+    Ok(ImportItemResult {
+        kind: ImportItemKind::Event,
+        status,
+        uid: imported.uid.clone(),
+        title: Some(event.title),
+        event_id: Some(event.event_id),
+        deadline_id: None,
+        reason,
+    })
+}
+
+// --- Helper: Apply a VEVENT with RECURRENCE-ID as a modified occurrence of the imported series ---
+async fn import_occurrence(
+    conn: &mut PgConnection,
+    user_id: i32,
+    imported: &ImportedEvent,
+) -> Result<ImportItemResult, AppError> {
+    let skip = |reason: &str| skipped(ImportItemKind::Occurrence, imported.uid.clone(), Some(imported.title.clone()), reason.to_string());
+    let (Some(uid), Some(original_start)) = (&imported.uid, imported.recurrence_id) else {
+        return Ok(skip("Missing UID"));
+    };
+
+    let series = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+           event_id, user_id, category_id, title, description as "description!: _", start_time, end_time,
+           location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE user_id = $1 AND ical_uid = $2 AND deleted_at IS NULL
+        "#,
+        user_id,
+        uid
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(series) = series else {
+        return Ok(skip("No imported recurring event with this UID"));
+    };
+    if !is_occurrence(&series, original_start) {
+        return Ok(skip("RECURRENCE-ID is not an occurrence of the recurring event"));
+    }
+
+    // Only store what differs from the series (None means inherit)
+    let title = Some(&imported.title).filter(|t| **t != series.title);
+    let description = imported.description.as_ref().filter(|d| Some(*d) != series.description.as_ref());
+    let location = imported.location.as_ref().filter(|l| Some(*l) != series.location.as_ref());
+
+    let existing = sqlx::query!(
+        r#"
+        SELECT is_deleted, title, description, start_time, end_time, location
+        FROM event_exceptions
+        WHERE event_id = $1 AND original_occurrence_time = $2
+        "#,
+        series.event_id,
+        original_start
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let status = match existing {
+        None => ImportItemStatus::Created,
+        Some(ex) if !ex.is_deleted
+            && ex.title.as_ref() == title
+            && ex.description.as_ref() == description
+            && ex.location.as_ref() == location
+            && ex.start_time == Some(imported.start_time)
+            && ex.end_time == Some(imported.end_time) => {
+            return Ok(ImportItemResult {
+                kind: ImportItemKind::Occurrence,
+                status: ImportItemStatus::Skipped,
+                uid: imported.uid.clone(),
+                title: Some(imported.title.clone()),
+                event_id: Some(series.event_id),
+                deadline_id: None,
+                reason: Some("Unchanged".to_string()),
+            });
+        }
+        Some(_) => ImportItemStatus::Updated,
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO event_exceptions (event_id, original_occurrence_time, is_deleted, title, description, start_time, end_time, location)
+        VALUES ($1, $2, FALSE, $3, $4, $5, $6, $7)
+        ON CONFLICT (event_id, original_occurrence_time) DO UPDATE
+        SET is_deleted = FALSE, title = EXCLUDED.title, description = EXCLUDED.description,
+            start_time = EXCLUDED.start_time, end_time = EXCLUDED.end_time, location = EXCLUDED.location
+        "#,
+        series.event_id,
+        original_start,
+        title,
+        description,
+        imported.start_time,
+        imported.end_time,
+        location
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(ImportItemResult {
+        kind: ImportItemKind::Occurrence,
+        status,
+        uid: imported.uid.clone(),
+        title: Some(imported.title.clone()),
+        event_id: Some(series.event_id),
+        deadline_id: None,
+        reason: None,
+    })
+}
+
+// --- Helper: Create or update one deadline by UID ---
+async fn import_deadline(
+    conn: &mut PgConnection,
+    user_id: i32,
+    category_id: i32,
+    imported: &ImportedDeadline,
+) -> Result<ImportItemResult, AppError> {
+    let existing = match &imported.uid {
+        Some(uid) => sqlx::query!(
+            r#"
+            SELECT deadline_id, category_id, title, description, due_date, priority as "priority!: DeadlinePriorityLevel", deleted_at
+            FROM deadlines
+            WHERE user_id = $1 AND ical_uid = $2
+            "#,
+            user_id,
+            uid
+        )
+        .fetch_optional(&mut *conn)
+        .await?,
+        None => None,
+    };
+
+    let result = |status, deadline_id, reason: Option<&str>| ImportItemResult {
+        kind: ImportItemKind::Deadline,
+        status,
+        uid: imported.uid.clone(),
+        title: Some(imported.title.clone()),
+        event_id: None,
+        deadline_id,
+        reason: reason.map(str::to_string),
+    };
+
+    match existing {
+        // Respect deletions made after an earlier import
+        Some(existing) if existing.deleted_at.is_some() => {
+            Ok(result(ImportItemStatus::Skipped, None, Some("Previously imported deadline was deleted")))
+        }
+        Some(existing) if existing.category_id == category_id
+            && existing.title == imported.title
+            && existing.description == imported.description
+            && existing.due_date == imported.due_date
+            && existing.priority == imported.priority => {
+            Ok(result(ImportItemStatus::Skipped, Some(existing.deadline_id), Some("Unchanged")))
+        }
+        Some(existing) => {
+            sqlx::query!(
+                r#"
+                UPDATE deadlines
+                SET category_id = $2, title = $3, description = $4, due_date = $5, priority = $6
+                WHERE deadline_id = $1
+                "#,
+                existing.deadline_id,
+                category_id,
+                imported.title,
+                imported.description,
+                imported.due_date,
+                imported.priority as DeadlinePriorityLevel
+            )
+            .execute(&mut *conn)
+            .await?;
+            Ok(result(ImportItemStatus::Updated, Some(existing.deadline_id), None))
+        }
+        None => {
+            let deadline_id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO deadlines (user_id, category_id, title, description, due_date, priority, ical_uid)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING deadline_id
+                "#,
+                user_id,
+                category_id,
+                imported.title,
+                imported.description,
+                imported.due_date,
+                imported.priority as DeadlinePriorityLevel,
+                imported.uid
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            Ok(result(ImportItemStatus::Created, Some(deadline_id), None))
+        }
+    }
+}
@@ -5,10 +5,14 @@ use axum::{
 use sqlx::{PgPool, Row, types::chrono::Utc}; // Added Row for exists check
 use validator::Validate;
 use crate::{
-    errors::AppError, middleware::auth::AuthenticatedUser, models::{
-        enums::EventInvitationStatus, event_invitation::{EventInvitation, EventInvitationResponseItem, InvitationResponsePayload, InviteUserPayload, ListEventInvitationsParams, ListMyInvitationsParams, MyInvitationResponseItem}, user::{User, BasicUserInfo} // Need User model to look up invitee by email
+    errors::AppError, middleware::auth::{scope, AuthenticatedUser, RequireScope}, models::{
+        enums::EventInvitationStatus, event::Event, event_invitation::{EventInvitation, EventInvitationOrPending, EventInvitationResponseItem, InvitationResponsePayload, InviteUserPayload, ListEventInvitationsParams, ListMyInvitationsParams, MyInvitationResponseItem, PendingEventInvitation, RsvpLinkParams}, user::BasicUserInfo
     }, AppState
 };
+use crate::auth::jwt::{create_rsvp_token, validate_rsvp_token};
+use crate::email::RsvpLinks;
+use crate::utils::calendar::fetch_event_exceptions;
+use crate::utils::ics::IcsCalendar;
 
 // --- Helper: Check if event exists and is owned by the user ---
 async fn check_event_ownership(pool: &PgPool, event_id: i32, user_id: i32) -> Result<bool, AppError> {
@@ -22,26 +26,259 @@ async fn check_event_ownership(pool: &PgPool, event_id: i32, user_id: i32) -> Re
     Ok(exists.unwrap_or(false)) // query_scalar!(EXISTS(...)) returns Option<bool>
 }
 
-// --- Helper: Check if invitation exists and is for the invited user ---
-async fn check_invitation_invitee(pool: &PgPool, invitation_id: i32, invited_user_id: i32) -> Result<bool, AppError> {
-    let exists = sqlx::query_scalar!(
-        "SELECT EXISTS(SELECT 1 FROM event_invitations WHERE invitation_id = $1 AND invited_user_id = $2)",
+// --- Helper: Record the invitee's response (PUT /api/me/invitations/{id}/status and RSVP links) ---
+// The owner is emailed when the status actually changes.
+async fn apply_invitation_response(
+    state: &AppState,
+    invitation_id: i32,
+    invited_user_id: i32,
+    new_status: EventInvitationStatus,
+) -> Result<EventInvitation, AppError> {
+    // 1. Check if the invitation exists, is for the invitee and wasn't revoked by the owner
+    let previous_status = sqlx::query_scalar!(
+        r#"SELECT status as "status!: EventInvitationStatus" FROM event_invitations WHERE invitation_id = $1 AND invited_user_id = $2 AND deleted_at IS NULL"#,
         invitation_id,
         invited_user_id
     )
-    .fetch_one(pool)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::InvitationNotFound)?;
+
+    // 2. Update the invitation status
+    let updated_invitation = sqlx::query_as!(
+        EventInvitation,
+        r#"
+        UPDATE event_invitations
+        SET status = $1 -- updated_at trigger handles timestamp
+        WHERE invitation_id = $2 AND invited_user_id = $3 -- IMPORTANT: Check invited_user_id!
+          AND deleted_at IS NULL -- Revoked in the meantime
+        RETURNING invitation_id, event_id, owner_user_id, invited_user_id, status as "status!: _",
+        created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        "#,
+        new_status as EventInvitationStatus, // Cast the ENUM
+        invitation_id,
+        invited_user_id // Crucial check
+    )
+    .fetch_optional(&state.pool)
+    .await? // Propagates sqlx errors
+    .ok_or(AppError::InvitationNotFound)?;
+
+    if previous_status != new_status {
+        notify_owner_of_response(state, invitation_id, false);
+    }
+
+    Ok(updated_invitation)
+}
+
+// --- Helper: Email the invitee the event, an .ics REQUEST and one-click RSVP links ---
+// `pending`: invitation_id is a pending_invitation_id (the invitee has no account yet)
+async fn send_invitation_email(state: &AppState, invitation_id: i32, pending: bool) -> Result<(), AppError> {
+    let (event_id, owner_name, owner_email, invitee_email) = if pending {
+        let invitation = sqlx::query!(
+            r#"
+            SELECT pi.event_id, owner.display_name as owner_name, owner.email as owner_email, pi.invited_email
+            FROM pending_event_invitations pi
+            JOIN users owner ON owner.user_id = pi.owner_user_id
+            WHERE pi.pending_invitation_id = $1
+            "#,
+            invitation_id
+        )
+        .fetch_one(&state.pool)
+        .await?;
+        (invitation.event_id, invitation.owner_name, invitation.owner_email, invitation.invited_email)
+    } else {
+        let invitation = sqlx::query!(
+            r#"
+            SELECT ei.event_id, owner.display_name as owner_name, owner.email as owner_email, invitee.email as invitee_email
+            FROM event_invitations ei
+            JOIN users owner ON owner.user_id = ei.owner_user_id
+            JOIN users invitee ON invitee.user_id = ei.invited_user_id
+            WHERE ei.invitation_id = $1
+            "#,
+            invitation_id
+        )
+        .fetch_one(&state.pool)
+        .await?;
+        (invitation.event_id, invitation.owner_name, invitation.owner_email, invitation.invitee_email)
+    };
+
+    let event = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+           event_id, user_id, category_id, title, description as "description!: _",
+           start_time, end_time, location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE event_id = $1
+        "#,
+        event_id
+    )
+    .fetch_one(&state.pool)
     .await?;
-    Ok(exists.unwrap_or(false))
+    let exceptions = fetch_event_exceptions(&state.pool, &[event.event_id]).await?;
+
+    let mut calendar = IcsCalendar::request("Qalendar invitation", &owner_name, &owner_email, &invitee_email);
+    calendar.add_event(&event, &exceptions);
+
+    let link = |status| -> Result<String, AppError> {
+        let token = create_rsvp_token(invitation_id, pending, status, &state.config)?;
+        Ok(format!("{}/api/invitations/respond?token={}", state.config.api_url, urlencoding::encode(&token)))
+    };
+    let links = RsvpLinks {
+        accept: link(EventInvitationStatus::Accepted)?,
+        maybe: link(EventInvitationStatus::Maybe)?,
+        reject: link(EventInvitationStatus::Rejected)?,
+    };
+
+    state.email_service
+        .send_invitation_email(&invitee_email, &owner_name, &event, calendar.finish(), &links, !pending)
+        .await
+}
+
+// --- Helper: Email the event owner the invitee's current response ---
+// Invitees without an account are named by their email address.
+async fn send_response_notification(state: &AppState, invitation_id: i32, pending: bool) -> Result<(), AppError> {
+    let (status, title, owner_email, invitee_name) = if pending {
+        let response = sqlx::query!(
+            r#"
+            SELECT pi.status as "status!: EventInvitationStatus", e.title, owner.email as owner_email, pi.invited_email
+            FROM pending_event_invitations pi
+            JOIN events e ON e.event_id = pi.event_id
+            JOIN users owner ON owner.user_id = pi.owner_user_id
+            WHERE pi.pending_invitation_id = $1
+            "#,
+            invitation_id
+        )
+        .fetch_one(&state.pool)
+        .await?;
+        (response.status, response.title, response.owner_email, response.invited_email)
+    } else {
+        let response = sqlx::query!(
+            r#"
+            SELECT ei.status as "status!: EventInvitationStatus", e.title, owner.email as owner_email,
+                   invitee.display_name as invitee_name
+            FROM event_invitations ei
+            JOIN events e ON e.event_id = ei.event_id
+            JOIN users owner ON owner.user_id = ei.owner_user_id
+            JOIN users invitee ON invitee.user_id = ei.invited_user_id
+            WHERE ei.invitation_id = $1
+            "#,
+            invitation_id
+        )
+        .fetch_one(&state.pool)
+        .await?;
+        (response.status, response.title, response.owner_email, response.invitee_name)
+    };
+
+    state.email_service
+        .send_invitation_response_email(&owner_email, &invitee_name, &title, status)
+        .await
+}
+
+// --- Helper: Spawn the owner notification for a changed response ---
+fn notify_owner_of_response(state: &AppState, invitation_id: i32, pending: bool) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = send_response_notification(&state, invitation_id, pending).await {
+            tracing::error!("Failed to notify owner about invitation {} response: {:?}", invitation_id, e);
+        }
+    });
+}
+
+// --- Helper: Spawn the invitation email; the invitation stands even if sending fails ---
+fn email_invitee(state: &AppState, invitation_id: i32, pending: bool) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = send_invitation_email(&state, invitation_id, pending).await {
+            tracing::error!("Failed to send invitation email for invitation {}: {:?}", invitation_id, e);
+        }
+    });
+}
+
+// --- Helper: Record a response given through the email links of a pending invitation ---
+async fn apply_pending_response(
+    state: &AppState,
+    pending_invitation_id: i32,
+    previous_status: EventInvitationStatus,
+    new_status: EventInvitationStatus,
+) -> Result<PendingEventInvitation, AppError> {
+    let updated_invitation = sqlx::query_as!(
+        PendingEventInvitation,
+        r#"
+        UPDATE pending_event_invitations
+        SET status = $1
+        WHERE pending_invitation_id = $2 AND deleted_at IS NULL
+        RETURNING pending_invitation_id, event_id, owner_user_id, invited_email, status as "status!: _",
+        created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        "#,
+        new_status as EventInvitationStatus,
+        pending_invitation_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::InvitationNotFound)?; // Revoked
+
+    if previous_status != new_status {
+        notify_owner_of_response(state, pending_invitation_id, true);
+    }
+
+    Ok(updated_invitation)
+}
+
+// --- Helper: Invite an email address that has no account yet ---
+async fn create_pending_invitation(
+    state: &AppState,
+    event_id: i32,
+    owner_user_id: i32,
+    invited_email: &str,
+) -> Result<PendingEventInvitation, AppError> {
+    let invited_email = invited_email.to_lowercase();
+
+    let invitation_exists: bool = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM pending_event_invitations WHERE event_id = $1 AND invited_email = $2 AND deleted_at IS NULL)",
+        event_id,
+        invited_email
+    )
+    .fetch_one(&state.pool)
+    .await?
+    .unwrap_or(false);
+
+    if invitation_exists {
+        return Err(AppError::InvitationAlreadyExists); // Same as for users
+    }
+
+    let created_invitation = sqlx::query_as!(
+        PendingEventInvitation,
+        r#"
+        INSERT INTO pending_event_invitations (event_id, owner_user_id, invited_email)
+        VALUES ($1, $2, $3)
+        RETURNING
+            pending_invitation_id, event_id, owner_user_id, invited_email, status as "status!: _",
+            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        "#,
+        event_id,
+        owner_user_id,
+        invited_email
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    email_invitee(state, created_invitation.pending_invitation_id, true);
+
+    Ok(created_invitation)
 }
 
 
 // --- OWNER ACTION: Create Invitation (POST /api/me/events/:event_id/invitations) ---
+// Addresses without an account get a pending invitation instead (see verify_email_handler)
 pub async fn create_invitation(
     State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser, // Renamed for clarity
     Path(event_id): Path<i32>,
     Json(payload): Json<InviteUserPayload>,
-) -> Result<(StatusCode, Json<EventInvitation>), AppError> {
+) -> Result<(StatusCode, Json<EventInvitationOrPending>), AppError> {
     payload.validate()?;
     let invited_user_email = payload.invited_user_email.unwrap();
 
@@ -59,7 +296,7 @@ pub async fn create_invitation(
             email_verified as "email_verified!",
             created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM users
-        WHERE email = $1
+        WHERE lower(email) = lower($1)
         "#,
         invited_user_email
     )
@@ -69,9 +306,9 @@ pub async fn create_invitation(
     let invited_user = match invited_user {
         Some(user) => user,
         None => {
-            // User not found means they cannot be invited
-            // Consider a specific error like AppError::InvitedUserNotFound
-            return Err(AppError::UserNotFound); // Re-using UserNotFound for now
+            // No account yet: the invitation waits until someone registers and verifies this address
+            let pending = create_pending_invitation(&state, event_id, owner_user_id, &invited_user_email).await?;
+            return Ok((StatusCode::CREATED, Json(EventInvitationOrPending::Pending(pending))));
         }
     };
 
@@ -93,8 +330,7 @@ pub async fn create_invitation(
     .unwrap_or(false);
 
     if invitation_exists {
-        // Consider a specific error like AppError::InvitationAlreadyExists
-        return Err(AppError::InternalServerError("Invitation already exists for this user and event".to_string())); // Simple error for now
+        return Err(AppError::InvitationAlreadyExists);
     }
 
     // 4. Create the invitation
@@ -114,13 +350,17 @@ pub async fn create_invitation(
     .fetch_one(&state.pool)
     .await?;
 
-    Ok((StatusCode::CREATED, Json(created_invitation)))
+    // 5. Email the invitee in the background
+    email_invitee(&state, created_invitation.invitation_id, false);
+
+    Ok((StatusCode::CREATED, Json(EventInvitationOrPending::Invitation(created_invitation))))
 }
 
 // --- OWNER ACTION: List Invitations for an Event (GET /api/me/events/:event_id/invitations) ---
 // NOW returns EventInvitationResponseItem (with invited user details) and supports status filter
 pub async fn list_invitations_for_event(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path(event_id): Path<i32>,
     Query(params): Query<ListEventInvitationsParams>, // Accept query params for filtering
@@ -179,6 +419,7 @@ pub async fn list_invitations_for_event(
 // --- OWNER ACTION: Revoke Invitation (DELETE /api/me/events/:event_id/invitations/:invitation_id) ---
 pub async fn revoke_invitation(
     State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path((event_id, invitation_id)): Path<(i32, i32)>, // Extract both IDs from the path
 ) -> Result<StatusCode, AppError> {
@@ -210,10 +451,73 @@ pub async fn revoke_invitation(
     }
 }
 
+// --- OWNER ACTION: List Pending Invitations for an Event (GET /api/me/events/:event_id/invitations/pending) ---
+// Invitations for addresses that have no account yet
+pub async fn list_pending_invitations_for_event(
+    State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
+    Path(event_id): Path<i32>,
+) -> Result<Json<Vec<PendingEventInvitation>>, AppError> {
+    if !check_event_ownership(&state.pool, event_id, owner_user_id).await? {
+        return Err(AppError::EventNotFound);
+    }
+
+    let invitations = sqlx::query_as!(
+        PendingEventInvitation,
+        r#"
+        SELECT
+            pending_invitation_id, event_id, owner_user_id, invited_email, status as "status!: _",
+            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM pending_event_invitations
+        WHERE event_id = $1 AND owner_user_id = $2 AND deleted_at IS NULL
+        ORDER BY created_at
+        "#,
+        event_id,
+        owner_user_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(invitations))
+}
+
+// --- OWNER ACTION: Revoke Pending Invitation (DELETE /api/me/events/:event_id/invitations/pending/:pending_invitation_id) ---
+pub async fn revoke_pending_invitation(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
+    Path((event_id, pending_invitation_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, AppError> {
+    if !check_event_ownership(&state.pool, event_id, owner_user_id).await? {
+        return Err(AppError::EventNotFound);
+    }
+
+    let delete_result = sqlx::query!(
+        r#"
+        UPDATE pending_event_invitations
+        SET deleted_at = NOW() -- Soft delete; its email links stop working
+        WHERE pending_invitation_id = $1 AND event_id = $2 AND owner_user_id = $3 AND deleted_at IS NULL
+        "#,
+        pending_invitation_id,
+        event_id,
+        owner_user_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if delete_result.rows_affected() == 0 {
+        Err(AppError::InvitationNotFound)
+    } else {
+        Ok(StatusCode::NO_CONTENT)
+    }
+}
+
 // --- INVITEE ACTION: List My Invitations (GET /api/me/invitations) ---
 // NOW returns MyInvitationResponseItem (with event details) and supports status filter
 pub async fn list_my_invitations(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id: invited_user_id }: AuthenticatedUser,
     Query(params): Query<ListMyInvitationsParams>, // Accept query params for filtering
 ) -> Result<Json<Vec<MyInvitationResponseItem>>, AppError> { // Return new response item struct
@@ -271,6 +575,7 @@ pub async fn list_my_invitations(
 // --- INVITEE ACTION: Respond to Invitation (PUT /api/me/invitations/:invitation_id/status) ---
 pub async fn respond_to_invitation(
     State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
     AuthenticatedUser { user_id: invited_user_id }: AuthenticatedUser, // Renamed
     Path(invitation_id): Path<i32>,
     Json(payload): Json<InvitationResponsePayload>,
@@ -279,27 +584,48 @@ pub async fn respond_to_invitation(
     payload.validate()?;
     let new_status = payload.status.unwrap(); // Required by validation
 
-    // 1. Check if the invitation exists and is for the authenticated user (the invitee)
-    if !check_invitation_invitee(&state.pool, invitation_id, invited_user_id).await? {
-        return Err(AppError::InvitationNotFound); // Use InvitationNotFound
+    let updated_invitation = apply_invitation_response(&state, invitation_id, invited_user_id, new_status).await?;
+
+    Ok(Json(updated_invitation)) // Return the updated invitation object
+}
+
+// --- INVITEE ACTION: Respond via an email link (GET /api/invitations/respond?token=) ---
+// Unauthenticated; the signed token identifies the invitation and the response.
+// Links of a pending invitation keep working after it was claimed, and then update the regular invitation.
+pub async fn respond_via_link(
+    State(state): State<AppState>,
+    Query(params): Query<RsvpLinkParams>,
+) -> Result<Json<EventInvitationOrPending>, AppError> {
+    let claims = validate_rsvp_token(&params.token, &state.config)?;
+
+    let mut invitation_id = claims.invitation_id;
+    if claims.pending {
+        let pending = sqlx::query!(
+            r#"SELECT status as "status!: EventInvitationStatus", invitation_id FROM pending_event_invitations WHERE pending_invitation_id = $1"#,
+            claims.invitation_id
+        )
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(AppError::InvitationNotFound)?;
+
+        match pending.invitation_id {
+            Some(claimed_invitation_id) => invitation_id = claimed_invitation_id,
+            None => {
+                let updated = apply_pending_response(&state, claims.invitation_id, pending.status, claims.status).await?;
+                return Ok(Json(EventInvitationOrPending::Pending(updated)));
+            }
+        }
     }
 
-    // 2. Update the invitation status
-    let updated_invitation = sqlx::query_as!(
-        EventInvitation,
-        r#"
-        UPDATE event_invitations
-        SET status = $1 -- updated_at trigger handles timestamp
-        WHERE invitation_id = $2 AND invited_user_id = $3 -- IMPORTANT: Check invited_user_id!
-        RETURNING invitation_id, event_id, owner_user_id, invited_user_id, status as "status!: _",
-        created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
-        "#,
-        new_status as EventInvitationStatus, // Cast the ENUM
-        invitation_id,
-        invited_user_id // Crucial check
+    let invited_user_id = sqlx::query_scalar!(
+        "SELECT invited_user_id FROM event_invitations WHERE invitation_id = $1",
+        invitation_id
     )
-    .fetch_one(&state.pool)
-    .await?; // Propagates sqlx errors
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(AppError::InvitationNotFound)?;
 
-    Ok(Json(updated_invitation)) // Return the updated invitation object
+    let updated_invitation = apply_invitation_response(&state, invitation_id, invited_user_id, claims.status).await?;
+
+    Ok(Json(EventInvitationOrPending::Invitation(updated_invitation)))
 }
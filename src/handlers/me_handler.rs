@@ -7,6 +7,7 @@ use sqlx::{Execute, PgPool};
 use validator::Validate;
 use crate::{
     AppState,
+    auth::{session::revoke_user_sessions, tfa::remaining_recovery_codes},
     errors::AppError,
     models::user::{
         User, UserData, // Import User and UserData
@@ -56,6 +57,13 @@ pub async fn get_authenticated_user_info(
     let user = find_user_by_id(&state.pool, user_id).await?
         .ok_or(AppError::UserNotFound)?; // Should ideally never happen if auth succeeded
 
+    // Recovery codes left, so clients can warn before they run out
+    let tfa_recovery_codes_remaining = if user.tfa_enabled {
+        Some(remaining_recovery_codes(&state.pool, user_id).await?)
+    } else {
+        None
+    };
+
     // Map User DB model to UserData response struct
     let user_data = UserData {
         user_id: user.user_id,
@@ -65,6 +73,7 @@ pub async fn get_authenticated_user_info(
         created_at: user.created_at,
         date_of_birth: user.date_of_birth,
         tfa_enabled: Some(user.tfa_enabled), // Include TFA status
+        tfa_recovery_codes_remaining,
     };
 
     tracing::info!("Authenticated user {} accessed /me.", user_id);
@@ -142,6 +151,7 @@ pub async fn update_user_handler(
             created_at: updated_user.created_at,
             date_of_birth: updated_user.date_of_birth,
             tfa_enabled: Some(updated_user.tfa_enabled),
+            tfa_recovery_codes_remaining: None,
         };
         return Ok(Json(user_data));
     }
@@ -180,6 +190,7 @@ pub async fn update_user_handler(
         created_at: updated_user.created_at,
         date_of_birth: updated_user.date_of_birth,
         tfa_enabled: Some(updated_user.tfa_enabled),
+        tfa_recovery_codes_remaining: None,
     };
 
     tracing::info!("User {} updated profile.", user_id);
@@ -235,10 +246,9 @@ pub async fn delete_user_handler(
 
     tracing::info!("User {} soft-deleted their account.", user_id);
 
-    // Consider invalidating the user's current JWT here if possible/desired
-    // (Requires a JWT revocation mechanism, which we haven't implemented)
-    // For now, the user will still have a valid token until expiry, but subsequent
-    // requests will fail if handlers check for deleted_at IS NULL.
+    // 5. Sign out everywhere. Access tokens of a soft-deleted user are rejected anyway,
+    // but revoking the sessions also stops their refresh tokens.
+    revoke_user_sessions(&state.pool, user_id, None).await?;
 
     Ok(StatusCode::NO_CONTENT) // 204 No Content
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use axum::{
+    extract::{State, Path, Json},
+    http::{HeaderMap, StatusCode},
+};
+use validator::Validate;
+use crate::{
+    AppState,
+    auth::{oidc, passkey::has_passkeys, tfa::start_tfa_challenge},
+    errors::AppError,
+    handlers::auth_handler::session_auth_response,
+    middleware::auth::AuthenticatedUser,
+    models::{
+        oidc::{FinishOidcLoginPayload, IdentityInfo, OidcAuthorizationResponse, OidcProviderInfo},
+        user::{LoginResponse, TfaRequiredResponse},
+    },
+};
+
+// --- Social Login (/api/auth/oidc) ---
+
+// GET /api/auth/oidc/providers
+pub async fn list_oidc_providers(
+    State(state): State<AppState>,
+) -> Json<Vec<OidcProviderInfo>> {
+    let providers = state.config.oidc_providers
+        .iter()
+        .map(|provider| OidcProviderInfo { id: provider.id.clone(), name: provider.name.clone() })
+        .collect();
+    Json(providers)
+}
+
+// POST /api/auth/oidc/{provider}/start
+pub async fn start_oidc_login(
+    State(state): State<AppState>,
+    Path(provider_id): Path<String>,
+) -> Result<Json<OidcAuthorizationResponse>, AppError> {
+    let provider = oidc::find_provider(&state.config, &provider_id)?;
+    let authorization_url = oidc::start_login(&state.pool, &state.oidc, &state.config, provider).await?;
+    Ok(Json(OidcAuthorizationResponse { authorization_url }))
+}
+
+// POST /api/auth/oidc/{provider}/finish
+// Like the password login, asks for the second factor when 2FA is enabled
+pub async fn finish_oidc_login(
+    State(state): State<AppState>,
+    Path(provider_id): Path<String>,
+    headers: HeaderMap,
+    Json(payload): Json<FinishOidcLoginPayload>,
+) -> Result<Json<LoginResponse>, AppError> {
+    payload.validate()?;
+    let provider = oidc::find_provider(&state.config, &provider_id)?;
+
+    let identity = oidc::finish_login(
+        &state.pool,
+        &state.oidc,
+        &state.config,
+        provider,
+        &payload.code.unwrap(), // Required by validation
+        &payload.state.unwrap(), // Required by validation
+    )
+    .await?;
+    let user_id = oidc::resolve_user(&state.pool, &provider.id, &identity).await?;
+
+    let tfa_enabled = sqlx::query_scalar!("SELECT tfa_enabled FROM users WHERE user_id = $1", user_id)
+        .fetch_one(&state.pool)
+        .await?;
+    if tfa_enabled {
+        tracing::info!("2FA required for user {} after {} login.", user_id, provider.id);
+        let (challenge_token, expires_in) = start_tfa_challenge(&state.pool, &state.config, user_id).await?;
+        return Ok(Json(LoginResponse::TfaRequired(TfaRequiredResponse {
+            user_id,
+            challenge_token,
+            expires_in,
+            passkey_available: has_passkeys(&state.pool, user_id).await?,
+        })));
+    }
+
+    tracing::info!("User {} logged in with {}.", user_id, provider.id);
+    Ok(Json(LoginResponse::Auth(session_auth_response(&state, user_id, &headers).await?)))
+}
+
+// --- Linked Identities (/api/me/identities) ---
+
+pub async fn list_identities(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<Json<Vec<IdentityInfo>>, AppError> {
+    Ok(Json(oidc::list_identities(&state.pool, user_id).await?))
+}
+
+pub async fn delete_identity(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(identity_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    oidc::delete_identity(&state.pool, user_id, identity_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -16,7 +16,7 @@ use crate::{
         enums::SharePrivacyLevel,
         user::User, // Need to look up owner user details for response
     },
-    middleware::auth::AuthenticatedUser,
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
 };
 use chrono::DateTime; // For parsing date strings
 use uuid::Uuid;
@@ -41,6 +41,7 @@ use crate::utils::calendar::validate_category_ids;
 // --- Create Open Share (POST /api/me/open-shares) ---
 pub async fn create_open_share(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Json(payload): Json<CreateOpenSharePayload>,
 ) -> Result<(StatusCode, Json<OpenShareDetailsResponse>), AppError> { // Return OpenShareDetailsResponse
@@ -157,6 +158,7 @@ pub async fn create_open_share(
 // Returns a list of open shares created by the authenticated user
 pub async fn list_open_shares(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
 ) -> Result<Json<Vec<ListOpenSharesResponseItem>>, AppError> { // Return ListOpenSharesResponseItem
 
@@ -199,6 +201,7 @@ pub async fn list_open_shares(
 // Returns details for a specific open share owned by the user
 pub async fn get_open_share_by_uuid(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path(open_share_id): Path<Uuid>, // Extract UUID from path
 ) -> Result<Json<OpenShareDetailsResponse>, AppError> { // Return OpenShareDetailsResponse
@@ -241,6 +244,7 @@ pub async fn get_open_share_by_uuid(
 // --- Update Open Share (PUT /api/me/open-shares/:uuid) ---
 pub async fn update_open_share(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path(open_share_id): Path<Uuid>, // Extract UUID from path
     Json(payload): Json<UpdateOpenSharePayload>,
@@ -374,6 +378,7 @@ pub async fn update_open_share(
 // --- Delete Open Share (Soft) (DELETE /api/me/open-shares/:uuid) ---
 pub async fn delete_open_share(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path(open_share_id): Path<Uuid>, // Extract UUID
 ) -> Result<StatusCode, AppError> {
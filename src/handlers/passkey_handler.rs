@@ -0,0 +1,150 @@
+use axum::{
+    extract::{State, Path, Json},
+    http::{HeaderMap, StatusCode},
+};
+use validator::Validate;
+use crate::{
+    AppState,
+    auth::{
+        jwt::validate_tfa_challenge_token,
+        lockout::{clear_failures, ensure_not_locked, record_failure, AuthAction},
+        passkey,
+        tfa::{begin_tfa_attempt, complete_tfa_challenge, MAX_TFA_ATTEMPTS},
+    },
+    errors::AppError,
+    middleware::auth::AuthenticatedUser,
+    models::{
+        passkey::{
+            FinishPasskeyLoginPayload, FinishPasskeyRegistrationPayload, FinishPasskeyTfaPayload,
+            PasskeyAuthenticationOptions, PasskeyInfo, PasskeyRegistrationOptions, StartPasskeyTfaPayload,
+        },
+        user::AuthResponse,
+    },
+};
+use crate::handlers::auth_handler::session_auth_response;
+
+// --- Manage Passkeys (/api/me/passkeys) ---
+
+pub async fn list_passkeys(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<Json<Vec<PasskeyInfo>>, AppError> {
+    Ok(Json(passkey::list_passkeys(&state.pool, user_id).await?))
+}
+
+// POST /api/me/passkeys/register/start
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<Json<PasskeyRegistrationOptions>, AppError> {
+    let (ceremony_id, options) = passkey::start_registration(&state.pool, &state.webauthn, user_id).await?;
+    Ok(Json(PasskeyRegistrationOptions { ceremony_id, options }))
+}
+
+// POST /api/me/passkeys/register/finish
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Json(payload): Json<FinishPasskeyRegistrationPayload>,
+) -> Result<(StatusCode, Json<PasskeyInfo>), AppError> {
+    payload.validate()?;
+    let credential = payload.credential.unwrap(); // Required by validation
+
+    let info = passkey::finish_registration(
+        &state.pool,
+        &state.webauthn,
+        user_id,
+        payload.ceremony_id.unwrap(), // Required by validation
+        payload.name,
+        &credential,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+pub async fn delete_passkey(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(passkey_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    passkey::delete_passkey(&state.pool, user_id, passkey_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Passwordless Login (/api/auth/passkey/login) ---
+
+// POST /api/auth/passkey/login/start
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+) -> Result<Json<PasskeyAuthenticationOptions>, AppError> {
+    let (ceremony_id, options) = passkey::start_login(&state.pool, &state.webauthn).await?;
+    Ok(Json(PasskeyAuthenticationOptions { ceremony_id, options }))
+}
+
+// POST /api/auth/passkey/login/finish
+// A passkey verifies the user on its own, so no TOTP step follows even with 2FA enabled
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<FinishPasskeyLoginPayload>,
+) -> Result<Json<AuthResponse>, AppError> {
+    payload.validate()?;
+    let credential = payload.credential.unwrap(); // Required by validation
+
+    let user_id = passkey::finish_login(&state.pool, &state.webauthn, payload.ceremony_id.unwrap(), &credential).await?;
+
+    tracing::info!("User {} logged in with a passkey.", user_id);
+    Ok(Json(session_auth_response(&state, user_id, &headers).await?))
+}
+
+// --- Passkey as Second Factor (/api/auth/passkey/verify-tfa) ---
+// Alternative to POST /api/auth/verify-tfa after the password step
+
+// POST /api/auth/passkey/verify-tfa/start
+pub async fn start_passkey_tfa(
+    State(state): State<AppState>,
+    Json(payload): Json<StartPasskeyTfaPayload>,
+) -> Result<Json<PasskeyAuthenticationOptions>, AppError> {
+    payload.validate()?;
+    let claims = validate_tfa_challenge_token(&payload.challenge_token.unwrap(), &state.config)?;
+
+    let (ceremony_id, options) = passkey::start_second_factor(&state.pool, &state.webauthn, claims.sub).await?;
+    Ok(Json(PasskeyAuthenticationOptions { ceremony_id, options }))
+}
+
+// POST /api/auth/passkey/verify-tfa/finish
+// Counts against the challenge's attempts like a wrong TOTP code
+pub async fn finish_passkey_tfa(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<FinishPasskeyTfaPayload>,
+) -> Result<Json<AuthResponse>, AppError> {
+    payload.validate()?;
+    let credential = payload.credential.unwrap(); // Required by validation
+
+    let challenge = begin_tfa_attempt(&state.pool, &state.config, &payload.challenge_token.unwrap()).await?;
+    ensure_not_locked(&state.pool, challenge.user_id, AuthAction::VerifyTfa).await?;
+
+    let verified = passkey::finish_second_factor(
+        &state.pool,
+        &state.webauthn,
+        challenge.user_id,
+        payload.ceremony_id.unwrap(),
+        &credential,
+    )
+    .await?;
+
+    if !verified {
+        record_failure(&state, challenge.user_id, AuthAction::VerifyTfa).await?;
+        if challenge.attempts >= MAX_TFA_ATTEMPTS {
+            return Err(AppError::TfaTooManyAttempts);
+        }
+        return Err(AppError::PasskeyVerificationFailed);
+    }
+    complete_tfa_challenge(&state.pool, challenge.challenge_id).await?;
+    clear_failures(&state.pool, challenge.user_id, AuthAction::VerifyTfa).await?;
+
+    tracing::info!("User {} successfully logged in with a passkey as second factor.", challenge.user_id);
+    Ok(Json(session_auth_response(&state, challenge.user_id, &headers).await?))
+}
@@ -0,0 +1,294 @@
+use axum::{
+    extract::{State, Path, Json},
+    http::StatusCode,
+};
+use sqlx::PgPool;
+use validator::Validate;
+use crate::{
+    AppState,
+    errors::AppError,
+    models::{
+        enums::{EventInvitationStatus, ReminderChannel},
+        reminder::{CreateReminderPayload, Reminder, UpdateReminderPayload},
+    },
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
+};
+
+// Reminders a user may keep on a single event or deadline
+const MAX_REMINDERS_PER_ITEM: i64 = 10;
+
+// What a reminder is attached to
+#[derive(Clone, Copy)]
+enum ReminderTarget {
+    Event(i32),
+    Deadline(i32),
+}
+
+impl ReminderTarget {
+    // (event_id, deadline_id) columns
+    fn ids(self) -> (Option<i32>, Option<i32>) {
+        match self {
+            ReminderTarget::Event(id) => (Some(id), None),
+            ReminderTarget::Deadline(id) => (None, Some(id)),
+        }
+    }
+}
+
+// --- Helper: Check that the user can set reminders on the target ---
+// Events: owned, or invited with the invitation accepted. Deadlines: owned.
+async fn check_target_access(pool: &PgPool, target: ReminderTarget, user_id: i32) -> Result<(), AppError> {
+    match target {
+        ReminderTarget::Event(event_id) => {
+            let exists = sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM events e
+                    WHERE e.event_id = $1 AND e.deleted_at IS NULL
+                      AND ( e.user_id = $2
+                         OR EXISTS (
+                             SELECT 1 FROM event_invitations
+                             WHERE event_id = e.event_id AND invited_user_id = $2 AND status = $3 AND deleted_at IS NULL
+                         ) )
+                )
+                "#,
+                event_id,
+                user_id,
+                EventInvitationStatus::Accepted as EventInvitationStatus
+            )
+            .fetch_one(pool)
+            .await?;
+            if exists.unwrap_or(false) { Ok(()) } else { Err(AppError::EventNotFound) }
+        }
+        ReminderTarget::Deadline(deadline_id) => {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM deadlines WHERE deadline_id = $1 AND user_id = $2 AND deleted_at IS NULL)",
+                deadline_id,
+                user_id
+            )
+            .fetch_one(pool)
+            .await?;
+            if exists.unwrap_or(false) { Ok(()) } else { Err(AppError::DeadlineNotFound) }
+        }
+    }
+}
+
+async fn list_reminders(pool: &PgPool, target: ReminderTarget, user_id: i32) -> Result<Vec<Reminder>, AppError> {
+    check_target_access(pool, target, user_id).await?;
+    let (event_id, deadline_id) = target.ids();
+
+    let reminders = sqlx::query_as!(
+        Reminder,
+        r#"
+        SELECT
+            reminder_id, user_id, event_id, deadline_id, minutes_before, channel as "channel!: _",
+            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM reminders
+        WHERE user_id = $1 AND event_id IS NOT DISTINCT FROM $2 AND deadline_id IS NOT DISTINCT FROM $3
+          AND deleted_at IS NULL
+        ORDER BY minutes_before DESC, reminder_id
+        "#,
+        user_id,
+        event_id,
+        deadline_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reminders)
+}
+
+async fn create_reminder(
+    pool: &PgPool,
+    target: ReminderTarget,
+    user_id: i32,
+    payload: CreateReminderPayload,
+) -> Result<Reminder, AppError> {
+    payload.validate()?;
+    check_target_access(pool, target, user_id).await?;
+    let (event_id, deadline_id) = target.ids();
+
+    let existing = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM reminders
+        WHERE user_id = $1 AND event_id IS NOT DISTINCT FROM $2 AND deadline_id IS NOT DISTINCT FROM $3
+          AND deleted_at IS NULL
+        "#,
+        user_id,
+        event_id,
+        deadline_id
+    )
+    .fetch_one(pool)
+    .await?;
+    if existing >= MAX_REMINDERS_PER_ITEM {
+        let mut err = validator::ValidationError::new("too_many_reminders");
+        err.message = Some(format!("At most {} reminders can be set per item", MAX_REMINDERS_PER_ITEM).into());
+        let mut errors = validator::ValidationErrors::new();
+        errors.add("minutesBefore", err);
+        return Err(AppError::ValidationFailed(errors));
+    }
+
+    let reminder = sqlx::query_as!(
+        Reminder,
+        r#"
+        INSERT INTO reminders (user_id, event_id, deadline_id, minutes_before, channel)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING
+            reminder_id, user_id, event_id, deadline_id, minutes_before, channel as "channel!: _",
+            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        "#,
+        user_id,
+        event_id,
+        deadline_id,
+        payload.minutes_before.unwrap(), // Required by validation
+        payload.channel.unwrap_or_default() as ReminderChannel
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(reminder)
+}
+
+async fn update_reminder(
+    pool: &PgPool,
+    target: ReminderTarget,
+    user_id: i32,
+    reminder_id: i32,
+    payload: UpdateReminderPayload,
+) -> Result<Reminder, AppError> {
+    payload.validate()?;
+    check_target_access(pool, target, user_id).await?;
+    let (event_id, deadline_id) = target.ids();
+
+    // COALESCE keeps the current value for fields left out of the payload
+    sqlx::query_as!(
+        Reminder,
+        r#"
+        UPDATE reminders
+        SET minutes_before = COALESCE($5, minutes_before),
+            channel = COALESCE($6, channel)
+        WHERE reminder_id = $1 AND user_id = $2
+          AND event_id IS NOT DISTINCT FROM $3 AND deadline_id IS NOT DISTINCT FROM $4
+          AND deleted_at IS NULL
+        RETURNING
+            reminder_id, user_id, event_id, deadline_id, minutes_before, channel as "channel!: _",
+            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        "#,
+        reminder_id,
+        user_id,
+        event_id,
+        deadline_id,
+        payload.minutes_before,
+        payload.channel as Option<ReminderChannel>
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(AppError::ReminderNotFound)
+}
+
+async fn delete_reminder(pool: &PgPool, target: ReminderTarget, user_id: i32, reminder_id: i32) -> Result<StatusCode, AppError> {
+    let (event_id, deadline_id) = target.ids();
+
+    let delete_result = sqlx::query!(
+        r#"
+        UPDATE reminders
+        SET deleted_at = NOW() -- Soft delete
+        WHERE reminder_id = $1 AND user_id = $2
+          AND event_id IS NOT DISTINCT FROM $3 AND deadline_id IS NOT DISTINCT FROM $4
+          AND deleted_at IS NULL
+        "#,
+        reminder_id,
+        user_id,
+        event_id,
+        deadline_id
+    )
+    .execute(pool)
+    .await?;
+
+    if delete_result.rows_affected() == 0 {
+        Err(AppError::ReminderNotFound)
+    } else {
+        Ok(StatusCode::NO_CONTENT)
+    }
+}
+
+// --- Event Reminders (/api/me/events/{event_id}/reminders) ---
+
+pub async fn list_event_reminders(
+    State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(event_id): Path<i32>,
+) -> Result<Json<Vec<Reminder>>, AppError> {
+    Ok(Json(list_reminders(&state.pool, ReminderTarget::Event(event_id), user_id).await?))
+}
+
+pub async fn create_event_reminder(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(event_id): Path<i32>,
+    Json(payload): Json<CreateReminderPayload>,
+) -> Result<(StatusCode, Json<Reminder>), AppError> {
+    let reminder = create_reminder(&state.pool, ReminderTarget::Event(event_id), user_id, payload).await?;
+    Ok((StatusCode::CREATED, Json(reminder)))
+}
+
+pub async fn update_event_reminder(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path((event_id, reminder_id)): Path<(i32, i32)>,
+    Json(payload): Json<UpdateReminderPayload>,
+) -> Result<Json<Reminder>, AppError> {
+    Ok(Json(update_reminder(&state.pool, ReminderTarget::Event(event_id), user_id, reminder_id, payload).await?))
+}
+
+pub async fn delete_event_reminder(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path((event_id, reminder_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, AppError> {
+    delete_reminder(&state.pool, ReminderTarget::Event(event_id), user_id, reminder_id).await
+}
+
+// --- Deadline Reminders (/api/me/deadlines/{deadline_id}/reminders) ---
+
+pub async fn list_deadline_reminders(
+    State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(deadline_id): Path<i32>,
+) -> Result<Json<Vec<Reminder>>, AppError> {
+    Ok(Json(list_reminders(&state.pool, ReminderTarget::Deadline(deadline_id), user_id).await?))
+}
+
+pub async fn create_deadline_reminder(
+    State(state): State<AppState>,
+    _: RequireScope<scope::DeadlinesWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(deadline_id): Path<i32>,
+    Json(payload): Json<CreateReminderPayload>,
+) -> Result<(StatusCode, Json<Reminder>), AppError> {
+    let reminder = create_reminder(&state.pool, ReminderTarget::Deadline(deadline_id), user_id, payload).await?;
+    Ok((StatusCode::CREATED, Json(reminder)))
+}
+
+pub async fn update_deadline_reminder(
+    State(state): State<AppState>,
+    _: RequireScope<scope::DeadlinesWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path((deadline_id, reminder_id)): Path<(i32, i32)>,
+    Json(payload): Json<UpdateReminderPayload>,
+) -> Result<Json<Reminder>, AppError> {
+    Ok(Json(update_reminder(&state.pool, ReminderTarget::Deadline(deadline_id), user_id, reminder_id, payload).await?))
+}
+
+pub async fn delete_deadline_reminder(
+    State(state): State<AppState>,
+    _: RequireScope<scope::DeadlinesWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path((deadline_id, reminder_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, AppError> {
+    delete_reminder(&state.pool, ReminderTarget::Deadline(deadline_id), user_id, reminder_id).await
+}
@@ -0,0 +1,107 @@
+use axum::extract::{State, Json};
+use chrono::{Duration, NaiveTime, Weekday};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use validator::Validate;
+use crate::{
+    AppState,
+    errors::AppError,
+    models::scheduling::{SuggestTimesPayload, SuggestTimesResponse, SuggestionAttendee},
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
+};
+use crate::utils::calendar::{parse_timestamp, validate_time_window};
+use crate::utils::freebusy::{load_busy_occurrences, merge_busy_intervals};
+use crate::utils::scheduling::{load_crunch_periods, rank_slots, WorkingSchedule};
+
+const DEFAULT_MIN_OPTIONS: usize = 3;
+
+// Helper to build a ValidationFailed error for one field
+fn invalid_field(field: &'static str, code: &'static str) -> AppError {
+    let mut err = validator::ValidationErrors::new();
+    err.add(field, validator::ValidationError::new(code));
+    AppError::ValidationFailed(err)
+}
+
+// --- Suggest Meeting Times (POST /api/me/events/suggest-times) ---
+// Ranks working-hours slots by how many attendees are busy, then by deadline crunch, then by time.
+// Slots where everyone is free are returned (up to MAX_SUGGESTIONS); partially available ones
+// only fill up the list when fewer than `minOptions` exist. Suggestions never overlap each other.
+pub async fn suggest_meeting_times(
+    State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Json(payload): Json<SuggestTimesPayload>,
+) -> Result<Json<SuggestTimesResponse>, AppError> {
+    let response = suggest_times(&state.pool, user_id, payload).await?;
+    Ok(Json(response))
+}
+
+// --- Helper: The ranking behind suggest_meeting_times, also used by the AI assistant ---
+pub async fn suggest_times(pool: &PgPool, user_id: i32, payload: SuggestTimesPayload) -> Result<SuggestTimesResponse, AppError> {
+    payload.validate()?;
+
+    let from = parse_timestamp(&payload.from.unwrap())?; // Required by validation
+    let to = parse_timestamp(&payload.to.unwrap())?;
+    let window = validate_time_window(from, to)?;
+    let duration_minutes = payload.duration_minutes.unwrap();
+    let min_options = payload.min_options.unwrap_or(DEFAULT_MIN_OPTIONS);
+
+    // Resolve working hours (defaults: Monday to Friday, 09:00-17:00 UTC)
+    let working_hours = payload.working_hours.unwrap_or_default();
+    let time_zone_name = working_hours.time_zone.unwrap_or_else(|| "UTC".to_string());
+    let time_zone: Tz = time_zone_name.parse().map_err(|_| invalid_field("timeZone", "invalid_time_zone"))?;
+    let parse_time = |value: Option<String>, default: &str| {
+        NaiveTime::parse_from_str(value.as_deref().unwrap_or(default), "%H:%M")
+            .map_err(|_| invalid_field("workingHours", "invalid_time"))
+    };
+    let schedule = WorkingSchedule {
+        time_zone,
+        start: parse_time(working_hours.start, "09:00")?,
+        end: parse_time(working_hours.end, "17:00")?,
+        days: working_hours.days.unwrap_or_else(|| vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri]),
+    };
+    if schedule.end <= schedule.start {
+        return Err(invalid_field("workingHours", "end_before_start"));
+    }
+
+    // The caller first, then each distinct attendee
+    let caller_email = sqlx::query_scalar!("SELECT email FROM users WHERE user_id = $1", user_id)
+        .fetch_one(pool)
+        .await?;
+    let mut attendees = vec![SuggestionAttendee { email: caller_email }];
+    let mut attendee_ids = vec![Some(user_id)];
+    for email in payload.attendee_emails.unwrap_or_default() {
+        if attendees.iter().any(|a| a.email.eq_ignore_ascii_case(&email)) {
+            continue;
+        }
+        // Unknown emails attend without busy time, so the endpoint can't be used to probe for accounts
+        let attendee_id = sqlx::query_scalar!(
+            "SELECT user_id FROM users WHERE lower(email) = lower($1) AND deleted_at IS NULL",
+            email
+        )
+        .fetch_optional(pool)
+        .await?;
+        attendees.push(SuggestionAttendee { email });
+        attendee_ids.push(attendee_id);
+    }
+
+    // Owned events and accepted invitations of everyone attending
+    let mut busy = Vec::with_capacity(attendees.len());
+    for attendee_id in &attendee_ids {
+        let intervals = match attendee_id {
+            Some(attendee_id) => {
+                let (_, occurrences) = load_busy_occurrences(pool, *attendee_id, window).await?;
+                merge_busy_intervals(&occurrences, window)
+            }
+            None => Vec::new(),
+        };
+        busy.push(intervals);
+    }
+    let known_ids: Vec<i32> = attendee_ids.iter().flatten().copied().collect();
+    let crunch_periods = load_crunch_periods(pool, &known_ids, window).await?;
+
+    let slots = schedule.candidate_slots(window, Duration::minutes(duration_minutes));
+    let suggestions = rank_slots(slots, &attendees, &busy, &crunch_periods, min_options);
+
+    Ok(SuggestTimesResponse { duration_minutes, time_zone: time_zone_name, attendees, suggestions })
+}
@@ -0,0 +1,71 @@
+use axum::{
+    extract::{State, Path, Json},
+    http::StatusCode,
+};
+use crate::{
+    AppState,
+    auth::session::revoke_user_sessions,
+    errors::AppError,
+    models::session::SessionInfo,
+    middleware::auth::CurrentSession,
+};
+
+// --- List My Sessions (GET /api/me/sessions) ---
+// Active sessions, most recently used first
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    CurrentSession { user_id, session_id }: CurrentSession,
+) -> Result<Json<Vec<SessionInfo>>, AppError> {
+    let sessions = sqlx::query_as!(
+        SessionInfo,
+        r#"
+        SELECT session_id, user_agent, created_at, last_used_at, expires_at, session_id = $2 as "current!"
+        FROM sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_used_at DESC
+        "#,
+        user_id,
+        session_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(sessions))
+}
+
+// --- Revoke Session (DELETE /api/me/sessions/{session_id}) ---
+// Signs out one device. Revoking the current session works like logging out.
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    CurrentSession { user_id, .. }: CurrentSession,
+    Path(session_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    let revoke_result = sqlx::query!(
+        r#"
+        UPDATE sessions
+        SET revoked_at = NOW()
+        WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if revoke_result.rows_affected() == 0 {
+        Err(AppError::SessionNotFound)
+    } else {
+        Ok(StatusCode::NO_CONTENT)
+    }
+}
+
+// --- Revoke Other Sessions (DELETE /api/me/sessions) ---
+// Signs out every device except the one making the request
+pub async fn revoke_other_sessions(
+    State(state): State<AppState>,
+    CurrentSession { user_id, session_id }: CurrentSession,
+) -> Result<StatusCode, AppError> {
+    let revoked = revoke_user_sessions(&state.pool, user_id, Some(session_id)).await?;
+    tracing::info!("User {} revoked {} other session(s)", user_id, revoked);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -9,13 +9,13 @@ use crate::{
     errors::AppError,
     models::{
         calendar_share::{
-            CalendarShare, CreateSharePayload, UpdateSharePayload,
-            ShareDetailsResponse, ListSharesResponseItem, SharedWithUserDetail // Import response structs
+            CalendarShare, CreateSharePayload, UpdateSharePayload, PendingCalendarShare,
+            ShareDetailsResponse, ShareDetailsOrPending, ListSharesResponseItem // Import response structs
         },
         enums::SharePrivacyLevel,
-        user::{User, BasicUserInfo}, // Need to look up shared_with user by email
+        user::BasicUserInfo,
     },
-    middleware::auth::AuthenticatedUser,
+    middleware::auth::{scope, AuthenticatedUser, RequireScope},
 };
 use chrono::DateTime; // For parsing date strings
 
@@ -46,13 +46,82 @@ async fn check_share_ownership(pool: &PgPool, share_id: i32, owner_user_id: i32)
     Ok(exists.unwrap_or(false))
 }
 
+// --- Helper: Share with an email address that has no account yet ---
+// Becomes a regular share once someone registers and verifies the address (see verify_email_handler)
+async fn create_pending_share(
+    state: &AppState,
+    owner_user_id: i32,
+    shared_with_email: &str,
+    category_ids: &[i32],
+    message: Option<String>,
+    privacy_level: SharePrivacyLevel,
+    expires_at: Option<DateTime<Utc>>,
+) -> Result<PendingCalendarShare, AppError> {
+    let shared_with_email = shared_with_email.to_lowercase();
+
+    let share_exists: bool = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM pending_calendar_shares WHERE owner_user_id = $1 AND shared_with_email = $2 AND deleted_at IS NULL)",
+        owner_user_id,
+        shared_with_email
+    )
+    .fetch_one(&state.pool)
+    .await?
+    .unwrap_or(false);
+
+    if share_exists {
+        return Err(AppError::ShareAlreadyExists); // Same as for users
+    }
+
+    validate_category_ids(&state.pool, owner_user_id, category_ids).await?;
+
+    let created_share = sqlx::query_as!(
+        PendingCalendarShare,
+        r#"
+        INSERT INTO pending_calendar_shares (owner_user_id, shared_with_email, category_ids, message, privacy_level, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING pending_share_id, owner_user_id, shared_with_email, category_ids, message as "message!: _",
+        privacy_level as "privacy_level!: _", expires_at as "expires_at!: _",
+        created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        "#,
+        owner_user_id,
+        shared_with_email,
+        category_ids,
+        message,
+        privacy_level as SharePrivacyLevel,
+        expires_at,
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    // Let them know in the background; the share stands even if sending fails
+    let email_state = state.clone();
+    let pending_share_id = created_share.pending_share_id;
+    let message = created_share.message.clone();
+    tokio::spawn(async move {
+        let result = async {
+            let owner_name = sqlx::query_scalar!("SELECT display_name FROM users WHERE user_id = $1", owner_user_id)
+                .fetch_one(&email_state.pool)
+                .await?;
+            email_state.email_service.send_pending_share_email(&shared_with_email, &owner_name, message.as_deref()).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!("Failed to send share email for pending share {}: {:?}", pending_share_id, e);
+        }
+    });
+
+    Ok(created_share)
+}
+
 
 // --- Create Share (POST /api/me/shares) ---
+// Addresses without an account get a pending share instead
 pub async fn create_share(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Json(payload): Json<CreateSharePayload>,
-) -> Result<(StatusCode, Json<ShareDetailsResponse>), AppError> { // Return ShareDetailsResponse
+) -> Result<(StatusCode, Json<ShareDetailsOrPending>), AppError> {
     payload.validate()?;
 
     let shared_with_user_email = payload.shared_with_user_email.unwrap(); // Required
@@ -76,7 +145,7 @@ pub async fn create_share(
             user_id, display_name, email, email_verified as "email_verified!: _",
             created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM users
-        WHERE email = $1
+        WHERE lower(email) = lower($1)
         "#,
         shared_with_user_email
     )
@@ -86,8 +155,11 @@ pub async fn create_share(
     let shared_with_user = match shared_with_user {
         Some(user) => user,
         None => {
-            // Consider a specific error like AppError::SharedWithUserNotFound
-            return Err(AppError::UserNotFound); // Re-using UserNotFound
+            // No account yet: the share waits until someone registers and verifies this address
+            let pending = create_pending_share(
+                &state, owner_user_id, &shared_with_user_email, &category_ids, message, privacy_level, expires_at,
+            ).await?;
+            return Ok((StatusCode::CREATED, Json(ShareDetailsOrPending::Pending(pending))));
         }
     };
 
@@ -108,8 +180,7 @@ pub async fn create_share(
     .unwrap_or(false);
 
     if share_exists {
-        // Consider a specific error like AppError::ShareAlreadyExists
-        return Err(AppError::InternalServerError("A share already exists with this user".to_string()));
+        return Err(AppError::ShareAlreadyExists);
     }
 
     // 3. Validate that the provided category IDs exist and belong to the owner
@@ -181,7 +252,7 @@ pub async fn create_share(
             ARRAY_AGG(csc.category_id) FILTER (WHERE csc.category_id IS NOT NULL) AS "shared_category_ids!: Vec<i32>" -- Explicit cast for Vec
         FROM calendar_shares cs
         JOIN users u ON cs.shared_with_user_id = u.user_id
-        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id
+        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
         WHERE cs.share_id = $1 -- Fetch the specific created share
         GROUP BY cs.share_id, u.user_id -- Group required for array_agg
         "#,
@@ -191,7 +262,7 @@ pub async fn create_share(
     .await?;
 
 
-    Ok((StatusCode::CREATED, Json(response_share)))
+    Ok((StatusCode::CREATED, Json(ShareDetailsOrPending::Share(response_share))))
 }
 
 
@@ -199,6 +270,7 @@ pub async fn create_share(
 // Returns a list of shares created by the authenticated user, including shared_with user and categories
 pub async fn list_shares(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
 ) -> Result<Json<Vec<ListSharesResponseItem>>, AppError> { // Return ListSharesResponseItem
 
@@ -223,7 +295,7 @@ pub async fn list_shares(
             ARRAY_AGG(csc.category_id) FILTER (WHERE csc.category_id IS NOT NULL) AS "shared_category_ids!: Vec<i32>" -- Explicit cast for Vec
         FROM calendar_shares cs
         JOIN users u ON cs.shared_with_user_id = u.user_id
-        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id
+        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
         WHERE cs.owner_user_id = $1 -- Filter by the owner user
         GROUP BY cs.share_id, u.user_id -- Group required for array_agg
         ORDER BY cs.created_at DESC -- Optional: order by creation date
@@ -240,6 +312,7 @@ pub async fn list_shares(
 // Returns details for a specific share owned by the user
 pub async fn get_share_by_id(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path(share_id): Path<i32>,
 ) -> Result<Json<ShareDetailsResponse>, AppError> { // Return ShareDetailsResponse
@@ -266,7 +339,7 @@ pub async fn get_share_by_id(
             ARRAY_AGG(csc.category_id) FILTER (WHERE csc.category_id IS NOT NULL) AS "shared_category_ids!: Vec<i32>" -- Explicit cast for Vec
         FROM calendar_shares cs
         JOIN users u ON cs.shared_with_user_id = u.user_id
-        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id
+        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
         WHERE cs.share_id = $1 AND cs.owner_user_id = $2 -- IMPORTANT: Filter by ID AND owner
         GROUP BY cs.share_id, u.user_id -- Group required for array_agg
         "#,
@@ -286,6 +359,7 @@ pub async fn get_share_by_id(
 // --- Update Share (PUT /api/me/shares/:share_id) ---
 pub async fn update_share(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path(share_id): Path<i32>,
     Json(payload): Json<UpdateSharePayload>,
@@ -349,17 +423,24 @@ pub async fn update_share(
         // if validation queries needed to acquire locks)
         validate_category_ids(&state.pool, owner_user_id, &category_ids).await?; // Use &state.pool
 
-        // Delete existing categories for this share *within the transaction*
-        sqlx::query!("UPDATE calendar_share_categories SET deleted_at = NOW() WHERE share_id = $1", share_id)
+        // Soft-delete categories no longer in the set *within the transaction*
+        // (kept as tombstones so the sharee's sync learns which categories were removed)
+        sqlx::query!(
+            "UPDATE calendar_share_categories SET deleted_at = NOW() WHERE share_id = $1 AND deleted_at IS NULL AND category_id <> ALL($2)",
+            share_id,
+            &category_ids
+        )
             .execute(&mut *tx)
             .await?;
 
-        // Insert the new set of category IDs *within the transaction*
+        // Insert the new set of category IDs *within the transaction* (reviving previously removed ones)
         for cat_id in &category_ids {
             sqlx::query!(
                 r#"
                 INSERT INTO calendar_share_categories (share_id, category_id)
                 VALUES ($1, $2)
+                ON CONFLICT (share_id, category_id) DO UPDATE SET deleted_at = NULL
+                WHERE calendar_share_categories.deleted_at IS NOT NULL
                 "#,
                 share_id,
                 cat_id,
@@ -414,7 +495,7 @@ pub async fn update_share(
             ARRAY_AGG(csc.category_id) FILTER (WHERE csc.category_id IS NOT NULL) AS "shared_category_ids!: Vec<i32>" -- Use FILTER for empty array
         FROM calendar_shares cs
         JOIN users u ON cs.shared_with_user_id = u.user_id
-        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id
+        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
         WHERE cs.share_id = $1 AND cs.owner_user_id = $2 -- Fetch the specific updated share
         GROUP BY cs.share_id, u.user_id
         "#,
@@ -432,6 +513,7 @@ pub async fn update_share(
 // --- Delete Share (DELETE /api/me/shares/:share_id) ---
 pub async fn delete_share(
     State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
     AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
     Path(share_id): Path<i32>,
 ) -> Result<StatusCode, AppError> {
@@ -456,4 +538,57 @@ pub async fn delete_share(
         // Return 204 No Content on successful deletion
         Ok(StatusCode::NO_CONTENT)
     }
+}
+
+
+// --- List Pending Shares (GET /api/me/shares/pending) ---
+// Shares with addresses that have no account yet
+pub async fn list_pending_shares(
+    State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
+    AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
+) -> Result<Json<Vec<PendingCalendarShare>>, AppError> {
+    let shares = sqlx::query_as!(
+        PendingCalendarShare,
+        r#"
+        SELECT pending_share_id, owner_user_id, shared_with_email, category_ids, message as "message!: _",
+        privacy_level as "privacy_level!: _", expires_at as "expires_at!: _",
+        created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM pending_calendar_shares
+        WHERE owner_user_id = $1 AND deleted_at IS NULL
+        ORDER BY created_at
+        "#,
+        owner_user_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(shares))
+}
+
+
+// --- Delete Pending Share (DELETE /api/me/shares/pending/:pending_share_id) ---
+pub async fn delete_pending_share(
+    State(state): State<AppState>,
+    _: RequireScope<scope::SharesAdmin>,
+    AuthenticatedUser { user_id: owner_user_id }: AuthenticatedUser,
+    Path(pending_share_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    let delete_result = sqlx::query!(
+        r#"
+        UPDATE pending_calendar_shares
+        SET deleted_at = NOW() -- Soft delete
+        WHERE pending_share_id = $1 AND owner_user_id = $2 AND deleted_at IS NULL
+        "#,
+        pending_share_id,
+        owner_user_id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if delete_result.rows_affected() == 0 {
+        Err(AppError::ShareNotFound)
+    } else {
+        Ok(StatusCode::NO_CONTENT)
+    }
 }
\ No newline at end of file
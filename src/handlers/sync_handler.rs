@@ -1,28 +1,109 @@
 // src/handlers/sync_handler.rs
 use axum::{
     extract::{Path, Query, State},
+    http::HeaderMap,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
     Json,
 };
-use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use sqlx::{Acquire, PgConnection, PgPool, Postgres, Transaction};
+use validator::Validate;
 use crate::{
-    errors::AppError, middleware::auth::AuthenticatedUser, models::{
-        calendar::{SharedCalendarDeadline, SharedCalendarEvent}, calendar_share::{CalendarShare, ListSharesResponseItem}, category::Category, deadline::Deadline, enums::*, event::Event, event_invitation::{EventInvitation, MyInvitationResponseItem}, sync::{SyncResponse, SyncSharedCalendarResponse, SyncSinceParams} // Import all enums
+    errors::AppError, middleware::auth::{scope, AuthenticatedUser, RequireScope}, models::{
+        calendar::{SharedCalendarDeadline, SharedCalendarEvent}, calendar_share::{CalendarShare, ListSharesResponseItem}, category::Category, deadline::Deadline, enums::*, event::Event, event_exception::EventException, event_invitation::EventInvitation, category::{CreateCategoryPayload, UpdateCategoryPayload}, deadline::{CreateDeadlinePayload, UpdateDeadlinePayload}, event::{CreateEventPayload, UpdateEventPayload}, sync::{ClientSyncPayload, ClientSyncResponse, DeletedItems, ShareCategoryRef, SyncChange, SyncEntityKind, SyncItemResult, SyncItemStatus, SyncOperation, SyncOperationKind, SyncRecord, SyncResponse, SharedCalendarDeletedItems, SyncSharedCalendarResponse, SyncSinceParams} // Import all enums
     }, AppState
 };
-use chrono::{DateTime, Utc, TimeZone}; // Import Utc, TimeZone
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tokio_stream::{Stream, StreamExt, wrappers::{BroadcastStream, errors::BroadcastStreamRecvError}};
 
-use crate::utils::calendar::parse_optional_timestamp; // Utility function for parsing timestamps
+use crate::handlers::category_handler::{apply_category_update, insert_category, soft_delete_category};
+use crate::handlers::deadline_handler::{apply_deadline_update, insert_deadline, soft_delete_deadline};
+use crate::handlers::event_handler::{apply_event_update, insert_event, validate_event_update};
+use crate::sync_feed::SyncFeedMessage;
+use crate::utils::sync_token::{encode_sync_token, invalid_sync_token, parse_optional_sync_token};
 
 
 // --- GET /api/me/sync handler ---
 pub async fn sync_owned_data(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id: authenticated_user_id }: AuthenticatedUser,
     Query(params): Query<SyncSinceParams>, // Extract 'since' query param
 ) -> Result<Json<SyncResponse>, AppError> {
 
-    let since_timestamp = parse_optional_timestamp(params.since)?;
-    let now = Utc::now(); // Timestamp for this sync operation
+    let since = parse_optional_sync_token(params.since)?;
+    let response = build_sync_response(&state.pool, authenticated_user_id, since).await?;
+
+    Ok(Json(response))
+}
+
+// --- Helper: Owned data changed since a change-log position (shared by GET /api/me/sync and POST /api/sync) ---
+// Runs in one REPEATABLE READ snapshot so the returned token matches exactly the data returned.
+async fn build_sync_response(
+    pool: &PgPool,
+    authenticated_user_id: i32,
+    since: Option<i64>,
+) -> Result<SyncResponse, AppError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+
+    // Position of the latest committed change; everything up to it is visible in this snapshot
+    let sync_position = sqlx::query_scalar!(
+        "SELECT last_change_id FROM sync_sequences WHERE user_id = $1",
+        authenticated_user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .unwrap_or(0);
+
+    // --- Collect changed IDs and tombstones from the change log (incremental sync only) ---
+    // None = full sync: every current item, no tombstones
+    let mut changed = since.map(|_| ChangedIds::default());
+    let mut deleted = DeletedItems::default();
+    if let Some(since) = since {
+        let entries = sqlx::query!(
+            r#"
+            SELECT entity, entity_id, deleted
+            FROM sync_change_log
+            WHERE user_id = $1 AND change_id > $2
+            "#,
+            authenticated_user_id,
+            since
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let changed = changed.get_or_insert_with(ChangedIds::default);
+        for entry in entries {
+            if entry.entity == "shareCategory" {
+                // Only removals matter; additions show up in the share's sharedCategoryIds
+                if let Some((share_id, category_id)) = parse_share_category_id(&entry.entity_id).filter(|_| entry.deleted) {
+                    deleted.share_categories.push(ShareCategoryRef { share_id, category_id });
+                }
+                continue;
+            }
+
+            let Ok(id) = entry.entity_id.parse::<i32>() else { continue }; // openShare (UUID) isn't part of this response
+            let (changed_ids, deleted_ids) = match entry.entity.as_str() {
+                "category" => (&mut changed.categories, &mut deleted.category_ids),
+                "deadline" => (&mut changed.deadlines, &mut deleted.deadline_ids),
+                "event" => (&mut changed.events, &mut deleted.event_ids),
+                "eventException" => (&mut changed.event_exceptions, &mut deleted.event_exception_ids),
+                "invitation" => (&mut changed.invitations, &mut deleted.invitation_ids),
+                "share" => (&mut changed.shares, &mut deleted.share_ids),
+                _ => continue,
+            };
+            if entry.deleted {
+                deleted_ids.push(id);
+            } else {
+                changed_ids.push(id);
+            }
+        }
+    }
+    let changed = changed.as_ref();
 
     // --- Fetch Categories ---
     let categories = sqlx::query_as!(
@@ -31,12 +112,13 @@ pub async fn sync_owned_data(
         SELECT category_id, user_id, name, color, is_visible as "is_visible!: _",
         created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM categories
-        WHERE user_id = $1 AND ( ($2::TIMESTAMPTZ IS NULL) OR (updated_at > $2) )
+        WHERE user_id = $1 AND deleted_at IS NULL
+          AND ( ($2::INTEGER[] IS NULL) OR (category_id = ANY($2)) )
         "#,
         authenticated_user_id,
-        since_timestamp, // Bind Option<DateTime<Utc>>
+        changed.map(|c| c.categories.as_slice()) as Option<&[i32]>, // NULL = full sync
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
     // --- Fetch Deadlines ---
@@ -50,67 +132,92 @@ pub async fn sync_owned_data(
            workload_magnitude as "workload_magnitude!: _", workload_unit as "workload_unit!: _",
            created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM deadlines
-        WHERE user_id = $1 AND ( ($2::TIMESTAMPTZ IS NULL) OR (updated_at > $2) )
+        WHERE user_id = $1 AND deleted_at IS NULL
+          AND ( ($2::INTEGER[] IS NULL) OR (deadline_id = ANY($2)) )
         "#,
         authenticated_user_id,
-        since_timestamp,
+        changed.map(|c| c.deadlines.as_slice()) as Option<&[i32]>,
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
-    // --- Fetch Events (Owned OR Accepted Invite, Updated Since) ---
-    // This needs to fetch the base event record if it or its relevant invitation status changed.
+    // --- Fetch Events (Owned OR Accepted Invite) ---
+    // Accepting an invitation logs the event for the invitee, so it shows up here as changed
     let events = sqlx::query_as!(
         Event,
         r#"
-        WITH RelevantEvents AS (
-            -- Owned Events Updated Since
-            SELECT event_id FROM events
-            WHERE user_id = $1 AND ( ($2::TIMESTAMPTZ IS NULL) OR (updated_at > $2) )
-            UNION
-            -- Events Where I am Accepted Invitee AND Invitation Status Changed Since
-            SELECT event_id FROM event_invitations
-            WHERE invited_user_id = $1 AND status = $3 AND ( ($2::TIMESTAMPTZ IS NULL) OR (updated_at > $2) )
-            UNION
-            -- Events Where I am Accepted Invitee AND Event Itself Changed Since
-            SELECT ei.event_id FROM event_invitations ei
-            JOIN events e ON ei.event_id = e.event_id
-            WHERE ei.invited_user_id = $1 AND ei.status = $3 AND ( ($2::TIMESTAMPTZ IS NULL) OR (e.updated_at > $2) )
-        )
         SELECT
-           e.event_id, e.user_id, e.category_id, e.title, e.description as "description!: _",
-           e.start_time as "start_time!", e.end_time as "end_time!",
-           e.location as "location!: _", e.rrule as "rrule!: _",
-           e.created_at as "created_at!", e.updated_at as "updated_at!", e.deleted_at as "deleted_at!: _"
-        FROM events e
-        JOIN RelevantEvents re ON e.event_id = re.event_id
-        ORDER BY e.start_time
+           event_id, user_id, category_id, title, description as "description!: _",
+           start_time, end_time, location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE ( user_id = $1
+           OR event_id IN (
+               SELECT event_id FROM event_invitations
+               WHERE invited_user_id = $1 AND status = $3 AND deleted_at IS NULL
+           ) )
+          AND deleted_at IS NULL
+          AND ( ($2::INTEGER[] IS NULL) OR (event_id = ANY($2)) )
         "#,
         authenticated_user_id, // $1
-        since_timestamp,       // $2
+        changed.map(|c| c.events.as_slice()) as Option<&[i32]>, // $2
         EventInvitationStatus::Accepted as EventInvitationStatus // $3
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
 
-    // --- Fetch Received Invitations (Updates since 'since') ---
+    // --- Fetch Event Exceptions (Changed, or belonging to an event returned above) ---
+    // Events that just became relevant (e.g. invite accepted) need their full set of exceptions.
+    let synced_event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let event_exceptions = sqlx::query_as!(
+        EventException,
+        r#"
+        SELECT
+            ex.exception_id, ex.event_id, ex.original_occurrence_time, ex.is_deleted,
+            ex.title as "title!: _", ex.description as "description!: _",
+            ex.start_time as "start_time!: _", ex.end_time as "end_time!: _", ex.location as "location!: _",
+            ex.created_at as "created_at!", ex.updated_at as "updated_at!"
+        FROM event_exceptions ex
+        JOIN events e ON ex.event_id = e.event_id
+        WHERE (
+            e.user_id = $1
+            OR e.event_id IN (
+                SELECT event_id FROM event_invitations
+                WHERE invited_user_id = $1 AND status = $3 AND deleted_at IS NULL
+            )
+        )
+        AND e.deleted_at IS NULL
+        AND ( ($2::INTEGER[] IS NULL) OR (ex.exception_id = ANY($2)) OR (ex.event_id = ANY($4)) )
+        ORDER BY ex.event_id, ex.original_occurrence_time
+        "#,
+        authenticated_user_id, // $1
+        changed.map(|c| c.event_exceptions.as_slice()) as Option<&[i32]>, // $2
+        EventInvitationStatus::Accepted as EventInvitationStatus, // $3
+        &synced_event_ids      // $4
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+
+    // --- Fetch Received Invitations ---
     let received_invitations = sqlx::query_as!(
         EventInvitation,
         r#"
         SELECT invitation_id, event_id, owner_user_id, invited_user_id, status as "status!: _",
         created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
         FROM event_invitations
-        WHERE invited_user_id = $1 AND ( ($2::TIMESTAMPTZ IS NULL) OR (updated_at > $2) )
+        WHERE invited_user_id = $1 AND deleted_at IS NULL
+          AND ( ($2::INTEGER[] IS NULL) OR (invitation_id = ANY($2)) )
         "#,
         authenticated_user_id,
-        since_timestamp,
+        changed.map(|c| c.invitations.as_slice()) as Option<&[i32]>,
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
 
-    // --- Fetch Shares Created By Me (Updates since 'since') ---
+    // --- Fetch Shares Created By Me ---
      let shares_created = sqlx::query_as!(
         ListSharesResponseItem, // Use the detailed response struct
         r#"
@@ -122,19 +229,20 @@ pub async fn sync_owned_data(
             ARRAY_AGG(csc.category_id) FILTER (WHERE csc.category_id IS NOT NULL) AS "shared_category_ids!: Vec<i32>"
         FROM calendar_shares cs
         JOIN users u ON cs.shared_with_user_id = u.user_id
-        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id
-        WHERE cs.owner_user_id = $1 AND ( ($2::TIMESTAMPTZ IS NULL) OR (cs.updated_at > $2) )
+        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
+        WHERE cs.owner_user_id = $1 AND cs.deleted_at IS NULL
+          AND ( ($2::INTEGER[] IS NULL) OR (cs.share_id = ANY($2)) )
         GROUP BY cs.share_id, u.user_id
         ORDER BY cs.created_at DESC
         "#,
         authenticated_user_id, // $1
-        since_timestamp // $2
+        changed.map(|c| c.shares.as_slice()) as Option<&[i32]> // $2
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
 
-    // --- Fetch Shares Received By Me (Updates since 'since') ---
+    // --- Fetch Shares Received By Me ---
      let shares_received = sqlx::query_as!(
         ListSharesResponseItem, // Use the detailed response struct
         r#"
@@ -146,42 +254,72 @@ pub async fn sync_owned_data(
             ARRAY_AGG(csc.category_id) FILTER (WHERE csc.category_id IS NOT NULL) AS "shared_category_ids!: Vec<i32>"
         FROM calendar_shares cs
         JOIN users u ON cs.owner_user_id = u.user_id -- Join with OWNER this time
-        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id
-        WHERE cs.shared_with_user_id = $1 AND ( ($2::TIMESTAMPTZ IS NULL) OR (cs.updated_at > $2) )
+        LEFT JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
+        WHERE cs.shared_with_user_id = $1 AND cs.deleted_at IS NULL
+          AND ( ($2::INTEGER[] IS NULL) OR (cs.share_id = ANY($2)) )
         GROUP BY cs.share_id, u.user_id
         ORDER BY cs.created_at DESC
         "#,
         authenticated_user_id, // $1
-        since_timestamp // $2
+        changed.map(|c| c.shares.as_slice()) as Option<&[i32]> // $2
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
+    tx.commit().await?; // Read-only; just ends the snapshot
+
     // --- Combine into Response ---
     let response = SyncResponse {
         categories,
         deadlines,
         events,
+        event_exceptions,
         received_invitations,
         shares_created,
         shares_received,
-        sync_timestamp: now,
+        deleted,
+        sync_token: encode_sync_token(sync_position),
     };
 
-    Ok(Json(response))
+    Ok(response)
+}
+
+// IDs of items (re)appearing in a user's sync data since a change-log position
+#[derive(Default)]
+struct ChangedIds {
+    categories: Vec<i32>,
+    deadlines: Vec<i32>,
+    events: Vec<i32>,
+    event_exceptions: Vec<i32>,
+    invitations: Vec<i32>,
+    shares: Vec<i32>,
+}
+
+// Share categories are logged as "<share_id>:<category_id>"
+fn parse_share_category_id(entity_id: &str) -> Option<(i32, i32)> {
+    let (share_id, category_id) = entity_id.split_once(':')?;
+    Some((share_id.parse().ok()?, category_id.parse().ok()?))
 }
 
 // --- GET /api/sync/calendar/shares/:share_id handler ---
-// Fetches UPDATES for a specific shared calendar view since a given timestamp
+// Fetches UPDATES for a specific shared calendar view since a sync token.
+// Everything a share shows belongs to its owner, so tokens are positions in the owner's change log
+// and work exactly like those of GET /api/sync/me.
 pub async fn sync_shared_calendar_data(
     State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
     AuthenticatedUser { user_id: authenticated_user_id }: AuthenticatedUser, // The sharee
     Path(share_id): Path<i32>, // The ID of the specific share instance
     Query(params): Query<SyncSinceParams>, // Extract 'since' query param
 ) -> Result<Json<SyncSharedCalendarResponse>, AppError> {
 
-    let since_timestamp = parse_optional_timestamp(params.since)?;
-    let now = Utc::now();
+    let since = parse_optional_sync_token(params.since)?;
+
+    // One REPEATABLE READ snapshot, so the returned token matches exactly the data returned
+    let mut tx = state.pool.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
 
     // 1. Fetch the CURRENT share details (even if deleted) to check access and get info
     // We fetch regardless of deleted_at here to know if access *was* possible.
@@ -197,7 +335,7 @@ pub async fn sync_shared_calendar_data(
         share_id,
         authenticated_user_id
     )
-    .fetch_optional(&state.pool)
+    .fetch_optional(&mut *tx)
     .await?;
 
     // If share doesn't exist or isn't for this user, return empty sync result (or 404?)
@@ -209,76 +347,93 @@ pub async fn sync_shared_calendar_data(
                 share_info: None, // Indicate share not found/accessible
                 events: vec![],
                 deadlines: vec![],
-                sync_timestamp: now,
+                deleted: SharedCalendarDeletedItems::default(),
+                sync_token: encode_sync_token(since.unwrap_or(0)),
             }));
         }
     };
 
-    // Check if expired BEFORE the 'since' timestamp (if since exists). If so, no updates needed.
-    // If it expired AFTER 'since', we still need to fetch the share_info update (deleted_at).
-    if let Some(expires_at) = share.expires_at {
-         if let Some(since) = since_timestamp {
-              if expires_at <= since {
-                   // Expired before last sync, check if share itself was updated (e.g., deleted)
-                    return sync_just_share_update(state, share_id, authenticated_user_id, since_timestamp, now).await;
-              }
-         } else {
-              // No 'since' timestamp, check if expired now
-              if Utc::now() > expires_at {
-                   // Expired now, check if share itself was updated recently
-                   return sync_just_share_update(state, share_id, authenticated_user_id, since_timestamp, now).await;
-              }
-         }
+    let owner_user_id = share.owner_user_id;
+    let privacy_level = share.privacy_level;
+
+    // Position of the owner's latest committed change; everything up to it is visible in this snapshot
+    let sync_position = sqlx::query_scalar!(
+        "SELECT last_change_id FROM sync_sequences WHERE user_id = $1",
+        owner_user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .unwrap_or(0);
+    if since.is_some_and(|since| since > sync_position) {
+        return Err(invalid_sync_token()); // Issued for another share or user
     }
 
-     // If the share was deleted AFTER the 'since' timestamp, we only need to return the share_info
-     if let Some(deleted_at) = share.deleted_at {
-          if let Some(since) = since_timestamp {
-               if deleted_at > since {
-                    return Ok(Json(SyncSharedCalendarResponse {
-                         share_info: Some(share), // Return share with deleted_at set
-                         events: vec![],
-                         deadlines: vec![],
-                         sync_timestamp: now,
-                    }));
-               } else {
-                    // Deleted before last sync, return empty
-                     return Ok(Json(SyncSharedCalendarResponse {
-                         share_info: None, // Indicate no longer accessible
-                         events: vec![],
-                         deadlines: vec![],
-                         sync_timestamp: now,
-                     }));
-               }
-          } else {
-               // No 'since', but share is deleted, return empty
-                return Ok(Json(SyncSharedCalendarResponse {
-                     share_info: None, // Indicate no longer accessible
-                     events: vec![],
-                     deadlines: vec![],
-                     sync_timestamp: now,
-                 }));
-          }
-     }
+    // --- Collect the owner's changes since the token (incremental sync only) ---
+    let mut changes = SharedCalendarChanges::default();
+    if let Some(since) = since {
+        let entries = sqlx::query!(
+            r#"
+            SELECT entity, entity_id, deleted
+            FROM sync_change_log
+            WHERE user_id = $1 AND change_id > $2 AND entity IN ('event', 'deadline', 'share', 'shareCategory')
+            "#,
+            owner_user_id,
+            since
+        )
+        .fetch_all(&mut *tx)
+        .await?;
 
-    // --- If share is active and accessible ---
+        for entry in entries {
+            match entry.entity.as_str() {
+                "share" => changes.share |= entry.entity_id == share_id.to_string(),
+                "shareCategory" => {
+                    let Some((_, category_id)) = parse_share_category_id(&entry.entity_id).filter(|(id, _)| *id == share_id) else { continue };
+                    if entry.deleted {
+                        changes.removed_categories.push(category_id);
+                    } else {
+                        changes.added_categories.push(category_id);
+                    }
+                }
+                "event" => changes.events.extend(entry.entity_id.parse::<i32>().ok()),
+                "deadline" => changes.deadlines.extend(entry.entity_id.parse::<i32>().ok()),
+                _ => {}
+            }
+        }
+    }
 
-    let owner_user_id = share.owner_user_id;
-    let privacy_level = share.privacy_level;
+    // A revoked or expired share shows nothing. Its info is sent when the revocation is news to the client,
+    // and always once expired (expiry isn't a logged change).
+    let expired = share.expires_at.is_some_and(|expires_at| Utc::now() > expires_at);
+    if share.deleted_at.is_some() || expired {
+        tx.commit().await?; // Read-only; just ends the snapshot
+        let share_info = (expired || (since.is_some() && changes.share)).then_some(share);
+        return Ok(Json(SyncSharedCalendarResponse {
+            share_info,
+            events: vec![],
+            deadlines: vec![],
+            deleted: SharedCalendarDeletedItems::default(),
+            sync_token: encode_sync_token(sync_position),
+        }));
+    }
+
+    // --- If share is active and accessible ---
 
     // 2. Get the list of categories currently included in this share (needed for filtering)
-    // No need to check 'since' for categories, just get the current list for filtering items.
     let shared_category_ids: Vec<i32> = sqlx::query_scalar!(
-        "SELECT category_id FROM calendar_share_categories WHERE share_id = $1",
+        "SELECT category_id FROM calendar_share_categories WHERE share_id = $1 AND deleted_at IS NULL",
         share_id
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
+    // Which items to send: all of them on a full sync or when the share itself changed (e.g. its privacy level),
+    // otherwise the changed ones plus everything in newly added categories. NULL = all.
+    let resend_all = since.is_none() || changes.share;
+    let changed_events = (!resend_all).then_some(changes.events.as_slice());
+    let changed_deadlines = (!resend_all).then_some(changes.deadlines.as_slice());
 
     // 3. Fetch Events (owned by sharer in shared categories OR sharer is accepted invitee)
-    //    AND updated since 'since'
-    let events_query = sqlx::query_as!(
+    let events = sqlx::query_as!(
         Event,
         r#"
         SELECT
@@ -288,7 +443,8 @@ pub async fn sync_shared_calendar_data(
            e.created_at as "created_at!", e.updated_at as "updated_at!", e.deleted_at as "deleted_at!: _"
         FROM events e
         WHERE
-           ( ($3::TIMESTAMPTZ IS NULL) OR (e.updated_at > $3) ) -- Filter by event update time
+           e.deleted_at IS NULL
+           AND ( ($3::INTEGER[] IS NULL) OR (e.event_id = ANY($3)) OR (e.user_id = $1 AND e.category_id = ANY($5)) )
            AND
            (
                ( -- Case 1: Events owned by the sharer included in the share
@@ -301,8 +457,7 @@ pub async fn sync_shared_calendar_data(
                    AND e.event_id IN (
                        SELECT event_id
                        FROM event_invitations
-                       WHERE invited_user_id = $1 AND status = $4 -- Sharer is accepted invitee
-                       -- No need to check invitation updated_at here, only event updated_at matters for showing the event
+                       WHERE invited_user_id = $1 AND status = $4 AND deleted_at IS NULL -- Sharer is accepted invitee
                    )
                )
            )
@@ -310,15 +465,16 @@ pub async fn sync_shared_calendar_data(
         "#,
         owner_user_id, // $1
         &shared_category_ids, // $2
-        since_timestamp, // $3 - Bind Option<DateTime<Utc>>
-        EventInvitationStatus::Accepted as EventInvitationStatus // $4
-    );
-
-    let mut events = events_query.fetch_all(&state.pool).await?;
+        changed_events as Option<&[i32]>, // $3
+        EventInvitationStatus::Accepted as EventInvitationStatus, // $4
+        &changes.added_categories // $5
+    )
+    .fetch_all(&mut *tx)
+    .await?;
 
 
-    // 4. Fetch Deadlines (owned by sharer, in shared categories, updated since 'since')
-    let mut deadlines = sqlx::query_as!(
+    // 4. Fetch Deadlines (owned by sharer, in shared categories)
+    let deadlines = sqlx::query_as!(
         Deadline,
         r#"
         SELECT
@@ -330,16 +486,51 @@ pub async fn sync_shared_calendar_data(
         FROM deadlines
         WHERE user_id = $1 -- Only deadlines owned by the sharer
           AND category_id = ANY($2) -- Filter by shared categories
-          AND ( ($3::TIMESTAMPTZ IS NULL) OR (updated_at > $3) ) -- Filter by deadline update time
+          AND deleted_at IS NULL
+          AND ( ($3::INTEGER[] IS NULL) OR (deadline_id = ANY($3)) OR (category_id = ANY($4)) )
         ORDER BY due_date
         "#,
         owner_user_id, // $1
         &shared_category_ids, // $2
-        since_timestamp, // $3
+        changed_deadlines as Option<&[i32]>, // $3
+        &changes.added_categories // $4
     )
-    .fetch_all(&state.pool)
+    .fetch_all(&mut *tx)
     .await?;
 
+    // Tombstones: changed items that aren't part of the view any more, and the owner's items
+    // in categories removed from the share
+    let mut deleted = SharedCalendarDeletedItems::default();
+    if since.is_some() {
+        let removed = sqlx::query!(
+            r#"
+            SELECT 'event' as "entity!", event_id as "id!" FROM events WHERE user_id = $1 AND category_id = ANY($2)
+            UNION ALL
+            SELECT 'deadline', deadline_id FROM deadlines WHERE user_id = $1 AND category_id = ANY($2)
+            "#,
+            owner_user_id,
+            &changes.removed_categories
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in removed {
+            match row.entity.as_str() {
+                "event" => changes.events.push(row.id),
+                _ => changes.deadlines.push(row.id),
+            }
+        }
+
+        let shown_events: HashSet<i32> = events.iter().map(|e| e.event_id).collect();
+        let shown_deadlines: HashSet<i32> = deadlines.iter().map(|d| d.deadline_id).collect();
+        deleted.event_ids = changes.events.iter().copied().filter(|id| !shown_events.contains(id)).collect();
+        deleted.deadline_ids = changes.deadlines.iter().copied().filter(|id| !shown_deadlines.contains(id)).collect();
+        deleted.event_ids.sort_unstable();
+        deleted.event_ids.dedup();
+        deleted.deadline_ids.sort_unstable();
+        deleted.deadline_ids.dedup();
+    }
+
+    tx.commit().await?; // Read-only; just ends the snapshot
 
     // 5. Apply Privacy Level (apply BEFORE returning)
     let events: Vec<SharedCalendarEvent> = if privacy_level == SharePrivacyLevel::Limited {
@@ -407,20 +598,8 @@ pub async fn sync_shared_calendar_data(
         }).collect()
     };
 
-    // 6. Check if the share configuration ITSELF was updated since 'since'
-    // If items haven't updated but the share config (e.g., privacy) did, we still need to include share_info
-    let share_updated_since = if let Some(since) = since_timestamp {
-        share.updated_at > since
-    } else {
-        true // No 'since', always include current share info
-    };
-
-    // Only include share_info if it was updated or if there are updated items
-     let final_share_info = if share_updated_since || !events.is_empty() || !deadlines.is_empty() {
-         Some(share)
-     } else {
-         None
-     };
+    // 6. Include share_info on a full sync, or if the share itself changed since 'since'
+    let final_share_info = resend_all.then_some(share);
 
 
     // 7. Combine results into the response struct
@@ -428,42 +607,428 @@ pub async fn sync_shared_calendar_data(
         share_info: final_share_info, // Send current share info only if relevant update occurred
         events,
         deadlines,
-        sync_timestamp: now,
+        deleted,
+        sync_token: encode_sync_token(sync_position),
     };
 
     Ok(Json(response))
 }
 
+// Owner changes relevant to one share since a sync token
+#[derive(Default)]
+struct SharedCalendarChanges {
+    share: bool,
+    added_categories: Vec<i32>,
+    removed_categories: Vec<i32>,
+    events: Vec<i32>,
+    deadlines: Vec<i32>,
+}
 
-// --- Helper function for sync_shared_calendar_data edge cases ---
-async fn sync_just_share_update(
-    state: AppState,
-    share_id: i32,
-    authenticated_user_id: i32,
-    since_timestamp: Option<DateTime<Utc>>,
-    now: DateTime<Utc>
-) -> Result<Json<SyncSharedCalendarResponse>, AppError> {
-     // Check if the share record itself was updated (e.g., deleted) since 'since'
-     let share_info = sqlx::query_as!(
-        CalendarShare,
+// Most changes replayed when a stream resumes; beyond this the client is told to pull instead
+const MAX_STREAM_REPLAY: i64 = 1000;
+
+fn change_event(change: &SyncChange) -> Result<SseEvent, axum::Error> {
+    // The event id is a sync token, so it also works as ?since= for GET /api/sync/me
+    SseEvent::default().event("change").id(encode_sync_token(change.change_id)).json_data(change)
+}
+
+fn resync_event() -> SseEvent {
+    // Client should pull GET /api/sync/me?since=<last sync token> to catch up
+    SseEvent::default().event("resync").data("{}")
+}
+
+// --- GET /api/sync/stream handler ---
+// Server-Sent Events stream of change notifications for the user's sync data.
+// Resumes from the Last-Event-ID header (sent automatically by EventSource on reconnect) or ?since=.
+pub async fn sync_stream(
+    State(state): State<AppState>,
+    _: RequireScope<scope::CalendarRead>,
+    AuthenticatedUser { user_id: authenticated_user_id }: AuthenticatedUser,
+    headers: HeaderMap,
+    Query(params): Query<SyncSinceParams>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, axum::Error>>>, AppError> {
+    let last_event_id = headers.get("last-event-id").and_then(|v| v.to_str().ok()).map(str::to_string);
+    let since = parse_optional_sync_token(last_event_id.or(params.since))?;
+
+    // Subscribe before reading the change log so nothing committed in between is lost;
+    // live changes already covered by the replay are skipped below
+    let receiver = state.sync_feed.subscribe();
+
+    let mut initial = Vec::new();
+    let resume_from = sqlx::query_scalar!(
+        "SELECT last_change_id FROM sync_sequences WHERE user_id = $1",
+        authenticated_user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .unwrap_or(0);
+
+    if let Some(since) = since {
+        // Replay what changed while the client was disconnected (latest change per item)
+        let changes = sqlx::query!(
+            r#"
+            SELECT entity, entity_id, change_id, deleted, updated_at
+            FROM sync_change_log
+            WHERE user_id = $1 AND change_id > $2 AND change_id <= $3
+            ORDER BY change_id
+            LIMIT $4
+            "#,
+            authenticated_user_id,
+            since,
+            resume_from,
+            MAX_STREAM_REPLAY + 1
+        )
+        .fetch_all(&state.pool)
+        .await?;
+
+        if changes.len() as i64 > MAX_STREAM_REPLAY {
+            initial.push(Ok(resync_event()));
+        } else {
+            for row in changes {
+                let change = SyncChange {
+                    entity: row.entity,
+                    id: row.entity_id,
+                    deleted: row.deleted,
+                    user_id: authenticated_user_id,
+                    change_id: row.change_id,
+                    updated_at: row.updated_at,
+                };
+                initial.push(change_event(&change));
+            }
+        }
+    }
+
+    // Tells the client the stream is live, with a token to resume from if nothing else arrives
+    let ready_token = encode_sync_token(resume_from);
+    initial.push(SseEvent::default().event("ready").id(ready_token.clone()).json_data(json!({ "syncToken": ready_token })));
+
+    // Live changes to this user's change log. Per-user change IDs are committed in order,
+    // so anything at or below the ready position was already covered.
+    let live = BroadcastStream::new(receiver).filter_map(move |message| match message {
+        Ok(SyncFeedMessage::Change(change)) if change.user_id == authenticated_user_id && change.change_id > resume_from => {
+            Some(change_event(&change))
+        }
+        Ok(SyncFeedMessage::Change(_)) => None,
+        Ok(SyncFeedMessage::Reminder(reminder)) if reminder.user_id == authenticated_user_id => {
+            Some(SseEvent::default().event("reminder").json_data(&reminder))
+        }
+        Ok(SyncFeedMessage::Reminder(_)) => None,
+        // Listener reconnected or this stream fell behind: notifications were dropped
+        Ok(SyncFeedMessage::Resync) | Err(BroadcastStreamRecvError::Lagged(_)) => Some(Ok(resync_event())),
+    });
+
+    Ok(Sse::new(tokio_stream::iter(initial).chain(live)).keep_alive(KeepAlive::default()))
+}
+
+
+// --- POST /api/sync handler ---
+// Applies a batch of client changes in one transaction. Each operation runs in its own savepoint so a
+// rejected item doesn't undo the others; updates/deletes based on a stale updatedAt are reported as conflicts.
+const MAX_CLIENT_SYNC_OPERATIONS: usize = 500;
+
+pub async fn process_client_sync(
+    State(state): State<AppState>,
+    _: RequireScope<scope::EventsWrite>,
+    _: RequireScope<scope::DeadlinesWrite>,
+    _: RequireScope<scope::CategoriesWrite>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Json(payload): Json<ClientSyncPayload>,
+) -> Result<Json<ClientSyncResponse>, AppError> {
+    let since = parse_optional_sync_token(payload.since)?;
+
+    let total = payload.categories.len() + payload.events.len() + payload.deadlines.len();
+    if total > MAX_CLIENT_SYNC_OPERATIONS {
+        let mut err = validator::ValidationError::new("too_many_operations");
+        err.message = Some(format!("At most {} operations can be synced at once", MAX_CLIENT_SYNC_OPERATIONS).into());
+        let mut errors = validator::ValidationErrors::new();
+        errors.add("operations", err);
+        return Err(errors.into());
+    }
+
+    let mut tx = state.pool.begin().await?;
+    let mut results = Vec::with_capacity(total);
+    let mut category_ids: HashMap<String, i32> = HashMap::new(); // clientId -> server ID of categories created in this batch
+
+    // Categories first, so events/deadlines can reference new ones through categoryClientId
+    for op in payload.categories {
+        let (kind, client_id, id) = (op.kind(), op.client_id(), op.id());
+        let mut savepoint = tx.begin().await?;
+        let outcome = apply_category_operation(&mut savepoint, user_id, op).await;
+        let result = finish_sync_item(savepoint, SyncEntityKind::Category, kind, client_id, id, outcome).await?;
+        if let (Some(client_id), Some(id), SyncItemStatus::Applied) = (&result.client_id, result.id, result.status) {
+            category_ids.insert(client_id.clone(), id);
+        }
+        results.push(result);
+    }
+
+    for op in payload.events {
+        let (kind, client_id, id) = (op.kind(), op.client_id(), op.id());
+        let mut savepoint = tx.begin().await?;
+        let outcome = apply_event_operation(&mut savepoint, user_id, &category_ids, op).await;
+        results.push(finish_sync_item(savepoint, SyncEntityKind::Event, kind, client_id, id, outcome).await?);
+    }
+
+    for op in payload.deadlines {
+        let (kind, client_id, id) = (op.kind(), op.client_id(), op.id());
+        let mut savepoint = tx.begin().await?;
+        let outcome = apply_deadline_operation(&mut savepoint, user_id, &category_ids, op).await;
+        results.push(finish_sync_item(savepoint, SyncEntityKind::Deadline, kind, client_id, id, outcome).await?);
+    }
+
+    tx.commit().await?;
+
+    // Server-side changes since the client's last sync, including the ones just applied
+    let changes = build_sync_response(&state.pool, user_id, since).await?;
+
+    Ok(Json(ClientSyncResponse { results, changes }))
+}
+
+// --- Helper: Release or roll back an operation's savepoint and build its result ---
+async fn finish_sync_item(
+    savepoint: Transaction<'_, Postgres>,
+    entity: SyncEntityKind,
+    op: SyncOperationKind,
+    client_id: Option<String>,
+    id: Option<i32>,
+    outcome: Result<(SyncItemStatus, SyncRecord), AppError>,
+) -> Result<SyncItemResult, AppError> {
+    match outcome {
+        Ok((status, record)) => {
+            if status == SyncItemStatus::Applied {
+                savepoint.commit().await?;
+            } else {
+                savepoint.rollback().await?;
+            }
+            Ok(SyncItemResult { entity, op, client_id, id: Some(record.id()), status, error: None, server: Some(record) })
+        }
+        Err(e) => {
+            savepoint.rollback().await?;
+            let error = sync_item_error(e)?; // Anything else (e.g. database failures) aborts the whole batch
+            Ok(SyncItemResult { entity, op, client_id, id, status: SyncItemStatus::Rejected, error: Some(error), server: None })
+        }
+    }
+}
+
+// Errors that only reject the operation that caused them
+fn sync_item_error(error: AppError) -> Result<String, AppError> {
+    match error {
+        AppError::ValidationFailed(e) if e.is_empty() => Ok("Validation failed: invalid timestamp".to_string()),
+        AppError::ValidationFailed(e) => Ok(format!("Validation failed: {}", e)),
+        AppError::CategoryNotFound => Ok("Category not found".to_string()),
+        AppError::EventNotFound => Ok("Event not found".to_string()),
+        AppError::DeadlineNotFound => Ok("Deadline not found".to_string()),
+        AppError::CategoryNameAlreadyExists => Ok("A category with this name already exists".to_string()),
+        other => Err(other),
+    }
+}
+
+// Update: the client must have seen the latest version, and the item must not be deleted
+fn update_conflicts(updated_at: DateTime<Utc>, deleted_at: Option<DateTime<Utc>>, base_updated_at: DateTime<Utc>) -> bool {
+    deleted_at.is_some() || updated_at > base_updated_at
+}
+
+// Resolve the category of an event/deadline operation (categoryClientId wins over categoryId)
+fn resolve_category_id(
+    category_ids: &HashMap<String, i32>,
+    category_client_id: Option<String>,
+    category_id: Option<i32>,
+) -> Result<Option<i32>, AppError> {
+    match category_client_id {
+        Some(client_id) => category_ids.get(&client_id).copied().map(Some).ok_or(AppError::CategoryNotFound),
+        None => Ok(category_id),
+    }
+}
+
+// --- Helper: Apply one category operation ---
+async fn apply_category_operation(
+    conn: &mut PgConnection,
+    user_id: i32,
+    op: SyncOperation<CreateCategoryPayload, UpdateCategoryPayload>,
+) -> Result<(SyncItemStatus, SyncRecord), AppError> {
+    match op {
+        SyncOperation::Create { data, .. } => {
+            let created = insert_category(conn, user_id, data).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Category(created)))
+        }
+        SyncOperation::Update { id, base_updated_at, data, .. } => {
+            data.validate()?;
+            let category = lock_category(conn, user_id, id).await?;
+            if update_conflicts(category.updated_at, category.deleted_at, base_updated_at) {
+                return Ok((SyncItemStatus::Conflict, SyncRecord::Category(category)));
+            }
+
+            // Same field semantics as PUT /api/me/categories/{category_id}
+            let updated = apply_category_update(conn, user_id, category, data).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Category(updated)))
+        }
+        SyncOperation::Delete { id, base_updated_at } => {
+            let category = lock_category(conn, user_id, id).await?;
+            if category.deleted_at.is_some() {
+                return Ok((SyncItemStatus::Applied, SyncRecord::Category(category))); // Already deleted
+            }
+            if category.updated_at > base_updated_at {
+                return Ok((SyncItemStatus::Conflict, SyncRecord::Category(category)));
+            }
+
+            let deleted = soft_delete_category(conn, user_id, id).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Category(deleted)))
+        }
+    }
+}
+
+async fn lock_category(conn: &mut PgConnection, user_id: i32, category_id: i32) -> Result<Category, AppError> {
+    sqlx::query_as!(
+        Category,
         r#"
-        SELECT share_id, owner_user_id, shared_with_user_id, message as "message!: _", privacy_level as "privacy_level!: _",
-        expires_at as "expires_at!: _", created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
-        FROM calendar_shares
-        WHERE share_id = $1 AND shared_with_user_id = $2
-          AND ( ($3::TIMESTAMPTZ IS NULL) OR (updated_at > $3) ) -- Check if share record updated
+        SELECT category_id, user_id, name, color, is_visible as "is_visible!",
+        created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM categories
+        WHERE category_id = $1 AND user_id = $2
+        FOR UPDATE
         "#,
-        share_id,
-        authenticated_user_id,
-        since_timestamp
+        category_id,
+        user_id
     )
-    .fetch_optional(&state.pool)
-    .await?;
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::CategoryNotFound)
+}
+
+// --- Helper: Apply one event operation ---
+async fn apply_event_operation(
+    conn: &mut PgConnection,
+    user_id: i32,
+    category_ids: &HashMap<String, i32>,
+    op: SyncOperation<CreateEventPayload, UpdateEventPayload>,
+) -> Result<(SyncItemStatus, SyncRecord), AppError> {
+    match op {
+        SyncOperation::Create { category_client_id, mut data, .. } => {
+            data.category_id = resolve_category_id(category_ids, category_client_id, data.category_id)?;
+            let created = insert_event(conn, user_id, data).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Event(created)))
+        }
+        SyncOperation::Update { id, base_updated_at, category_client_id, mut data } => {
+            data.category_id = resolve_category_id(category_ids, category_client_id, data.category_id)?;
+            validate_event_update(&data)?;
+
+            let event = lock_event(conn, user_id, id).await?;
+            if update_conflicts(event.updated_at, event.deleted_at, base_updated_at) {
+                return Ok((SyncItemStatus::Conflict, SyncRecord::Event(event)));
+            }
+
+            // Same field semantics as PUT /api/me/events/{event_id}
+            let updated = apply_event_update(conn, user_id, event, data).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Event(updated)))
+        }
+        SyncOperation::Delete { id, base_updated_at } => {
+            let event = lock_event(conn, user_id, id).await?;
+            if event.deleted_at.is_some() {
+                return Ok((SyncItemStatus::Applied, SyncRecord::Event(event))); // Already deleted
+            }
+            if event.updated_at > base_updated_at {
+                return Ok((SyncItemStatus::Conflict, SyncRecord::Event(event)));
+            }
+
+            let deleted = sqlx::query_as!(
+                Event,
+                r#"
+                UPDATE events
+                SET deleted_at = NOW() -- Soft delete
+                WHERE event_id = $1 AND user_id = $2
+                RETURNING
+                   event_id, user_id, category_id, title, description as "description!: _", start_time, end_time,
+                   location as "location!: _", rrule as "rrule!: _",
+                   created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+                "#,
+                id,
+                user_id
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Event(deleted)))
+        }
+    }
+}
+
+// Only the owner can change an event through sync
+async fn lock_event(conn: &mut PgConnection, user_id: i32, event_id: i32) -> Result<Event, AppError> {
+    sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+           event_id, user_id, category_id, title, description as "description!: _", start_time, end_time,
+           location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE event_id = $1 AND user_id = $2
+        FOR UPDATE
+        "#,
+        event_id,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::EventNotFound)
+}
+
+// --- Helper: Apply one deadline operation ---
+async fn apply_deadline_operation(
+    conn: &mut PgConnection,
+    user_id: i32,
+    category_ids: &HashMap<String, i32>,
+    op: SyncOperation<CreateDeadlinePayload, UpdateDeadlinePayload>,
+) -> Result<(SyncItemStatus, SyncRecord), AppError> {
+    match op {
+        SyncOperation::Create { category_client_id, mut data, .. } => {
+            data.category_id = resolve_category_id(category_ids, category_client_id, data.category_id)?;
+            let created = insert_deadline(conn, user_id, data).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Deadline(created)))
+        }
+        SyncOperation::Update { id, base_updated_at, category_client_id, mut data } => {
+            data.category_id = resolve_category_id(category_ids, category_client_id, data.category_id)?;
+            data.validate()?;
+
+            let deadline = lock_deadline(conn, user_id, id).await?;
+            if update_conflicts(deadline.updated_at, deadline.deleted_at, base_updated_at) {
+                return Ok((SyncItemStatus::Conflict, SyncRecord::Deadline(deadline)));
+            }
+
+            // Same field semantics as PUT /api/me/deadlines/{deadline_id}
+            let updated = apply_deadline_update(conn, user_id, deadline, data).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Deadline(updated)))
+        }
+        SyncOperation::Delete { id, base_updated_at } => {
+            let deadline = lock_deadline(conn, user_id, id).await?;
+            if deadline.deleted_at.is_some() {
+                return Ok((SyncItemStatus::Applied, SyncRecord::Deadline(deadline))); // Already deleted
+            }
+            if deadline.updated_at > base_updated_at {
+                return Ok((SyncItemStatus::Conflict, SyncRecord::Deadline(deadline)));
+            }
+
+            let deleted = soft_delete_deadline(conn, user_id, id).await?;
+            Ok((SyncItemStatus::Applied, SyncRecord::Deadline(deleted)))
+        }
+    }
+}
 
-     Ok(Json(SyncSharedCalendarResponse {
-         share_info, // Send share info only if it was updated since last sync
-         events: vec![],
-         deadlines: vec![],
-         sync_timestamp: now,
-    }))
+async fn lock_deadline(conn: &mut PgConnection, user_id: i32, deadline_id: i32) -> Result<Deadline, AppError> {
+    sqlx::query_as!(
+        Deadline,
+        r#"
+        SELECT
+           deadline_id, user_id, category_id, title, description, due_date, virtual_due_date as "virtual_due_date!: _",
+           priority as "priority!: _",
+           workload_magnitude as "workload_magnitude!: _", workload_unit as "workload_unit!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM deadlines
+        WHERE deadline_id = $1 AND user_id = $2
+        FOR UPDATE
+        "#,
+        deadline_id,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::DeadlineNotFound)
 }
\ No newline at end of file
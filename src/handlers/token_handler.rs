@@ -0,0 +1,48 @@
+use axum::{
+    extract::{State, Path, Json},
+    http::StatusCode,
+};
+use chrono::{Duration, Utc};
+use validator::Validate;
+use crate::{
+    AppState,
+    auth::token,
+    errors::AppError,
+    middleware::auth::AuthenticatedUser,
+    models::token::{CreateTokenPayload, CreatedTokenResponse, TokenInfo},
+};
+
+// --- Personal Access Tokens (/api/me/tokens) ---
+// Managing tokens needs a login session; a personal access token can't create or list others
+
+pub async fn create_token(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Json(payload): Json<CreateTokenPayload>,
+) -> Result<(StatusCode, Json<CreatedTokenResponse>), AppError> {
+    payload.validate()?;
+    let name = payload.name.unwrap(); // Required by validation
+    let scopes = payload.scopes.unwrap(); // Required by validation
+    let expires_at = payload.expires_in_days.map(|days| Utc::now() + Duration::days(days));
+
+    let (token, info) = token::create_token(&state.pool, user_id, &name, &scopes, expires_at).await?;
+
+    tracing::info!("User {} created personal access token {}", user_id, info.token_id);
+    Ok((StatusCode::CREATED, Json(CreatedTokenResponse { token, info })))
+}
+
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+) -> Result<Json<Vec<TokenInfo>>, AppError> {
+    Ok(Json(token::list_tokens(&state.pool, user_id).await?))
+}
+
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    AuthenticatedUser { user_id }: AuthenticatedUser,
+    Path(token_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    token::delete_token(&state.pool, user_id, token_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
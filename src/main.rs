@@ -1,4 +1,4 @@
-use ai::OpenAIClient;
+use ai::AiClient;
 use axum::Router;
 use std::{net::SocketAddr, sync::Arc};
 use tokio;
@@ -24,12 +24,16 @@ mod utils; // Declares security module inside
 mod middleware; // Declares auth module inside
 mod email; // Declares email module inside
 mod ai; // Declares ai module inside
+mod sync_feed; // Declares the sync change feed
+mod reminder_scheduler; // Declares the background reminder scheduler
 
 use config::Config; // Use the Config struct
 use errors::AppError; // Use our custom error type
 use routes::create_api_router; // Use the function from routes module
 use state::AppState; // Use the AppState struct
 use email::EmailService; // Use the EmailService struct
+use sync_feed::SyncFeed; // Use the SyncFeed struct
+use middleware::rate_limit::RateLimiter; // Use the RateLimiter struct
 
 
 #[tokio::main]
@@ -57,15 +61,32 @@ async fn main() -> Result<(), AppError> { // Return our AppError
     let email_service = EmailService::new(config.clone())?; // Pass clone of Arc<Config>
     tracing::info!("Email service initialized.");
 
-    let openai_client = OpenAIClient::new(&config); // Client takes Arc<Config> ref
-    tracing::info!("OpenAI client initialized.");
+    let ai_client = config.ai.as_ref().map(AiClient::new); // None disables the AI assistant
+
+    // Start listening for database change notifications (feeds GET /api/sync/stream)
+    let sync_feed = SyncFeed::start(&pool).await?;
+    tracing::info!("Sync feed listening for changes.");
+
+    let webauthn = Arc::new(auth::passkey::build_webauthn(&config)?);
+    tracing::info!("WebAuthn relying party configured.");
+
+    let oidc = auth::oidc::OidcClient::new()?;
+    tracing::info!("Social login providers: {}.", config.oidc_providers.len());
+
+    // Start firing event and deadline reminders in the background
+    reminder_scheduler::start(pool.clone(), email_service.clone());
+    tracing::info!("Reminder scheduler started.");
 
     // Create the application state - this is the single source of truth for state
     let app_state = AppState {
         pool: pool.clone(), // Clone the pool for the state
         config: config.clone(), // Clone the Arc<Config>
         email_service,
-        openai_client
+        ai_client,
+        sync_feed,
+        webauthn,
+        auth_rate_limiter: RateLimiter::new(config.auth_rate_limit_per_minute),
+        oidc,
     };
 
     // Configure CORS
@@ -86,7 +107,9 @@ async fn main() -> Result<(), AppError> { // Return our AppError
             http::header::AUTHORIZATION,
             http::header::ACCEPT,
             http::header::CONTENT_TYPE,
-        ]);
+        ])
+        // Let browser clients read the pagination cursor
+        .expose_headers([utils::pagination::NEXT_CURSOR_HEADER]);
         // For production, replace .allow_origin(Any) with:
         // .allow_origin("http://your-frontend-domain.com".parse::<HeaderValue>().unwrap())
         // Or use a list of allowed origins.
@@ -109,7 +132,7 @@ async fn main() -> Result<(), AppError> { // Return our AppError
 
     // Start the server using axum-server
     axum_server::bind(addr) // Use axum_server::bind
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>()) // Client address for rate limiting
         .await
         .map_err(|e| { // Handle potential server binding/runtime errors
             tracing::error!("Server failed: {}", e);
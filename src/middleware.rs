@@ -1 +1,2 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod rate_limit; // Per-IP rate limiting of the auth endpoints
\ No newline at end of file
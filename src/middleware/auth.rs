@@ -11,7 +11,8 @@ use axum_extra::{
 };
 // use axum::body::Body;
 // use axum::http::header::AUTHORIZATION;
-use crate::{AppState, errors::AppError, auth::jwt};
+use crate::{AppState, errors::AppError, auth::{jwt, session, token}, models::enums::TokenScope};
+use std::marker::PhantomData;
 
 // Struct that will be injected into handlers upon successful authentication
 #[derive(Debug)]
@@ -21,6 +22,54 @@ pub struct AuthenticatedUser {
     // but user_id is often sufficient for authorization checks
 }
 
+// Same as AuthenticatedUser, plus the session the access token belongs to.
+// For handlers that act on the caller's own session (e.g. GET /api/me/sessions).
+#[derive(Debug)]
+pub struct CurrentSession {
+    pub user_id: i32,
+    pub session_id: i32,
+}
+
+// Who sent the request: a login session (every scope) or a personal access token (its scopes only).
+// Kept in the request extensions, so several extractors on one handler authenticate once.
+#[derive(Debug, Clone)]
+struct Principal {
+    user_id: i32,
+    session_id: Option<i32>, // None for personal access tokens
+    scopes: Option<Vec<TokenScope>>, // None for sessions
+}
+
+// Put in the request extensions by RequireScope once the token's scope was checked
+#[derive(Clone)]
+struct ScopeChecked;
+
+// Validate the Bearer token and check that its session (or personal access token) is still active
+async fn authenticate(parts: &mut http::request::Parts, state: &AppState) -> Result<Principal, AppError> {
+    if let Some(principal) = parts.extensions.get::<Principal>() {
+        return Ok(principal.clone());
+    }
+
+    let TypedHeader(Authorization(bearer)) = parts.extract::<TypedHeader<Authorization<Bearer>>>()
+        .await
+        .map_err(|_| AppError::JwtError(jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)))?; // Return 401 if header is missing/invalid
+
+    let principal = if token::is_personal_access_token(bearer.token()) {
+        let (user_id, scopes) = token::authenticate_token(&state.pool, bearer.token()).await?;
+        Principal { user_id, session_id: None, scopes: Some(scopes) }
+    } else {
+        let claims = jwt::validate_token(bearer.token(), &state.config)?; // Handles expiration and signature validation
+
+        // Revoked sessions and deleted accounts lose access before the token expires
+        if !session::is_session_active(&state.pool, claims.sid, claims.sub).await? {
+            return Err(AppError::SessionInvalid);
+        }
+        Principal { user_id: claims.sub, session_id: Some(claims.sid), scopes: None }
+    };
+
+    parts.extensions.insert(principal.clone());
+    Ok(principal)
+}
+
 // Implement the FromRequestParts trait to make AuthenticatedUser an extractor
 // #[async_trait::async_trait] // Use async_trait for async trait methods
 impl axum::extract::FromRequestParts<AppState> for AuthenticatedUser // Use AppState directly
@@ -34,26 +83,78 @@ where
     async fn from_request_parts(
         parts: &mut http::request::Parts, state: &AppState
     ) -> Result<Self, Self::Rejection> {
-        // Extract the Bearer token from the Authorization header
-        let TypedHeader(Authorization(bearer)) = parts.extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| AppError::JwtError(jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)))?; // Return 401 if header is missing/invalid
-        
-        // Get the token string from the Bearer struct
-        let token = bearer.token();
-
-        // Get the JWT secret from the application state
-        // state is now directly &AppState
-        let config = &state.config;
+        let principal = authenticate(parts, state).await?;
 
-        // Validate the token using our helper function
-        let claims = jwt::validate_token(&token, config)?; // Handles expiration and signature validation
+        // Personal access tokens only get to handlers that declare a scope with RequireScope (before this extractor)
+        if principal.scopes.is_some() && parts.extensions.get::<ScopeChecked>().is_none() {
+            return Err(AppError::AccessTokenNotAllowed);
+        }
 
         // If validation succeeds, construct AuthenticatedUser
-        Ok(AuthenticatedUser { user_id: claims.sub })
+        Ok(AuthenticatedUser { user_id: principal.user_id })
+    }
+}
+
+impl axum::extract::FromRequestParts<AppState> for CurrentSession {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts, state: &AppState
+    ) -> Result<Self, Self::Rejection> {
+        let principal = authenticate(parts, state).await?;
+        let session_id = principal.session_id.ok_or(AppError::AccessTokenNotAllowed)?;
+        Ok(CurrentSession { user_id: principal.user_id, session_id })
     }
 }
 
+// --- Scopes for personal access tokens ---
+// Handlers open to personal access tokens declare the scope they need, placed before AuthenticatedUser:
+//     _: RequireScope<scope::EventsWrite>,
+//     AuthenticatedUser { user_id }: AuthenticatedUser,
+// Login sessions pass every scope. Handlers without RequireScope reject personal access tokens.
+pub trait ScopeRequirement {
+    const SCOPE: TokenScope;
+}
+
+pub struct RequireScope<S>(PhantomData<fn() -> S>);
+
+impl<S: ScopeRequirement> axum::extract::FromRequestParts<AppState> for RequireScope<S> {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut http::request::Parts, state: &AppState
+    ) -> Result<Self, Self::Rejection> {
+        let principal = authenticate(parts, state).await?;
+
+        if let Some(scopes) = &principal.scopes {
+            if !scopes.contains(&S::SCOPE) {
+                return Err(AppError::InsufficientScope(S::SCOPE.as_str()));
+            }
+            parts.extensions.insert(ScopeChecked);
+        }
+        Ok(RequireScope(PhantomData))
+    }
+}
+
+// Type-level names for the scopes, for RequireScope<...>
+pub mod scope {
+    use super::ScopeRequirement;
+    use crate::models::enums::TokenScope;
+
+    macro_rules! scopes {
+        ($($name:ident),* $(,)?) => {
+            $(
+                pub struct $name;
+                impl ScopeRequirement for $name {
+                    const SCOPE: TokenScope = TokenScope::$name;
+                }
+            )*
+        };
+    }
+
+    scopes!(CalendarRead, EventsWrite, DeadlinesWrite, CategoriesWrite, SharesAdmin);
+}
+
 // --- Optional: A handler-based middleware approach ---
 // You can also write middleware as a standard async function that takes `Request` and `Next`.
 // This is useful for things like logging, CORS, or transforming the request/response body.
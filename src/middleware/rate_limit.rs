@@ -0,0 +1,87 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use crate::{AppState, errors::AppError};
+
+const WINDOW: Duration = Duration::from_secs(60);
+const PRUNE_THRESHOLD: usize = 10_000; // Drop finished windows once this many IPs are tracked
+
+// Fixed one-minute window of requests per client IP, kept in memory.
+// Guards the unauthenticated auth endpoints (login, codes, password reset) against brute force
+// from a single address; per-account lockouts are in auth::lockout.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limit: u32, // Requests per IP and window
+    windows: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>, // Window start, requests in it
+}
+
+impl RateLimiter {
+    pub fn new(limit_per_minute: u32) -> Self {
+        Self { limit: limit_per_minute, windows: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    // Counts a request; returns the seconds until the window resets if the IP is over the limit
+    fn hit(&self, ip: IpAddr) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, (started, _)| now.duration_since(*started) < WINDOW);
+        }
+
+        let (started, count) = windows.entry(ip).or_insert((now, 0));
+        if now.duration_since(*started) >= WINDOW {
+            *started = now;
+            *count = 0;
+        }
+        *count += 1;
+
+        if *count > self.limit {
+            let retry_after = WINDOW.saturating_sub(now.duration_since(*started));
+            return Err(retry_after.as_secs().max(1));
+        }
+        Ok(())
+    }
+}
+
+// Address of the client: the peer address, or the last X-Forwarded-For hop (added by our own
+// proxy) when TRUST_PROXY_HEADERS is set. The other hops are client-controlled.
+fn client_ip(request: &Request, peer: SocketAddr, trust_proxy_headers: bool) -> IpAddr {
+    if trust_proxy_headers {
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|hop| hop.trim().parse().ok());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+    peer.ip()
+}
+
+// Middleware for the auth routes (see routes::auth)
+pub async fn limit_by_ip(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let ip = client_ip(&request, peer, state.config.trust_proxy_headers);
+
+    if let Err(retry_after) = state.auth_rate_limiter.hit(ip) {
+        tracing::warn!("Rate limit hit by {} on {}", ip, request.uri().path());
+        return Err(AppError::RateLimited(retry_after));
+    }
+
+    Ok(next.run(request).await)
+}
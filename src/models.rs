@@ -4,8 +4,18 @@ pub mod user;
 pub mod category;
 pub mod deadline;
 pub mod event;
+pub mod event_exception;
+pub mod ics_import;
 pub mod event_invitation;
 pub mod calendar_share;
 pub mod calendar;
 pub mod sync;
-pub mod open_share;
\ No newline at end of file
+pub mod open_share;
+pub mod freebusy;
+pub mod scheduling;
+pub mod reminder;
+pub mod session;
+pub mod passkey;
+pub mod oidc;
+pub mod token;
+pub mod ai;
\ No newline at end of file
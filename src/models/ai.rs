@@ -0,0 +1,125 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+use crate::ai::AiUsage;
+use crate::models::{
+    deadline::{CreateDeadlinePayload, Deadline},
+    event::{CreateEventPayload, Event},
+};
+
+// --- Database Models ---
+
+// A conversation with the assistant, for GET /api/me/ai-assistant/threads
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiThreadInfo {
+    pub thread_id: i32,
+    pub title: String,
+    pub message_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>, // Last message
+}
+
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiThreadMessage {
+    pub message_id: i32,
+    pub role: String, // "user" or "assistant"
+    pub content: String,
+    pub image_count: i32, // Images sent with a user message; the images themselves are not kept
+    pub proposal: Option<serde_json::Value>, // AiProposal of an assistant message, if it proposed anything
+    pub created_at: DateTime<Utc>,
+}
+
+// Tokens used on one UTC day (ai_usage)
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiUsageDay {
+    pub date: NaiveDate,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+    pub request_count: i32,
+}
+
+// --- API Payloads ---
+
+// Query parameters for POST /api/me/ai-assistant
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AiAssistantParams {
+    pub apply: Option<bool>, // Create the proposed items right away instead of returning them for confirmation
+}
+
+// Items the assistant wants to create, in the same shape as the create endpoints take them.
+// Returned by the assistant and sent back to POST /api/me/ai-assistant/apply once the user confirms.
+#[derive(Deserialize, Serialize, Validate, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AiProposal {
+    #[serde(default)]
+    #[validate(length(max = 100))]
+    pub events: Vec<CreateEventPayload>,
+    #[serde(default)]
+    #[validate(length(max = 100))]
+    pub deadlines: Vec<CreateDeadlinePayload>,
+}
+
+// --- API Responses ---
+
+// Items created from a proposal
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedProposal {
+    pub events: Vec<Event>,
+    pub deadlines: Vec<Deadline>,
+}
+
+// Response for POST /api/me/ai-assistant
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AiAssistantResponse {
+    pub thread_id: i32,                   // Pass as 'threadId' to continue the conversation
+    pub response: String,                 // The assistant's answer
+    pub proposal: AiProposal,             // What it wants to create (empty if nothing)
+    pub applied: Option<AppliedProposal>, // Set with ?apply=true
+}
+
+// Final 'done' event of POST /api/me/ai-assistant/stream
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AiStreamDone {
+    #[serde(flatten)]
+    pub response: AiAssistantResponse,
+    pub usage: AiUsage,                // Tokens of all model turns of the request
+    pub finish_reason: Option<String>, // Of the last model turn, e.g. "stop" or "length"
+}
+
+// Usage within a quota period
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AiUsagePeriod {
+    pub used_tokens: i64,
+    pub quota: Option<i64>,     // None = unlimited
+    pub remaining: Option<i64>, // None = unlimited
+    pub resets_at: DateTime<Utc>,
+}
+
+// Response for GET /api/me/ai-assistant/usage
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AiUsageResponse {
+    pub today: AiUsagePeriod, // UTC day
+    pub month: AiUsagePeriod, // Calendar month in UTC
+    pub days: Vec<AiUsageDay>, // Days of this month with usage, oldest first
+}
+
+// Response for GET /api/me/ai-assistant/threads/{thread_id}
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AiThreadResponse {
+    #[serde(flatten)]
+    pub thread: AiThreadInfo,
+    pub messages: Vec<AiThreadMessage>, // Oldest first
+}
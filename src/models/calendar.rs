@@ -4,15 +4,53 @@ use sqlx::types::Uuid;
 use crate::models::enums::{DeadlinePriorityLevel, WorkloadUnitType, SharePrivacyLevel}; // Import enums
 use crate::models::event::Event; // Import base Event structure
 use crate::models::deadline::Deadline; // Import base Deadline structure
+use crate::models::event_exception::EventException;
+
+
+// --- Query params shared by the calendar views (GET /api/calendar, shares, open-shares) ---
+// ?expand=true&from=...&to=... expands recurring events into concrete occurrences within [from, to)
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarViewParams {
+    pub expand: Option<bool>,
+    pub from: Option<String>, // RFC3339, required when expand=true
+    pub to: Option<String>,   // RFC3339, required when expand=true
+
+    // Only honoured by GET /api/calendar for now
+    pub category_id: Option<i32>,
+    pub limit: Option<i64>,    // Page size over the merged event/deadline timeline
+    pub after: Option<String>, // Cursor from the previous page's X-Next-Cursor header
+}
+
+// A single instance of an event (recurring or not) within the requested window
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventOccurrence {
+    pub event_id: i32,
+    pub original_start: DateTime<Utc>, // Identifies the occurrence (/me/events/{event_id}/occurrences/{original_start})
+    pub occurrence_start: DateTime<Utc>,
+    pub occurrence_end: DateTime<Utc>,
+    pub is_modified: bool, // True if an exception overrides this occurrence
+
+    // Overrides from the exception, if any (otherwise inherit from the event)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
 
 
 // --- Response struct for GET /api/calendar ---
-// (This remains as is)
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserCalendarResponse {
     pub events: Vec<Event>,
     pub deadlines: Vec<Deadline>,
+    pub exceptions: Vec<EventException>, // Cancelled/modified occurrences of the recurring events above
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occurrences: Option<Vec<EventOccurrence>>, // Only present when expand=true
 }
 
 
@@ -85,6 +123,9 @@ pub struct SharedCalendarResponse {
 
     pub events: Vec<SharedCalendarEvent>,
     pub deadlines: Vec<SharedCalendarDeadline>,
+    pub exceptions: Vec<EventException>, // Override details are cleared in limited mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occurrences: Option<Vec<EventOccurrence>>, // Only present when expand=true
     // Could also include shared categories list here if useful
     // pub shared_category_ids: Vec<i32>,
 }
@@ -99,5 +140,7 @@ pub struct OpenSharedCalendarResponse {
 
     pub events: Vec<SharedCalendarEvent>,
     pub deadlines: Vec<SharedCalendarDeadline>,
-
+    pub exceptions: Vec<EventException>, // Override details are cleared in limited mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub occurrences: Option<Vec<EventOccurrence>>, // Only present when expand=true
 }
\ No newline at end of file
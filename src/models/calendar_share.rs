@@ -24,6 +24,23 @@ pub struct CalendarShare {
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
+// A share with an email address without an account (pending_calendar_shares).
+// Becomes a regular CalendarShare when the address is registered and verified.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingCalendarShare {
+    pub pending_share_id: i32,
+    pub owner_user_id: i32,
+    pub shared_with_email: String,
+    pub category_ids: Vec<i32>,
+    pub message: Option<String>,
+    pub privacy_level: SharePrivacyLevel,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
 
 // --- API Payloads ---
 
@@ -112,6 +129,14 @@ pub struct ShareDetailsResponse {
 // Let's re-use ShareDetailsResponse for simplicity, assuming the query returns the same structure.
 pub type ListSharesResponseItem = ShareDetailsResponse;
 
+// Response for POST /api/me/shares: a regular share, or a pending one when the address has no account yet
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ShareDetailsOrPending {
+    Share(ShareDetailsResponse),
+    Pending(PendingCalendarShare),
+}
+
 // Keep this as a conversion target for API responses, serialization, or documentation if needed
 #[derive(Debug, FromRow, Serialize)]
 #[serde(rename_all = "camelCase")]
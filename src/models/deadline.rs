@@ -28,7 +28,7 @@ pub struct Deadline {
 // --- API Payloads ---
 
 // For creating a Deadline
-#[derive(Deserialize, Validate, Debug)]
+#[derive(Deserialize, Serialize, Validate, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 #[validate(schema(function = "validate_workload_pair"))] // Apply validation at struct level
 pub struct CreateDeadlinePayload {
@@ -43,6 +43,7 @@ pub struct CreateDeadlinePayload {
     pub virtual_due_date: Option<String>, // Optional virtual deadline
     #[validate(required)]
     pub priority: Option<DeadlinePriorityLevel>,
+    #[validate(range(min = 0, max = 10000))]
     pub workload_magnitude: Option<i32>,
     pub workload_unit: Option<WorkloadUnitType>,
 }
@@ -60,6 +61,7 @@ pub struct UpdateDeadlinePayload {
     pub due_date: Option<String>,
     pub virtual_due_date: Option<String>, // Optional virtual deadline
     pub priority: Option<DeadlinePriorityLevel>,
+    #[validate(range(min = 0, max = 10000))]
     pub workload_magnitude: Option<i32>,
     pub workload_unit: Option<WorkloadUnitType>,
 }
@@ -88,4 +90,15 @@ fn validate_workload_pair_update(payload: &UpdateDeadlinePayload) -> Result<(),
         },
         _ => Ok(()), // Valid: either both are Some, or both are None
     }
+}
+
+// Query params for GET /api/me/deadlines
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ListDeadlinesParams {
+    pub from: Option<String>, // RFC3339; deadlines due at or after this
+    pub to: Option<String>,   // RFC3339; deadlines due before this
+    pub category_id: Option<i32>,
+    pub limit: Option<i64>,     // Page size; all matching deadlines if omitted
+    pub after: Option<String>,  // Cursor from the previous page's X-Next-Cursor header
 }
\ No newline at end of file
@@ -58,3 +58,56 @@ pub enum SharePrivacyLevel {
     Full,
     Limited,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[sqlx(type_name = "reminder_channel", rename_all = "snake_case")]
+#[serde(rename_all = "camelCase")]
+#[derive(Default)]
+pub enum ReminderChannel {
+    #[default]
+    Email,
+    InApp, // Delivered as a `reminder` event on the sync stream (GET /api/sync/stream)
+}
+
+// Scope of a personal access token (/api/me/tokens). Sessions from a login have every scope.
+// Stored as text in personal_access_tokens.scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenScope {
+    #[serde(rename = "calendar:read")]
+    CalendarRead, // Read events, deadlines, categories, reminders, invitations, calendars, sync and free/busy
+    #[serde(rename = "events:write")]
+    EventsWrite, // Events, occurrences, event reminders, invitations (sending and responding), ICS import
+    #[serde(rename = "deadlines:write")]
+    DeadlinesWrite, // Deadlines and their reminders
+    #[serde(rename = "categories:write")]
+    CategoriesWrite,
+    #[serde(rename = "shares:admin")]
+    SharesAdmin, // Calendar shares and open shares, including listing them
+}
+
+impl TokenScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::CalendarRead => "calendar:read",
+            TokenScope::EventsWrite => "events:write",
+            TokenScope::DeadlinesWrite => "deadlines:write",
+            TokenScope::CategoriesWrite => "categories:write",
+            TokenScope::SharesAdmin => "shares:admin",
+        }
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "calendar:read" => Ok(TokenScope::CalendarRead),
+            "events:write" => Ok(TokenScope::EventsWrite),
+            "deadlines:write" => Ok(TokenScope::DeadlinesWrite),
+            "categories:write" => Ok(TokenScope::CategoriesWrite),
+            "shares:admin" => Ok(TokenScope::SharesAdmin),
+            _ => Err(format!("Invalid token scope: {}", s)),
+        }
+    }
+}
@@ -24,7 +24,7 @@ pub struct Event {
 
 // --- API Payloads ---
 
-#[derive(Deserialize, Validate, Debug)]
+#[derive(Deserialize, Serialize, Validate, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateEventPayload {
     #[validate(required, length(min = 1, max = 255))]
@@ -47,7 +47,7 @@ pub struct CreateEventPayload {
     pub location: Option<String>,
 
     // RRULE is optional (makes it a recurring event if present)
-    // Could add custom validation for RRULE format if desired (complex!)
+    // Format is checked in the handler via utils::recurrence::validate_rrule
     pub rrule: Option<String>,
 }
 
@@ -74,3 +74,15 @@ pub struct UpdateEventPayload {
     // Allow updating or removing RRULE
     pub rrule: Option<String>, // Allow setting to null/empty string to make non-recurring
 }
+
+
+// Query params for GET /api/me/events
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ListEventsParams {
+    pub from: Option<String>, // RFC3339; events (or series instances) ending after this
+    pub to: Option<String>,   // RFC3339; events starting before this
+    pub category_id: Option<i32>,
+    pub limit: Option<i64>,     // Page size; all matching events if omitted
+    pub after: Option<String>,  // Cursor from the previous page's X-Next-Cursor header
+}
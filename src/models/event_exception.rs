@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// --- Database Model ---
+
+// A cancelled or modified occurrence of a recurring event
+#[derive(Debug, FromRow, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EventException {
+    pub exception_id: i32,
+    pub event_id: i32, // The recurring event (series) this exception belongs to
+    pub original_occurrence_time: DateTime<Utc>, // Start of the occurrence as generated by the RRULE
+    pub is_deleted: bool, // True if this occurrence is cancelled
+    // Override fields (None means inherit from the series)
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub start_time: Option<DateTime<Utc>>, // Always set for modifications
+    pub end_time: Option<DateTime<Utc>>,   // Always set for modifications
+    pub location: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+
+// --- API Payloads ---
+
+// For overriding a single occurrence (PUT /api/me/events/{event_id}/occurrences/{original_start})
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OverrideOccurrencePayload {
+    #[validate(length(min = 1, max = 255))]
+    pub title: Option<String>,
+
+    #[validate(length(max = 1000))]
+    pub description: Option<String>,
+
+    // Default to the original occurrence's timing if omitted
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+
+    #[validate(length(max = 255))]
+    pub location: Option<String>,
+}
@@ -22,6 +22,30 @@ pub struct EventInvitation {
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
+// An invitation for an email address without an account (pending_event_invitations).
+// Becomes a regular EventInvitation when the address is registered and verified.
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingEventInvitation {
+    pub pending_invitation_id: i32,
+    pub event_id: i32,
+    pub owner_user_id: i32,
+    pub invited_email: String,
+    pub status: EventInvitationStatus, // Response given through the email links so far
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// Result of inviting by email or responding through an email link:
+// a regular invitation, or a pending one when the address has no account yet
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum EventInvitationOrPending {
+    Invitation(EventInvitation),
+    Pending(PendingEventInvitation),
+}
+
 
 // --- API Payloads ---
 
@@ -41,6 +65,12 @@ pub struct InvitationResponsePayload {
     pub status: Option<EventInvitationStatus>, // Expect the new status
 }
 
+// Query for GET /api/invitations/respond (one-click RSVP links in invitation emails)
+#[derive(Deserialize, Debug)]
+pub struct RsvpLinkParams {
+    pub token: String, // Signed; identifies the invitation and the response
+}
+
 // Payload for listing *my* invitations (invitee) - now includes filtering
 #[derive(Deserialize, Validate, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+// --- API Payloads ---
+
+// Payload for POST /api/freebusy
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeBusyPayload {
+    #[validate(required, length(min = 1, max = 50))]
+    pub emails: Option<Vec<String>>, // Users to look up
+
+    #[validate(required)]
+    pub from: Option<String>, // RFC3339, start of the window (inclusive)
+    #[validate(required)]
+    pub to: Option<String>,   // RFC3339, end of the window (exclusive)
+
+    // Open share links the caller holds; their owners' shared categories are shown in detail
+    pub open_share_ids: Option<Vec<Uuid>>,
+}
+
+// --- API Responses ---
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FreeBusyAccess {
+    BusyOnly, // Nothing shared with the caller (or no such user): merged busy blocks only
+    Shared,   // Some categories are shared with the caller (or it is the caller): `events` lists them
+}
+
+// A merged block of time in which the user has at least one event
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BusyInterval {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+// An occurrence from a category shared with the caller (privacy level of the share applied)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeBusyEvent {
+    pub event_id: i32,
+    pub category_id: i32,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>, // None for limited shares
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>, // None for limited shares
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeBusyCalendar {
+    pub email: String, // Users are identified by the email asked for, so unknown emails can't be told apart
+    pub access: FreeBusyAccess,
+    pub busy: Vec<BusyInterval>, // Sorted, non-overlapping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<FreeBusyEvent>>, // Only with Shared access
+}
+
+// Response for POST /api/freebusy
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeBusyResponse {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub calendars: Vec<FreeBusyCalendar>,
+}
@@ -0,0 +1,56 @@
+use serde::Serialize;
+
+// --- API Responses ---
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportItemKind {
+    Event,
+    Deadline,
+    Occurrence, // A modified occurrence (VEVENT with RECURRENCE-ID) of an imported recurring event
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportItemStatus {
+    Created,
+    Updated,
+    Skipped,
+}
+
+// Outcome for one VEVENT/VTODO of the uploaded file
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportItemResult {
+    pub kind: ImportItemKind,
+    pub status: ImportItemStatus,
+    pub uid: Option<String>,
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>, // Why the item was skipped
+}
+
+// Response for POST /api/me/import/ics
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IcsImportReport {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub items: Vec<ImportItemResult>,
+}
+
+impl IcsImportReport {
+    pub fn push(&mut self, item: ImportItemResult) {
+        match item.status {
+            ImportItemStatus::Created => self.created += 1,
+            ImportItemStatus::Updated => self.updated += 1,
+            ImportItemStatus::Skipped => self.skipped += 1,
+        }
+        self.items.push(item);
+    }
+}
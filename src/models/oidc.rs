@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// --- API Payloads ---
+
+// For POST /api/auth/oidc/{provider}/finish, with the query parameters the provider redirected back with
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishOidcLoginPayload {
+    #[validate(required, length(min = 1))]
+    pub code: Option<String>,
+    #[validate(required, length(min = 1))]
+    pub state: Option<String>,
+}
+
+// --- API Responses ---
+
+// A configured provider, as listed by GET /api/auth/oidc/providers (for the login buttons)
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcProviderInfo {
+    pub id: String,
+    pub name: String,
+}
+
+// Response for POST /api/auth/oidc/{provider}/start
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcAuthorizationResponse {
+    pub authorization_url: String, // Send the browser here
+}
+
+// A linked provider account, as listed by GET /api/me/identities
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityInfo {
+    pub identity_id: i32,
+    pub provider: String,
+    pub email: Option<String>, // As reported by the provider when it was linked
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
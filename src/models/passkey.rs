@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use uuid::Uuid;
+use webauthn_rs::prelude::{CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse};
+
+// --- API Payloads ---
+// `credential` fields carry the browser's response as returned by navigator.credentials.create()/get(),
+// with binary fields base64url encoded.
+
+// For POST /api/me/passkeys/register/finish
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishPasskeyRegistrationPayload {
+    #[validate(required)]
+    pub ceremony_id: Option<Uuid>, // From the start response
+    #[validate(length(min = 1, max = 100))]
+    pub name: Option<String>, // Label shown in the passkey list, e.g. "MacBook"; defaults to "Passkey"
+    #[validate(required)]
+    pub credential: Option<RegisterPublicKeyCredential>,
+}
+
+// For POST /api/auth/passkey/login/finish
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishPasskeyLoginPayload {
+    #[validate(required)]
+    pub ceremony_id: Option<Uuid>,
+    #[validate(required)]
+    pub credential: Option<PublicKeyCredential>,
+}
+
+// For POST /api/auth/passkey/verify-tfa/start
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StartPasskeyTfaPayload {
+    #[validate(required, length(min = 1))]
+    pub challenge_token: Option<String>, // From the TfaRequiredResponse of the password step
+}
+
+// For POST /api/auth/passkey/verify-tfa/finish
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FinishPasskeyTfaPayload {
+    #[validate(required, length(min = 1))]
+    pub challenge_token: Option<String>,
+    #[validate(required)]
+    pub ceremony_id: Option<Uuid>,
+    #[validate(required)]
+    pub credential: Option<PublicKeyCredential>,
+}
+
+// --- API Responses ---
+
+// Response for POST /api/me/passkeys/register/start
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyRegistrationOptions {
+    pub ceremony_id: Uuid, // Send back with the finish request
+    pub options: CreationChallengeResponse, // Pass to navigator.credentials.create()
+}
+
+// Response for the passkey login and verify-tfa start endpoints
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyAuthenticationOptions {
+    pub ceremony_id: Uuid, // Send back with the finish request
+    pub options: RequestChallengeResponse, // Pass to navigator.credentials.get()
+}
+
+// A registered passkey, as listed by GET /api/me/passkeys
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasskeyInfo {
+    pub passkey_id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>, // Last login or 2FA check with it
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+use crate::models::enums::ReminderChannel;
+
+// Longest supported lead time: four weeks
+pub const MAX_REMINDER_MINUTES_BEFORE: i32 = 4 * 7 * 24 * 60;
+
+// --- Database Models ---
+
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reminder {
+    pub reminder_id: i32,
+    pub user_id: i32, // Who gets reminded
+    pub event_id: Option<i32>,    // Exactly one of event_id / deadline_id is set
+    pub deadline_id: Option<i32>,
+    pub minutes_before: i32, // Before each occurrence start (events) or the due date (deadlines)
+    pub channel: ReminderChannel,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// --- API Payloads ---
+
+// For creating a reminder on an event or deadline
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateReminderPayload {
+    #[validate(required, range(min = 0, max = MAX_REMINDER_MINUTES_BEFORE))]
+    pub minutes_before: Option<i32>, // e.g. 15, or 1440 for "1 day before"
+    pub channel: Option<ReminderChannel>, // Defaults to email
+}
+
+// For updating a reminder
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateReminderPayload {
+    #[validate(range(min = 0, max = MAX_REMINDER_MINUTES_BEFORE))]
+    pub minutes_before: Option<i32>,
+    pub channel: Option<ReminderChannel>,
+}
+
+// --- Notifications ---
+
+// A fired reminder, as published to the sync stream for in-app delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReminderNotification {
+    #[serde(skip_serializing)] // Only used to route the notification to the right streams
+    pub user_id: i32,
+    pub reminder_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline_id: Option<i32>,
+    pub title: String,
+    pub at: DateTime<Utc>, // Occurrence start or due date
+    pub minutes_before: i32,
+}
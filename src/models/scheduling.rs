@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc, Weekday};
+
+// --- API Payloads ---
+
+// Working-hours constraints for suggested slots; every field has a default
+#[derive(Deserialize, Validate, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkingHours {
+    #[validate(length(min = 1, max = 64))]
+    pub time_zone: Option<String>, // IANA name, e.g. "Europe/Berlin" (default "UTC")
+    pub start: Option<String>,      // Local "HH:MM" (default "09:00")
+    pub end: Option<String>,        // Local "HH:MM" (default "17:00"), after start
+    #[validate(length(min = 1, max = 7))]
+    pub days: Option<Vec<Weekday>>, // e.g. ["mon", "tue"] (default Monday to Friday)
+}
+
+// Payload for POST /api/me/events/suggest-times
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestTimesPayload {
+    #[validate(length(max = 50))]
+    pub attendee_emails: Option<Vec<String>>, // Besides the caller, who always attends
+
+    #[validate(required, range(min = 5, max = 1440))]
+    pub duration_minutes: Option<i64>,
+
+    #[validate(required)]
+    pub from: Option<String>, // RFC3339, start of the search window
+    #[validate(required)]
+    pub to: Option<String>,   // RFC3339, end of the search window
+
+    #[validate(nested)]
+    pub working_hours: Option<WorkingHours>,
+
+    #[validate(range(min = 1, max = 20))]
+    pub min_options: Option<usize>, // Default 3
+}
+
+// --- API Responses ---
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestionAttendee {
+    pub email: String,
+}
+
+// A candidate slot. Suggestions are ordered best first
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestedSlot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub unavailable: Vec<String>, // Emails of attendees who are busy during the slot
+    pub crunch_hours: f64,        // Deadline workload of the attendees whose crunch period overlaps the slot
+}
+
+// Response for POST /api/me/events/suggest-times
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestTimesResponse {
+    pub duration_minutes: i64,
+    pub time_zone: String,
+    pub attendees: Vec<SuggestionAttendee>, // Including the caller
+    pub suggestions: Vec<SuggestedSlot>,
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// --- API Payloads ---
+
+// For POST /api/auth/refresh and POST /api/auth/logout
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenPayload {
+    #[validate(required, length(min = 1))]
+    pub refresh_token: Option<String>,
+}
+
+// --- API Responses ---
+
+// Response for POST /api/auth/refresh
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenResponse {
+    pub token: String,         // New access token
+    pub refresh_token: String, // Replaces the one sent; the old one stops working
+    pub expires_in: i64,       // Access token lifetime in seconds
+}
+
+// One active session, as listed by GET /api/me/sessions
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub session_id: i32,
+    pub user_agent: Option<String>, // Of the login request
+    pub created_at: DateTime<Utc>,  // Login time
+    pub last_used_at: DateTime<Utc>, // Last refresh
+    pub expires_at: DateTime<Utc>,
+    pub current: bool, // The session of the access token used for this request
+}
@@ -1,9 +1,10 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use crate::models::{
-    category::Category,
-    deadline::Deadline,
-    event::Event,
+    category::{Category, CreateCategoryPayload, UpdateCategoryPayload},
+    deadline::{CreateDeadlinePayload, Deadline, UpdateDeadlinePayload},
+    event::{CreateEventPayload, Event, UpdateEventPayload},
+    event_exception::EventException,
     event_invitation::{EventInvitation, MyInvitationResponseItem}, // Use MyInvitationResponseItem for detail
     calendar_share::{CalendarShare, ShareDetailsResponse, ListSharesResponseItem}, // Use ListSharesResponseItem
     // Add other models if needed later
@@ -14,56 +15,222 @@ use super::calendar::{SharedCalendarDeadline, SharedCalendarEvent};
 // Payload for GET /api/me/sync?since=...
 #[derive(Deserialize, Debug)]
 pub struct SyncSinceParams {
-    // Optional: Use String and parse, or directly deserialize if format is consistent
-    pub since: Option<String>, // ISO 8601 timestamp string
+    // The syncToken of a previous sync (per-user for GET /api/sync/me and the stream,
+    // per-share for GET /api/sync/calendar/shares/:share_id)
+    pub since: Option<String>,
 }
 
 // Response for GET /api/me/sync
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SyncResponse {
+    // Items created or changed since `since` (all current items on a full sync); never soft-deleted ones
     pub categories: Vec<Category>,
     pub deadlines: Vec<Deadline>,
-    pub events: Vec<Event>, // Includes owned and accepted invites (updated since `since`)
-    // Split invitations for clarity: those received by me, and those I created for my events?
-    // Let's just return updates to invitations I received for now.
-    // Frontend can use this + event list to figure out status for events I own.
-    pub received_invitations: Vec<EventInvitation>, // Just the base invitation updates
+    pub events: Vec<Event>, // Includes owned and accepted invites
+    pub event_exceptions: Vec<EventException>, // Cancelled/modified occurrences of those events
+    pub received_invitations: Vec<EventInvitation>, // Invitations received by me
     pub shares_created: Vec<ListSharesResponseItem>, // Shares created by me
     pub shares_received: Vec<ListSharesResponseItem>, // Shares received by me (uses same response item struct)
 
-    // Optional: Explicit list of deleted item IDs?
-    // pub deleted_items: DeletedItems,
+    // Items removed from my sync data since `since` (empty on a full sync)
+    pub deleted: DeletedItems,
 
-    // Timestamp of this sync operation on the server
-    pub sync_timestamp: DateTime<Utc>,
+    // Opaque change-log position covering everything above; pass as `since` next time
+    pub sync_token: String,
+}
+
+// Tombstones: deleted items, and items I lost access to (e.g. revoked invitations)
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedItems {
+    pub category_ids: Vec<i32>,
+    pub deadline_ids: Vec<i32>,
+    pub event_ids: Vec<i32>, // Deleted, or no longer accepted/invited; drop their exceptions too
+    pub event_exception_ids: Vec<i32>,
+    pub invitation_ids: Vec<i32>,
+    pub share_ids: Vec<i32>,
+    pub share_categories: Vec<ShareCategoryRef>, // Categories removed from a share
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareCategoryRef {
+    pub share_id: i32,
+    pub category_id: i32,
 }
 
 // Response for GET /api/sync/calendar/shares/:share_id
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SyncSharedCalendarResponse {
-    // Share details (updated since 'since', or current if share is new since 'since')
+    // Share details (changed since 'since', or current on a full sync)
     // Include deleted_at here so client knows if share was revoked
     pub share_info: Option<CalendarShare>, // Option in case the share was deleted since last sync
 
-    // Events relevant to the share (updated since 'since', filtered by categories/privacy)
+    // Events relevant to the share (changed since 'since', filtered by categories/privacy)
     pub events: Vec<SharedCalendarEvent>,
 
-    // Deadlines relevant to the share (updated since 'since', filtered by categories/privacy)
+    // Deadlines relevant to the share (changed since 'since', filtered by categories/privacy)
     pub deadlines: Vec<SharedCalendarDeadline>,
 
-    // Timestamp of this sync operation on the server
-    pub sync_timestamp: DateTime<Utc>,
+    // Items that left the shared view since 'since'
+    pub deleted: SharedCalendarDeletedItems,
+
+    // Opaque position in the share owner's change log; pass as `since` next time
+    pub sync_token: String,
+}
+
+// Tombstones of a shared calendar view: deleted items, items moved out of the shared categories,
+// and items of categories removed from the share
+#[derive(Debug, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedCalendarDeletedItems {
+    pub event_ids: Vec<i32>,
+    pub deadline_ids: Vec<i32>,
+}
+
+// A change notification pushed on GET /api/sync/stream (also the NOTIFY payload of log_sync_change())
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncChange {
+    pub entity: String, // "category", "deadline", "event", "eventException", "invitation", "share", "shareCategory" or "openShare"
+    pub id: String, // Numeric IDs as strings; UUID for open shares; "<shareId>:<categoryId>" for share categories
+    pub deleted: bool, // Removed from the user's sync data
+    #[serde(default, skip_serializing)]
+    pub user_id: i32, // User whose change log the change belongs to (routing only)
+    #[serde(default, skip_serializing)]
+    pub change_id: i64, // Position in that user's change log (sent as the SSE event id)
+    pub updated_at: DateTime<Utc>,
+}
+
+// --- POST /api/sync (client-to-server batch) ---
+
+// One client-side change. `baseUpdatedAt` is the server `updatedAt` the client last saw for the item;
+// if the server copy changed after that, the operation is reported as a conflict instead of applied.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum SyncOperation<C, U> {
+    #[serde(rename_all = "camelCase")]
+    Create {
+        client_id: Option<String>, // Client-side identifier, echoed back in the result
+        category_client_id: Option<String>, // Use a category created earlier in the same batch
+        data: C,
+    },
+    #[serde(rename_all = "camelCase")]
+    Update {
+        id: i32,
+        base_updated_at: DateTime<Utc>,
+        category_client_id: Option<String>,
+        data: U,
+    },
+    #[serde(rename_all = "camelCase")]
+    Delete {
+        id: i32,
+        base_updated_at: DateTime<Utc>,
+    },
+}
+
+impl<C, U> SyncOperation<C, U> {
+    pub fn kind(&self) -> SyncOperationKind {
+        match self {
+            SyncOperation::Create { .. } => SyncOperationKind::Create,
+            SyncOperation::Update { .. } => SyncOperationKind::Update,
+            SyncOperation::Delete { .. } => SyncOperationKind::Delete,
+        }
+    }
+
+    pub fn client_id(&self) -> Option<String> {
+        match self {
+            SyncOperation::Create { client_id, .. } => client_id.clone(),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> Option<i32> {
+        match self {
+            SyncOperation::Create { .. } => None,
+            SyncOperation::Update { id, .. } | SyncOperation::Delete { id, .. } => Some(*id),
+        }
+    }
 }
 
-// Optional structure for deleted items if not relying solely on deleted_at
-// #[derive(Debug, Serialize, Default)]
-// #[serde(rename_all = "camelCase")]
-// pub struct DeletedItems {
-//     pub category_ids: Vec<i32>,
-//     pub deadline_ids: Vec<i32>,
-//     pub event_ids: Vec<i32>,
-//     pub invitation_ids: Vec<i32>,
-//     pub share_ids: Vec<i32>,
-// }
\ No newline at end of file
+// Payload for POST /api/sync. Categories are applied first, then events, then deadlines.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientSyncPayload {
+    pub since: Option<String>, // Last sync timestamp; server changes since then are returned alongside the results
+    #[serde(default)]
+    pub categories: Vec<SyncOperation<CreateCategoryPayload, UpdateCategoryPayload>>,
+    #[serde(default)]
+    pub events: Vec<SyncOperation<CreateEventPayload, UpdateEventPayload>>,
+    #[serde(default)]
+    pub deadlines: Vec<SyncOperation<CreateDeadlinePayload, UpdateDeadlinePayload>>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncEntityKind {
+    Category,
+    Event,
+    Deadline,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncOperationKind {
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncItemStatus {
+    Applied,
+    Conflict, // Server copy changed after baseUpdatedAt; `server` holds the winning version
+    Rejected, // Invalid or not found; see `error`
+}
+
+// Server-side version of a synced item
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SyncRecord {
+    Category(Category),
+    Event(Event),
+    Deadline(Deadline),
+}
+
+impl SyncRecord {
+    pub fn id(&self) -> i32 {
+        match self {
+            SyncRecord::Category(c) => c.category_id,
+            SyncRecord::Event(e) => e.event_id,
+            SyncRecord::Deadline(d) => d.deadline_id,
+        }
+    }
+}
+
+// Outcome for one operation of the batch
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncItemResult {
+    pub entity: SyncEntityKind,
+    pub op: SyncOperationKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub id: Option<i32>, // Server ID (assigned on create)
+    pub status: SyncItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub server: Option<SyncRecord>, // Current server version after the operation (or the conflicting one)
+}
+
+// Response for POST /api/sync: per-item results plus the regular sync payload
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientSyncResponse {
+    pub results: Vec<SyncItemResult>,
+    #[serde(flatten)]
+    pub changes: SyncResponse,
+}
\ No newline at end of file
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use crate::models::enums::TokenScope;
+
+// --- API Payloads ---
+
+// For POST /api/me/tokens
+#[derive(Deserialize, Validate, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTokenPayload {
+    #[validate(required, length(min = 1, max = 100))]
+    pub name: Option<String>, // What the token is for, e.g. "Home Assistant"
+    #[validate(required, length(min = 1))]
+    pub scopes: Option<Vec<TokenScope>>,
+    #[validate(range(min = 1, max = 3650))]
+    pub expires_in_days: Option<i64>, // None = never expires
+}
+
+// --- API Responses ---
+
+// A personal access token, as listed by GET /api/me/tokens (never includes the token itself)
+#[derive(Debug, FromRow, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenInfo {
+    pub token_id: i32,
+    pub name: String,
+    pub token_prefix: String, // First characters of the token
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+// Response for POST /api/me/tokens
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedTokenResponse {
+    pub token: String, // Shown only this once
+    #[serde(flatten)]
+    pub info: TokenInfo,
+}
@@ -155,19 +155,25 @@ pub struct CompleteTfaSetupPayload {
 pub struct DisableTfaPayload {
     #[validate(required)]
     pub password: Option<String>, // Require password to disable 2FA
-    // Optional: require current 2FA code too?
     #[validate(required)]
-    pub tfa_code: Option<String>,
+    pub tfa_code: Option<String>, // Current TOTP code, or a recovery code
+}
+
+// Payload for regenerating 2FA recovery codes
+#[derive(Deserialize, Validate, Debug)]
+pub struct RegenerateRecoveryCodesPayload {
+    #[validate(required)]
+    pub password: Option<String>, // Require password to replace the codes
 }
 
 // Payload for verifying 2FA code during login (Implicit Two-Step)
 #[derive(Deserialize, Validate, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct VerifyTfaLoginPayload {
+    #[validate(required, length(min = 1))]
+    pub challenge_token: Option<String>, // From the TfaRequiredResponse of the password step
     #[validate(required)]
-    pub user_id: Option<i32>, // Identify the user who passed step 1 login
-    #[validate(required)]
-    pub tfa_code: Option<String>, // The TOTP code
+    pub tfa_code: Option<String>, // The TOTP code, or a recovery code
 }
 
 // --- API Responses ---
@@ -188,11 +194,17 @@ pub struct UserData {
     #[serde(rename = "dateOfBirth", skip_serializing_if = "Option::is_none")]
     pub date_of_birth: Option<NaiveDate>,
     pub tfa_enabled: Option<bool>, // <-- Add this
+    #[serde(rename = "tfaRecoveryCodesRemaining", skip_serializing_if = "Option::is_none")]
+    pub tfa_recovery_codes_remaining: Option<i64>, // Only reported by GET /api/me when 2FA is enabled
 }
 
 #[derive(Serialize, Debug)]
 pub struct AuthResponse {
-    pub token: String,
+    pub token: String, // Short-lived access token
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String, // Exchange at POST /api/auth/refresh for new tokens
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64, // Access token lifetime in seconds
     pub user: UserData,
     pub code_prefix: Option<String>, // Optional prefix for the verificatiion and reset codes
 }
@@ -207,7 +219,10 @@ pub struct CodeResponse {
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct TfaRequiredResponse {
-    pub user_id: i32, // Client needs this to call the verify-tfa endpoint
+    pub user_id: i32,
+    pub challenge_token: String, // Client sends this to the verify-tfa endpoint; single use
+    pub expires_in: i64, // Challenge lifetime in seconds
+    pub passkey_available: bool, // A passkey can be used instead of the code (/api/auth/passkey/verify-tfa)
     // Add other user details client might need *before* full authentication?
     // e.g., display_name, email - BE CAREFUL not to send sensitive data
     // For security, maybe only send userId and a flag.
@@ -231,6 +246,14 @@ pub struct InitiateTfaResponse {
     // Frontend uses these to generate QR code and show the secret text
 }
 
+// --- Response struct for Complete TFA Setup and Regenerate Recovery Codes ---
+// The codes are only ever shown here; the server keeps hashes
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>, // One-time codes, accepted in place of a TOTP code
+}
+
 // --- Database Model ---
 
 // Represents the structure in the 'users' table
@@ -0,0 +1,231 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use tokio::time::MissedTickBehavior;
+use crate::email::EmailService;
+use crate::errors::AppError;
+use crate::models::{
+    enums::{EventInvitationStatus, ReminderChannel},
+    event::Event,
+};
+use crate::sync_feed::REMINDER_CHANNEL;
+use crate::utils::calendar::fetch_event_exceptions;
+use crate::utils::recurrence::expand_events;
+
+// How often due reminders are looked up
+const POLL_INTERVAL_SECONDS: u64 = 30;
+// Reminders missed by up to this much (e.g. while the server was down) still fire; older ones are dropped
+const MISSED_REMINDER_GRACE_MINUTES: i64 = 60;
+// Delivery records are only needed while their reminder could still be due
+const DELIVERY_RETENTION_DAYS: i64 = 7;
+
+// A reminder that is due, resolved to the occurrence (or due date) it is for
+struct DueReminder {
+    reminder_id: i32,
+    user_id: i32,
+    email: String,
+    channel: ReminderChannel,
+    minutes_before: i32,
+    event_id: Option<i32>,
+    deadline_id: Option<i32>,
+    title: String,
+    occurrence_time: DateTime<Utc>, // Identifies the occurrence (original start, or due date)
+    at: DateTime<Utc>,              // When it actually starts / is due
+}
+
+// Fires event and deadline reminders in the background (started from main.rs).
+// Every delivery is claimed in reminder_deliveries first, so a reminder never fires twice for the same
+// occurrence, even across restarts or with several instances polling the same database.
+pub fn start(pool: PgPool, email_service: EmailService) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(POLL_INTERVAL_SECONDS));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            if let Err(e) = fire_due_reminders(&pool, &email_service).await {
+                tracing::error!("Reminder scheduler run failed: {:?}", e);
+            }
+        }
+    });
+}
+
+async fn fire_due_reminders(pool: &PgPool, email_service: &EmailService) -> Result<(), AppError> {
+    let now = Utc::now();
+    let earliest = now - Duration::minutes(MISSED_REMINDER_GRACE_MINUTES);
+
+    let mut due = due_event_reminders(pool, earliest, now).await?;
+    due.extend(due_deadline_reminders(pool, earliest, now).await?);
+
+    for reminder in due {
+        // Claim the delivery; another run (or instance) already handled it if the row exists
+        let claimed = sqlx::query!(
+            "INSERT INTO reminder_deliveries (reminder_id, occurrence_time) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            reminder.reminder_id,
+            reminder.occurrence_time
+        )
+        .execute(pool)
+        .await?
+        .rows_affected() == 1;
+        if !claimed {
+            continue;
+        }
+
+        // A failed delivery is not retried, so it can never turn into a duplicate
+        if let Err(e) = deliver(pool, email_service, &reminder).await {
+            tracing::error!("Failed to deliver reminder {}: {:?}", reminder.reminder_id, e);
+        }
+    }
+
+    sqlx::query!(
+        "DELETE FROM reminder_deliveries WHERE fired_at < $1",
+        now - Duration::days(DELIVERY_RETENTION_DAYS)
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn deliver(pool: &PgPool, email_service: &EmailService, reminder: &DueReminder) -> Result<(), AppError> {
+    match reminder.channel {
+        ReminderChannel::Email => {
+            email_service
+                .send_reminder_email(&reminder.email, &reminder.title, reminder.at, reminder.deadline_id.is_some())
+                .await
+        }
+        ReminderChannel::InApp => {
+            // Picked up by the sync feed and sent to the user's open sync streams
+            sqlx::query!(
+                r#"
+                SELECT pg_notify($1, json_build_object(
+                    'userId', $2::INTEGER, 'reminderId', $3::INTEGER, 'eventId', $4::INTEGER, 'deadlineId', $5::INTEGER,
+                    'title', $6::TEXT, 'at', $7::TIMESTAMPTZ, 'minutesBefore', $8::INTEGER
+                )::TEXT)
+                "#,
+                REMINDER_CHANNEL,
+                reminder.user_id,
+                reminder.reminder_id,
+                reminder.event_id,
+                reminder.deadline_id,
+                reminder.title,
+                reminder.at,
+                reminder.minutes_before
+            )
+            .execute(pool)
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+// --- Helper: Event reminders with an occurrence starting `minutes_before` after a moment in (earliest, now] ---
+// Only for live events the user still owns or has accepted.
+async fn due_event_reminders(pool: &PgPool, earliest: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<DueReminder>, AppError> {
+    // Recurring series can't be narrowed down by their first start; the expansion below handles them
+    let rows = sqlx::query!(
+        r#"
+        SELECT r.reminder_id, r.user_id, r.event_id as "event_id!", r.minutes_before,
+               r.channel as "channel!: ReminderChannel", u.email
+        FROM reminders r
+        JOIN events e ON e.event_id = r.event_id
+        JOIN users u ON u.user_id = r.user_id
+        WHERE r.deleted_at IS NULL AND e.deleted_at IS NULL AND u.deleted_at IS NULL
+          AND ( e.user_id = r.user_id
+             OR EXISTS (
+                 SELECT 1 FROM event_invitations
+                 WHERE event_id = e.event_id AND invited_user_id = r.user_id AND status = $3 AND deleted_at IS NULL
+             ) )
+          AND e.start_time - make_interval(mins => r.minutes_before) <= $2
+          AND ( COALESCE(e.rrule, '') <> '' OR e.start_time - make_interval(mins => r.minutes_before) > $1 )
+        "#,
+        earliest,
+        now,
+        EventInvitationStatus::Accepted as EventInvitationStatus
+    )
+    .fetch_all(pool)
+    .await?;
+    if rows.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut event_ids: Vec<i32> = rows.iter().map(|r| r.event_id).collect();
+    event_ids.sort_unstable();
+    event_ids.dedup();
+    let events = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+           event_id, user_id, category_id, title, description as "description!: _",
+           start_time, end_time, location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE event_id = ANY($1)
+        "#,
+        &event_ids
+    )
+    .fetch_all(pool)
+    .await?;
+    let exceptions = fetch_event_exceptions(pool, &event_ids).await?;
+
+    let mut due = Vec::new();
+    for row in rows {
+        let Some(event) = events.iter().find(|e| e.event_id == row.event_id) else { continue };
+        let lead = Duration::minutes(row.minutes_before as i64);
+        let (from, to) = (earliest + lead, now + lead);
+
+        // Occurrences overlapping the window; keep those that start in it
+        for occurrence in expand_events(std::slice::from_ref(event), &exceptions, from, to + Duration::seconds(1)) {
+            if occurrence.occurrence_start <= from || occurrence.occurrence_start > to {
+                continue;
+            }
+            due.push(DueReminder {
+                reminder_id: row.reminder_id,
+                user_id: row.user_id,
+                email: row.email.clone(),
+                channel: row.channel,
+                minutes_before: row.minutes_before,
+                event_id: Some(event.event_id),
+                deadline_id: None,
+                title: occurrence.title.unwrap_or_else(|| event.title.clone()),
+                occurrence_time: occurrence.original_start,
+                at: occurrence.occurrence_start,
+            });
+        }
+    }
+    Ok(due)
+}
+
+// --- Helper: Deadline reminders whose due date minus `minutes_before` is in (earliest, now] ---
+async fn due_deadline_reminders(pool: &PgPool, earliest: DateTime<Utc>, now: DateTime<Utc>) -> Result<Vec<DueReminder>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT r.reminder_id, r.user_id, r.minutes_before, r.channel as "channel!: ReminderChannel",
+               u.email, d.deadline_id, d.title, d.due_date
+        FROM reminders r
+        JOIN deadlines d ON d.deadline_id = r.deadline_id AND d.user_id = r.user_id
+        JOIN users u ON u.user_id = r.user_id
+        WHERE r.deleted_at IS NULL AND d.deleted_at IS NULL AND u.deleted_at IS NULL
+          AND d.due_date - make_interval(mins => r.minutes_before) > $1
+          AND d.due_date - make_interval(mins => r.minutes_before) <= $2
+        "#,
+        earliest,
+        now
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DueReminder {
+            reminder_id: row.reminder_id,
+            user_id: row.user_id,
+            email: row.email,
+            channel: row.channel,
+            minutes_before: row.minutes_before,
+            event_id: None,
+            deadline_id: Some(row.deadline_id),
+            title: row.title,
+            occurrence_time: row.due_date,
+            at: row.due_date,
+        })
+        .collect())
+}
@@ -12,19 +12,28 @@ pub mod share; // Declare the calendar_share submodule
 pub mod shared_calendar; // Declare the shared_calendar submodule
 pub mod calendar; // Declare the calendar submodule
 pub mod sync; // Declare the sync submodule
+pub mod freebusy; // Declare the freebusy submodule
 pub mod tfa; // Declare the tfa submodule
 pub mod ai; // Declare the ai submodule
 pub mod open_share; // Declare the open_share submodule
+pub mod import; // Declare the import submodule
 pub mod health; // Declare the health submodule
 pub mod teapot; // Declare the teapot submodule
 pub mod mirror; // Declare the mirror submodule
+pub mod session; // Declare the session submodule
+pub mod passkey; // Declare the passkey submodule
+pub mod identity; // Declare the identity submodule
+pub mod token; // Declare the token submodule
 
 // Function to create the main API router, combining all sub-routers
 pub fn create_api_router(app_state: AppState) -> Router {
     let auth_router = auth::auth_routes(app_state.clone()); // Pass state
     let me_router = me::me_routes(app_state.clone()); // Pass state
     let calendar_routes = calendar::calendar_routes(app_state.clone()); // Pass state
+    let calendar_export_routes = calendar::calendar_export_routes(app_state.clone()); // Pass state
     let sync_routes = sync::sync_routes(app_state.clone()); // Pass state
+    let freebusy_routes = freebusy::freebusy_routes(app_state.clone()); // Pass state
+    let rsvp_routes = invitation::rsvp_routes(app_state.clone()); // Pass state
     let health_routes = health::health_routes(app_state.clone()); // Pass state
     let teapot_routes = teapot::teapot_routes(app_state.clone()); // Pass state
     let mirror_routes = mirror::mirror_routes(app_state.clone()); // Pass state
@@ -33,7 +42,10 @@ pub fn create_api_router(app_state: AppState) -> Router {
         .nest("/auth", auth_router)
         .nest("/me", me_router)
         .nest("/calendar", calendar_routes) // Group calendar routes under /api/calendar
+        .merge(calendar_export_routes) // /api/calendar.ics
         .nest("/sync", sync_routes) // Group sync routes under /api/sync
+        .nest("/freebusy", freebusy_routes) // POST /api/freebusy
+        .nest("/invitations", rsvp_routes) // GET /api/invitations/respond (RSVP links)
         .nest("/health", health_routes) // Group health routes under /api/health
         .nest("/teapot", teapot_routes) // Group teapot routes under /api/teapot
         .nest("/mirror", mirror_routes) // Group mirror routes under /api/mirror
@@ -1,20 +1,35 @@
 use axum::{
-    routing::post, // Need POST for the AI endpoint
+    routing::{get, post}, // Need POST for the AI endpoint
     Router,
 };
 use crate::AppState; // Import AppState
 use crate::handlers::ai_handler; // Import AI handler
 
-// Function to create the AI sub-router
-pub fn ai_routes(app_state: AppState) -> Router<AppState> { // Explicitly type state
-     Router::new()
+// Function to create the AI sub-router; None when no AI provider is configured (AI_PROVIDER)
+pub fn ai_routes(app_state: AppState) -> Option<Router<AppState>> { // Explicitly type state
+    if app_state.ai_client.is_none() {
+        tracing::info!("No AI provider configured, the AI assistant is disabled.");
+        return None;
+    }
+
+     let router = Router::new()
         // Route: /api/me/ai-assistant (Handles AI prompt with optional files)
         // Nest this under /api/me or /api directly. Let's add to /me routes.
         // If adding directly under /api: Router::new().route("/ai/prompt", post(ai_handler::handle_ai_prompt))
         // If adding under /api/me: Router::new().route("/ai-assistant", post(ai_handler::handle_ai_prompt))
         // Let's follow the initial thought and add it under /api/me/ai-assistant
         .route("/", post(ai_handler::handle_ai_prompt)) // Mounted under /me, becomes /api/me/ai-assistant
+        // Route: /api/me/ai-assistant/apply (Creates the items of a confirmed proposal)
+        // Route: /api/me/ai-assistant/stream (Same as above, answered as Server-Sent Events)
+        .route("/stream", post(ai_handler::stream_ai_prompt))
+        .route("/apply", post(ai_handler::apply_ai_proposal))
+        // Route: /api/me/ai-assistant/usage (Token usage and quotas)
+        .route("/usage", get(ai_handler::get_ai_usage))
+        // Route: /api/me/ai-assistant/threads (Stored conversations)
+        .route("/threads", get(ai_handler::list_ai_threads).delete(ai_handler::delete_ai_threads))
+        .route("/threads/{thread_id}", get(ai_handler::get_ai_thread).delete(ai_handler::delete_ai_thread))
 
         // Make AppState available
-        .with_state(app_state)
+        .with_state(app_state);
+    Some(router)
 }
\ No newline at end of file
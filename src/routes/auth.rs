@@ -1,16 +1,24 @@
 use axum::{
-    routing::post,
+    middleware,
+    routing::{get, post},
     Router,
 };
 use crate::handlers::auth_handler::{
     register_user_handler, login_user_handler,
     verify_email_handler, resend_verification_email_handler,
     forgot_password_handler, reset_password_handler, verify_tfa_login_handler,
+    refresh_token_handler, logout_handler,
 };
+use crate::handlers::oidc_handler::{list_oidc_providers, start_oidc_login, finish_oidc_login};
+use crate::handlers::passkey_handler::{
+    start_passkey_login, finish_passkey_login, start_passkey_tfa, finish_passkey_tfa,
+};
+use crate::middleware::rate_limit::limit_by_ip;
 use crate::state::AppState;
 
 pub fn auth_routes(app_state: AppState) -> Router {
-     Router::new()
+    // Endpoints that check passwords or codes, or send emails, are rate limited per client IP
+    let limited_routes = Router::new()
         .route("/register", post(register_user_handler))
         .route("/login", post(login_user_handler))
         .route("/verify-email", post(verify_email_handler))
@@ -19,5 +27,21 @@ pub fn auth_routes(app_state: AppState) -> Router {
         .route("/reset-password", post(reset_password_handler))
         // This endpoint is called *after* a successful password login if 2FA is required
         .route("/verify-tfa", post(verify_tfa_login_handler))
+        // Passkeys: passwordless login, or instead of the TOTP code after the password step
+        .route("/passkey/login/finish", post(finish_passkey_login))
+        .route("/passkey/verify-tfa/finish", post(finish_passkey_tfa))
+        // Social login: finish is called with the code the provider redirected back with
+        .route("/oidc/{provider}/finish", post(finish_oidc_login))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), limit_by_ip));
+
+     Router::new()
+        .merge(limited_routes)
+        // Session handling with refresh tokens (no access token needed)
+        .route("/refresh", post(refresh_token_handler))
+        .route("/logout", post(logout_handler))
+        .route("/passkey/login/start", post(start_passkey_login))
+        .route("/passkey/verify-tfa/start", post(start_passkey_tfa))
+        .route("/oidc/providers", get(list_oidc_providers))
+        .route("/oidc/{provider}/start", post(start_oidc_login))
         .with_state(app_state)
 }
\ No newline at end of file
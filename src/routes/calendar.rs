@@ -3,7 +3,7 @@ use axum::{
     Router,
 };
 use crate::AppState; // Import AppState
-use crate::handlers::calendar_handler; // Import the calendar handler
+use crate::handlers::{calendar_handler, ics_handler}; // Import the calendar handlers
 use super::shared_calendar; // Import the shared_calendar module
 
 // Function to create the calendar sub-router
@@ -19,19 +19,28 @@ pub fn calendar_routes(app_state: AppState) -> Router { // Explicitly type state
                   // Route: /api/calendar/shares (List calendars shared WITH the authenticated user)
                   .route("/", get(calendar_handler::list_received_shares))
                   // Route: /api/calendar/shares/:share_id (View a specific private shared calendar)
-                  .route("/{share_id}", get(calendar_handler::get_shared_calendar))
+                  // Also serves /api/calendar/shares/:share_id.ics (iCalendar export)
+                  .route("/{share_id}", get(ics_handler::shared_calendar_route))
                   .with_state(app_state.clone())
         )
 
         // --- NEW: OPEN SHARED CALENDAR ROUTE (Public) ---
-        // Route: /api/calendar/open-shares/:uuid
+        // Route: /api/calendar/open-shares/:uuid (and /api/calendar/open-shares/:uuid.ics, a subscribable feed)
         .route(
             "/open-shares/{uuid}", // Use :uuid for path parameter
-            get(calendar_handler::get_open_shared_calendar) // Public handler
+            get(ics_handler::open_shared_calendar_route) // Public handler
         )
         // No .with_state needed on the public route itself, handler accesses it via State extractor
 
         // Make AppState available to handlers within this MAIN router (calendar_routes)
         // (Used by get_user_calendar directly, and passed down to nested routers)
         .with_state(app_state)
+}
+
+// Function to create the iCalendar export route for the user's own calendar
+// Mounted at /api/calendar.ics, next to (not under) /api/calendar
+pub fn calendar_export_routes(app_state: AppState) -> Router {
+    Router::new()
+        .route("/calendar.ics", get(ics_handler::export_user_calendar))
+        .with_state(app_state)
 }
\ No newline at end of file
@@ -3,7 +3,7 @@ use axum::{
     Router,
 };
 use crate::AppState; // Import AppState
-use crate::handlers::deadline_handler; // Import deadline handlers
+use crate::handlers::{deadline_handler, reminder_handler}; // Import deadline and reminder handlers
 
 // Function to create the deadlines sub-router
 pub fn deadlines_routes(app_state: AppState) -> Router<AppState> {
@@ -21,6 +21,17 @@ pub fn deadlines_routes(app_state: AppState) -> Router<AppState> {
             .put(deadline_handler::update_deadline)   // PUT to update by ID
             .delete(deadline_handler::delete_deadline) // DELETE by ID
         )
+        // Reminders: /api/me/deadlines/{deadline_id}/reminders
+        .route(
+            "/{deadline_id}/reminders",
+            post(reminder_handler::create_deadline_reminder) // POST to add a reminder
+            .get(reminder_handler::list_deadline_reminders) // GET to list reminders
+        )
+        .route(
+            "/{deadline_id}/reminders/{reminder_id}",
+            put(reminder_handler::update_deadline_reminder) // PUT to update
+            .delete(reminder_handler::delete_deadline_reminder) // DELETE to remove
+        )
         // Make AppState available to all handlers within this router
         .with_state(app_state)
 }
\ No newline at end of file
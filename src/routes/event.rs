@@ -4,7 +4,7 @@ use axum::{
 };
 use crate::AppState; // Import AppState
 use crate::handlers::{
-    event_handler, invitation_handler
+    event_handler, event_exception_handler, invitation_handler, scheduling_handler, reminder_handler
 }; // Import handlers
 
 // Function to create the events sub-router
@@ -16,6 +16,11 @@ pub fn events_routes(app_state: AppState) -> Router<AppState> {
             post(event_handler::create_event) // POST to create
             .get(event_handler::get_events)  // GET to list all
         )
+        // Meeting time suggestions: /api/me/events/suggest-times
+        .route(
+            "/suggest-times",
+            post(scheduling_handler::suggest_meeting_times) // POST to rank candidate slots
+        )
         // Routes with ID parameter: /api/me/events/{event_id}
         .route(
             "/{event_id}",
@@ -37,12 +42,38 @@ pub fn events_routes(app_state: AppState) -> Router<AppState> {
                 // Route for a specific invitation: /api/me/events/:event_id/invitations/:invitation_id
                .route("/{invitation_id}",
                    delete(invitation_handler::revoke_invitation) // DELETE to revoke
+               )
+                // Invitations for addresses without an account: /api/me/events/:event_id/invitations/pending
+               .route("/pending",
+                   get(invitation_handler::list_pending_invitations_for_event) // GET to list pending invites
+               )
+               .route("/pending/{pending_invitation_id}",
+                   delete(invitation_handler::revoke_pending_invitation) // DELETE to revoke
                )
                 // The handlers for these nested routes also need AppState.
                 // Since this nested router is created within events_routes,
                 // which has app_state available, we can pass it down.
                 .with_state(app_state.clone()) // Pass AppState down to this nested router
        )
+       // --- PER-OCCURRENCE EXCEPTIONS ---
+       // /api/me/events/{event_id}/occurrences/{original_start} (original_start is an RFC3339 timestamp)
+       .route(
+           "/{event_id}/occurrences/{original_start}",
+           put(event_exception_handler::override_occurrence) // PUT to modify one occurrence
+           .delete(event_exception_handler::delete_occurrence) // DELETE to cancel one occurrence
+       )
+       // --- REMINDERS ---
+       // /api/me/events/{event_id}/reminders
+       .route(
+           "/{event_id}/reminders",
+           post(reminder_handler::create_event_reminder) // POST to add a reminder
+           .get(reminder_handler::list_event_reminders) // GET to list my reminders for the event
+       )
+       .route(
+           "/{event_id}/reminders/{reminder_id}",
+           put(reminder_handler::update_event_reminder) // PUT to update
+           .delete(reminder_handler::delete_event_reminder) // DELETE to remove
+       )
        // Make AppState available to all handlers within this MAIN router (events_routes)
         .with_state(app_state)
 }
\ No newline at end of file
@@ -0,0 +1,17 @@
+use axum::{
+    routing::post,
+    Router,
+};
+use crate::AppState;
+use crate::handlers::freebusy_handler;
+
+// Function to create the free/busy sub-router
+pub fn freebusy_routes(app_state: AppState) -> Router {
+    Router::new()
+        // Route: POST /api/freebusy (Busy intervals of several users within a window)
+        .route(
+            "/",
+            post(freebusy_handler::query_free_busy)
+        )
+        .with_state(app_state)
+}
@@ -0,0 +1,16 @@
+use axum::{
+    routing::{delete, get},
+    Router,
+};
+use crate::AppState;
+use crate::handlers::oidc_handler;
+
+// Function to create the /api/me/identities sub-router
+pub fn identity_routes(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        // Base route: /api/me/identities
+        .route("/", get(oidc_handler::list_identities)) // GET to list linked provider accounts
+        // /api/me/identities/{identity_id}
+        .route("/{identity_id}", delete(oidc_handler::delete_identity))
+        .with_state(app_state)
+}
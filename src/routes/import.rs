@@ -0,0 +1,14 @@
+use axum::{
+    routing::post,
+    Router,
+};
+use crate::AppState; // Import AppState
+use crate::handlers::import_handler; // Import the import handler
+
+// Function to create the import sub-router
+pub fn import_routes(app_state: AppState) -> Router<AppState> { // Explicitly type state
+    Router::new()
+        // Route: /api/me/import/ics (Multipart upload of an iCalendar file)
+        .route("/ics", post(import_handler::import_ics))
+        .with_state(app_state)
+}
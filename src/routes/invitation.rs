@@ -14,4 +14,12 @@ pub fn invitations_routes(app_state: AppState) -> Router<AppState> {
         .route("/{invitation_id}/status", put(invitation_handler::respond_to_invitation))
         // Make AppState available
         .with_state(app_state)
+}
+
+// Function to create the /api/invitations sub-router (Public, token-authenticated RSVP links)
+pub fn rsvp_routes(app_state: AppState) -> Router {
+    Router::new()
+        // Route: GET /api/invitations/respond?token= (One-click response from the invitation email)
+        .route("/respond", get(invitation_handler::respond_via_link))
+        .with_state(app_state)
 }
\ No newline at end of file
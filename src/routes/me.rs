@@ -5,7 +5,7 @@ use crate::middleware::auth::AuthenticatedUser; // Import the extractor
 use axum::Json; // For returning JSON responses
 use serde_json::json; // For simple JSON responses
 
-use super::{category, deadline, event, invitation, share, tfa, ai, open_share}; // Import submodules
+use super::{category, deadline, event, invitation, share, tfa, ai, open_share, import, session, passkey, identity, token}; // Import submodules
 
 // Import me_handler for the /me routes
 use crate::handlers::me_handler::{
@@ -48,10 +48,14 @@ pub fn me_routes(app_state: AppState) -> Router {
     let invitations_router = invitation::invitations_routes(app_state.clone());
     let shares_router = share::share_routes(app_state.clone());
     let tfa_router = tfa::tfa_routes(app_state.clone());
-    let ai_router = ai::ai_routes(app_state.clone());
     let open_share_router = open_share::open_share_routes(app_state.clone());
+    let import_router = import::import_routes(app_state.clone());
+    let session_router = session::session_routes(app_state.clone());
+    let passkey_router = passkey::passkey_routes(app_state.clone());
+    let identity_router = identity::identity_routes(app_state.clone());
+    let token_router = token::token_routes(app_state.clone());
 
-    Router::new()
+    let router = Router::new()
        // --- Base /api/me routes (GET, PUT, DELETE for the user themselves) ---
        .route(
            "/",
@@ -69,9 +73,18 @@ pub fn me_routes(app_state: AppState) -> Router {
        // Make AppState available to direct /me handlers (like get_authenticated_user_info)
        // --- NEW 2FA Routes (Protected under /me/tfa) ---
        .nest("/tfa", tfa_router) // /api/me/tfa
-        // --- NEW AI Assistant Route ---
-       .nest("/ai-assistant", ai_router) // Nest the AI router here. Its route is "/ai-assistant",
-        // so combined path is /me/ai-assistant
+       .nest("/sessions", session_router) // /api/me/sessions
+       .nest("/passkeys", passkey_router) // /api/me/passkeys
+       .nest("/identities", identity_router) // /api/me/identities
+       .nest("/tokens", token_router) // /api/me/tokens
         .nest("/open-shares", open_share_router)
-       .with_state(app_state)
+        .nest("/import", import_router); // /api/me/import
+
+    // --- AI Assistant Route, only when an AI provider is configured ---
+    let router = match ai::ai_routes(app_state.clone()) {
+        Some(ai_router) => router.nest("/ai-assistant", ai_router), // /api/me/ai-assistant
+        None => router,
+    };
+
+    router.with_state(app_state)
 }
\ No newline at end of file
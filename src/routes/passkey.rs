@@ -0,0 +1,19 @@
+use axum::{
+    routing::{delete, get, post},
+    Router,
+};
+use crate::AppState;
+use crate::handlers::passkey_handler;
+
+// Function to create the /api/me/passkeys sub-router
+pub fn passkey_routes(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        // Base route: /api/me/passkeys
+        .route("/", get(passkey_handler::list_passkeys)) // GET to list registered passkeys
+        // Registration ceremony: start returns options for navigator.credentials.create()
+        .route("/register/start", post(passkey_handler::start_passkey_registration))
+        .route("/register/finish", post(passkey_handler::finish_passkey_registration))
+        // /api/me/passkeys/{passkey_id}
+        .route("/{passkey_id}", delete(passkey_handler::delete_passkey))
+        .with_state(app_state)
+}
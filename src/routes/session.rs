@@ -0,0 +1,20 @@
+use axum::{
+    routing::{delete, get},
+    Router,
+};
+use crate::AppState;
+use crate::handlers::session_handler;
+
+// Function to create the /api/me/sessions sub-router (signed-in devices)
+pub fn session_routes(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        // Base route: /api/me/sessions
+        .route(
+            "/",
+            get(session_handler::list_sessions) // GET to list active sessions
+            .delete(session_handler::revoke_other_sessions) // DELETE to sign out all other devices
+        )
+        // /api/me/sessions/{session_id}
+        .route("/{session_id}", delete(session_handler::revoke_session))
+        .with_state(app_state)
+}
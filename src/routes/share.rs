@@ -14,6 +14,9 @@ pub fn share_routes(app_state: AppState) -> Router<AppState> {
             post(share_handler::create_share) // POST to create
             .get(share_handler::list_shares)  // GET to list all
         )
+        // Shares with addresses that have no account yet: /api/me/shares/pending
+        .route("/pending", get(share_handler::list_pending_shares))
+        .route("/pending/{pending_share_id}", delete(share_handler::delete_pending_share))
         // Routes with ID parameter: /api/me/shares/{share_id}
         .route(
             "/{share_id}",
@@ -1,5 +1,5 @@
 use axum::{
-    routing::{get, post}, // Add post later for POST /api/sync
+    routing::{get, post},
     Router,
 };
 use crate::AppState; // Import AppState
@@ -14,16 +14,21 @@ pub fn sync_routes(app_state: AppState) -> Router { // Explicitly type state
             "/me", // Mounted under /api, so this becomes /api/me/sync
             get(sync_handler::sync_owned_data)
         )
+        // Route: GET /api/sync/stream (Server-Sent Events push of changes to owned data)
+        .route(
+            "/stream",
+            get(sync_handler::sync_stream)
+        )
         // Route: GET /api/sync/calendar/shares/:share_id (Fetch updates for a specific shared calendar view)
         .route(
             "/calendar/shares/{share_id}", // New path
             get(sync_handler::sync_shared_calendar_data)
         )
-        // Route: POST /api/sync (Process client changes - implement later) MAYBE
-        // .route(
-        //     "/",
-        //     post(sync_handler::process_client_sync)
-        // )
+        // Route: POST /api/sync (Apply a batch of client changes, returns per-item results + server changes)
+        .route(
+            "/",
+            post(sync_handler::process_client_sync)
+        )
 
         // Make AppState available to all handlers within this router
         .with_state(app_state)
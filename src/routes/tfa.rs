@@ -5,6 +5,7 @@ use axum::{
 use crate::AppState; // Import AppState
 use crate::handlers::auth_handler::{
     initiate_tfa_setup_handler, complete_tfa_setup_handler, disable_tfa_handler,
+    regenerate_recovery_codes_handler,
 }; // Import TFA handlers
 
 // Function to create the 2FA sub-router
@@ -24,6 +25,11 @@ pub fn tfa_routes(app_state: AppState) -> Router<AppState> {
     .route(
         "/disable",
     post(disable_tfa_handler)
+)
+    // Route: /api/me/tfa/recovery-codes/regenerate (Replace recovery codes)
+    .route(
+        "/recovery-codes/regenerate",
+    post(regenerate_recovery_codes_handler)
 )
     // These handlers require authentication via AuthenticatedUser extractor,
     // which runs before the handler, even though the router itself doesn't
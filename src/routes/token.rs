@@ -0,0 +1,16 @@
+use axum::{
+    routing::{delete, get},
+    Router,
+};
+use crate::AppState;
+use crate::handlers::token_handler;
+
+// Function to create the /api/me/tokens sub-router
+pub fn token_routes(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        // Base route: /api/me/tokens
+        .route("/", get(token_handler::list_tokens).post(token_handler::create_token))
+        // /api/me/tokens/{token_id}
+        .route("/{token_id}", delete(token_handler::revoke_token))
+        .with_state(app_state)
+}
@@ -2,12 +2,20 @@ use sqlx::postgres::PgPool;
 use std::sync::Arc;
 use crate::config::Config;
 use crate::email::EmailService;
-use crate::ai::OpenAIClient;
+use crate::ai::AiClient;
+use crate::sync_feed::SyncFeed;
+use crate::auth::oidc::OidcClient;
+use crate::middleware::rate_limit::RateLimiter;
+use webauthn_rs::Webauthn;
 
 #[derive(Clone)]
 pub struct AppState {
    pub pool: PgPool,
    pub config: Arc<Config>,
    pub email_service: EmailService,
-   pub openai_client: OpenAIClient,
+   pub ai_client: Option<AiClient>, // None when no AI provider is configured
+   pub sync_feed: SyncFeed,
+   pub webauthn: Arc<Webauthn>, // Passkey relying party
+   pub auth_rate_limiter: RateLimiter, // Requests per IP on the auth endpoints
+   pub oidc: OidcClient, // Social login providers
 }
\ No newline at end of file
@@ -0,0 +1,72 @@
+use sqlx::postgres::{PgListener, PgPool};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use crate::errors::AppError;
+use crate::models::reminder::ReminderNotification;
+use crate::models::sync::SyncChange;
+
+// Channel used by the log_sync_change() function (sql/setup.sql)
+const SYNC_CHANNEL: &str = "sync_changes";
+// Channel used by the reminder scheduler for in-app reminders (src/reminder_scheduler.rs)
+pub const REMINDER_CHANNEL: &str = "reminders";
+// Messages buffered per subscriber before it is considered lagging
+const FEED_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub enum SyncFeedMessage {
+    Change(SyncChange),
+    Reminder(ReminderNotification), // A reminder fired for the in-app channel
+    Resync, // Notifications may have been lost (listener reconnected); subscribers should pull instead
+}
+
+// Fans out Postgres change notifications and in-app reminders to all open sync streams.
+// A single LISTEN connection is shared by every subscriber.
+#[derive(Clone)] // SyncFeed needs to be cloneable to be in AppState
+pub struct SyncFeed {
+    sender: broadcast::Sender<SyncFeedMessage>,
+}
+
+impl SyncFeed {
+    pub async fn start(pool: &PgPool) -> Result<Self, AppError> {
+        let mut listener = PgListener::connect_with(pool).await.map_err(AppError::DatabaseConnectionError)?;
+        listener.listen_all([SYNC_CHANNEL, REMINDER_CHANNEL]).await.map_err(AppError::DatabaseConnectionError)?;
+
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        let feed_sender = sender.clone();
+        tokio::spawn(async move {
+            loop {
+                // Send errors only mean nobody is subscribed right now
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => {
+                        let message = if notification.channel() == REMINDER_CHANNEL {
+                            serde_json::from_str::<ReminderNotification>(notification.payload()).map(SyncFeedMessage::Reminder)
+                        } else {
+                            serde_json::from_str::<SyncChange>(notification.payload()).map(SyncFeedMessage::Change)
+                        };
+                        match message {
+                            Ok(message) => {
+                                let _ = feed_sender.send(message);
+                            }
+                            Err(e) => tracing::warn!("Ignoring malformed {} notification '{}': {}", notification.channel(), notification.payload(), e),
+                        }
+                    }
+                    Ok(None) => {
+                        // The connection was lost and has been re-established (and re-subscribed)
+                        tracing::warn!("Sync feed listener reconnected; notifications may have been missed");
+                        let _ = feed_sender.send(SyncFeedMessage::Resync);
+                    }
+                    Err(e) => {
+                        tracing::error!("Sync feed listener error: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await; // Retry reconnecting
+                    }
+                }
+            }
+        });
+
+        Ok(Self { sender })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncFeedMessage> {
+        self.sender.subscribe()
+    }
+}
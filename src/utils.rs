@@ -1,2 +1,8 @@
 pub mod security;
-pub mod calendar;
\ No newline at end of file
+pub mod calendar;
+pub mod recurrence;
+pub mod pagination;pub mod ics;
+pub mod sync_token;
+pub mod freebusy;
+pub mod scheduling;
+pub mod pending_invites;
\ No newline at end of file
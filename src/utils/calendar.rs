@@ -1,6 +1,14 @@
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 use crate::errors::AppError;
+use crate::models::calendar::{CalendarViewParams, EventOccurrence};
+use crate::models::event_exception::EventException;
+
+// Upper bound on how far a single request may expand recurring events
+const MAX_EXPANSION_WINDOW_DAYS: i64 = 366;
+
+// A [from, to) time range
+pub type TimeWindow = (DateTime<Utc>, DateTime<Utc>);
 
 // Helper to parse RFC3339 timestamp strings (like "2023-10-27T10:00:00Z")
 pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, AppError> {
@@ -57,4 +65,75 @@ pub async fn validate_category_ids(pool: &PgPool, owner_user_id: i32, category_i
     }
 
     Ok(())
+}
+
+// --- Helper: Resolve the expansion window for calendar views ---
+// Returns None when expand is not requested, otherwise the validated [from, to) range.
+pub fn resolve_expansion_window(params: &CalendarViewParams) -> Result<Option<TimeWindow>, AppError> {
+    if !params.expand.unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let (from, to) = match (params.from.as_deref(), params.to.as_deref()) {
+        (Some(from), Some(to)) => (parse_timestamp(from)?, parse_timestamp(to)?),
+        _ => {
+            let mut err = validator::ValidationErrors::new();
+            err.add("from", validator::ValidationError::new("from_and_to_required_with_expand"));
+            return Err(AppError::ValidationFailed(err));
+        }
+    };
+
+    validate_time_window(from, to).map(Some)
+}
+
+// --- Helper: Check that [from, to) is non-empty and short enough to expand recurring events over ---
+pub fn validate_time_window(from: DateTime<Utc>, to: DateTime<Utc>) -> Result<TimeWindow, AppError> {
+    if to <= from || (to - from).num_days() > MAX_EXPANSION_WINDOW_DAYS {
+        let mut err = validator::ValidationErrors::new();
+        err.add("to", validator::ValidationError::new("invalid_expansion_window"));
+        return Err(AppError::ValidationFailed(err));
+    }
+
+    Ok((from, to))
+}
+
+// --- Helper: Fetch the occurrence exceptions for a set of events ---
+pub async fn fetch_event_exceptions(pool: &PgPool, event_ids: &[i32]) -> Result<Vec<EventException>, AppError> {
+    if event_ids.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let exceptions = sqlx::query_as!(
+        EventException,
+        r#"
+        SELECT
+            exception_id, event_id, original_occurrence_time, is_deleted,
+            title as "title!: _", description as "description!: _",
+            start_time as "start_time!: _", end_time as "end_time!: _", location as "location!: _",
+            created_at as "created_at!", updated_at as "updated_at!"
+        FROM event_exceptions
+        WHERE event_id = ANY($1)
+        ORDER BY event_id, original_occurrence_time
+        "#,
+        event_ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(exceptions)
+}
+
+// --- Helper: Strip override details for limited (busy-only) shares ---
+// Timing is kept so the series still renders correctly; everything descriptive is cleared.
+pub fn mask_exception_details(occurrences: Option<&mut Vec<EventOccurrence>>, exceptions: &mut [EventException]) {
+    for exception in exceptions.iter_mut() {
+        exception.title = None;
+        exception.description = None;
+        exception.location = None;
+    }
+    for occurrence in occurrences.into_iter().flatten() {
+        occurrence.title = None;
+        occurrence.description = None;
+        occurrence.location = None;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::errors::AppError;
+use crate::models::{
+    calendar::EventOccurrence,
+    enums::{EventInvitationStatus, SharePrivacyLevel},
+    event::Event,
+    freebusy::BusyInterval,
+};
+use crate::utils::calendar::{fetch_event_exceptions, TimeWindow};
+use crate::utils::recurrence::expand_events;
+
+// --- Helper: Everything that makes a user busy within [from, to) ---
+// Owned events and accepted invitations, with recurring series expanded and exceptions applied.
+// Returns the events (for details lookups) and their occurrences ordered by start.
+pub async fn load_busy_occurrences(
+    pool: &PgPool,
+    user_id: i32,
+    (from, to): TimeWindow,
+) -> Result<(Vec<Event>, Vec<EventOccurrence>), AppError> {
+    // Recurring series pass the 'from' check and are narrowed down by the expansion
+    let events = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT
+           event_id, user_id, category_id, title, description as "description!: _",
+           start_time, end_time, location as "location!: _", rrule as "rrule!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM events
+        WHERE ( user_id = $1
+           OR event_id IN (
+               SELECT event_id FROM event_invitations
+               WHERE invited_user_id = $1 AND status = $2 AND deleted_at IS NULL
+           ) )
+          AND deleted_at IS NULL
+          AND ( end_time > $3 OR start_time >= $3 OR COALESCE(rrule, '') <> '' )
+          AND start_time < $4
+        "#,
+        user_id,
+        EventInvitationStatus::Accepted as EventInvitationStatus,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let event_ids: Vec<i32> = events.iter().map(|e| e.event_id).collect();
+    let exceptions = fetch_event_exceptions(pool, &event_ids).await?;
+    let occurrences = expand_events(&events, &exceptions, from, to);
+
+    Ok((events, occurrences))
+}
+
+// --- Helper: Merge occurrences into sorted, non-overlapping busy intervals clipped to the window ---
+// Touching intervals are joined; zero-length occurrences don't block any time.
+pub fn merge_busy_intervals(occurrences: &[EventOccurrence], (from, to): TimeWindow) -> Vec<BusyInterval> {
+    let mut spans: Vec<(DateTime<Utc>, DateTime<Utc>)> = occurrences
+        .iter()
+        .map(|o| (o.occurrence_start.max(from), o.occurrence_end.min(to)))
+        .filter(|(start, end)| start < end)
+        .collect();
+    spans.sort();
+
+    let mut merged: Vec<BusyInterval> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => merged.push(BusyInterval { start, end }),
+        }
+    }
+    merged
+}
+
+// --- Helper: Categories of `owner_user_id` the viewer may see in detail, with the privacy level ---
+// Granted by an active calendar share to the viewer, or by an open share whose link the viewer holds.
+// When several shares cover a category the most permissive level wins.
+pub async fn shared_category_levels(
+    pool: &PgPool,
+    owner_user_id: i32,
+    viewer_user_id: i32,
+    open_share_ids: &[Uuid],
+) -> Result<HashMap<i32, SharePrivacyLevel>, AppError> {
+    let grants = sqlx::query!(
+        r#"
+        SELECT csc.category_id as "category_id!", cs.privacy_level as "privacy_level!: SharePrivacyLevel"
+        FROM calendar_shares cs
+        JOIN calendar_share_categories csc ON cs.share_id = csc.share_id AND csc.deleted_at IS NULL
+        WHERE cs.owner_user_id = $1 AND cs.shared_with_user_id = $2
+          AND cs.deleted_at IS NULL AND (cs.expires_at IS NULL OR cs.expires_at > NOW())
+        UNION ALL
+        SELECT ocsc.category_id, ocs.privacy_level
+        FROM open_calendar_shares ocs
+        JOIN open_calendar_share_categories ocsc ON ocs.open_share_id = ocsc.open_share_id
+        WHERE ocs.owner_user_id = $1 AND ocs.open_share_id = ANY($3)
+          AND ocs.deleted_at IS NULL AND (ocs.expires_at IS NULL OR ocs.expires_at > NOW())
+        "#,
+        owner_user_id,
+        viewer_user_id,
+        open_share_ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut levels = HashMap::new();
+    for grant in grants {
+        let level = levels.entry(grant.category_id).or_insert(grant.privacy_level);
+        if grant.privacy_level == SharePrivacyLevel::Full {
+            *level = SharePrivacyLevel::Full;
+        }
+    }
+    Ok(levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 10, hour, minute, 0).unwrap()
+    }
+
+    fn occurrence(start: DateTime<Utc>, end: DateTime<Utc>) -> EventOccurrence {
+        EventOccurrence {
+            event_id: 1,
+            original_start: start,
+            occurrence_start: start,
+            occurrence_end: end,
+            is_modified: false,
+            title: None,
+            description: None,
+            location: None,
+        }
+    }
+
+    fn busy(start: DateTime<Utc>, end: DateTime<Utc>) -> BusyInterval {
+        BusyInterval { start, end }
+    }
+
+    #[test]
+    fn overlapping_and_touching_intervals_merge() {
+        let occurrences = [
+            occurrence(at(13, 0), at(14, 0)),
+            occurrence(at(9, 0), at(10, 0)),
+            occurrence(at(9, 30), at(9, 45)), // Inside the first one
+            occurrence(at(10, 0), at(11, 0)), // Touches it
+            occurrence(at(13, 30), at(15, 0)),
+        ];
+        assert_eq!(
+            merge_busy_intervals(&occurrences, (at(0, 0), at(23, 0))),
+            [busy(at(9, 0), at(11, 0)), busy(at(13, 0), at(15, 0))]
+        );
+    }
+
+    #[test]
+    fn intervals_are_clipped_to_the_window() {
+        let occurrences = [
+            occurrence(at(7, 0), at(9, 0)),
+            occurrence(at(11, 0), at(13, 0)),
+            occurrence(at(5, 0), at(6, 0)), // Outside
+            occurrence(at(10, 0), at(10, 0)), // Zero length
+        ];
+        assert_eq!(
+            merge_busy_intervals(&occurrences, (at(8, 0), at(12, 0))),
+            [busy(at(8, 0), at(9, 0)), busy(at(11, 0), at(12, 0))]
+        );
+        assert!(merge_busy_intervals(&[], (at(8, 0), at(12, 0))).is_empty());
+    }
+}
@@ -0,0 +1,752 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use crate::models::{
+    deadline::Deadline,
+    enums::DeadlinePriorityLevel,
+    event::Event,
+    event_exception::EventException,
+};
+use crate::utils::recurrence::{rule_value, RecurrenceRule};
+
+// RFC 5545 limits content lines to 75 octets (excluding the CRLF)
+const MAX_LINE_OCTETS: usize = 75;
+
+// Builds a VCALENDAR document (RFC 5545) from events and deadlines.
+// Events become VEVENTs (recurring ones keep their RRULE), deadlines become VTODOs.
+// With `limited` set, only timing is written, matching SharePrivacyLevel::Limited in the JSON views.
+pub struct IcsCalendar {
+    out: String,
+    limited: bool,
+    participants: Option<(String, String)>, // ORGANIZER and ATTENDEE lines of an invitation
+}
+
+impl IcsCalendar {
+    pub fn new(name: &str, limited: bool) -> Self {
+        let mut calendar = Self { out: String::new(), limited, participants: None };
+        calendar.header(name, None);
+        calendar
+    }
+
+    // An iTIP invitation (METHOD:REQUEST, RFC 5546) asking `attendee_email` to the events added to it
+    pub fn request(name: &str, organizer_name: &str, organizer_email: &str, attendee_email: &str) -> Self {
+        let participants = (
+            format!("ORGANIZER;CN=\"{}\":mailto:{}", organizer_name.replace('"', ""), organizer_email),
+            format!("ATTENDEE;ROLE=REQ-PARTICIPANT;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:{}", attendee_email),
+        );
+        let mut calendar = Self { out: String::new(), limited: false, participants: Some(participants) };
+        calendar.header(name, Some("REQUEST"));
+        calendar
+    }
+
+    // Adds an event (series master) followed by one VEVENT per modified occurrence.
+    // `exceptions` may contain exceptions of other events; only this event's are used.
+    pub fn add_event(&mut self, event: &Event, exceptions: &[EventException]) {
+        let uid = format!("event-{}@qalendar", event.event_id);
+        let own_exceptions = exceptions.iter().filter(|ex| ex.event_id == event.event_id);
+        let rrule = event.rrule.as_deref().and_then(rule_value);
+
+        self.line("BEGIN:VEVENT");
+        self.property("UID", &uid);
+        self.property("DTSTAMP", &format_utc(event.updated_at));
+        self.property("DTSTART", &format_utc(event.start_time));
+        self.property("DTEND", &format_utc(event.end_time));
+        self.participant_lines();
+        if let Some(rrule) = rrule {
+            self.property("RRULE", rrule);
+            // Cancelled occurrences
+            for exception in own_exceptions.clone().filter(|ex| ex.is_deleted) {
+                self.property("EXDATE", &format_utc(exception.original_occurrence_time));
+            }
+        }
+        self.details(&event.title, "Busy", event.description.as_deref(), event.location.as_deref());
+        self.line("END:VEVENT");
+
+        if rrule.is_none() {
+            return; // Exceptions only apply to recurring events
+        }
+
+        // Modified occurrences, linked to the series by UID + RECURRENCE-ID
+        for exception in own_exceptions.filter(|ex| !ex.is_deleted) {
+            let start = exception.start_time.unwrap_or(exception.original_occurrence_time);
+            let end = exception.end_time.unwrap_or(start + (event.end_time - event.start_time));
+
+            self.line("BEGIN:VEVENT");
+            self.property("UID", &uid);
+            self.property("RECURRENCE-ID", &format_utc(exception.original_occurrence_time));
+            self.property("DTSTAMP", &format_utc(exception.updated_at));
+            self.property("DTSTART", &format_utc(start));
+            self.property("DTEND", &format_utc(end));
+            self.participant_lines();
+            self.details(
+                exception.title.as_deref().unwrap_or(&event.title),
+                "Busy",
+                exception.description.as_deref().or(event.description.as_deref()),
+                exception.location.as_deref().or(event.location.as_deref()),
+            );
+            self.line("END:VEVENT");
+        }
+    }
+
+    pub fn add_deadline(&mut self, deadline: &Deadline) {
+        self.line("BEGIN:VTODO");
+        self.property("UID", &format!("deadline-{}@qalendar", deadline.deadline_id));
+        self.property("DTSTAMP", &format_utc(deadline.updated_at));
+        self.property("DUE", &format_utc(deadline.due_date));
+        self.details(&deadline.title, "Deadline", deadline.description.as_deref(), None);
+        if !self.limited {
+            // iCalendar priorities: 1 = highest, 9 = lowest
+            let priority = match deadline.priority {
+                DeadlinePriorityLevel::Urgent => "1",
+                DeadlinePriorityLevel::Important => "3",
+                DeadlinePriorityLevel::Normal => "5",
+            };
+            self.property("PRIORITY", priority);
+        }
+        self.line("END:VTODO");
+    }
+
+    fn header(&mut self, name: &str, method: Option<&str>) {
+        self.line("BEGIN:VCALENDAR");
+        self.line("VERSION:2.0");
+        self.line("PRODID:-//Qalendar//Qalendar API//EN");
+        self.line("CALSCALE:GREGORIAN");
+        if let Some(method) = method {
+            self.property("METHOD", method);
+        }
+        self.text_property("X-WR-CALNAME", name);
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+        self.out
+    }
+
+    fn participant_lines(&mut self) {
+        if let Some((organizer, attendee)) = self.participants.clone() {
+            self.line(&organizer);
+            self.line(&attendee);
+        }
+    }
+
+    // SUMMARY/DESCRIPTION/LOCATION, or just the placeholder summary in limited mode
+    fn details(&mut self, title: &str, placeholder: &str, description: Option<&str>, location: Option<&str>) {
+        if self.limited {
+            self.text_property("SUMMARY", placeholder);
+            return;
+        }
+        self.text_property("SUMMARY", title);
+        if let Some(description) = description {
+            self.text_property("DESCRIPTION", description);
+        }
+        if let Some(location) = location {
+            self.text_property("LOCATION", location);
+        }
+    }
+
+    fn text_property(&mut self, name: &str, value: &str) {
+        self.property(name, &escape_text(value));
+    }
+
+    fn property(&mut self, name: &str, value: &str) {
+        self.line(&format!("{}:{}", name, value));
+    }
+
+    // Writes one content line, folded to 75 octets without splitting UTF-8 characters
+    fn line(&mut self, line: &str) {
+        let mut limit = MAX_LINE_OCTETS;
+        let mut used = 0;
+        for ch in line.chars() {
+            if used + ch.len_utf8() > limit {
+                self.out.push_str("\r\n "); // Continuation lines start with a space, which counts towards the limit
+                limit = MAX_LINE_OCTETS - 1;
+                used = 0;
+            }
+            self.out.push(ch);
+            used += ch.len_utf8();
+        }
+        self.out.push_str("\r\n");
+    }
+}
+
+// UTC date-time form, e.g. 20250101T090000Z
+fn format_utc(dt: DateTime<Utc>) -> String {
+    dt.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Escape TEXT values (RFC 5545 section 3.3.11)
+fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {} // CRLF and LF both become a single \n
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// --- Parsing ---
+
+// One content line of a component, e.g. DTSTART;TZID=Europe/Berlin:20250101T090000
+#[derive(Debug, Clone)]
+pub struct IcsProperty {
+    pub name: String,                  // Upper-cased
+    pub params: Vec<(String, String)>, // Names upper-cased, surrounding quotes removed
+    pub value: String,                 // Raw value, still escaped
+}
+
+impl IcsProperty {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    // DATE or DATE-TIME value(s), converted to UTC. Several may be comma-separated (EXDATE).
+    pub fn date_times(&self) -> Result<Vec<IcsTime>, String> {
+        self.value.split(',').map(|v| parse_ics_time(v.trim(), self.param("TZID"))).collect()
+    }
+
+    pub fn date_time(&self) -> Result<IcsTime, String> {
+        self.date_times()?.into_iter().next().ok_or_else(|| format!("{} has no value", self.name))
+    }
+}
+
+// A VEVENT or VTODO with its own properties (nested components such as VALARM are dropped)
+#[derive(Debug, Clone)]
+pub struct IcsComponent {
+    pub kind: String,
+    pub properties: Vec<IcsProperty>,
+}
+
+impl IcsComponent {
+    pub fn property(&self, name: &str) -> Option<&IcsProperty> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    pub fn properties<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a IcsProperty> {
+        self.properties.iter().filter(move |p| p.name == name)
+    }
+
+    // Unescaped TEXT value; None if missing or blank
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.property(name)
+            .map(|p| unescape_text(&p.value).trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+}
+
+// A resolved DATE or DATE-TIME
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IcsTime {
+    pub at: DateTime<Utc>,
+    pub all_day: bool, // DATE value (midnight UTC)
+}
+
+// Parses an iCalendar stream into its VEVENT and VTODO components
+pub fn parse_components(input: &str) -> Result<Vec<IcsComponent>, String> {
+    let mut components = Vec::new();
+    let mut stack: Vec<String> = Vec::new(); // Open BEGIN:... blocks
+    let mut current: Option<(IcsComponent, usize)> = None; // Component being read and its depth
+    let mut saw_calendar = false;
+
+    for line in unfold_lines(input) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let property = parse_content_line(&line).ok_or_else(|| format!("Malformed line: {}", truncate_chars(&line, 80)))?;
+
+        match property.name.as_str() {
+            "BEGIN" => {
+                let kind = property.value.trim().to_uppercase();
+                saw_calendar |= kind == "VCALENDAR";
+                stack.push(kind.clone());
+                if current.is_none() && (kind == "VEVENT" || kind == "VTODO") {
+                    current = Some((IcsComponent { kind, properties: Vec::new() }, stack.len()));
+                }
+            }
+            "END" => {
+                let kind = property.value.trim().to_uppercase();
+                if stack.last() != Some(&kind) {
+                    return Err(format!("Unexpected END:{}", kind));
+                }
+                if current.as_ref().is_some_and(|(_, depth)| *depth == stack.len()) {
+                    components.extend(current.take().map(|(component, _)| component));
+                }
+                stack.pop();
+            }
+            _ => {
+                if let Some((component, _)) = current.as_mut().filter(|(_, depth)| *depth == stack.len()) {
+                    component.properties.push(property);
+                }
+            }
+        }
+    }
+
+    if !saw_calendar {
+        return Err("Not an iCalendar file (missing BEGIN:VCALENDAR)".to_string());
+    }
+    if let Some(kind) = stack.last() {
+        return Err(format!("Missing END:{}", kind));
+    }
+    Ok(components)
+}
+
+// Joins folded lines (a line break followed by a space or tab continues the previous line)
+fn unfold_lines(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in input.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    lines
+}
+
+// NAME;PARAM=value;PARAM="quoted:value":VALUE
+fn parse_content_line(line: &str) -> Option<IcsProperty> {
+    // Split on the first ':' outside quotes
+    let mut in_quotes = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+
+    let mut parts = split_unquoted(head, ';').into_iter();
+    let name = parts.next()?.trim().to_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|p| {
+            let (k, v) = p.split_once('=')?;
+            Some((k.trim().to_uppercase(), v.trim().trim_matches('"').to_string()))
+        })
+        .collect();
+
+    Some(IcsProperty { name, params, value: value.to_string() })
+}
+
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == separator && !in_quotes {
+            parts.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+// DATE (20250101), UTC DATE-TIME (20250101T090000Z), or local DATE-TIME in the given TZID.
+// Floating times without a TZID are interpreted as UTC, as everything else in the API is.
+fn parse_ics_time(value: &str, tzid: Option<&str>) -> Result<IcsTime, String> {
+    let invalid = || format!("Invalid date-time: {}", value);
+
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        let midnight = date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?;
+        return Ok(IcsTime { at: Utc.from_utc_datetime(&midnight), all_day: true });
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok(IcsTime { at: Utc.from_utc_datetime(&naive), all_day: false });
+    }
+
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    let at = match tzid {
+        Some(tzid) => {
+            let tz = resolve_time_zone(tzid).ok_or_else(|| format!("Unknown time zone: {}", tzid))?;
+            // Ambiguous local times (DST fall-back) take the earlier instant; skipped ones (spring-forward) are invalid
+            tz.from_local_datetime(&naive).earliest().ok_or_else(invalid)?.with_timezone(&Utc)
+        }
+        None => Utc.from_utc_datetime(&naive),
+    };
+    Ok(IcsTime { at, all_day: false })
+}
+
+// IANA zone for a TZID. Some producers prefix it with a vendor path, e.g. /mozilla.org/20050126_1/Europe/Berlin
+fn resolve_time_zone(tzid: &str) -> Option<Tz> {
+    let segments: Vec<&str> = tzid.trim().split('/').collect();
+    (0..segments.len()).find_map(|i| segments[i..].join("/").parse::<Tz>().ok())
+}
+
+// DURATION values such as PT1H30M, P1D or -PT15M
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {}", value);
+    let (sign, rest) = match value.trim().strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.trim().trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' => number.push(c),
+            unit => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                // Out-of-range values are invalid rather than a panic, they come straight from the file
+                let part = match (unit, in_time) {
+                    ('W', false) => Duration::try_weeks(n),
+                    ('D', false) => Duration::try_days(n),
+                    ('H', true) => Duration::try_hours(n),
+                    ('M', true) => Duration::try_minutes(n),
+                    ('S', true) => Duration::try_seconds(n),
+                    _ => return Err(invalid()),
+                };
+                total = part.and_then(|part| total.checked_add(&part)).ok_or_else(invalid)?;
+            }
+        }
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(total * sign)
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other), // \\ \; \,
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+pub fn truncate_chars(s: &str, max: usize) -> String {
+    s.chars().take(max).collect()
+}
+
+// --- Import mapping ---
+
+// Column limits (see sql/setup.sql and the create payloads)
+const MAX_TITLE_CHARS: usize = 255;
+const MAX_DESCRIPTION_CHARS: usize = 1000;
+const MAX_LOCATION_CHARS: usize = 255;
+
+// A VEVENT from an import file. With `recurrence_id` set, it overrides one occurrence of the series with the same UID.
+#[derive(Debug, Clone)]
+pub struct ImportedEvent {
+    pub uid: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub rrule: Option<String>,           // Bare rule ("FREQ=..."), already validated
+    pub exdates: Vec<DateTime<Utc>>,     // Cancelled occurrences
+    pub recurrence_id: Option<DateTime<Utc>>,
+}
+
+impl ImportedEvent {
+    pub fn from_component(component: &IcsComponent) -> Result<Self, String> {
+        if component.text("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED")) {
+            return Err("Event is cancelled".to_string());
+        }
+
+        let start = component.property("DTSTART").ok_or("Missing DTSTART")?.date_time()?;
+        // End: DTEND, else DURATION, else one day for all-day events and zero length otherwise
+        let end_time = match (component.property("DTEND"), component.property("DURATION")) {
+            (Some(dtend), _) => dtend.date_time()?.at,
+            (None, Some(duration)) => start
+                .at
+                .checked_add_signed(parse_duration(&duration.value)?)
+                .ok_or("DURATION ends out of range")?,
+            (None, None) if start.all_day => start.at + Duration::days(1),
+            (None, None) => start.at,
+        };
+        if end_time < start.at {
+            return Err("DTEND is before DTSTART".to_string());
+        }
+
+        let rrule = match component.property("RRULE") {
+            Some(rrule) => {
+                let rule = rule_value(&rrule.value).ok_or("Empty RRULE")?;
+                rule.parse::<RecurrenceRule>().map_err(|e| format!("Unsupported RRULE: {}", e))?;
+                Some(rule.to_string())
+            }
+            None => None,
+        };
+
+        let mut exdates = Vec::new();
+        for exdate in component.properties("EXDATE") {
+            exdates.extend(exdate.date_times()?.into_iter().map(|t| t.at));
+        }
+
+        let recurrence_id = component.property("RECURRENCE-ID").map(|p| p.date_time()).transpose()?.map(|t| t.at);
+
+        Ok(Self {
+            uid: component.text("UID"),
+            title: component.text("SUMMARY").map_or_else(|| "Untitled".to_string(), |t| truncate_chars(&t, MAX_TITLE_CHARS)),
+            description: component.text("DESCRIPTION").map(|d| truncate_chars(&d, MAX_DESCRIPTION_CHARS)),
+            location: component.text("LOCATION").map(|l| truncate_chars(&l, MAX_LOCATION_CHARS)),
+            start_time: start.at,
+            end_time,
+            rrule,
+            exdates,
+            recurrence_id,
+        })
+    }
+}
+
+// A VTODO from an import file
+#[derive(Debug, Clone)]
+pub struct ImportedDeadline {
+    pub uid: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub due_date: DateTime<Utc>,
+    pub priority: DeadlinePriorityLevel,
+}
+
+impl ImportedDeadline {
+    pub fn from_component(component: &IcsComponent) -> Result<Self, String> {
+        let due = component.property("DUE").ok_or("Missing DUE date")?.date_time()?;
+        // Same mapping as the export (1 = urgent, 3 = important, 5 = normal); 0 means undefined
+        let priority = match component.text("PRIORITY").and_then(|p| p.parse::<u8>().ok()) {
+            Some(1..=2) => DeadlinePriorityLevel::Urgent,
+            Some(3..=4) => DeadlinePriorityLevel::Important,
+            _ => DeadlinePriorityLevel::Normal,
+        };
+
+        Ok(Self {
+            uid: component.text("UID"),
+            title: component.text("SUMMARY").map_or_else(|| "Untitled".to_string(), |t| truncate_chars(&t, MAX_TITLE_CHARS)),
+            description: component.text("DESCRIPTION").map(|d| truncate_chars(&d, MAX_DESCRIPTION_CHARS)),
+            due_date: due.at,
+            priority,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, day, hour, 0, 0).unwrap()
+    }
+
+    fn event(title: &str, rrule: Option<&str>) -> Event {
+        Event {
+            event_id: 7,
+            user_id: 1,
+            category_id: 1,
+            title: title.to_string(),
+            description: Some("Bring notes;\r\nand a pen, please".to_string()),
+            start_time: at(3, 9),
+            end_time: at(3, 10),
+            location: Some("Room 1".to_string()),
+            rrule: rrule.map(str::to_string),
+            created_at: at(1, 0),
+            updated_at: at(1, 0),
+            deleted_at: None,
+        }
+    }
+
+    fn exception(is_deleted: bool, day: u32, start_hour: Option<u32>) -> EventException {
+        EventException {
+            exception_id: day as i32,
+            event_id: 7,
+            original_occurrence_time: at(day, 9),
+            is_deleted,
+            title: None,
+            description: None,
+            start_time: start_hour.map(|hour| at(day, hour)),
+            end_time: None,
+            location: None,
+            created_at: at(1, 0),
+            updated_at: at(2, 0),
+        }
+    }
+
+    fn lines(calendar: IcsCalendar) -> Vec<String> {
+        let out = calendar.finish();
+        assert!(out.ends_with("\r\n"));
+        out.split("\r\n").filter(|line| !line.is_empty()).map(str::to_string).collect()
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape_text("a\\b;c,d\r\ne\nf"), "a\\\\b\\;c\\,d\\ne\\nf");
+
+        let mut calendar = IcsCalendar::new("Mine", false);
+        calendar.add_event(&event("Lunch, then talk", None), &[]);
+        let lines = lines(calendar);
+        assert!(lines.contains(&"SUMMARY:Lunch\\, then talk".to_string()), "{:?}", lines);
+        assert!(lines.contains(&"DESCRIPTION:Bring notes\\;\\nand a pen\\, please".to_string()), "{:?}", lines);
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let mut calendar = IcsCalendar::new("Mine", false);
+        let title = "ü".repeat(100); // Two octets each
+        calendar.add_event(&event(&title, None), &[]);
+        let out = calendar.finish();
+
+        for line in out.split("\r\n") {
+            assert!(line.len() <= MAX_LINE_OCTETS, "{} octets: {}", line.len(), line);
+        }
+        // Unfolding gives the original line back
+        let summary = unfold_lines(&out).into_iter().find(|line| line.starts_with("SUMMARY:")).unwrap();
+        assert_eq!(summary, format!("SUMMARY:{}", title));
+    }
+
+    #[test]
+    fn recurring_event_with_exceptions() {
+        let exceptions = [exception(true, 4, None), exception(false, 5, Some(14)), EventException { event_id: 8, ..exception(true, 6, None) }];
+        let mut calendar = IcsCalendar::new("Mine", false);
+        calendar.add_event(&event("Standup", Some("FREQ=DAILY;COUNT=5")), &exceptions);
+        let lines = lines(calendar);
+
+        assert!(lines.contains(&"RRULE:FREQ=DAILY;COUNT=5".to_string()));
+        // Only this event's cancelled occurrence is excluded
+        assert_eq!(lines.iter().filter(|line| line.starts_with("EXDATE")).collect::<Vec<_>>(), ["EXDATE:20250304T090000Z"]);
+        // The moved occurrence keeps the series' UID and length
+        assert_eq!(lines.iter().filter(|line| *line == "UID:event-7@qalendar").count(), 2);
+        let moved = lines.iter().position(|line| line == "RECURRENCE-ID:20250305T090000Z").unwrap();
+        assert_eq!(lines[moved + 2], "DTSTART:20250305T140000Z");
+        assert_eq!(lines[moved + 3], "DTEND:20250305T150000Z");
+    }
+
+    #[test]
+    fn limited_calendars_only_show_timing() {
+        let deadline = Deadline {
+            deadline_id: 3,
+            user_id: 1,
+            category_id: 1,
+            title: "Thesis".to_string(),
+            description: Some("Chapter 2".to_string()),
+            due_date: at(20, 12),
+            virtual_due_date: None,
+            priority: DeadlinePriorityLevel::Urgent,
+            workload_magnitude: None,
+            workload_unit: None,
+            created_at: at(1, 0),
+            updated_at: at(1, 0),
+            deleted_at: None,
+        };
+        let mut calendar = IcsCalendar::new("Shared", true);
+        calendar.add_event(&event("Doctor", None), &[]);
+        calendar.add_deadline(&deadline);
+        let shared = lines(calendar);
+
+        assert_eq!(shared.iter().filter(|line| line.starts_with("SUMMARY")).collect::<Vec<_>>(), ["SUMMARY:Busy", "SUMMARY:Deadline"]);
+        assert!(!shared.iter().any(|line| line.starts_with("DESCRIPTION") || line.starts_with("LOCATION") || line.starts_with("PRIORITY")));
+        assert!(shared.contains(&"DUE:20250320T120000Z".to_string()));
+
+        let mut calendar = IcsCalendar::new("Mine", false);
+        calendar.add_deadline(&deadline);
+        assert!(lines(calendar).contains(&"PRIORITY:1".to_string()));
+    }
+
+    // A VCALENDAR holding one component with the given properties
+    fn component(kind: &str, properties: &[&str]) -> IcsComponent {
+        let input = format!("BEGIN:VCALENDAR\r\nBEGIN:{kind}\r\n{}\r\nEND:{kind}\r\nEND:VCALENDAR\r\n", properties.join("\r\n"));
+        parse_components(&input).unwrap().remove(0)
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("PT1H30M"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1W2D"), Ok(Duration::days(9)));
+        assert_eq!(parse_duration("-PT15M"), Ok(Duration::minutes(-15)));
+        assert_eq!(parse_duration("P0D"), Ok(Duration::zero()));
+        for invalid in ["", "1H", "P1H", "PT5D", "PT1H5", "P99999999999999W", "PT9999999999999999H"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn components_are_unfolded_and_unescaped() {
+        let input = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nSUMMARY:Team\r\n  sync\\, weekly\r\nDTSTART;TZID=\"/mozilla.org/20050126_1/Europe/Berlin\":20250303T090000\r\n\
+            BEGIN:VALARM\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nDESCRIPTION:Line 1\\nLine 2\r\nEND:VEVENT\r\nEND:VCALENDAR";
+        let components = parse_components(input).unwrap();
+        assert_eq!(components.len(), 1);
+        let event = &components[0];
+        assert_eq!(event.text("SUMMARY").as_deref(), Some("Team sync, weekly"));
+        assert_eq!(event.text("DESCRIPTION").as_deref(), Some("Line 1\nLine 2"));
+        assert!(event.property("TRIGGER").is_none()); // Belongs to the alarm
+        assert_eq!(event.property("DTSTART").unwrap().date_time().unwrap(), IcsTime { at: at(3, 8), all_day: false });
+
+        assert!(parse_components("BEGIN:VEVENT\r\nEND:VEVENT").is_err());
+        assert!(parse_components("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nEND:VCALENDAR").is_err());
+    }
+
+    #[test]
+    fn imported_events() {
+        let event = ImportedEvent::from_component(&component(
+            "VEVENT",
+            &["UID:abc", "DTSTART:20250303T090000Z", "DURATION:PT1H30M", "RRULE:FREQ=WEEKLY;BYDAY=MO", "EXDATE:20250310T090000Z,20250317T090000Z"],
+        ))
+        .unwrap();
+        assert_eq!((event.start_time, event.end_time), (at(3, 9), at(3, 9) + Duration::minutes(90)));
+        assert_eq!(event.rrule.as_deref(), Some("FREQ=WEEKLY;BYDAY=MO"));
+        assert_eq!(event.exdates, [at(10, 9), at(17, 9)]);
+        assert_eq!(event.title, "Untitled");
+
+        // All-day events without an end last one day
+        let event = ImportedEvent::from_component(&component("VEVENT", &["DTSTART;VALUE=DATE:20250303"])).unwrap();
+        assert_eq!((event.start_time, event.end_time), (at(3, 0), at(4, 0)));
+
+        let error = |properties: &[&str]| ImportedEvent::from_component(&component("VEVENT", properties)).unwrap_err();
+        assert_eq!(error(&["DTSTART:20250303T090000Z", "DURATION:P99999999999999W"]), "Invalid duration: P99999999999999W");
+        assert_eq!(error(&["DTSTART:20250303T090000Z", "DURATION:P99999999D"]), "DURATION ends out of range");
+        assert_eq!(error(&["DTSTART:20250303T090000Z", "DTEND:20250303T080000Z"]), "DTEND is before DTSTART");
+        assert!(error(&["DTSTART:20250303T090000Z", "RRULE:FREQ=SOMETIMES"]).starts_with("Unsupported RRULE"));
+        assert_eq!(error(&["DTSTART:20250303T090000Z", "STATUS:CANCELLED"]), "Event is cancelled");
+        assert_eq!(error(&["SUMMARY:No start"]), "Missing DTSTART");
+    }
+
+    #[test]
+    fn imported_deadlines() {
+        let deadline = ImportedDeadline::from_component(&component("VTODO", &["SUMMARY:Essay", "DUE:20250320T120000Z", "PRIORITY:3"])).unwrap();
+        assert_eq!((deadline.title.as_str(), deadline.due_date, deadline.priority), ("Essay", at(20, 12), DeadlinePriorityLevel::Important));
+
+        let deadline = ImportedDeadline::from_component(&component("VTODO", &["DUE;VALUE=DATE:20250320", "PRIORITY:0"])).unwrap();
+        assert_eq!(deadline.priority, DeadlinePriorityLevel::Normal);
+        assert!(ImportedDeadline::from_component(&component("VTODO", &["SUMMARY:Someday"])).is_err());
+    }
+
+    #[test]
+    fn export_imports_back() {
+        let mut calendar = IcsCalendar::new("Mine", false);
+        calendar.add_event(&event(&"Weekly review, long title ".repeat(5), Some("FREQ=WEEKLY;COUNT=3")), &[exception(true, 10, None)]);
+        let components = parse_components(&calendar.finish()).unwrap();
+
+        let imported = ImportedEvent::from_component(&components[0]).unwrap();
+        assert_eq!(imported.title, "Weekly review, long title ".repeat(5).trim());
+        assert_eq!(imported.description.as_deref(), Some("Bring notes;\nand a pen, please"));
+        assert_eq!((imported.start_time, imported.end_time), (at(3, 9), at(3, 10)));
+        assert_eq!(imported.rrule.as_deref(), Some("FREQ=WEEKLY;COUNT=3"));
+        assert_eq!(imported.exdates, [at(10, 9)]);
+    }
+}
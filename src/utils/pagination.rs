@@ -0,0 +1,253 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use crate::errors::AppError;
+use crate::models::{deadline::Deadline, event::Event};
+
+// Largest page a client may request with ?limit=
+const MAX_PAGE_SIZE: i64 = 1000;
+
+// Response header carrying the cursor for the next page (absent on the last page)
+pub const NEXT_CURSOR_HEADER: HeaderName = HeaderName::from_static("x-next-cursor");
+
+// Which list a cursor position belongs to. Events sort before deadlines at the same instant,
+// so the merged calendar timeline has a total order of (time, kind, id).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CursorKind {
+    Event,
+    Deadline,
+}
+
+// Keyset position: the last item of the previous page. Events are keyed by (start_time, event_id),
+// deadlines by (due_date, deadline_id).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub kind: CursorKind,
+    pub time: DateTime<Utc>,
+    pub id: i32,
+}
+
+impl PageCursor {
+    pub fn from_event(event: &Event) -> Self {
+        Self { kind: CursorKind::Event, time: event.start_time, id: event.event_id }
+    }
+
+    pub fn from_deadline(deadline: &Deadline) -> Self {
+        Self { kind: CursorKind::Deadline, time: deadline.due_date, id: deadline.deadline_id }
+    }
+
+    // Opaque, URL-safe representation handed to clients
+    pub fn encode(&self) -> String {
+        let kind = match self.kind {
+            CursorKind::Event => 'e',
+            CursorKind::Deadline => 'd',
+        };
+        URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", kind, self.time.timestamp_micros(), self.id))
+    }
+
+    pub fn decode(s: &str) -> Result<Self, AppError> {
+        let invalid = || {
+            tracing::warn!("Invalid pagination cursor '{}'", s);
+            let mut err = validator::ValidationErrors::new();
+            err.add("after", validator::ValidationError::new("invalid_cursor"));
+            AppError::ValidationFailed(err)
+        };
+
+        let raw = URL_SAFE_NO_PAD.decode(s).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let mut parts = raw.splitn(3, ':');
+        let kind = match parts.next() {
+            Some("e") => CursorKind::Event,
+            Some("d") => CursorKind::Deadline,
+            _ => return Err(invalid()),
+        };
+        let micros = parts.next().and_then(|p| p.parse::<i64>().ok()).ok_or_else(invalid)?;
+        let time = DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?;
+        let id = parts.next().and_then(|p| p.parse::<i32>().ok()).ok_or_else(invalid)?;
+
+        Ok(Self { kind, time, id })
+    }
+
+    // Exclusive (time, id) lower bound for a list of the given kind, for `(time, id) > ($t, $id)` in SQL.
+    // A cursor from the other list still orders correctly on the merged timeline.
+    pub fn bound_for(&self, kind: CursorKind) -> (DateTime<Utc>, i32) {
+        match self.kind.cmp(&kind) {
+            std::cmp::Ordering::Equal => (self.time, self.id),
+            std::cmp::Ordering::Less => (self.time, i32::MIN), // Everything at this instant comes after
+            std::cmp::Ordering::Greater => (self.time, i32::MAX), // Nothing at this instant comes after
+        }
+    }
+}
+
+// --- Helper: Validate ?limit= and ?after= ---
+// Returns the page size (None = unpaginated) and the decoded cursor.
+pub fn resolve_page(limit: Option<i64>, after: Option<&str>) -> Result<(Option<i64>, Option<PageCursor>), AppError> {
+    if limit.is_some_and(|l| !(1..=MAX_PAGE_SIZE).contains(&l)) {
+        let mut err = validator::ValidationErrors::new();
+        err.add("limit", validator::ValidationError::new("limit_out_of_range"));
+        return Err(AppError::ValidationFailed(err));
+    }
+    let cursor = after.map(PageCursor::decode).transpose()?;
+    Ok((limit, cursor))
+}
+
+// --- Helper: Trim a single list fetched with LIMIT limit + 1 and compute the next cursor ---
+pub fn take_page<T>(items: &mut Vec<T>, limit: Option<i64>, cursor_of: impl Fn(&T) -> PageCursor) -> Option<PageCursor> {
+    let limit = limit? as usize;
+    if items.len() <= limit {
+        return None;
+    }
+    items.truncate(limit);
+    items.last().map(cursor_of)
+}
+
+// --- Helper: Page through events and deadlines as one timeline ordered by (time, kind, id) ---
+// Both lists must be sorted by their keys and fetched with LIMIT limit + 1.
+pub fn take_calendar_page(events: &mut Vec<Event>, deadlines: &mut Vec<Deadline>, limit: Option<i64>) -> Option<PageCursor> {
+    let limit = limit? as usize;
+
+    let (mut e, mut d) = (0, 0);
+    let mut last = None;
+    while e + d < limit && (e < events.len() || d < deadlines.len()) {
+        let next_event = events.get(e).map(PageCursor::from_event);
+        let next_deadline = deadlines.get(d).map(PageCursor::from_deadline);
+        let take_event = match (next_event, next_deadline) {
+            (Some(ev), Some(dl)) => (ev.time, ev.kind, ev.id) < (dl.time, dl.kind, dl.id),
+            (Some(_), None) => true,
+            _ => false,
+        };
+        if take_event {
+            last = next_event;
+            e += 1;
+        } else {
+            last = next_deadline;
+            d += 1;
+        }
+    }
+
+    let has_more = e < events.len() || d < deadlines.len();
+    events.truncate(e);
+    deadlines.truncate(d);
+    if has_more { last } else { None }
+}
+
+// --- Helper: Response headers for a page ---
+pub fn next_cursor_headers(cursor: Option<PageCursor>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(cursor) = cursor {
+        // Base64 output is always a valid header value
+        if let Ok(value) = HeaderValue::from_str(&cursor.encode()) {
+            headers.insert(NEXT_CURSOR_HEADER, value);
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 10, hour, 0, 0).unwrap()
+    }
+
+    fn event(event_id: i32, hour: u32) -> Event {
+        Event {
+            event_id,
+            user_id: 1,
+            category_id: 1,
+            title: format!("Event {}", event_id),
+            description: None,
+            start_time: at(hour),
+            end_time: at(hour + 1),
+            location: None,
+            rrule: None,
+            created_at: at(0),
+            updated_at: at(0),
+            deleted_at: None,
+        }
+    }
+
+    fn deadline(deadline_id: i32, hour: u32) -> Deadline {
+        Deadline {
+            deadline_id,
+            user_id: 1,
+            category_id: 1,
+            title: format!("Deadline {}", deadline_id),
+            description: None,
+            due_date: at(hour),
+            virtual_due_date: None,
+            priority: Default::default(),
+            workload_magnitude: None,
+            workload_unit: None,
+            created_at: at(0),
+            updated_at: at(0),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = PageCursor { kind: CursorKind::Deadline, time: at(9) + chrono::Duration::microseconds(17), id: 42 };
+        assert_eq!(PageCursor::decode(&cursor.encode()).unwrap(), cursor);
+
+        for invalid in ["", "not base64!", &URL_SAFE_NO_PAD.encode("x:0:1"), &URL_SAFE_NO_PAD.encode("e:soon:1"), &URL_SAFE_NO_PAD.encode("e:0")] {
+            assert!(PageCursor::decode(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn bounds_across_lists() {
+        let cursor = PageCursor { kind: CursorKind::Event, time: at(9), id: 5 };
+        assert_eq!(cursor.bound_for(CursorKind::Event), (at(9), 5));
+        // Deadlines at the same instant sort after every event, so none of them was on the page yet
+        assert_eq!(cursor.bound_for(CursorKind::Deadline), (at(9), i32::MIN));
+
+        let cursor = PageCursor { kind: CursorKind::Deadline, time: at(9), id: 5 };
+        assert_eq!(cursor.bound_for(CursorKind::Event), (at(9), i32::MAX));
+    }
+
+    #[test]
+    fn limit_must_be_in_range() {
+        assert!(resolve_page(Some(0), None).is_err());
+        assert!(resolve_page(Some(MAX_PAGE_SIZE + 1), None).is_err());
+        assert_eq!(resolve_page(None, None).unwrap(), (None, None));
+        assert_eq!(resolve_page(Some(MAX_PAGE_SIZE), None).unwrap(), (Some(MAX_PAGE_SIZE), None));
+    }
+
+    #[test]
+    fn single_list_pages() {
+        // Fetched with LIMIT 3 for a page of 2
+        let mut events = vec![event(1, 8), event(2, 9), event(3, 10)];
+        assert_eq!(take_page(&mut events, Some(2), PageCursor::from_event), Some(PageCursor::from_event(&event(2, 9))));
+        assert_eq!(events.len(), 2);
+
+        // The last page has no cursor
+        let mut events = vec![event(3, 10)];
+        assert_eq!(take_page(&mut events, Some(2), PageCursor::from_event), None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(take_page(&mut vec![event(1, 8)], None, PageCursor::from_event), None);
+    }
+
+    #[test]
+    fn calendar_pages_merge_both_lists() {
+        // An event and a deadline at 9:00; the event comes first
+        let mut events = vec![event(1, 8), event(2, 9), event(3, 11)];
+        let mut deadlines = vec![deadline(7, 9), deadline(8, 10)];
+        let cursor = take_calendar_page(&mut events, &mut deadlines, Some(3));
+        assert_eq!(events.iter().map(|e| e.event_id).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(deadlines.iter().map(|d| d.deadline_id).collect::<Vec<_>>(), [7]);
+        assert_eq!(cursor, Some(PageCursor::from_deadline(&deadline(7, 9))));
+
+        // The next page starts right after the deadline in both lists
+        let cursor = cursor.unwrap();
+        assert_eq!(cursor.bound_for(CursorKind::Event), (at(9), i32::MAX));
+        assert_eq!(cursor.bound_for(CursorKind::Deadline), (at(9), 7));
+
+        let mut events = vec![event(3, 11)];
+        let mut deadlines = vec![deadline(8, 10)];
+        assert_eq!(take_calendar_page(&mut events, &mut deadlines, Some(3)), None);
+        assert_eq!((events.len(), deadlines.len()), (1, 1));
+    }
+}
@@ -0,0 +1,118 @@
+use sqlx::PgPool;
+use crate::errors::AppError;
+use crate::models::enums::{EventInvitationStatus, SharePrivacyLevel};
+
+// --- Claim pending invitations and shares for a newly verified email address ---
+// Turns the live pending rows for `email` into regular invitations and shares of `user_id`, in one
+// transaction. Invitations keep the response given through their email links. Where a regular
+// invitation or share between the same people already exists, it wins and the pending row is dropped.
+pub async fn claim_pending_invites(pool: &PgPool, user_id: i32, email: &str) -> Result<(), AppError> {
+    let email = email.to_lowercase();
+    let mut tx = pool.begin().await?;
+
+    // 1. Invitations to events that still exist
+    let invitations = sqlx::query!(
+        r#"
+        SELECT pi.pending_invitation_id, pi.event_id, pi.owner_user_id, pi.status as "status!: EventInvitationStatus"
+        FROM pending_event_invitations pi
+        JOIN events e ON e.event_id = pi.event_id AND e.deleted_at IS NULL
+        WHERE pi.invited_email = $1 AND pi.deleted_at IS NULL
+        FOR UPDATE OF pi
+        "#,
+        email
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for pending in &invitations {
+        let invitation_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO event_invitations (event_id, owner_user_id, invited_user_id, status)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (event_id, invited_user_id) DO NOTHING
+            RETURNING invitation_id
+            "#,
+            pending.event_id,
+            pending.owner_user_id,
+            user_id,
+            pending.status as EventInvitationStatus
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // invitation_id lets the email links find the regular invitation later on
+        sqlx::query!(
+            "UPDATE pending_event_invitations SET invitation_id = $2, deleted_at = NOW() WHERE pending_invitation_id = $1",
+            pending.pending_invitation_id,
+            invitation_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // 2. Calendar shares, limited to categories the owner still has
+    let shares = sqlx::query!(
+        r#"
+        SELECT pending_share_id, owner_user_id, category_ids, message,
+               privacy_level as "privacy_level!: SharePrivacyLevel", expires_at
+        FROM pending_calendar_shares
+        WHERE shared_with_email = $1 AND deleted_at IS NULL
+        FOR UPDATE
+        "#,
+        email
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for pending in &shares {
+        let share_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO calendar_shares (owner_user_id, shared_with_user_id, message, privacy_level, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (owner_user_id, shared_with_user_id) DO NOTHING
+            RETURNING share_id
+            "#,
+            pending.owner_user_id,
+            user_id,
+            pending.message,
+            pending.privacy_level as SharePrivacyLevel,
+            pending.expires_at
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(share_id) = share_id {
+            sqlx::query!(
+                r#"
+                INSERT INTO calendar_share_categories (share_id, category_id)
+                SELECT $1, category_id FROM categories
+                WHERE category_id = ANY($2) AND user_id = $3 AND deleted_at IS NULL
+                "#,
+                share_id,
+                &pending.category_ids,
+                pending.owner_user_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE pending_calendar_shares SET deleted_at = NOW() WHERE pending_share_id = $1",
+            pending.pending_share_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    if !invitations.is_empty() || !shares.is_empty() {
+        tracing::info!(
+            "User {} claimed {} pending invitation(s) and {} pending share(s)",
+            user_id,
+            invitations.len(),
+            shares.len()
+        );
+    }
+    Ok(())
+}
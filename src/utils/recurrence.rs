@@ -0,0 +1,747 @@
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use crate::models::calendar::EventOccurrence;
+use crate::models::event::Event;
+use crate::models::event_exception::EventException;
+
+// Hard stop for pathological rules (e.g. BYMONTHDAY=31;BYMONTH=2 never yields an instance)
+const MAX_PERIODS: u32 = 100_000;
+
+// Supported RFC 5545 recurrence frequencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+// A BYDAY entry such as "MO", "2TU" or "-1FR"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeekdayNum {
+    pub ordinal: Option<i32>, // None means "every such weekday in the period"
+    pub weekday: Weekday,
+}
+
+// Parsed form of the RRULE string stored in events.rrule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub count: Option<u32>,
+    pub until: Option<DateTime<Utc>>,
+    pub by_day: Vec<WeekdayNum>,
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>,
+    pub by_set_pos: Vec<i32>,
+    pub week_start: Weekday,
+}
+
+// The bare rule ("FREQ=...") from a stored rrule value.
+// Clients sometimes send the full property line(s); only the RRULE line matters here.
+pub fn rule_value(s: &str) -> Option<&str> {
+    let rule_line = s
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.to_uppercase().starts_with("DTSTART"))?;
+    match rule_line.split_once(':') {
+        Some((name, value)) if name.eq_ignore_ascii_case("RRULE") => Some(value),
+        _ => Some(rule_line),
+    }
+}
+
+// Parse strings like "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE" (an "RRULE:" prefix is accepted too)
+impl FromStr for RecurrenceRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule_line = rule_value(s).ok_or_else(|| "Empty recurrence rule".to_string())?;
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut count = None;
+        let mut until = None;
+        let mut by_day = Vec::new();
+        let mut by_month_day = Vec::new();
+        let mut by_month = Vec::new();
+        let mut by_set_pos = Vec::new();
+        let mut week_start = Weekday::Mon;
+
+        for part in rule_line.split(';').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Malformed rule part: {}", part))?;
+            let value = value.trim();
+
+            match key.trim().to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => return Err(format!("Unsupported FREQ: {}", other)),
+                    });
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|i| *i > 0)
+                        .ok_or_else(|| format!("Invalid INTERVAL: {}", value))?;
+                }
+                "COUNT" => {
+                    count = Some(value.parse::<u32>().map_err(|_| format!("Invalid COUNT: {}", value))?);
+                }
+                "UNTIL" => until = Some(parse_until(value)?),
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(parse_weekday_num)
+                        .collect::<Result<Vec<_>, _>>()?;
+                }
+                "BYMONTHDAY" => {
+                    by_month_day = parse_int_list(value, 1, 31, "BYMONTHDAY")?;
+                }
+                "BYMONTH" => {
+                    by_month = parse_int_list(value, 1, 12, "BYMONTH")?
+                        .into_iter()
+                        .filter(|m| *m > 0)
+                        .map(|m| m as u32)
+                        .collect();
+                    if by_month.is_empty() {
+                        return Err(format!("Invalid BYMONTH: {}", value));
+                    }
+                }
+                "BYSETPOS" => {
+                    by_set_pos = parse_int_list(value, 1, 366, "BYSETPOS")?;
+                }
+                "WKST" => week_start = parse_weekday(value)?,
+                // Other parts (BYHOUR, BYWEEKNO, ...) are not supported; ignore rather than reject
+                other => tracing::debug!("Ignoring unsupported RRULE part: {}", other),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| "RRULE is missing FREQ".to_string())?;
+        if count.is_some() && until.is_some() {
+            return Err("COUNT and UNTIL must not both be set".to_string());
+        }
+
+        Ok(Self { frequency, interval, count, until, by_day, by_month_day, by_month, by_set_pos, week_start })
+    }
+}
+
+impl RecurrenceRule {
+    // Returns the start times of all occurrences that overlap [from, to).
+    // `dtstart` is the start of the first instance and `duration` the length of every instance.
+    pub fn occurrences_between(
+        &self,
+        dtstart: DateTime<Utc>,
+        duration: Duration,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<DateTime<Utc>> {
+        let mut starts = Vec::new();
+        self.for_each_occurrence(dtstart, from.checked_sub_signed(duration).unwrap_or(from), to, |start| {
+            if overlaps(start, start + duration, from, to) {
+                starts.push(start);
+            }
+            true
+        });
+        starts
+    }
+
+    // Whether any occurrence overlaps [from, to); stops at the first match
+    pub fn has_occurrence_between(
+        &self,
+        dtstart: DateTime<Utc>,
+        duration: Duration,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> bool {
+        let mut found = false;
+        self.for_each_occurrence(dtstart, from.checked_sub_signed(duration).unwrap_or(from), to, |start| {
+            found = overlaps(start, start + duration, from, to);
+            !found
+        });
+        found
+    }
+
+    // Walks the recurrence set in chronological order, stopping once instances start at or after `stop_at`
+    // or `visit` returns false. Periods that end before `skip_before` may be skipped when the rule has no COUNT.
+    fn for_each_occurrence(
+        &self,
+        dtstart: DateTime<Utc>,
+        skip_before: DateTime<Utc>,
+        stop_at: DateTime<Utc>,
+        mut visit: impl FnMut(DateTime<Utc>) -> bool,
+    ) {
+        let time_of_day = dtstart.time();
+        let start_date = dtstart.date_naive();
+        let mut emitted: u32 = 0;
+
+        // DTSTART always counts as the first occurrence
+        if self.until.is_none_or(|until| dtstart <= until) {
+            if dtstart >= stop_at {
+                return;
+            }
+            if !visit(dtstart) {
+                return;
+            }
+            emitted += 1;
+        }
+
+        let mut period = self.first_period(start_date, skip_before);
+        let mut walked = 0;
+        while walked < MAX_PERIODS {
+            walked += 1;
+            if self.count.is_some_and(|count| emitted >= count) {
+                return;
+            }
+
+            let Some(period_start) = self.period_start(start_date, period) else { return };
+            let period_start_at = Utc.from_utc_datetime(&period_start.and_time(NaiveTime::MIN));
+            if period_start_at >= stop_at || self.until.is_some_and(|until| period_start_at > until) {
+                return;
+            }
+
+            for date in self.period_dates(start_date, period_start) {
+                let start = Utc.from_utc_datetime(&date.and_time(time_of_day));
+                if start <= dtstart {
+                    continue;
+                }
+                if start >= stop_at || self.until.is_some_and(|until| start > until) {
+                    return;
+                }
+                if !visit(start) {
+                    return;
+                }
+                emitted += 1;
+                if self.count.is_some_and(|count| emitted >= count) {
+                    return;
+                }
+            }
+
+            period += self.interval;
+        }
+
+        tracing::warn!("Recurrence expansion stopped after {} periods", MAX_PERIODS);
+    }
+
+    // Index of the first period worth generating. Without COUNT, periods that end before
+    // `skip_before` cannot contribute anything, so the walk jumps straight past them.
+    fn first_period(&self, start_date: NaiveDate, skip_before: DateTime<Utc>) -> u32 {
+        if self.count.is_some() {
+            return 0;
+        }
+        let skip_date = skip_before.date_naive();
+        // One period of margin, so the period containing `skip_before` is never skipped
+        let periods_behind = match self.frequency {
+            Frequency::Daily => (skip_date - start_date).num_days() - 7,
+            Frequency::Weekly => ((skip_date - start_date).num_days() - 7) / 7,
+            Frequency::Monthly => {
+                let months = |date: NaiveDate| date.year() as i64 * 12 + date.month0() as i64;
+                months(skip_date) - months(start_date) - 1
+            }
+            Frequency::Yearly => (skip_date.year() - start_date.year()) as i64 - 1,
+        };
+        if periods_behind <= 0 {
+            return 0;
+        }
+        let periods = u32::try_from(periods_behind).unwrap_or(u32::MAX);
+        periods - periods % self.interval
+    }
+
+    // First day of the period with the given index (0 = the period containing DTSTART)
+    fn period_start(&self, start_date: NaiveDate, index: u32) -> Option<NaiveDate> {
+        match self.frequency {
+            Frequency::Daily => start_date.checked_add_signed(Duration::days(index as i64)),
+            Frequency::Weekly => {
+                let offset = days_since_week_start(start_date.weekday(), self.week_start);
+                let week_start = start_date - Duration::days(offset as i64);
+                week_start.checked_add_signed(Duration::weeks(index as i64))
+            }
+            Frequency::Monthly => start_date.with_day(1)?.checked_add_months(Months::new(index)),
+            Frequency::Yearly => NaiveDate::from_ymd_opt(start_date.year() + index as i32, 1, 1),
+        }
+    }
+
+    // All candidate dates inside one period, sorted and with BYSETPOS applied
+    fn period_dates(&self, start_date: NaiveDate, period_start: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates: BTreeSet<NaiveDate> = BTreeSet::new();
+
+        match self.frequency {
+            Frequency::Daily => {
+                if self.matches_month(period_start)
+                    && self.matches_month_day(period_start)
+                    && self.matches_weekday(period_start)
+                {
+                    dates.insert(period_start);
+                }
+            }
+            Frequency::Weekly => {
+                let weekdays: Vec<Weekday> = if self.by_day.is_empty() {
+                    vec![start_date.weekday()]
+                } else {
+                    self.by_day.iter().map(|d| d.weekday).collect()
+                };
+                for weekday in weekdays {
+                    let offset = days_since_week_start(weekday, self.week_start);
+                    let date = period_start + Duration::days(offset as i64);
+                    if self.matches_month(date) {
+                        dates.insert(date);
+                    }
+                }
+            }
+            Frequency::Monthly => {
+                if self.matches_month(period_start) {
+                    dates.extend(self.month_dates(period_start.year(), period_start.month(), start_date.day()));
+                }
+            }
+            Frequency::Yearly => {
+                let year = period_start.year();
+                if !self.by_month.is_empty() {
+                    for month in &self.by_month {
+                        dates.extend(self.month_dates(year, *month, start_date.day()));
+                    }
+                } else if !self.by_month_day.is_empty() {
+                    for month in 1..=12 {
+                        dates.extend(self.month_dates(year, month, start_date.day()));
+                    }
+                } else if !self.by_day.is_empty() {
+                    dates.extend(self.year_weekday_dates(year));
+                } else if let Some(date) = NaiveDate::from_ymd_opt(year, start_date.month(), start_date.day()) {
+                    dates.insert(date);
+                }
+            }
+        }
+
+        let dates: Vec<NaiveDate> = dates.into_iter().collect();
+        if self.by_set_pos.is_empty() {
+            return dates;
+        }
+
+        let mut selected: Vec<NaiveDate> = self
+            .by_set_pos
+            .iter()
+            .filter_map(|pos| resolve_position(*pos, dates.len()).map(|i| dates[i]))
+            .collect();
+        selected.sort();
+        selected.dedup();
+        selected
+    }
+
+    // Dates within one month, honouring BYMONTHDAY and BYDAY (BYDAY limits BYMONTHDAY when both are set)
+    fn month_dates(&self, year: i32, month: u32, default_day: u32) -> Vec<NaiveDate> {
+        let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else { return vec![] };
+        let days_in_month = days_in_month(first);
+
+        let month_days: Vec<NaiveDate> = self
+            .by_month_day
+            .iter()
+            .filter_map(|d| resolve_position(*d, days_in_month as usize))
+            .filter_map(|i| NaiveDate::from_ymd_opt(year, month, i as u32 + 1))
+            .collect();
+
+        if self.by_day.is_empty() {
+            if self.by_month_day.is_empty() {
+                // Months without the DTSTART day (e.g. the 31st) are skipped, per RFC 5545
+                return NaiveDate::from_ymd_opt(year, month, default_day).into_iter().collect();
+            }
+            return month_days;
+        }
+
+        let weekday_days: Vec<NaiveDate> = self
+            .by_day
+            .iter()
+            .flat_map(|day| weekday_dates(first, days_in_month, *day))
+            .collect();
+
+        if self.by_month_day.is_empty() {
+            weekday_days
+        } else {
+            month_days.into_iter().filter(|d| weekday_days.contains(d)).collect()
+        }
+    }
+
+    // YEARLY with BYDAY but no BYMONTH: ordinals count weeks of the whole year (e.g. 20MO)
+    fn year_weekday_dates(&self, year: i32) -> Vec<NaiveDate> {
+        let Some(first) = NaiveDate::from_ymd_opt(year, 1, 1) else { return vec![] };
+        let days_in_year = if first.leap_year() { 366 } else { 365 };
+        self.by_day
+            .iter()
+            .flat_map(|day| weekday_dates(first, days_in_year, *day))
+            .collect()
+    }
+
+    fn matches_month(&self, date: NaiveDate) -> bool {
+        self.by_month.is_empty() || self.by_month.contains(&date.month())
+    }
+
+    fn matches_month_day(&self, date: NaiveDate) -> bool {
+        if self.by_month_day.is_empty() {
+            return true;
+        }
+        let days_in_month = days_in_month(date) as usize;
+        self.by_month_day
+            .iter()
+            .filter_map(|d| resolve_position(*d, days_in_month))
+            .any(|i| i as u32 + 1 == date.day())
+    }
+
+    fn matches_weekday(&self, date: NaiveDate) -> bool {
+        self.by_day.is_empty() || self.by_day.iter().any(|d| d.weekday == date.weekday())
+    }
+}
+
+// --- Helper: Expand a stored event (start, end, optional rrule) into concrete instances within [from, to) ---
+// Returns (occurrence_start, occurrence_end) pairs. Events with an unparseable rrule are treated as single
+// instances so a bad rule never hides the event from calendar views.
+pub fn expand_event(
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    rrule: Option<&str>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let duration = end_time - start_time;
+
+    let rule = match rrule.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(r) => match r.parse::<RecurrenceRule>() {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                tracing::warn!("Failed to parse rrule '{}': {}", r, e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    match rule {
+        Some(rule) => rule
+            .occurrences_between(start_time, duration, from, to)
+            .into_iter()
+            .map(|start| (start, start + duration))
+            .collect(),
+        None if overlaps(start_time, end_time, from, to) => vec![(start_time, end_time)],
+        None => vec![],
+    }
+}
+
+// --- Helper: Check whether an event (recurring or not) has an instance within an optional window ---
+// Used to drop series from range queries whose rows overlap the window but whose instances don't.
+pub fn event_overlaps(
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    rrule: Option<&str>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> bool {
+    let from = from.unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let to = to.unwrap_or(DateTime::<Utc>::MAX_UTC);
+
+    match rrule.map(str::trim).filter(|r| !r.is_empty()).map(str::parse::<RecurrenceRule>) {
+        Some(Ok(rule)) => rule.has_occurrence_between(start_time, end_time - start_time, from, to),
+        // Unparseable rules are treated as single instances, as in expand_event
+        _ => overlaps(start_time, end_time, from, to),
+    }
+}
+
+// --- Helper: Check whether `original_start` is a generated occurrence of a recurring event ---
+pub fn is_occurrence(event: &Event, original_start: DateTime<Utc>) -> bool {
+    let has_rrule = event.rrule.as_deref().is_some_and(|r| !r.trim().is_empty());
+    has_rrule
+        && expand_event(
+            event.start_time,
+            event.end_time,
+            event.rrule.as_deref(),
+            original_start,
+            original_start + Duration::seconds(1),
+        )
+        .iter()
+        .any(|(start, _)| *start == original_start)
+}
+
+// --- Helper: Expand a list of events into occurrences within [from, to), ordered by start ---
+// Cancelled occurrences are dropped and modified ones carry their overrides. A modified occurrence is
+// placed by its new time, so it shows up even if its original slot lies outside the window.
+// Soft-deleted events never produce occurrences.
+pub fn expand_events(
+    events: &[Event],
+    exceptions: &[EventException],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<EventOccurrence> {
+    let mut exceptions_by_event: HashMap<i32, Vec<&EventException>> = HashMap::new();
+    for exception in exceptions {
+        exceptions_by_event.entry(exception.event_id).or_default().push(exception);
+    }
+
+    let mut occurrences = Vec::new();
+    for event in events.iter().filter(|event| event.deleted_at.is_none()) {
+        let event_exceptions = exceptions_by_event.get(&event.event_id).map(Vec::as_slice).unwrap_or(&[]);
+
+        // Regular occurrences, minus any slot that has an exception
+        for (occurrence_start, occurrence_end) in
+            expand_event(event.start_time, event.end_time, event.rrule.as_deref(), from, to)
+        {
+            if event_exceptions.iter().any(|ex| ex.original_occurrence_time == occurrence_start) {
+                continue;
+            }
+            occurrences.push(EventOccurrence {
+                event_id: event.event_id,
+                original_start: occurrence_start,
+                occurrence_start,
+                occurrence_end,
+                is_modified: false,
+                title: None,
+                description: None,
+                location: None,
+            });
+        }
+
+        // Modified occurrences, placed at their new time
+        for exception in event_exceptions.iter().filter(|ex| !ex.is_deleted) {
+            let (Some(start), Some(end)) = (exception.start_time, exception.end_time) else { continue };
+            // Exceptions left behind by an edit to the series no longer match an occurrence
+            if !overlaps(start, end, from, to) || !is_occurrence(event, exception.original_occurrence_time) {
+                continue;
+            }
+            occurrences.push(EventOccurrence {
+                event_id: event.event_id,
+                original_start: exception.original_occurrence_time,
+                occurrence_start: start,
+                occurrence_end: end,
+                is_modified: true,
+                title: exception.title.clone(),
+                description: exception.description.clone(),
+                location: exception.location.clone(),
+            });
+        }
+    }
+
+    occurrences.sort_by_key(|o| (o.occurrence_start, o.event_id));
+    occurrences
+}
+
+// --- Helper: Validate an optional rrule from a create/update payload ---
+pub fn validate_rrule(rrule: Option<&str>) -> Result<(), validator::ValidationErrors> {
+    match rrule.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(r) => r.parse::<RecurrenceRule>().map(|_| ()).map_err(|e| {
+            tracing::warn!("Invalid rrule '{}': {}", r, e);
+            let mut err = validator::ValidationError::new("invalid_rrule");
+            err.message = Some(e.into());
+            let mut errors = validator::ValidationErrors::new();
+            errors.add("rrule", err);
+            errors
+        }),
+        None => Ok(()),
+    }
+}
+
+fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    let invalid = || format!("Invalid UNTIL: {}", value);
+    let trimmed = value.trim_end_matches('Z');
+    if trimmed.len() == 8 {
+        // DATE form is inclusive of the whole day
+        let date = NaiveDate::parse_from_str(trimmed, "%Y%m%d").map_err(|_| invalid())?;
+        let end_of_day = date.and_hms_opt(23, 59, 59).ok_or_else(invalid)?;
+        return Ok(Utc.from_utc_datetime(&end_of_day));
+    }
+    // DATE-TIME form; floating times are interpreted as UTC, as everything else in the API is
+    NaiveDateTime::parse_from_str(trimmed, "%Y%m%dT%H%M%S")
+        .map(|dt| Utc.from_utc_datetime(&dt))
+        .map_err(|_| invalid())
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, String> {
+    match value.trim().to_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        other => Err(format!("Invalid weekday: {}", other)),
+    }
+}
+
+fn parse_weekday_num(value: &str) -> Result<WeekdayNum, String> {
+    let value = value.trim();
+    if value.len() < 2 {
+        return Err(format!("Invalid BYDAY value: {}", value));
+    }
+    let (ordinal, day) = value.split_at(value.len() - 2);
+    let weekday = parse_weekday(day)?;
+    let ordinal = if ordinal.is_empty() {
+        None
+    } else {
+        let n = ordinal
+            .trim_start_matches('+')
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid BYDAY value: {}", value))?;
+        if n == 0 || n.abs() > 53 {
+            return Err(format!("Invalid BYDAY ordinal: {}", value));
+        }
+        Some(n)
+    };
+    Ok(WeekdayNum { ordinal, weekday })
+}
+
+fn parse_int_list(value: &str, min_abs: i32, max_abs: i32, name: &str) -> Result<Vec<i32>, String> {
+    value
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .trim_start_matches('+')
+                .parse::<i32>()
+                .ok()
+                .filter(|n| (min_abs..=max_abs).contains(&n.abs()))
+                .ok_or_else(|| format!("Invalid {}: {}", name, value))
+        })
+        .collect()
+}
+
+// Whether [start, end) touches [from, to); zero-length instances count if they start inside the window
+fn overlaps(start: DateTime<Utc>, end: DateTime<Utc>, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+    start < to && (end > from || start >= from)
+}
+
+// Maps a 1-based (or negative, from the end) position onto a 0-based index
+fn resolve_position(pos: i32, len: usize) -> Option<usize> {
+    let len = len as i32;
+    let index = if pos > 0 { pos - 1 } else { len + pos };
+    (0..len).contains(&index).then_some(index as usize)
+}
+
+fn days_since_week_start(weekday: Weekday, week_start: Weekday) -> u32 {
+    (weekday.num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    let first = date.with_day(1).unwrap_or(date);
+    let next = first.checked_add_months(Months::new(1)).unwrap_or(first);
+    (next - first).num_days() as u32
+}
+
+// Dates of a weekday within a span of `len` days starting at `first`, optionally picking the nth one
+fn weekday_dates(first: NaiveDate, len: u32, day: WeekdayNum) -> Vec<NaiveDate> {
+    let offset = days_since_week_start(day.weekday, first.weekday());
+    let all: Vec<NaiveDate> = (offset..len)
+        .step_by(7)
+        .map(|d| first + Duration::days(d as i64))
+        .collect();
+    match day.ordinal {
+        None => all,
+        Some(n) => resolve_position(n, all.len()).map(|i| all[i]).into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+    }
+
+    // Occurrence start dates of a one-hour event within [from, to), as YYYY-MM-DD
+    fn dates(rule: &str, dtstart: DateTime<Utc>, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<String> {
+        let rule: RecurrenceRule = rule.parse().unwrap();
+        rule.occurrences_between(dtstart, Duration::hours(1), from, to)
+            .into_iter()
+            .map(|start| start.format("%Y-%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn by_day_with_ordinals() {
+        // Second Tuesday and last Friday of every month
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=2TU,-1FR", at(2025, 1, 14, 10), at(2025, 1, 1, 0), at(2025, 4, 1, 0)),
+            ["2025-01-14", "2025-01-31", "2025-02-11", "2025-02-28", "2025-03-11", "2025-03-28"]
+        );
+        // Without BYMONTH, yearly ordinals count through the whole year
+        assert_eq!(
+            dates("FREQ=YEARLY;BYDAY=20MO", at(2025, 5, 19, 10), at(2025, 1, 1, 0), at(2027, 1, 1, 0)),
+            ["2025-05-19", "2026-05-18"]
+        );
+        assert!("FREQ=MONTHLY;BYDAY=0MO".parse::<RecurrenceRule>().is_err());
+    }
+
+    #[test]
+    fn by_set_pos_picks_from_each_period() {
+        // Last workday of the month
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1", at(2025, 1, 31, 9), at(2025, 1, 1, 0), at(2025, 5, 1, 0)),
+            ["2025-01-31", "2025-02-28", "2025-03-31", "2025-04-30"]
+        );
+        // First and last weekend day of the month
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=SA,SU;BYSETPOS=1,-1", at(2025, 3, 1, 9), at(2025, 3, 1, 0), at(2025, 5, 1, 0)),
+            ["2025-03-01", "2025-03-30", "2025-04-05", "2025-04-27"]
+        );
+    }
+
+    #[test]
+    fn count_and_until() {
+        let dtstart = at(2025, 1, 1, 9);
+        let window = (at(2025, 1, 1, 0), at(2025, 2, 1, 0));
+        assert_eq!(dates("FREQ=DAILY;COUNT=3", dtstart, window.0, window.1), ["2025-01-01", "2025-01-02", "2025-01-03"]);
+        // COUNT includes occurrences before the window
+        assert_eq!(dates("FREQ=DAILY;COUNT=5", dtstart, at(2025, 1, 4, 0), window.1), ["2025-01-04", "2025-01-05"]);
+        // UNTIL as a date includes that whole day, as a date-time it is an exact bound
+        assert_eq!(dates("FREQ=DAILY;UNTIL=20250103", dtstart, window.0, window.1), ["2025-01-01", "2025-01-02", "2025-01-03"]);
+        assert_eq!(dates("FREQ=DAILY;UNTIL=20250103T080000Z", dtstart, window.0, window.1), ["2025-01-01", "2025-01-02"]);
+        assert!("FREQ=DAILY;COUNT=3;UNTIL=20250103".parse::<RecurrenceRule>().is_err());
+    }
+
+    #[test]
+    fn negative_by_month_day() {
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=-1", at(2024, 1, 31, 9), at(2024, 1, 1, 0), at(2024, 5, 1, 0)),
+            ["2024-01-31", "2024-02-29", "2024-03-31", "2024-04-30"]
+        );
+        // A plain day that some months don't have skips those months
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=31", at(2025, 1, 31, 9), at(2025, 1, 1, 0), at(2025, 5, 1, 0)),
+            ["2025-01-31", "2025-03-31"]
+        );
+    }
+
+    #[test]
+    fn interval_seeks_to_distant_windows() {
+        // Walking from DTSTART would need far more than MAX_PERIODS quarters
+        assert_eq!(
+            dates("FREQ=MONTHLY;INTERVAL=3", at(2000, 1, 15, 9), at(30000, 1, 1, 0), at(30001, 1, 1, 0)),
+            ["+30000-01-15", "+30000-04-15", "+30000-07-15", "+30000-10-15"]
+        );
+        assert_eq!(
+            dates("FREQ=YEARLY;INTERVAL=4", at(2000, 2, 29, 9), at(2023, 1, 1, 0), at(2033, 1, 1, 0)),
+            ["2024-02-29", "2028-02-29", "2032-02-29"]
+        );
+        assert_eq!(
+            dates("FREQ=DAILY;INTERVAL=10", at(2000, 1, 1, 9), at(2300, 1, 1, 0), at(2300, 1, 21, 0)).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn rules_without_instances_stop_after_max_periods() {
+        let rule: RecurrenceRule = "FREQ=DAILY;INTERVAL=2;BYMONTH=2;BYMONTHDAY=30".parse().unwrap();
+        let dtstart = at(2025, 1, 1, 9);
+        assert!(!rule.has_occurrence_between(dtstart, Duration::hours(1), at(2025, 1, 2, 0), DateTime::<Utc>::MAX_UTC));
+    }
+
+    #[test]
+    fn expansion_keeps_the_utc_time_of_day() {
+        // 09:00 in Berlin (UTC+1) before the switch to summer time stays at 08:00 UTC afterwards
+        let starts: Vec<_> = expand_event(at(2025, 3, 24, 8), at(2025, 3, 24, 9), Some("FREQ=WEEKLY"), at(2025, 3, 24, 0), at(2025, 4, 7, 0))
+            .into_iter()
+            .map(|(start, _)| start)
+            .collect();
+        assert_eq!(starts, [at(2025, 3, 24, 8), at(2025, 3, 31, 8)]);
+    }
+}
@@ -0,0 +1,308 @@
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc, Weekday, Datelike};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use crate::errors::AppError;
+use crate::models::{
+    deadline::Deadline,
+    enums::WorkloadUnitType,
+    freebusy::BusyInterval,
+    scheduling::{SuggestedSlot, SuggestionAttendee},
+};
+use crate::utils::calendar::TimeWindow;
+
+const SLOT_STEP_MINUTES: i64 = 15; // Candidate starts are aligned to quarter hours of the working day
+const WORKDAY_MINUTES: i64 = 8 * 60; // A workload "day" is one working day
+const CRUNCH_SPAN_FACTOR: i64 = 3; // Workload needs about three times its length of calendar time before the due date
+const MAX_SUGGESTIONS: usize = 20;
+
+// --- Helper: Resolved working hours ---
+pub struct WorkingSchedule {
+    pub time_zone: Tz,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub days: Vec<Weekday>,
+}
+
+impl WorkingSchedule {
+    // Candidate slots of `duration` inside the working hours and the window, in chronological order
+    pub fn candidate_slots(&self, (from, to): TimeWindow, duration: Duration) -> Vec<TimeWindow> {
+        let step = Duration::minutes(SLOT_STEP_MINUTES);
+        let last_day = to.with_timezone(&self.time_zone).date_naive();
+        let mut day = from.with_timezone(&self.time_zone).date_naive();
+        let mut slots = Vec::new();
+
+        while day <= last_day {
+            if self.days.contains(&day.weekday()) {
+                // Ambiguous local times take the earlier instant; days whose hours fall into a DST gap are skipped
+                let bounds = (
+                    self.time_zone.from_local_datetime(&day.and_time(self.start)).earliest(),
+                    self.time_zone.from_local_datetime(&day.and_time(self.end)).earliest(),
+                );
+                if let (Some(day_start), Some(day_end)) = bounds {
+                    let (day_start, day_end) = (day_start.with_timezone(&Utc), day_end.with_timezone(&Utc));
+                    let mut start = day_start;
+                    while start + duration <= day_end.min(to) {
+                        if start >= from {
+                            slots.push((start, start + duration));
+                        }
+                        start += step;
+                    }
+                }
+            }
+            day = match day.succ_opt() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        slots
+    }
+}
+
+// --- Helper: A period before a deadline in which its workload is likely being worked on ---
+pub struct CrunchPeriod {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub hours: f64, // Workload of the deadline
+}
+
+fn workload_minutes(magnitude: i32, unit: WorkloadUnitType) -> i64 {
+    let magnitude = magnitude.max(0) as i64;
+    match unit {
+        WorkloadUnitType::Minutes => magnitude,
+        WorkloadUnitType::Hours => magnitude * 60,
+        WorkloadUnitType::Days => magnitude * WORKDAY_MINUTES,
+    }
+}
+
+// The crunch period of a workload due at `end`; None if it reaches out of the representable time range
+fn crunch_period(magnitude: i32, unit: WorkloadUnitType, end: DateTime<Utc>) -> Option<CrunchPeriod> {
+    let minutes = workload_minutes(magnitude, unit);
+    let span = minutes.checked_mul(CRUNCH_SPAN_FACTOR).and_then(Duration::try_minutes)?;
+    let start = end.checked_sub_signed(span)?;
+    Some(CrunchPeriod { start, end, hours: minutes as f64 / 60.0 })
+}
+
+// --- Helper: Crunch periods of the users' live deadlines with a workload that overlap [from, to) ---
+// A period ends at the virtual due date when one is set, otherwise at the due date.
+pub async fn load_crunch_periods(
+    pool: &PgPool,
+    user_ids: &[i32],
+    (from, to): TimeWindow,
+) -> Result<Vec<CrunchPeriod>, AppError> {
+    let deadlines = sqlx::query_as!(
+        Deadline,
+        r#"
+        SELECT
+           deadline_id, user_id, category_id, title, description as "description!: _",
+           due_date, virtual_due_date as "virtual_due_date!: _", priority as "priority!: _",
+           workload_magnitude as "workload_magnitude!: _", workload_unit as "workload_unit!: _",
+           created_at as "created_at!", updated_at as "updated_at!", deleted_at as "deleted_at!: _"
+        FROM deadlines
+        WHERE user_id = ANY($1)
+          AND deleted_at IS NULL
+          AND workload_magnitude IS NOT NULL AND workload_unit IS NOT NULL
+          AND COALESCE(virtual_due_date, due_date) > $2
+        "#,
+        user_ids,
+        from
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let periods = deadlines
+        .into_iter()
+        .filter_map(|deadline| {
+            let end = deadline.virtual_due_date.unwrap_or(deadline.due_date);
+            crunch_period(deadline.workload_magnitude?, deadline.workload_unit?, end)
+        })
+        .filter(|period| period.end > period.start && period.start < to)
+        .collect();
+
+    Ok(periods)
+}
+
+// --- Helper: Whether a sorted, non-overlapping list of busy intervals overlaps [start, end) ---
+pub fn is_busy(busy: &[BusyInterval], (start, end): TimeWindow) -> bool {
+    let first_ending_after = busy.partition_point(|interval| interval.end <= start);
+    busy.get(first_ending_after).is_some_and(|interval| interval.start < end)
+}
+
+// --- Helper: Rank candidate slots and pick the suggestions, best first ---
+// `busy` holds the busy intervals of each attendee, in the same order. See suggest_meeting_times for the ranking.
+pub fn rank_slots(
+    slots: Vec<TimeWindow>,
+    attendees: &[SuggestionAttendee],
+    busy: &[Vec<BusyInterval>],
+    crunch_periods: &[CrunchPeriod],
+    min_options: usize,
+) -> Vec<SuggestedSlot> {
+    let mut ranked: Vec<SuggestedSlot> = slots
+        .into_iter()
+        .map(|slot| {
+            let unavailable = attendees
+                .iter()
+                .zip(busy)
+                .filter(|(_, intervals)| is_busy(intervals, slot))
+                .map(|(attendee, _)| attendee.email.clone())
+                .collect();
+            let crunch_hours = crunch_periods
+                .iter()
+                .filter(|period| period.start < slot.1 && slot.0 < period.end)
+                .fold(0.0, |total, period| total + period.hours);
+            SuggestedSlot { start: slot.0, end: slot.1, unavailable, crunch_hours }
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        a.unavailable.len().cmp(&b.unavailable.len())
+            .then(a.crunch_hours.total_cmp(&b.crunch_hours))
+            .then(a.start.cmp(&b.start))
+    });
+
+    let mut suggestions: Vec<SuggestedSlot> = Vec::new();
+    for slot in ranked {
+        if suggestions.len() >= MAX_SUGGESTIONS || (!slot.unavailable.is_empty() && suggestions.len() >= min_options) {
+            break;
+        }
+        if suggestions.iter().any(|s| s.start < slot.end && slot.start < s.end) {
+            continue;
+        }
+        suggestions.push(slot);
+    }
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2025-03-10 is a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, day, hour, minute, 0).unwrap()
+    }
+
+    fn schedule(time_zone: Tz) -> WorkingSchedule {
+        WorkingSchedule {
+            time_zone,
+            start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+            days: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+        }
+    }
+
+    fn attendee(email: &str) -> SuggestionAttendee {
+        SuggestionAttendee { email: email.to_string() }
+    }
+
+    fn busy(start: DateTime<Utc>, end: DateTime<Utc>) -> BusyInterval {
+        BusyInterval { start, end }
+    }
+
+    fn crunch(start: DateTime<Utc>, end: DateTime<Utc>, hours: f64) -> CrunchPeriod {
+        CrunchPeriod { start, end, hours }
+    }
+
+    #[test]
+    fn candidate_slots_stay_inside_working_hours_and_days() {
+        // Saturday 8th to Monday 10th: the weekend has no working hours
+        let slots = schedule(chrono_tz::UTC).candidate_slots((at(8, 0, 0), at(11, 0, 0)), Duration::minutes(90));
+        assert_eq!(slots, vec![
+            (at(10, 9, 0), at(10, 10, 30)),
+            (at(10, 9, 15), at(10, 10, 45)),
+            (at(10, 9, 30), at(10, 11, 0)),
+        ]);
+    }
+
+    #[test]
+    fn candidate_slots_are_clipped_to_the_window_and_stay_aligned() {
+        let slots = schedule(chrono_tz::UTC).candidate_slots((at(10, 9, 10), at(10, 10, 0)), Duration::minutes(30));
+        assert_eq!(slots, vec![(at(10, 9, 15), at(10, 9, 45)), (at(10, 9, 30), at(10, 10, 0))]);
+    }
+
+    #[test]
+    fn candidate_slots_follow_the_time_zone() {
+        // New York is UTC-4 after the DST change on 2025-03-09
+        let slots = schedule(chrono_tz::America::New_York)
+            .candidate_slots((at(10, 0, 0), at(11, 0, 0)), Duration::minutes(120));
+        assert_eq!(slots, vec![(at(10, 13, 0), at(10, 15, 0))]);
+    }
+
+    #[test]
+    fn busy_checks_overlap_not_touching() {
+        let intervals = [busy(at(10, 9, 0), at(10, 10, 0)), busy(at(10, 12, 0), at(10, 13, 0))];
+        assert!(is_busy(&intervals, (at(10, 9, 30), at(10, 9, 45))));
+        assert!(is_busy(&intervals, (at(10, 11, 30), at(10, 12, 30))));
+        assert!(!is_busy(&intervals, (at(10, 10, 0), at(10, 12, 0))));
+        assert!(!is_busy(&intervals, (at(10, 13, 0), at(10, 14, 0))));
+        assert!(!is_busy(&[], (at(10, 9, 0), at(10, 10, 0))));
+    }
+
+    #[test]
+    fn crunch_period_spans_three_times_the_workload() {
+        let due = at(14, 17, 0);
+        let period = crunch_period(2, WorkloadUnitType::Hours, due).unwrap();
+        assert_eq!((period.start, period.end, period.hours), (at(14, 11, 0), due, 2.0));
+
+        let period = crunch_period(1, WorkloadUnitType::Days, due).unwrap();
+        assert_eq!((period.start, period.hours), (at(13, 17, 0), 8.0));
+
+        let period = crunch_period(-5, WorkloadUnitType::Minutes, due).unwrap();
+        assert_eq!((period.start, period.hours), (due, 0.0));
+    }
+
+    #[test]
+    fn crunch_period_out_of_range_is_dropped() {
+        assert!(crunch_period(i32::MAX, WorkloadUnitType::Days, at(14, 17, 0)).is_none());
+        assert!(crunch_period(i32::MAX, WorkloadUnitType::Minutes, at(14, 17, 0)).is_some());
+    }
+
+    #[test]
+    fn slots_rank_by_unavailable_then_crunch_then_time() {
+        let slots = vec![
+            (at(10, 9, 0), at(10, 10, 0)),
+            (at(10, 10, 0), at(10, 11, 0)),
+            (at(10, 11, 0), at(10, 12, 0)),
+            (at(10, 12, 0), at(10, 13, 0)),
+        ];
+        let attendees = [attendee("a@example.com"), attendee("b@example.com")];
+        let busy = [vec![busy(at(10, 9, 0), at(10, 10, 0))], vec![]];
+        let crunch = [crunch(at(10, 10, 0), at(10, 11, 0), 4.0), crunch(at(10, 11, 0), at(10, 12, 0), 1.0)];
+
+        let ranked = rank_slots(slots, &attendees, &busy, &crunch, 4);
+        let starts: Vec<_> = ranked.iter().map(|s| s.start).collect();
+        assert_eq!(starts, vec![at(10, 12, 0), at(10, 11, 0), at(10, 10, 0), at(10, 9, 0)]);
+        assert_eq!(ranked[2].crunch_hours, 4.0);
+        assert_eq!(ranked[3].unavailable, vec!["a@example.com".to_string()]);
+    }
+
+    #[test]
+    fn suggestions_never_overlap() {
+        let slots = schedule(chrono_tz::UTC).candidate_slots((at(10, 0, 0), at(11, 0, 0)), Duration::minutes(60));
+        let ranked = rank_slots(slots, &[], &[], &[], 3);
+        let starts: Vec<_> = ranked.iter().map(|s| s.start).collect();
+        assert_eq!(starts, vec![at(10, 9, 0), at(10, 10, 0)]);
+    }
+
+    #[test]
+    fn partially_available_slots_only_fill_up_to_min_options() {
+        let slots = vec![
+            (at(10, 9, 0), at(10, 10, 0)),
+            (at(10, 10, 0), at(10, 11, 0)),
+            (at(10, 11, 0), at(10, 12, 0)),
+        ];
+        let attendees = [attendee("a@example.com")];
+        let busy = [vec![busy(at(10, 10, 0), at(10, 12, 0))]];
+
+        assert_eq!(rank_slots(slots.clone(), &attendees, &busy, &[], 2).len(), 2);
+        assert_eq!(rank_slots(slots.clone(), &attendees, &busy, &[], 0).len(), 1);
+        assert_eq!(rank_slots(slots, &attendees, &busy, &[], 5).len(), 3);
+    }
+
+    #[test]
+    fn suggestions_are_capped() {
+        let slots = (0..30).map(|day| {
+            let start = at(1, 9, 0) + Duration::days(day);
+            (start, start + Duration::hours(1))
+        }).collect();
+        assert_eq!(rank_slots(slots, &[], &[], &[], 3).len(), MAX_SUGGESTIONS);
+    }
+}
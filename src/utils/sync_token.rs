@@ -0,0 +1,39 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use crate::errors::AppError;
+
+// Sync tokens are opaque to clients. Internally a token is a position in the user's change log
+// (sync_change_log.change_id), which only ever grows and never skips uncommitted changes.
+pub fn encode_sync_token(change_id: i64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("c:{}", change_id))
+}
+
+pub fn decode_sync_token(token: &str) -> Result<i64, AppError> {
+    let invalid = || {
+        tracing::warn!("Invalid sync token '{}'", token);
+        invalid_sync_token()
+    };
+
+    let raw = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+    let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+    raw.strip_prefix("c:")
+        .and_then(|id| id.parse::<i64>().ok())
+        .filter(|id| *id >= 0)
+        .ok_or_else(invalid)
+}
+
+// Error for tokens that can't be used, e.g. ones issued for another change log
+pub fn invalid_sync_token() -> AppError {
+    let mut err = validator::ValidationError::new("invalid_sync_token");
+    err.message = Some("Unknown sync token; sync again without one".into());
+    let mut errors = validator::ValidationErrors::new();
+    errors.add("since", err);
+    AppError::ValidationFailed(errors)
+}
+
+// Helper to parse an optional 'since' sync token (empty means "from the beginning")
+pub fn parse_optional_sync_token(token: Option<String>) -> Result<Option<i64>, AppError> {
+    match token {
+        Some(t) if !t.is_empty() => decode_sync_token(&t).map(Some),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use common::{read, TestServer};
+use reqwest::multipart::Form;
+use serde_json::{json, Value};
+
+// --- AI assistant against the mock provider (AI_PROVIDER=mock) ---
+// The mock turns prompt lines like `/call create_event {...}` into tool calls and then answers with a
+// summary of the tool results; see src/ai/mock.rs.
+
+struct Assistant {
+    server: TestServer,
+    token: String,
+    category_id: i32,         // One of the user's default categories
+    foreign_category_id: i32, // Belongs to another user
+}
+
+impl Assistant {
+    async fn start(env: &[(&str, &str)]) -> Assistant {
+        let server = TestServer::start(&[[("AI_PROVIDER", "mock")].as_slice(), env].concat()).await;
+        let user_id = server.create_user("alice@example.com", "password123").await;
+        let other_user_id = server.create_user("bob@example.com", "password123").await;
+        let token = server.token("alice@example.com", "password123").await;
+        let first_category = |user_id: i32| {
+            sqlx::query_scalar("SELECT MIN(category_id) FROM categories WHERE user_id = $1").bind(user_id).fetch_one(&server.pool)
+        };
+        let category_id = first_category(user_id).await.unwrap();
+        let foreign_category_id = first_category(other_user_id).await.unwrap();
+        Assistant { server, token, category_id, foreign_category_id }
+    }
+
+    async fn prompt(&self, query: &str, prompt: &str) -> (u16, Value, reqwest::header::HeaderMap) {
+        let form = Form::new().text("prompt", prompt.to_string()).text("timeZone", "Europe/Berlin");
+        let response = self
+            .server
+            .client
+            .post(self.server.url(&format!("/api/me/ai-assistant{}", query)))
+            .bearer_auth(&self.token)
+            .multipart(form)
+            .send()
+            .await
+            .unwrap();
+        let headers = response.headers().clone();
+        let (status, body) = read(response).await;
+        (status, body, headers)
+    }
+
+    fn event(&self, title: &str, category_id: i32) -> Value {
+        json!({
+            "title": title,
+            "categoryId": category_id,
+            "startTime": "2030-05-06T09:00:00Z",
+            "endTime": "2030-05-06T10:00:00Z",
+        })
+    }
+
+    fn deadline(&self, title: &str) -> Value {
+        json!({ "title": title, "categoryId": self.category_id, "dueDate": "2030-05-10T12:00:00Z", "priority": "important" })
+    }
+
+    async fn item_counts(&self) -> (i64, i64) {
+        (
+            self.server.count("SELECT COUNT(*) FROM events").await,
+            self.server.count("SELECT COUNT(*) FROM deadlines").await,
+        )
+    }
+}
+
+#[tokio::test]
+async fn proposal_is_returned_for_confirmation() {
+    let assistant = Assistant::start(&[]).await;
+    let prompt = format!(
+        "Plan my week\n/call create_event {}\n/call create_deadline {}\n/call create_event {}",
+        assistant.event("Review", assistant.category_id),
+        assistant.deadline("Report"),
+        assistant.event("Not mine", assistant.foreign_category_id),
+    );
+
+    let (status, body, _) = assistant.prompt("", &prompt).await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(body["response"].as_str().unwrap().starts_with("Mock reply after 3 tool call(s)"), "{}", body);
+
+    // The invalid event went back to the model as an error instead of into the proposal
+    let events = body["proposal"]["events"].as_array().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["title"], "Review");
+    assert_eq!(body["proposal"]["deadlines"][0]["title"], "Report");
+    assert!(body["applied"].is_null());
+    assert_eq!(assistant.item_counts().await, (0, 0));
+
+    let (status, thread) = assistant
+        .server
+        .get(&format!("/api/me/ai-assistant/threads/{}", body["threadId"]), Some(&assistant.token))
+        .await;
+    assert_eq!(status, 200);
+    assert_eq!(thread["messages"].as_array().unwrap().len(), 2);
+    assert_eq!(thread["messages"][1]["proposal"]["events"][0]["title"], "Review");
+}
+
+#[tokio::test]
+async fn apply_parameter_creates_the_proposal() {
+    let assistant = Assistant::start(&[]).await;
+    let prompt = format!(
+        "/call create_event {}\n/call create_deadline {}",
+        assistant.event("Review", assistant.category_id),
+        assistant.deadline("Report"),
+    );
+
+    let (status, body, _) = assistant.prompt("?apply=true", &prompt).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["applied"]["events"][0]["title"], "Review");
+    assert!(body["applied"]["events"][0]["eventId"].is_i64(), "{}", body);
+    assert_eq!(body["applied"]["deadlines"][0]["title"], "Report");
+    assert_eq!(assistant.item_counts().await, (1, 1));
+}
+
+#[tokio::test]
+async fn applying_a_proposal_is_all_or_nothing() {
+    let assistant = Assistant::start(&[]).await;
+    let token = Some(assistant.token.as_str());
+
+    // The client may edit a proposal before confirming it; one bad item rejects all of them
+    let proposal = json!({
+        "events": [assistant.event("Review", assistant.category_id), assistant.event("Not mine", assistant.foreign_category_id)],
+        "deadlines": [assistant.deadline("Report")],
+    });
+    let (status, body) = assistant.server.post("/api/me/ai-assistant/apply", token, &proposal).await;
+    assert!((400..500).contains(&status), "{}: {}", status, body);
+    assert_eq!(assistant.item_counts().await, (0, 0));
+
+    let proposal = json!({
+        "events": [assistant.event("Review", assistant.category_id)],
+        "deadlines": [assistant.deadline("Report")],
+    });
+    let (status, body) = assistant.server.post("/api/me/ai-assistant/apply", token, &proposal).await;
+    assert_eq!(status, 201, "{}", body);
+    assert_eq!(assistant.item_counts().await, (1, 1));
+}
+
+#[tokio::test]
+async fn exhausted_quota_stops_further_prompts() {
+    let assistant = Assistant::start(&[("AI_DAILY_TOKEN_QUOTA", "1")]).await;
+
+    // The first prompt starts below the quota and runs to the end
+    let (status, body, _) = assistant.prompt("", "What is due this week?").await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, body, headers) = assistant.prompt("", "And next week?").await;
+    assert_eq!(status, 429);
+    assert_eq!(body["error"], "Daily AI usage quota exceeded");
+    let retry_after: u64 = headers["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=86_400).contains(&retry_after));
+
+    let (status, usage) = assistant.server.get("/api/me/ai-assistant/usage", Some(&assistant.token)).await;
+    assert_eq!(status, 200);
+    assert_eq!(usage["today"]["quota"], 1);
+    assert_eq!(usage["today"]["remaining"], 0);
+    assert_eq!(usage["days"][0]["requestCount"], 1);
+}
+
+#[tokio::test]
+async fn parallel_prompts_cannot_share_the_rest_of_a_quota() {
+    let assistant = Assistant::start(&[("AI_DAILY_TOKEN_QUOTA", "1")]).await;
+
+    // Each prompt holds an estimate of its first turn, so only one of them starts below the quota
+    let (a, b, c, d) = tokio::join!(
+        assistant.prompt("", "What is due?"),
+        assistant.prompt("", "Any events?"),
+        assistant.prompt("", "What is due?"),
+        assistant.prompt("", "Any events?"),
+    );
+    let statuses = [a.0, b.0, c.0, d.0];
+    assert_eq!(statuses.iter().filter(|&&status| status == 200).count(), 1, "{:?}", statuses);
+    assert_eq!(statuses.iter().filter(|&&status| status == 429).count(), 3, "{:?}", statuses);
+
+    let (_, usage) = assistant.server.get("/api/me/ai-assistant/usage", Some(&assistant.token)).await;
+    assert_eq!(usage["days"][0]["requestCount"], 1);
+}
@@ -0,0 +1,189 @@
+#![allow(dead_code)] // Each test binary uses a different part of the harness
+
+use serde_json::{json, Value};
+use sqlx::{Connection, PgConnection, PgPool};
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+// --- Integration test harness ---
+// Every test runs the real server binary against its own database, created from sql/setup.sql in the
+// Postgres at TEST_DATABASE_URL (default: local postgres user, no password) and dropped afterwards.
+// The server only sees the environment given here; its log is printed when a test fails.
+
+const DEFAULT_ADMIN_URL: &str = "postgres://postgres@localhost:5432/postgres";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+pub const FRONTEND_URL: &str = "http://localhost:3000"; // Also the WebAuthn origin
+
+pub struct TestServer {
+    pub base_url: String,
+    pub client: reqwest::Client,
+    pub pool: PgPool, // The test database, for setting up and checking rows
+    child: Child,
+    dir: PathBuf,
+    admin_url: String,
+    database: String,
+}
+
+impl TestServer {
+    // Starts the server with the base configuration plus `env`, which can override it
+    pub async fn start(env: &[(&str, &str)]) -> TestServer {
+        let admin_url = std::env::var("TEST_DATABASE_URL").unwrap_or_else(|_| DEFAULT_ADMIN_URL.to_string());
+        let database = format!("qalendar_test_{}", Uuid::new_v4().simple());
+        let database_url = format!("{}/{}", admin_url.rsplit_once('/').expect("TEST_DATABASE_URL has no database").0, database);
+
+        let mut admin = PgConnection::connect(&admin_url).await.expect("Postgres not reachable at TEST_DATABASE_URL");
+        sqlx::query(&format!("CREATE DATABASE {}", database)).execute(&mut admin).await.expect("Failed to create test database");
+        admin.close().await.ok();
+        let pool = PgPool::connect(&database_url).await.expect("Failed to connect to test database");
+        sqlx::raw_sql(include_str!("../../sql/setup.sql")).execute(&pool).await.expect("Failed to load sql/setup.sql");
+
+        // The debug build loads .env from the working directory; an empty one keeps the developer's out
+        let dir = std::env::temp_dir().join(&database);
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+        std::fs::write(dir.join(".env"), "").expect("Failed to write .env");
+        let log = std::fs::File::create(dir.join("server.log")).expect("Failed to create server log");
+
+        let address = TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).expect("No free port");
+        let base_env = [
+            ("DATABASE_URL", database_url.as_str()),
+            ("JWT_SECRET", "integration-test-secret"),
+            ("SERVER_ADDRESS", &address.to_string()),
+            ("SMTP_SERVER", "localhost"),
+            ("SMTP_PORT", "2525"),
+            ("SMTP_USER", "test"),
+            ("SMTP_PASSWORD", "test"),
+            ("SENDER_EMAIL", "noreply@example.com"),
+            ("FRONTEND_URL", FRONTEND_URL),
+            ("AUTH_RATE_LIMIT_PER_MINUTE", "1000"),
+            ("RUST_LOG", "qalendar_api=debug"),
+        ]
+        .map(|(key, value)| (key, value.to_string()));
+
+        let child = Command::new(env!("CARGO_BIN_EXE_qalendar-api"))
+            .current_dir(&dir)
+            .env_clear()
+            .envs(base_env)
+            .envs(env.iter().copied())
+            .stdout(log.try_clone().expect("Failed to share server log"))
+            .stderr(log)
+            .stdin(Stdio::null())
+            .spawn()
+            .expect("Failed to start server");
+
+        let mut server = TestServer {
+            base_url: format!("http://{}", address),
+            client: reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build().unwrap(),
+            pool,
+            child,
+            dir,
+            admin_url,
+            database,
+        };
+        server.wait_until_ready().await;
+        server
+    }
+
+    async fn wait_until_ready(&mut self) {
+        let started = Instant::now();
+        loop {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                panic!("Server exited during startup ({}):\n{}", status, self.log());
+            }
+            if self.client.get(self.url("/api/auth/oidc/providers")).send().await.is_ok() {
+                return;
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                panic!("Server did not start within {:?}:\n{}", STARTUP_TIMEOUT, self.log());
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    pub fn log(&self) -> String {
+        std::fs::read_to_string(self.dir.join("server.log")).unwrap_or_default()
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // A verified user who can log in with the password; returns the user ID
+    pub async fn create_user(&self, email: &str, password: &str) -> i32 {
+        let password_hash = bcrypt::hash(password, 4).unwrap(); // Lowest cost, tests don't need more
+        sqlx::query_scalar(
+            "INSERT INTO users (display_name, email, password_hash, email_verified) VALUES ($1, $2, $3, true) RETURNING user_id",
+        )
+        .bind(email.split('@').next().unwrap())
+        .bind(email)
+        .bind(password_hash)
+        .fetch_one(&self.pool)
+        .await
+        .unwrap()
+    }
+
+    // The password step of the login: an AuthResponse, or a TfaRequiredResponse with 2FA enabled
+    pub async fn login(&self, email: &str, password: &str) -> Value {
+        let (status, body) = self.post("/api/auth/login", None, &json!({ "email": email, "password": password })).await;
+        assert_eq!(status, 200, "login failed: {}", body);
+        body
+    }
+
+    // Access token of a user without 2FA
+    pub async fn token(&self, email: &str, password: &str) -> String {
+        self.login(email, password).await["token"].as_str().expect("login asked for 2FA").to_string()
+    }
+
+    pub async fn get(&self, path: &str, token: Option<&str>) -> (u16, Value) {
+        let mut request = self.client.get(self.url(path));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        read(request.send().await.unwrap()).await
+    }
+
+    pub async fn post(&self, path: &str, token: Option<&str>, body: &Value) -> (u16, Value) {
+        let mut request = self.client.post(self.url(path)).json(body);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        read(request.send().await.unwrap()).await
+    }
+
+    pub async fn count(&self, sql: &str) -> i64 {
+        sqlx::query_scalar(sql).fetch_one(&self.pool).await.unwrap()
+    }
+}
+
+// Status and JSON body of a response (Null if the body is empty or not JSON)
+pub async fn read(response: reqwest::Response) -> (u16, Value) {
+    let status = response.status().as_u16();
+    let body = response.json().await.unwrap_or(Value::Null);
+    (status, body)
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if std::thread::panicking() {
+            eprintln!("--- server log ---\n{}", self.log());
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+
+        // Drop runs inside the test's runtime, which can't be blocked on; use a thread with its own
+        let (admin_url, database) = (self.admin_url.clone(), self.database.clone());
+        let _ = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+                if let Ok(mut admin) = PgConnection::connect(&admin_url).await {
+                    let _ = sqlx::query(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", database)).execute(&mut admin).await;
+                }
+            })
+        })
+        .join();
+    }
+}
@@ -0,0 +1,369 @@
+mod common;
+
+use axum::{
+    extract::{Form, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use common::TestServer;
+use jsonwebtoken::{EncodingKey, Header};
+use openssl::rsa::Rsa;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// --- Mock OpenID Connect provider ---
+// Serves discovery, JWKS, an authorization endpoint that approves every request and a token endpoint
+// that checks the client and the PKCE verifier. ID tokens are signed with RS256.
+
+const CLIENT_ID: &str = "qalendar";
+const CLIENT_SECRET: &str = "provider-secret";
+
+struct SigningKey {
+    kid: String,
+    key: EncodingKey,
+    jwk: Value,
+}
+
+impl SigningKey {
+    fn generate(kid: &str) -> Self {
+        let rsa = Rsa::generate(2048).unwrap();
+        let jwk = json!({
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": kid,
+            "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+            "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+        });
+        let key = EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap();
+        SigningKey { kid: kid.to_string(), key, jwk }
+    }
+}
+
+// What the authorization endpoint was asked for, by code
+struct Authorization {
+    client_id: String,
+    redirect_uri: String,
+    code_challenge: String,
+    nonce: String,
+}
+
+struct ProviderState {
+    issuer: String, // As in the discovery document
+    signing_key: Arc<SigningKey>,
+    published_keys: Vec<Value>,
+    authorizations: HashMap<String, Authorization>,
+    claims: Value, // Added to the ID tokens, overriding the defaults
+    jwks_fetches: usize,
+}
+
+type SharedState = Arc<Mutex<ProviderState>>;
+
+struct MockProvider {
+    base_url: String,
+    state: SharedState,
+}
+
+impl MockProvider {
+    // `issuer` may refer to the provider's address as {base}
+    async fn start(issuer: &str) -> MockProvider {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let signing_key = Arc::new(SigningKey::generate("key-1"));
+        let state = Arc::new(Mutex::new(ProviderState {
+            issuer: issuer.replace("{base}", &base_url),
+            published_keys: vec![signing_key.jwk.clone()],
+            signing_key,
+            authorizations: HashMap::new(),
+            claims: json!({}),
+            jwks_fetches: 0,
+        }));
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        MockProvider { base_url, state }
+    }
+
+    // The identity in the next ID tokens
+    fn set_claims(&self, claims: Value) {
+        self.state.lock().unwrap().claims = claims;
+    }
+
+    // Sign with a new key, published in the JWKS or not
+    fn rotate_key(&self, kid: &str, publish: bool) {
+        let key = Arc::new(SigningKey::generate(kid));
+        let mut state = self.state.lock().unwrap();
+        if publish {
+            state.published_keys = vec![key.jwk.clone()];
+        }
+        state.signing_key = key;
+    }
+
+    fn jwks_fetches(&self) -> usize {
+        self.state.lock().unwrap().jwks_fetches
+    }
+}
+
+async fn discovery(State(state): State<SharedState>) -> Json<Value> {
+    let issuer = state.lock().unwrap().issuer.clone();
+    let base = issuer.split("/{tenantid}").next().unwrap().to_string();
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", base),
+        "token_endpoint": format!("{}/token", base),
+        "jwks_uri": format!("{}/jwks", base),
+    }))
+}
+
+async fn jwks(State(state): State<SharedState>) -> Json<Value> {
+    let mut state = state.lock().unwrap();
+    state.jwks_fetches += 1;
+    Json(json!({ "keys": state.published_keys }))
+}
+
+async fn authorize(State(state): State<SharedState>, Query(params): Query<HashMap<String, String>>) -> Response {
+    if params.get("response_type").map(String::as_str) != Some("code")
+        || params.get("code_challenge_method").map(String::as_str) != Some("S256")
+    {
+        return (StatusCode::BAD_REQUEST, "unsupported request").into_response();
+    }
+    let code = uuid::Uuid::new_v4().to_string();
+    let redirect_uri = params["redirect_uri"].clone();
+    let location = format!("{}?code={}&state={}", redirect_uri, code, urlencoding::encode(&params["state"]));
+    state.lock().unwrap().authorizations.insert(
+        code,
+        Authorization {
+            client_id: params["client_id"].clone(),
+            redirect_uri,
+            code_challenge: params["code_challenge"].clone(),
+            nonce: params["nonce"].clone(),
+        },
+    );
+    Redirect::to(&location).into_response()
+}
+
+async fn token(State(state): State<SharedState>, Form(form): Form<HashMap<String, String>>) -> Response {
+    let mut state = state.lock().unwrap();
+    let field = |name: &str| form.get(name).map(String::as_str).unwrap_or_default();
+    let invalid_grant = (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid_grant" }))).into_response();
+
+    // Codes are single use, even when the request fails
+    let Some(authorization) = state.authorizations.remove(field("code")) else {
+        return invalid_grant;
+    };
+    let verifier_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(field("code_verifier").as_bytes()));
+    if field("grant_type") != "authorization_code"
+        || field("client_id") != authorization.client_id
+        || field("client_secret") != CLIENT_SECRET
+        || field("redirect_uri") != authorization.redirect_uri
+        || verifier_challenge != authorization.code_challenge
+    {
+        return invalid_grant;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut claims = json!({
+        "iss": state.issuer,
+        "aud": CLIENT_ID,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": authorization.nonce,
+    });
+    for (name, value) in state.claims.as_object().unwrap() {
+        claims[name] = value.clone();
+    }
+    let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+    header.kid = Some(state.signing_key.kid.clone());
+    let id_token = jsonwebtoken::encode(&header, &claims, &state.signing_key.key).unwrap();
+    Json(json!({ "access_token": "unused", "token_type": "Bearer", "id_token": id_token })).into_response()
+}
+
+// --- Helpers ---
+
+async fn start_server(providers: &[(&str, &MockProvider)]) -> TestServer {
+    let ids: Vec<&str> = providers.iter().map(|(id, _)| *id).collect();
+    let mut env = vec![("OIDC_PROVIDERS".to_string(), ids.join(","))];
+    for (id, provider) in providers {
+        let prefix = format!("OIDC_{}", id.to_uppercase());
+        env.push((format!("{}_ISSUER", prefix), provider.base_url.clone()));
+        env.push((format!("{}_CLIENT_ID", prefix), CLIENT_ID.to_string()));
+        env.push((format!("{}_CLIENT_SECRET", prefix), CLIENT_SECRET.to_string()));
+    }
+    let env: Vec<(&str, &str)> = env.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+    TestServer::start(&env).await
+}
+
+// Starts a login and lets the provider approve it; returns the code and state of the redirect
+async fn authorize_login(server: &TestServer, provider_id: &str) -> (String, String) {
+    let (status, start) = server.post(&format!("/api/auth/oidc/{}/start", provider_id), None, &json!({})).await;
+    assert_eq!(status, 200, "{}", start);
+    let response = server.client.get(start["authorizationUrl"].as_str().unwrap()).send().await.unwrap();
+    assert!(response.status().is_redirection(), "provider refused the authorization request");
+    let location = reqwest::Url::parse(response.headers()["location"].to_str().unwrap()).unwrap();
+    assert!(location.as_str().starts_with(common::FRONTEND_URL));
+    let params: HashMap<String, String> = location.query_pairs().into_owned().collect();
+    (params["code"].clone(), params["state"].clone())
+}
+
+async fn finish_login(server: &TestServer, provider_id: &str, code: &str, state: &str) -> (u16, Value) {
+    server
+        .post(&format!("/api/auth/oidc/{}/finish", provider_id), None, &json!({ "code": code, "state": state }))
+        .await
+}
+
+async fn sign_in(server: &TestServer, provider_id: &str) -> (u16, Value) {
+    let (code, state) = authorize_login(server, provider_id).await;
+    finish_login(server, provider_id, &code, &state).await
+}
+
+// --- Tests ---
+
+#[tokio::test]
+async fn first_login_creates_an_account_that_later_logins_reuse() {
+    let provider = MockProvider::start("{base}").await;
+    let server = start_server(&[("mock", &provider)]).await;
+    provider.set_claims(json!({ "sub": "subject-1", "email": "new@example.com", "email_verified": true, "name": "New User" }));
+
+    let (status, body) = sign_in(&server, "mock").await;
+    assert_eq!(status, 200, "{}", body);
+    let user_id = body["user"]["userId"].as_i64().unwrap();
+    assert_eq!(body["user"]["displayName"], "New User");
+    assert_eq!(server.count("SELECT COUNT(*) FROM user_identities WHERE provider = 'mock' AND subject = 'subject-1'").await, 1);
+
+    // The linked identity decides, not the email
+    provider.set_claims(json!({ "sub": "subject-1", "email": "changed@example.com", "email_verified": true }));
+    let (status, body) = sign_in(&server, "mock").await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["user"]["userId"].as_i64(), Some(user_id));
+    assert_eq!(server.count("SELECT COUNT(*) FROM users").await, 1);
+}
+
+#[tokio::test]
+async fn code_is_only_redeemed_with_the_verifier_of_its_login() {
+    let provider = MockProvider::start("{base}").await;
+    let server = start_server(&[("mock", &provider)]).await;
+    provider.set_claims(json!({ "sub": "subject-1", "email": "new@example.com", "email_verified": true }));
+
+    // The code of one login sent with the state, and so the PKCE verifier, of another
+    let (first_code, _) = authorize_login(&server, "mock").await;
+    let (_, second_state) = authorize_login(&server, "mock").await;
+    let (status, _) = finish_login(&server, "mock", &first_code, &second_state).await;
+    assert_eq!(status, 401);
+
+    // The state is used up either way
+    let (status, _) = finish_login(&server, "mock", &first_code, &second_state).await;
+    assert_eq!(status, 400);
+    assert_eq!(server.count("SELECT COUNT(*) FROM users").await, 0);
+}
+
+#[tokio::test]
+async fn id_token_nonce_must_match_the_login() {
+    let provider = MockProvider::start("{base}").await;
+    let server = start_server(&[("mock", &provider)]).await;
+    provider.set_claims(json!({ "sub": "subject-1", "email": "new@example.com", "email_verified": true, "nonce": "replayed" }));
+
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 401);
+    assert_eq!(server.count("SELECT COUNT(*) FROM users").await, 0);
+}
+
+#[tokio::test]
+async fn rotated_signing_keys_are_fetched_again() {
+    let provider = MockProvider::start("{base}").await;
+    let server = start_server(&[("mock", &provider)]).await;
+    provider.set_claims(json!({ "sub": "subject-1", "email": "new@example.com", "email_verified": true }));
+
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 200);
+    assert_eq!(provider.jwks_fetches(), 1);
+
+    // An unknown key ID refreshes the cached keys once, then they are cached again
+    provider.rotate_key("key-2", true);
+    let (status, body) = sign_in(&server, "mock").await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(provider.jwks_fetches(), 2);
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 200);
+    assert_eq!(provider.jwks_fetches(), 2);
+
+    // A key the provider doesn't publish is refused
+    provider.rotate_key("key-3", false);
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn issuer_and_audience_are_checked() {
+    let provider = MockProvider::start("{base}").await;
+    let server = start_server(&[("mock", &provider)]).await;
+
+    provider.set_claims(json!({ "sub": "subject-1", "email": "new@example.com", "email_verified": true, "aud": "another-client" }));
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 401);
+
+    provider.set_claims(json!({ "sub": "subject-1", "email": "new@example.com", "email_verified": true, "iss": "https://issuer.example.com" }));
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 401);
+    assert_eq!(server.count("SELECT COUNT(*) FROM users").await, 0);
+}
+
+#[tokio::test]
+async fn tenant_placeholder_of_the_issuer_is_filled_from_the_token() {
+    // Like Microsoft's multi-tenant endpoint
+    let provider = MockProvider::start("{base}/{tenantid}/v2.0").await;
+    let server = start_server(&[("microsoft", &provider)]).await;
+    let tenant_issuer = |tenant: &str| format!("{}/{}/v2.0", provider.base_url, tenant);
+
+    provider.set_claims(json!({
+        "sub": "subject-1", "email": "new@example.com", "email_verified": true,
+        "tid": "tenant-a", "iss": tenant_issuer("tenant-b"),
+    }));
+    let (status, _) = sign_in(&server, "microsoft").await;
+    assert_eq!(status, 401);
+
+    provider.set_claims(json!({
+        "sub": "subject-1", "email": "new@example.com", "email_verified": true,
+        "tid": "tenant-a", "iss": tenant_issuer("tenant-a"),
+    }));
+    let (status, body) = sign_in(&server, "microsoft").await;
+    assert_eq!(status, 200, "{}", body);
+}
+
+#[tokio::test]
+async fn identities_link_to_accounts_only_by_verified_email() {
+    let provider = MockProvider::start("{base}").await;
+    let server = start_server(&[("mock", &provider)]).await;
+    let alice = server.create_user("Alice@Example.com", "password123").await;
+    let bob = server.create_user("bob@example.com", "password123").await;
+    sqlx::query("UPDATE users SET email_verified = false WHERE user_id = $1").bind(bob).execute(&server.pool).await.unwrap();
+
+    // The provider doesn't vouch for the address
+    provider.set_claims(json!({ "sub": "subject-1", "email": "alice@example.com", "email_verified": false }));
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 409);
+
+    // We don't: the address of bob was never confirmed
+    provider.set_claims(json!({ "sub": "subject-2", "email": "bob@example.com", "email_verified": true }));
+    let (status, _) = sign_in(&server, "mock").await;
+    assert_eq!(status, 409);
+    assert_eq!(server.count("SELECT COUNT(*) FROM user_identities").await, 0);
+
+    // Both do; the address matches whatever its case, and some providers send the flag as a string
+    provider.set_claims(json!({ "sub": "subject-1", "email": "alice@example.com", "email_verified": "true" }));
+    let (status, body) = sign_in(&server, "mock").await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["user"]["userId"], alice);
+    assert_eq!(server.count("SELECT COUNT(*) FROM users").await, 2);
+}
@@ -0,0 +1,293 @@
+mod common;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use common::{TestServer, FRONTEND_URL};
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use serde_cbor_2::Value as Cbor;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+// --- Software authenticator ---
+// One ES256 credential with "none" attestation, which always reports user presence and verification.
+
+struct SoftAuthenticator {
+    key: EcKey<Private>,
+    credential_id: Vec<u8>,
+    user_handle: Vec<u8>,
+    counter: u32,
+}
+
+fn b64(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(data)
+}
+
+fn client_data(kind: &str, options: &Value) -> Vec<u8> {
+    json!({
+        "type": kind,
+        "challenge": options["publicKey"]["challenge"],
+        "origin": FRONTEND_URL,
+        "crossOrigin": false,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+impl SoftAuthenticator {
+    fn new() -> Self {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        SoftAuthenticator {
+            key: EcKey::generate(&group).unwrap(),
+            credential_id: rand::random::<[u8; 16]>().to_vec(),
+            user_handle: Vec::new(),
+            counter: 0,
+        }
+    }
+
+    // Same credential ID and user, different key: its signatures don't verify
+    fn impostor(&self) -> Self {
+        SoftAuthenticator { credential_id: self.credential_id.clone(), user_handle: self.user_handle.clone(), ..Self::new() }
+    }
+
+    fn sign(&self, data: &[u8]) -> Vec<u8> {
+        let key = PKey::from_ec_key(self.key.clone()).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+        signer.update(data).unwrap();
+        signer.sign_to_vec().unwrap()
+    }
+
+    fn auth_data(&self, rp_id: &str, flags: u8) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend(self.counter.to_be_bytes());
+        data
+    }
+
+    // navigator.credentials.create() for the options of register/start
+    fn register(&mut self, options: &Value) -> Value {
+        let rp_id = options["publicKey"]["rp"]["id"].as_str().unwrap();
+        self.user_handle = URL_SAFE_NO_PAD.decode(options["publicKey"]["user"]["id"].as_str().unwrap()).unwrap();
+
+        let mut context = BigNumContext::new().unwrap();
+        let (mut x, mut y) = (BigNum::new().unwrap(), BigNum::new().unwrap());
+        self.key.public_key().affine_coordinates_gfp(self.key.group(), &mut x, &mut y, &mut context).unwrap();
+        let cose_key = Cbor::Map(BTreeMap::from([
+            (Cbor::Integer(1), Cbor::Integer(2)),   // kty: EC2
+            (Cbor::Integer(3), Cbor::Integer(-7)),  // alg: ES256
+            (Cbor::Integer(-1), Cbor::Integer(1)),  // crv: P-256
+            (Cbor::Integer(-2), Cbor::Bytes(x.to_vec_padded(32).unwrap())),
+            (Cbor::Integer(-3), Cbor::Bytes(y.to_vec_padded(32).unwrap())),
+        ]));
+
+        let mut auth_data = self.auth_data(rp_id, 0x45); // User present, user verified, attested credential
+        auth_data.extend([0; 16]); // AAGUID
+        auth_data.extend((self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend(&self.credential_id);
+        auth_data.extend(serde_cbor_2::to_vec(&cose_key).unwrap());
+
+        let attestation_object = Cbor::Map(BTreeMap::from([
+            (Cbor::Text("fmt".to_string()), Cbor::Text("none".to_string())),
+            (Cbor::Text("attStmt".to_string()), Cbor::Map(BTreeMap::new())),
+            (Cbor::Text("authData".to_string()), Cbor::Bytes(auth_data)),
+        ]));
+
+        json!({
+            "id": b64(&self.credential_id),
+            "rawId": b64(&self.credential_id),
+            "type": "public-key",
+            "extensions": {},
+            "response": {
+                "attestationObject": b64(&serde_cbor_2::to_vec(&attestation_object).unwrap()),
+                "clientDataJSON": b64(&client_data("webauthn.create", options)),
+            },
+        })
+    }
+
+    // navigator.credentials.get() for the options of a login or verify-tfa start
+    fn assert(&mut self, options: &Value) -> Value {
+        self.counter += 1;
+        let auth_data = self.auth_data(options["publicKey"]["rpId"].as_str().unwrap(), 0x05); // User present and verified
+        let client_data = client_data("webauthn.get", options);
+        let signature = self.sign(&[auth_data.as_slice(), &Sha256::digest(&client_data)].concat());
+
+        json!({
+            "id": b64(&self.credential_id),
+            "rawId": b64(&self.credential_id),
+            "type": "public-key",
+            "extensions": {},
+            "response": {
+                "authenticatorData": b64(&auth_data),
+                "clientDataJSON": b64(&client_data),
+                "signature": b64(&signature),
+                "userHandle": b64(&self.user_handle),
+            },
+        })
+    }
+}
+
+// Registers a new passkey for the signed-in user
+async fn register_passkey(server: &TestServer, token: &str) -> SoftAuthenticator {
+    let mut authenticator = SoftAuthenticator::new();
+    let (status, start) = server.post("/api/me/passkeys/register/start", Some(token), &json!({})).await;
+    assert_eq!(status, 200, "{}", start);
+    let credential = authenticator.register(&start["options"]);
+    let (status, body) = server
+        .post(
+            "/api/me/passkeys/register/finish",
+            Some(token),
+            &json!({ "ceremonyId": start["ceremonyId"], "credential": credential, "name": "Test key" }),
+        )
+        .await;
+    assert_eq!(status, 201, "{}", body);
+    assert_eq!(body["name"], "Test key");
+    authenticator
+}
+
+// A user with a passkey and 2FA enabled
+async fn create_tfa_user(server: &TestServer, email: &str) -> (i32, SoftAuthenticator) {
+    let user_id = server.create_user(email, "password123").await;
+    let token = server.token(email, "password123").await;
+    let authenticator = register_passkey(server, &token).await;
+    sqlx::query("UPDATE users SET tfa_enabled = true, tfa_secret = 'JBSWY3DPEHPK3PXP' WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&server.pool)
+        .await
+        .unwrap();
+    (user_id, authenticator)
+}
+
+#[tokio::test]
+async fn registered_passkey_logs_in_without_password() {
+    let server = TestServer::start(&[]).await;
+    let user_id = server.create_user("alice@example.com", "password123").await;
+    let token = server.token("alice@example.com", "password123").await;
+    let mut authenticator = register_passkey(&server, &token).await;
+
+    let (status, passkeys) = server.get("/api/me/passkeys", Some(&token)).await;
+    assert_eq!(status, 200);
+    assert_eq!(passkeys.as_array().unwrap().len(), 1);
+
+    // Discoverable login: the server learns the user from the credential
+    let (status, start) = server.post("/api/auth/passkey/login/start", None, &json!({})).await;
+    assert_eq!(status, 200, "{}", start);
+    assert!(start["options"]["publicKey"]["allowCredentials"].as_array().is_none_or(Vec::is_empty));
+    let finish = json!({ "ceremonyId": start["ceremonyId"], "credential": authenticator.assert(&start["options"]) });
+    let (status, body) = server.post("/api/auth/passkey/login/finish", None, &finish).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["user"]["userId"], user_id);
+    assert!(body["token"].is_string());
+
+    // The ceremony is gone once finished
+    let (status, _) = server.post("/api/auth/passkey/login/finish", None, &finish).await;
+    assert_eq!(status, 400);
+}
+
+#[tokio::test]
+async fn passkey_login_rejects_bad_signatures_and_replayed_registrations() {
+    let server = TestServer::start(&[]).await;
+    server.create_user("alice@example.com", "password123").await;
+    let token = server.token("alice@example.com", "password123").await;
+
+    // Registration ceremonies are single use too
+    let mut authenticator = SoftAuthenticator::new();
+    let (_, start) = server.post("/api/me/passkeys/register/start", Some(&token), &json!({})).await;
+    let finish = json!({ "ceremonyId": start["ceremonyId"], "credential": authenticator.register(&start["options"]) });
+    let (status, _) = server.post("/api/me/passkeys/register/finish", Some(&token), &finish).await;
+    assert_eq!(status, 201);
+    let (status, _) = server.post("/api/me/passkeys/register/finish", Some(&token), &finish).await;
+    assert_eq!(status, 400);
+
+    let mut impostor = authenticator.impostor();
+    let (_, start) = server.post("/api/auth/passkey/login/start", None, &json!({})).await;
+    let finish = json!({ "ceremonyId": start["ceremonyId"], "credential": impostor.assert(&start["options"]) });
+    let (status, body) = server.post("/api/auth/passkey/login/finish", None, &finish).await;
+    assert_eq!(status, 401, "{}", body);
+
+    // An unknown credential doesn't identify anyone
+    let mut stranger = SoftAuthenticator::new();
+    stranger.user_handle = authenticator.user_handle.clone();
+    let (_, start) = server.post("/api/auth/passkey/login/start", None, &json!({})).await;
+    let finish = json!({ "ceremonyId": start["ceremonyId"], "credential": stranger.assert(&start["options"]) });
+    let (status, _) = server.post("/api/auth/passkey/login/finish", None, &finish).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn passkey_completes_the_second_factor() {
+    let server = TestServer::start(&[]).await;
+    let (user_id, mut authenticator) = create_tfa_user(&server, "alice@example.com").await;
+
+    let login = server.login("alice@example.com", "password123").await;
+    assert_eq!(login["passkeyAvailable"], true, "{}", login);
+    let challenge_token = login["challengeToken"].as_str().unwrap();
+
+    let (status, start) = server.post("/api/auth/passkey/verify-tfa/start", None, &json!({ "challengeToken": challenge_token })).await;
+    assert_eq!(status, 200, "{}", start);
+    let finish = json!({
+        "challengeToken": challenge_token,
+        "ceremonyId": start["ceremonyId"],
+        "credential": authenticator.assert(&start["options"]),
+    });
+    let (status, body) = server.post("/api/auth/passkey/verify-tfa/finish", None, &finish).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["user"]["userId"], user_id);
+
+    // The challenge is used up
+    let (status, _) = server.post("/api/auth/passkey/verify-tfa/finish", None, &finish).await;
+    assert_eq!(status, 401);
+}
+
+#[tokio::test]
+async fn failed_passkey_attempts_count_against_the_challenge() {
+    let server = TestServer::start(&[]).await;
+    let (_, mut authenticator) = create_tfa_user(&server, "alice@example.com").await;
+    let mut impostor = authenticator.impostor();
+
+    let login = server.login("alice@example.com", "password123").await;
+    let challenge_token = login["challengeToken"].as_str().unwrap();
+    let start_second_factor = || async {
+        let (status, start) = server.post("/api/auth/passkey/verify-tfa/start", None, &json!({ "challengeToken": challenge_token })).await;
+        assert_eq!(status, 200, "{}", start);
+        start
+    };
+
+    // A ceremony can't be finished twice, and the replay is an attempt as well
+    let start = start_second_factor().await;
+    let finish = json!({
+        "challengeToken": challenge_token,
+        "ceremonyId": start["ceremonyId"],
+        "credential": impostor.assert(&start["options"]),
+    });
+    let (status, _) = server.post("/api/auth/passkey/verify-tfa/finish", None, &finish).await;
+    assert_eq!(status, 401);
+    let (status, _) = server.post("/api/auth/passkey/verify-tfa/finish", None, &finish).await;
+    assert_eq!(status, 400);
+
+    for attempt in 3..=5 {
+        let start = start_second_factor().await;
+        let finish = json!({
+            "challengeToken": challenge_token,
+            "ceremonyId": start["ceremonyId"],
+            "credential": impostor.assert(&start["options"]),
+        });
+        let (status, body) = server.post("/api/auth/passkey/verify-tfa/finish", None, &finish).await;
+        assert_eq!(status, if attempt < 5 { 401 } else { 429 }, "attempt {}: {}", attempt, body);
+    }
+
+    // Even the right passkey can't use the challenge anymore
+    let start = start_second_factor().await;
+    let finish = json!({
+        "challengeToken": challenge_token,
+        "ceremonyId": start["ceremonyId"],
+        "credential": authenticator.assert(&start["options"]),
+    });
+    let (status, _) = server.post("/api/auth/passkey/verify-tfa/finish", None, &finish).await;
+    assert_eq!(status, 429);
+}